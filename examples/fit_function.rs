@@ -0,0 +1,37 @@
+use rust_ml::optim::{fit_function, Adam, Lbfgs};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+// uses the autograd engine as a general curve fitter: recovers the parameters of
+// y(t) = a * exp(-b t) + c from noisy samples, once with adam and once with l-bfgs
+fn main() {
+    random::seed(7);
+    let (a_true, b_true, c_true) = (2.0, 0.8, 0.5);
+    let ts: Vec<f64> = (0..30).map(|i| i as f64 * 0.2).collect();
+    let ys: Vec<f64> = ts.iter().map(|t| a_true * (-b_true * t).exp() + c_true + random::normal(0.0, 0.02)).collect();
+
+    // sum of squared residuals over the parameters
+    let objective = |a: &Value, b: &Value, c: &Value| {
+        let mut loss = Value::new(0.0);
+        for (t, y) in ts.iter().zip(ys.iter()) {
+            let decay = Value::exp(&Value::mul(&Value::neg(b), &Value::new(*t)));
+            let pred = Value::add(&Value::mul(a, &decay), c);
+            loss = Value::add(&loss, &Value::square(&Value::sub(&pred, &Value::new(*y))));
+        }
+        loss
+    };
+
+    let (a, b, c) = (Value::new(1.0), Value::new(1.0), Value::new(0.0));
+    let mut adam = Adam::new(vec![a.clone_rc(), b.clone_rc(), c.clone_rc()], 0.05);
+    let losses = fit_function(&mut adam, || objective(&a, &b, &c), 2000, 1e-12);
+    println!("adam:  {} steps, loss {:.6}, a={:.3} b={:.3} c={:.3}",
+        losses.len(), losses.last().unwrap(), a.get_data(), b.get_data(), c.get_data());
+
+    let (a, b, c) = (Value::new(1.0), Value::new(1.0), Value::new(0.0));
+    let mut lbfgs = Lbfgs::new(vec![a.clone_rc(), b.clone_rc(), c.clone_rc()], 1.0);
+    let losses = fit_function(&mut lbfgs, || objective(&a, &b, &c), 50, 1e-12);
+    println!("lbfgs: {} steps, loss {:.6}, a={:.3} b={:.3} c={:.3}",
+        losses.len(), losses.last().unwrap(), a.get_data(), b.get_data(), c.get_data());
+
+    println!("true:  a={} b={} c={}", a_true, b_true, c_true);
+}
@@ -0,0 +1,60 @@
+use rust_ml::loss;
+use rust_ml::nn::{Activation, Layer, Module, MLP};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+// fits noisy data whose noise grows with |x|, predicting both a mean and a log variance,
+// then checks that roughly 95% of the points land inside the predicted 95% interval
+fn main() {
+    random::seed(42);
+
+    // y = sin(x) + noise with std 0.05 + 0.15|x|
+    let n = 60;
+    let xs: Vec<f64> = (0..n).map(|_| random::uniform(-3.0, 3.0)).collect();
+    let ys: Vec<f64> = xs.iter().map(|x| x.sin() + random::normal(0.0, 0.05 + 0.15 * x.abs())).collect();
+
+    // tanh body with a linear head: output 0 is the mean, output 1 the log variance
+    let body = MLP::new(&[1, 8, 8]);
+    let head = Layer::with_activation(8, 2, Activation::Linear);
+    let predict = |x: f64| head.forward(&body.forward(&[Value::new(x)]));
+    let mut params = body.parameters();
+    params.extend(head.parameters());
+
+    let lr = 0.01;
+    for epoch in 0..600 {
+        let outs: Vec<Vec<Value>> = xs.iter().map(|x| predict(*x)).collect();
+        let means: Vec<Value> = outs.iter().map(|o| o[0].clone_rc()).collect();
+        let log_vars: Vec<Value> = outs.iter().map(|o| o[1].clone_rc()).collect();
+        let targets: Vec<Value> = ys.iter().map(|y| Value::new(*y)).collect();
+        let loss = loss::gaussian_nll_mean(&means, &log_vars, &targets);
+
+        for p in params.iter() {
+            p.set_grad(0.0);
+        }
+        loss.backward();
+        for p in params.iter() {
+            p.update_data(-lr * p.get_grad());
+        }
+
+        if epoch % 100 == 0 {
+            println!("epoch: {} nll: {}", epoch, loss.get_data());
+        }
+    }
+
+    // calibration: fraction of points within mean +- 1.96 std
+    let mut inside = 0;
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        let out = predict(*x);
+        let std = (0.5 * out[1].get_data()).exp();
+        if (y - out[0].get_data()).abs() <= 1.96 * std {
+            inside += 1;
+        }
+    }
+    println!("{}/{} points inside the predicted 95% interval", inside, n);
+
+    for x in [-2.5, -1.0, 0.0, 1.0, 2.5] {
+        let out = predict(x);
+        println!("x: {:5.2} mean: {:6.3} (true {:6.3}) std: {:.3} (true {:.3})",
+            x, out[0].get_data(), f64::sin(x), (0.5 * out[1].get_data()).exp(), 0.05 + 0.15 * f64::abs(x));
+    }
+}
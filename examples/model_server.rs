@@ -0,0 +1,81 @@
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use rust_ml::nn::{Module, MLP};
+use rust_ml::serialize;
+
+// serves a saved MLP over http:
+//     cargo run --example model_server --features server -- model.json 127.0.0.1:8080
+//     curl -d '{"inputs": [[2.0, 3.0, -1.0]]}' http://127.0.0.1:8080/predict
+// the model file is what rust_ml::serialize::save writes
+
+#[derive(Deserialize)]
+struct PredictRequest {
+    inputs: Vec<Vec<f64>>,
+}
+
+#[derive(Serialize)]
+struct PredictResponse {
+    outputs: Vec<Vec<f64>>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn json_response<T: Serialize>(status: u16, body: &T) -> Response<std::io::Cursor<Vec<u8>>> {
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_string(serde_json::to_string(body).unwrap())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn error(status: u16, msg: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, &ErrorResponse { error: msg })
+}
+
+fn handle(model: &MLP, request: &mut Request) -> Response<std::io::Cursor<Vec<u8>>> {
+    if request.method() != &Method::Post || request.url() != "/predict" {
+        return error(404, "only POST /predict is served".to_string());
+    }
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return error(400, format!("could not read body: {}", e));
+    }
+    let req: PredictRequest = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => return error(400, format!("invalid json: {}", e)),
+    };
+    if let Some((i, x)) = req.inputs.iter().enumerate().find(|(_, x)| x.len() != model.nin()) {
+        return error(400, format!("input {} has {} features, the model expects {}", i, x.len(), model.nin()));
+    }
+    json_response(200, &PredictResponse { outputs: model.predict_batch(&req.inputs) })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: model_server <model.json> [address]");
+        std::process::exit(1);
+    }
+    let addr = args.get(2).map_or("127.0.0.1:8080", |a| a.as_str());
+
+    let model = match serialize::load(&args[1]) {
+        Ok(model) => model,
+        Err(e) => {
+            eprintln!("could not load {}: {}", args[1], e);
+            std::process::exit(1);
+        },
+    };
+    println!("loaded model with {} inputs and {} layers", model.nin(), model.layers().len());
+
+    let server = Server::http(addr).expect("could not bind the address");
+    println!("listening on http://{}/predict", addr);
+    for mut request in server.incoming_requests() {
+        let response = handle(&model, &mut request);
+        let _ = request.respond(response);
+    }
+}
@@ -0,0 +1,45 @@
+use rust_ml::nn::{Activation, Layer, Module};
+use rust_ml::ode::odeint;
+use rust_ml::optim::{fit_function, Adam};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+// learns the dynamics of a damped spiral dy/dt = A y from one observed trajectory,
+// with a linear layer as the dynamics and gradients going through every rk4 step
+fn main() {
+    random::seed(0);
+    let a = [[-0.1, 2.0], [-2.0, -0.1]];
+    let (dt, steps) = (0.1, 30);
+
+    // ground truth trajectory from the exact dynamics, integrated finely with euler steps
+    let mut truth = vec![[2.0, 0.0]];
+    let mut y = truth[0];
+    for _ in 0..steps {
+        for _ in 0..100 {
+            let dy = [a[0][0] * y[0] + a[0][1] * y[1], a[1][0] * y[0] + a[1][1] * y[1]];
+            y = [y[0] + dt / 100.0 * dy[0], y[1] + dt / 100.0 * dy[1]];
+        }
+        truth.push(y);
+    }
+
+    let f = Layer::with_activation(2, 2, Activation::Linear);
+    let mut opt = Adam::new(f.parameters(), 0.05);
+    let y0 = vec![Value::new(truth[0][0]), Value::new(truth[0][1])];
+    let objective = || {
+        let traj = odeint(&f, &y0, dt, steps);
+        let mut loss = Value::new(0.0);
+        for (pred, obs) in traj.iter().zip(truth.iter()) {
+            for k in 0..2 {
+                loss = Value::add(&loss, &Value::square(&Value::sub(&pred[k], &Value::new(obs[k]))));
+            }
+        }
+        loss
+    };
+    let losses = fit_function(&mut opt, objective, 300, 1e-10);
+    println!("loss {:.3} -> {:.6} after {} steps", losses[0], losses[losses.len() - 1], losses.len());
+
+    let p = f.parameters();
+    println!("learned A = [[{:.3}, {:.3}], [{:.3}, {:.3}]] (bias {:.3}, {:.3})",
+        p[0].get_data(), p[1].get_data(), p[3].get_data(), p[4].get_data(), p[2].get_data(), p[5].get_data());
+    println!("true    A = [[{}, {}], [{}, {}]]", a[0][0], a[0][1], a[1][0], a[1][1]);
+}
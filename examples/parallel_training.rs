@@ -0,0 +1,37 @@
+use std::time::Instant;
+
+use rust_ml::datasets;
+use rust_ml::nn::{Module, MLP};
+use rust_ml::optim::Sgd;
+use rust_ml::parallel::{DataParallelTrainer, Hogwild};
+use rust_ml::random;
+
+// synchronous gradient averaging against hogwild on the same problem: wall time for a fixed number
+// of epochs and the loss each one ends up at, for a few worker counts
+// run with --release, the debug build is dominated by the autograd bookkeeping either way
+fn main() {
+    random::seed(3);
+    let data = datasets::moons(2000, 0.1);
+    let (xs, ys) = (&data.xs, &data.signs());
+    let epochs = 10;
+    let build = || Box::new(MLP::new(&[2, 32, 32, 1])) as Box<dyn Module>;
+
+    println!("{:<10} {:>8} {:>10} {:>12}", "mode", "workers", "seconds", "final loss");
+    for workers in [1, 2, 4, 8] {
+        random::seed(4);
+        let model = MLP::new(&[2, 32, 32, 1]);
+        let mut sync = DataParallelTrainer::new(&model, build, Sgd::new(model.parameters(), 0.05).with_momentum(0.9), workers)
+            .with_batch_size(32);
+        let start = Instant::now();
+        let losses = sync.fit(xs, ys, epochs);
+        println!("{:<10} {:>8} {:>10.2} {:>12.4}", "sync", workers, start.elapsed().as_secs_f64(), losses[epochs - 1]);
+
+        random::seed(4);
+        let model = MLP::new(&[2, 32, 32, 1]);
+        // each worker steps on its own, so the same batch size means workers times as many steps
+        let mut hogwild = Hogwild::new(&model, build, 0.05, workers).with_batch_size(32);
+        let start = Instant::now();
+        let losses = hogwild.fit(xs, ys, epochs);
+        println!("{:<10} {:>8} {:>10.2} {:>12.4}", "hogwild", workers, start.elapsed().as_secs_f64(), losses[epochs - 1]);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use rust_ml::graph;
+use rust_ml::value::Value;
+
+// a tiny calculator over Value graphs, for playing with autograd without writing rust
+//     > x = 2
+//     > y = x * (x + 3)
+//     > grad
+//     > dot
+const HELP: &str = "\
+commands:
+  <name> = <expr>   define a variable (numbers become leaves, expressions keep their graph)
+  <expr>            evaluate an expression, it becomes the current result
+  grad              backpropagate from the current result and print every variable's gradient
+  trace             same as grad, printing each chain rule step
+  dot | json        print the current result's graph
+  vars              list the variables
+  help | quit
+expressions: numbers, variables, + - * / ^, parentheses, and
+  exp ln log2 log10 sin cos tan tanh softplus gelu silu relu sigmoid square cube reciprocal sqrt rsqrt abs";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Sym(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(text.parse().map_err(|_| format!("bad number {}", text))?));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/^()=".contains(c) {
+            tokens.push(Token::Sym(c));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+// recursive descent over the usual precedence levels
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    vars: &'a HashMap<String, Value>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Sym(c)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expr(&mut self) -> Result<Value, String> {
+        let mut v = self.term()?;
+        loop {
+            if self.eat('+') {
+                v = Value::add(&v, &self.term()?);
+            } else if self.eat('-') {
+                v = Value::sub(&v, &self.term()?);
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Value, String> {
+        let mut v = self.unary()?;
+        loop {
+            if self.eat('*') {
+                v = Value::mul(&v, &self.unary()?);
+            } else if self.eat('/') {
+                v = Value::div(&v, &self.unary()?);
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat('-') {
+            return Ok(Value::neg(&self.unary()?));
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            // an exponent without variables in it is used as a constant
+            let p = self.unary()?;
+            if p.requires_grad() {
+                return Ok(Value::powv(&base, &p));
+            }
+            return Ok(Value::pow(&base, p.get_data()));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Value, String> {
+        match self.peek().cloned() {
+            Some(Token::Num(x)) => {
+                self.pos += 1;
+                Ok(Value::constant(x))
+            },
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                if self.eat('(') {
+                    let arg = self.expr()?;
+                    if !self.eat(')') {
+                        return Err(format!("missing ')' after {}(", name));
+                    }
+                    return apply(&name, &arg);
+                }
+                self.vars.get(&name).map(|v| v.clone_rc()).ok_or(format!("unknown variable {}", name))
+            },
+            Some(Token::Sym('(')) => {
+                self.pos += 1;
+                let v = self.expr()?;
+                if !self.eat(')') {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(v)
+            },
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+}
+
+fn apply(name: &str, x: &Value) -> Result<Value, String> {
+    Ok(match name {
+        "exp" => Value::exp(x),
+        "ln" => Value::ln(x),
+        "log2" => Value::log(x, 2.0),
+        "log10" => Value::log(x, 10.0),
+        "sin" => Value::sin(x),
+        "cos" => Value::cos(x),
+        "tan" => Value::tan(x),
+        "tanh" => Value::tanh(x),
+        "softplus" => Value::softplus(x),
+        "gelu" => Value::gelu(x),
+        "silu" => Value::silu(x),
+        "relu" => Value::relu(x),
+        "sigmoid" => Value::sigmoid(x),
+        "square" => Value::square(x),
+        "cube" => Value::cube(x),
+        "reciprocal" => Value::reciprocal(x),
+        "sqrt" => Value::sqrt(x),
+        "rsqrt" => Value::rsqrt(x),
+        "abs" => Value::abs(x),
+        _ => return Err(format!("unknown function {}", name)),
+    })
+}
+
+fn parse(tokens: &[Token], vars: &HashMap<String, Value>) -> Result<Value, String> {
+    let mut p = Parser { tokens, pos: 0, vars };
+    let v = p.expr()?;
+    if p.pos != tokens.len() {
+        return Err(format!("unexpected {:?}", tokens[p.pos]));
+    }
+    Ok(v)
+}
+
+fn label(v: &Value) -> String {
+    v.0.borrow().label.clone()
+}
+
+fn main() {
+    println!("rust-ml autograd repl, type help for commands");
+    let mut vars: HashMap<String, Value> = HashMap::new();
+    let mut current: Option<Value> = None;
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let line = line.trim();
+        match line {
+            "" => continue,
+            "quit" | "exit" => break,
+            "help" => println!("{}", HELP),
+            "vars" => {
+                let mut names: Vec<&String> = vars.keys().collect();
+                names.sort();
+                for name in names {
+                    println!("{} = {}", name, vars[name]);
+                }
+            },
+            "grad" | "trace" | "dot" | "json" => {
+                let Some(v) = current.as_ref() else {
+                    println!("nothing evaluated yet");
+                    continue;
+                };
+                match line {
+                    "dot" => print!("{}", graph::to_dot(v)),
+                    "json" => println!("{}", graph::to_json(v).unwrap()),
+                    _ => {
+                        for x in vars.values() {
+                            x.set_grad(0.0);
+                        }
+                        for node in graph::topo_order(v) {
+                            node.set_grad(0.0);
+                        }
+                        if line == "trace" {
+                            for step in v.backward_traced() {
+                                let name = |n: &Value| if label(n).is_empty() { n.0.borrow().op.to_string() } else { label(n) };
+                                println!("{} -> {}: local {:.6}, passes {:.6}", name(&step.node), name(&step.child), step.local_grad, step.contribution);
+                            }
+                        } else {
+                            v.backward();
+                        }
+                        let mut names: Vec<&String> = vars.keys().collect();
+                        names.sort();
+                        for name in names {
+                            println!("d/d{} = {}", name, vars[name].get_grad());
+                        }
+                    },
+                }
+            },
+            _ => {
+                let tokens = match tokenize(line) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        println!("error: {}", e);
+                        continue;
+                    },
+                };
+                // name = expr
+                if let [Token::Ident(name), Token::Sym('='), rest @ ..] = tokens.as_slice() {
+                    match parse(rest, &vars) {
+                        Ok(v) => {
+                            // a plain number becomes a fresh leaf that gradients flow to
+                            let v = if v.get_children().is_empty() { Value::new(v.get_data()) } else { v };
+                            v.0.borrow_mut().label = name.clone();
+                            println!("{} = {}", name, v.get_data());
+                            vars.insert(name.clone(), v.clone_rc());
+                            current = Some(v);
+                        },
+                        Err(e) => println!("error: {}", e),
+                    }
+                    continue;
+                }
+                match parse(&tokens, &vars) {
+                    Ok(v) => {
+                        println!("{}", v.get_data());
+                        current = Some(v);
+                    },
+                    Err(e) => println!("error: {}", e),
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::error::{Result, RustMlError};
+use crate::random;
+use crate::stats;
+
+// unsupervised outlier detection on rows of features (rows are samples, like the Trainer takes)
+// fit() learns what normal looks like, score_samples() gives higher scores to stranger rows,
+// predict() flags the rows whose score is past the detector's threshold
+
+pub trait OutlierDetector {
+    fn fit(&mut self, xs: &[Vec<f64>]) -> Result<()>;
+
+    // higher is more anomalous
+    fn score_samples(&self, xs: &[Vec<f64>]) -> Result<Vec<f64>>;
+
+    // true for outliers
+    fn predict(&self, xs: &[Vec<f64>]) -> Result<Vec<bool>>;
+}
+
+fn n_cols(xs: &[Vec<f64>]) -> Result<usize> {
+    let d = xs.first().map_or(0, |x| x.len());
+    if let Some((i, x)) = xs.iter().enumerate().find(|(_, x)| x.len() != d) {
+        return Err(RustMlError::Shape(format!("row {} has {} columns, row 0 has {}", i, x.len(), d)));
+    }
+    return Ok(d);
+}
+
+fn check_cols(xs: &[Vec<f64>], expected: usize) -> Result<()> {
+    let d = n_cols(xs)?;
+    if !xs.is_empty() && d != expected {
+        return Err(RustMlError::Shape(format!("fit on {} columns, got {}", expected, d)));
+    }
+    return Ok(());
+}
+
+// per-feature z-scores against the training data, a row's score is its largest |z|
+// robust uses the median and the median absolute deviation instead (the modified z-score,
+// 0.6745 (x - median) / mad), which the outliers themselves can't drag around
+pub struct ZScoreFilter {
+    pub threshold: f64,
+    pub robust: bool,
+
+    // per column center and scale, empty until fit
+    pub center: Vec<f64>,
+    pub scale: Vec<f64>,
+}
+
+impl ZScoreFilter {
+    pub fn new(threshold: f64) -> Self {
+        ZScoreFilter {
+            threshold,
+            robust: false,
+            center: vec![],
+            scale: vec![],
+        }
+    }
+
+    pub fn with_robust(mut self, robust: bool) -> Self {
+        self.robust = robust;
+        self
+    }
+}
+
+impl OutlierDetector for ZScoreFilter {
+    fn fit(&mut self, xs: &[Vec<f64>]) -> Result<()> {
+        if xs.len() < 2 {
+            return Err(RustMlError::Config("a z-score filter needs at least 2 rows".to_string()));
+        }
+        let d = n_cols(xs)?;
+        self.center = vec![];
+        self.scale = vec![];
+        for j in 0..d {
+            let col: Vec<f64> = xs.iter().map(|x| x[j]).collect();
+            let (center, scale) = if self.robust {
+                let median = stats::quantile(&col, 0.5);
+                let deviations: Vec<f64> = col.iter().map(|x| (x - median).abs()).collect();
+                (median, stats::quantile(&deviations, 0.5) / 0.6745)
+            } else {
+                (stats::mean(&col), stats::std(&col, 1))
+            };
+            self.center.push(center);
+            self.scale.push(scale);
+        }
+        return Ok(());
+    }
+
+    fn score_samples(&self, xs: &[Vec<f64>]) -> Result<Vec<f64>> {
+        if self.center.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_cols(xs, self.center.len())?;
+        return Ok(xs.iter().map(|x| {
+            x.iter().zip(self.center.iter().zip(self.scale.iter())).map(|(v, (c, s))| {
+                // a constant column: anything off its value is infinitely far
+                if *s > 0.0 { ((v - c) / s).abs() } else if v == c { 0.0 } else { f64::INFINITY }
+            }).fold(0.0, f64::max)
+        }).collect());
+    }
+
+    fn predict(&self, xs: &[Vec<f64>]) -> Result<Vec<bool>> {
+        return Ok(self.score_samples(xs)?.iter().map(|s| *s > self.threshold).collect());
+    }
+}
+
+// average path length of an unsuccessful search in a binary search tree of n points,
+// used to normalize path lengths (and to stand in for the subtree below a leaf of n points)
+fn average_path(n: usize) -> f64 {
+    if n <= 1 {
+        return 0.0;
+    }
+    let n = n as f64;
+    let harmonic = (n - 1.0).ln() + 0.5772156649015329;
+    return 2.0 * harmonic - 2.0 * (n - 1.0) / n;
+}
+
+enum Tree {
+    Leaf(usize),
+    Split {
+        feature: usize,
+        value: f64,
+        left: Box<Tree>,
+        right: Box<Tree>,
+    },
+}
+
+impl Tree {
+    // random feature, random split point between its min and max, until points are isolated
+    // or the height limit is hit
+    fn grow(xs: &[&Vec<f64>], depth: usize, max_depth: usize) -> Tree {
+        if xs.len() <= 1 || depth >= max_depth {
+            return Tree::Leaf(xs.len());
+        }
+        let d = xs[0].len();
+        // only features that still vary can split the points
+        let varying: Vec<(usize, f64, f64)> = (0..d).filter_map(|j| {
+            let lo = xs.iter().map(|x| x[j]).fold(f64::INFINITY, f64::min);
+            let hi = xs.iter().map(|x| x[j]).fold(f64::NEG_INFINITY, f64::max);
+            if hi > lo { Some((j, lo, hi)) } else { None }
+        }).collect();
+        if varying.is_empty() {
+            return Tree::Leaf(xs.len());
+        }
+        let (feature, lo, hi) = varying[random::index(varying.len())];
+        let value = random::uniform(lo, hi);
+        let (left, right): (Vec<&Vec<f64>>, Vec<&Vec<f64>>) = xs.iter().partition(|x| x[feature] < value);
+        return Tree::Split {
+            feature,
+            value,
+            left: Box::new(Tree::grow(&left, depth + 1, max_depth)),
+            right: Box::new(Tree::grow(&right, depth + 1, max_depth)),
+        };
+    }
+
+    fn path_length(&self, x: &[f64]) -> f64 {
+        let mut node = self;
+        let mut depth = 0.0;
+        loop {
+            match node {
+                Tree::Leaf(n) => return depth + average_path(*n),
+                Tree::Split { feature, value, left, right } => {
+                    node = if x[*feature] < *value { left } else { right };
+                    depth += 1.0;
+                },
+            }
+        }
+    }
+}
+
+// isolation forest (liu et al. 2008): anomalies are few and different, so random axis-aligned
+// splits isolate them in fewer steps; each tree is grown on a random subsample and the score is
+// 2^(-mean path length / c(sample size)), close to 1 for anomalies and below 0.5 for normal points
+pub struct IsolationForest {
+    pub n_trees: usize,
+    pub sample_size: usize,
+    // expected share of outliers in the training data, sets the predict() threshold
+    pub contamination: f64,
+
+    // score above which predict() flags a row, set by fit
+    pub threshold: f64,
+    trees: Vec<Tree>,
+    // the subsample size actually used (at most the number of training rows)
+    psi: usize,
+    d: usize,
+}
+
+impl IsolationForest {
+    // 100 trees on subsamples of 256 and 10% contamination by default
+    pub fn new() -> Self {
+        IsolationForest {
+            n_trees: 100,
+            sample_size: 256,
+            contamination: 0.1,
+            threshold: 0.5,
+            trees: vec![],
+            psi: 0,
+            d: 0,
+        }
+    }
+
+    pub fn with_trees(mut self, n_trees: usize) -> Self {
+        self.n_trees = n_trees;
+        self
+    }
+
+    pub fn with_sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_size = sample_size;
+        self
+    }
+
+    pub fn with_contamination(mut self, contamination: f64) -> Self {
+        self.contamination = contamination;
+        self
+    }
+}
+
+impl Default for IsolationForest {
+    fn default() -> Self {
+        IsolationForest::new()
+    }
+}
+
+impl OutlierDetector for IsolationForest {
+    fn fit(&mut self, xs: &[Vec<f64>]) -> Result<()> {
+        if xs.len() < 2 || self.n_trees == 0 || self.sample_size < 2 {
+            return Err(RustMlError::Config(format!("isolation forest needs at least 2 rows, a tree and a sample size of 2, got {} rows, {} trees, sample size {}", xs.len(), self.n_trees, self.sample_size)));
+        }
+        if !(0.0..0.5).contains(&self.contamination) {
+            return Err(RustMlError::Config(format!("contamination must be in [0, 0.5), got {}", self.contamination)));
+        }
+        self.d = n_cols(xs)?;
+        self.psi = self.sample_size.min(xs.len());
+        let max_depth = (self.psi as f64).log2().ceil() as usize;
+        let mut idx: Vec<usize> = (0..xs.len()).collect();
+        self.trees = (0..self.n_trees).map(|_| {
+            random::shuffle(&mut idx);
+            let sample: Vec<&Vec<f64>> = idx[..self.psi].iter().map(|&i| &xs[i]).collect();
+            Tree::grow(&sample, 0, max_depth)
+        }).collect();
+
+        // the contamination share of the training rows scores above the threshold
+        let scores = self.score_samples(xs)?;
+        self.threshold = if self.contamination == 0.0 { f64::INFINITY } else { stats::quantile(&scores, 1.0 - self.contamination) };
+        tracing::debug!(threshold = self.threshold, trees = self.n_trees, "isolation forest fit");
+        return Ok(());
+    }
+
+    fn score_samples(&self, xs: &[Vec<f64>]) -> Result<Vec<f64>> {
+        if self.trees.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_cols(xs, self.d)?;
+        let c = average_path(self.psi);
+        return Ok(xs.iter().map(|x| {
+            let mean = self.trees.iter().map(|t| t.path_length(x)).sum::<f64>() / self.trees.len() as f64;
+            2f64.powf(-mean / c)
+        }).collect());
+    }
+
+    fn predict(&self, xs: &[Vec<f64>]) -> Result<Vec<bool>> {
+        return Ok(self.score_samples(xs)?.iter().map(|s| *s > self.threshold).collect());
+    }
+}
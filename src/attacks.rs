@@ -0,0 +1,72 @@
+use crate::nn::Module;
+use crate::value::Value;
+
+// adversarial examples: small input perturbations chosen to increase the loss the most
+// the input gradient comes from the normal backward pass, with the inputs as leaf Values
+// every function here leaves the model's parameter gradients zeroed
+
+// loss at x and its gradient with respect to x
+pub fn input_gradient(model: &dyn Module, loss: &dyn Fn(&[Value], &[f64]) -> Value, x: &[f64], y: &[f64]) -> (f64, Vec<f64>) {
+    let xv: Vec<Value> = x.iter().map(|xi| Value::new(*xi)).collect();
+    let l = loss(&model.forward(&xv), y);
+    l.backward();
+    let grad = xv.iter().map(|v| v.get_grad()).collect();
+    model.zero_grad();
+    return (l.get_data(), grad);
+}
+
+fn sign(g: f64) -> f64 {
+    if g > 0.0 {
+        1.0
+    } else if g < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+// fast gradient sign method (goodfellow et al.): one step of size eps along the sign of the gradient
+pub fn fgsm(model: &dyn Module, loss: &dyn Fn(&[Value], &[f64]) -> Value, x: &[f64], y: &[f64], eps: f64) -> Vec<f64> {
+    let (_, g) = input_gradient(model, loss, x, y);
+    return x.iter().zip(g.iter()).map(|(xi, gi)| xi + eps * sign(*gi)).collect();
+}
+
+// projected gradient descent (madry et al.): repeated signed steps, each followed by
+// projecting back into the l-infinity ball of radius eps around x
+pub fn pgd(
+    model: &dyn Module,
+    loss: &dyn Fn(&[Value], &[f64]) -> Value,
+    x: &[f64],
+    y: &[f64],
+    eps: f64,
+    step_size: f64,
+    steps: usize,
+) -> Vec<f64> {
+    let mut adv = x.to_vec();
+    for _ in 0..steps {
+        let (_, g) = input_gradient(model, loss, &adv, y);
+        for ((a, xi), gi) in adv.iter_mut().zip(x.iter()).zip(g.iter()) {
+            *a = (*a + step_size * sign(*gi)).clamp(xi - eps, xi + eps);
+        }
+    }
+    return adv;
+}
+
+// adversarial training settings for the Trainer, see Trainer::with_adversarial
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adversarial {
+    pub eps: f64,
+    // 1 step is fgsm, more is pgd with steps of 2.5 * eps / steps
+    pub steps: usize,
+    // weight of the adversarial loss, the clean loss gets 1 - weight
+    pub weight: f64,
+}
+
+impl Adversarial {
+    pub fn perturb(&self, model: &dyn Module, loss: &dyn Fn(&[Value], &[f64]) -> Value, x: &[f64], y: &[f64]) -> Vec<f64> {
+        if self.steps <= 1 {
+            return fgsm(model, loss, x, y, self.eps);
+        }
+        return pgd(model, loss, x, y, self.eps, 2.5 * self.eps / self.steps as f64, self.steps);
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::{boxed::Box, collections::BTreeSet, string::String, vec, vec::Vec};
+use core::any::Any;
+
+use crate::profiler::{self, Phase};
+
+// one node of the autograd graph, implemented by the handles of both engines (Value and Matrix)
+// edges between the engines (Value::from_matrix, Matrix::from_values) are ordinary inputs,
+// so one topological pass runs backward through a graph mixing scalars and matrices
+pub trait Node {
+    // identity of the underlying node, shared by every handle pointing at it
+    fn id(&self) -> usize;
+    // the nodes this one was computed from, in either engine
+    fn inputs(&self) -> Vec<Box<dyn Node>>;
+    // false when nothing below this node wants a gradient, backward skips the whole subgraph
+    fn requires_grad(&self) -> bool {
+        return true;
+    }
+    // local chain rule step: adds this node's gradient, times the local derivative, to its inputs
+    fn backward_local(&self);
+    // what the profiler files the backward step under
+    fn op_name(&self) -> String;
+    // for callers that need the concrete handle back (e.g. Value::backward_traced)
+    fn as_any(&self) -> &dyn Any;
+}
+
+// nodes that need a gradient, inputs before the nodes using them, each once
+// a node reachable from more than one root is still listed once
+pub fn topo_sort(roots: Vec<Box<dyn Node>>) -> Vec<Box<dyn Node>> {
+    let mut topo_sort: Vec<Box<dyn Node>> = vec![];
+    let mut visited: BTreeSet<usize> = BTreeSet::new();
+    let mut sorted: BTreeSet<usize> = BTreeSet::new();
+
+    // iterative dfs
+    // a node can sit on the stack more than once (e.g. both children of a + a),
+    // it must still only appear once in the topo sort, otherwise its backward runs
+    // twice and everything below it gets its gradient counted twice
+    let mut stack = roots;
+    while let Some(node) = stack.pop() {
+        let id = node.id();
+        if !visited.contains(&id) {
+            visited.insert(id);
+            let inputs: Vec<Box<dyn Node>> = node.inputs().into_iter()
+                .filter(|input| input.requires_grad() && !visited.contains(&input.id()))
+                .collect();
+            stack.push(node);
+            stack.extend(inputs);
+        } else if !sorted.contains(&id) {
+            sorted.insert(id);
+            topo_sort.push(node);
+        }
+    }
+    return topo_sort;
+}
+
+// backward from gradients already sitting on the roots, without seeding them
+pub fn propagate(roots: Vec<Box<dyn Node>>) {
+    let t = profiler::start();
+    let topo_sort = topo_sort(roots);
+    profiler::record("graph bookkeeping", Phase::Backward, t);
+    tracing::trace!(nodes = topo_sort.len(), "backward");
+
+    let profiling = profiler::is_enabled();
+    for node in topo_sort.iter().rev() {
+        if profiling {
+            let t = profiler::start();
+            node.backward_local();
+            profiler::record(&node.op_name(), Phase::Backward, t);
+        } else {
+            node.backward_local();
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::error::Result;
+use crate::matrix::Matrix;
+use crate::nn::{get_flat_params, set_flat_params};
+use crate::random;
+use crate::value::Value;
+
+// gradient-free optimizers, they only ever evaluate the objective (lower is better),
+// so it doesn't have to be differentiable
+
+// evolution strategies with antithetic sampling (openai-es style):
+// the gradient is estimated from the objective at theta +- sigma * eps
+pub struct EvolutionStrategy {
+    pub sigma: f64,
+    pub lr: f64,
+    // number of +-eps pairs per step
+    pub pairs: usize,
+}
+
+impl EvolutionStrategy {
+    pub fn new(sigma: f64, lr: f64, pairs: usize) -> Self {
+        EvolutionStrategy {
+            sigma,
+            lr,
+            pairs
+        }
+    }
+
+    // one update of the parameters in place, objective reads the current parameter values
+    // returns the objective at the updated parameters
+    pub fn step(&self, params: &[Value], mut objective: impl FnMut() -> f64) -> f64 {
+        let theta = get_flat_params(params);
+        let mut grad = vec![0.0; theta.len()];
+        for _ in 0..self.pairs {
+            let eps: Vec<f64> = theta.iter().map(|_| random::normal(0.0, 1.0)).collect();
+            let plus: Vec<f64> = theta.iter().zip(eps.iter()).map(|(t, e)| t + self.sigma * e).collect();
+            let minus: Vec<f64> = theta.iter().zip(eps.iter()).map(|(t, e)| t - self.sigma * e).collect();
+            set_flat_params(params, &plus);
+            let f_plus = objective();
+            set_flat_params(params, &minus);
+            let f_minus = objective();
+            for (g, e) in grad.iter_mut().zip(eps.iter()) {
+                *g += (f_plus - f_minus) * e / (2.0 * self.sigma * self.pairs as f64);
+            }
+        }
+        let next: Vec<f64> = theta.iter().zip(grad.iter()).map(|(t, g)| t - self.lr * g).collect();
+        set_flat_params(params, &next);
+        return objective();
+    }
+}
+
+// CMA-ES: samples candidates from a gaussian whose mean, covariance and step size adapt to the
+// best candidates of every generation, see hansen's "the CMA evolution strategy: a tutorial"
+// use ask() / tell() directly, or step() to drive a set of Value parameters
+pub struct CmaEs {
+    pub mean: Vec<f64>,
+    pub sigma: f64,
+    pub lambda: usize,
+    pub generation: usize,
+
+    n: usize,
+    mu: usize,
+    weights: Vec<f64>,
+    mueff: f64,
+    cc: f64,
+    cs: f64,
+    c1: f64,
+    cmu: f64,
+    damps: f64,
+    chi_n: f64,
+
+    // covariance C = B diag(d^2) B^T, refreshed after every tell
+    cov: Vec<f64>,
+    b: Vec<f64>,
+    d: Vec<f64>,
+    pc: Vec<f64>,
+    ps: Vec<f64>,
+}
+
+impl CmaEs {
+    pub fn new(x0: Vec<f64>, sigma: f64) -> Self {
+        let n = x0.len();
+        let nf = n as f64;
+        let lambda = 4 + (3.0 * nf.ln()).floor() as usize;
+        let mu = lambda / 2;
+        let raw: Vec<f64> = (0..mu).map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln()).collect();
+        let total: f64 = raw.iter().sum();
+        let weights: Vec<f64> = raw.iter().map(|w| w / total).collect();
+        let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+        let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+        let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+        let c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+        let cmu = (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff));
+        let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+        let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+        let identity = Matrix::identity(n).get_data();
+        CmaEs {
+            mean: x0,
+            sigma,
+            lambda,
+            generation: 0,
+            n,
+            mu,
+            weights,
+            mueff,
+            cc,
+            cs,
+            c1,
+            cmu,
+            damps,
+            chi_n,
+            cov: identity.clone(),
+            b: identity,
+            d: vec![1.0; n],
+            pc: vec![0.0; n],
+            ps: vec![0.0; n],
+        }
+    }
+
+    // lambda new candidates x = mean + sigma * B D z with z ~ N(0, I)
+    pub fn ask(&self) -> Vec<Vec<f64>> {
+        let n = self.n;
+        return (0..self.lambda).map(|_| {
+            let z: Vec<f64> = (0..n).map(|_| random::normal(0.0, 1.0)).collect();
+            (0..n).map(|i| {
+                let y: f64 = (0..n).map(|j| self.b[i * n + j] * self.d[j] * z[j]).sum();
+                self.mean[i] + self.sigma * y
+            }).collect()
+        }).collect();
+    }
+
+    // updates the distribution from the candidates returned by ask() and their objective values
+    pub fn tell(&mut self, candidates: &[Vec<f64>], fitness: &[f64]) -> Result<()> {
+        let n = self.n;
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by(|&a, &b| fitness[a].total_cmp(&fitness[b]));
+
+        let old_mean = self.mean.clone();
+        let mut mean = vec![0.0; n];
+        for (k, &idx) in order.iter().take(self.mu).enumerate() {
+            for i in 0..n {
+                mean[i] += self.weights[k] * candidates[idx][i];
+            }
+        }
+        let y_w: Vec<f64> = (0..n).map(|i| (mean[i] - old_mean[i]) / self.sigma).collect();
+
+        // C^-1/2 y_w = B D^-1 B^T y_w
+        let bt_y: Vec<f64> = (0..n).map(|j| (0..n).map(|i| self.b[i * n + j] * y_w[i]).sum::<f64>() / self.d[j]).collect();
+        let c_inv_sqrt_y: Vec<f64> = (0..n).map(|i| (0..n).map(|j| self.b[i * n + j] * bt_y[j]).sum()).collect();
+
+        // step size path
+        let a = (self.cs * (2.0 - self.cs) * self.mueff).sqrt();
+        for (p, c) in self.ps.iter_mut().zip(c_inv_sqrt_y.iter()) {
+            *p = (1.0 - self.cs) * *p + a * c;
+        }
+        let ps_norm = self.ps.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let decay = 1.0 - (1.0 - self.cs).powi(2 * (self.generation as i32 + 1));
+        let hsig = if ps_norm / decay.sqrt() / self.chi_n < 1.4 + 2.0 / (n as f64 + 1.0) { 1.0 } else { 0.0 };
+
+        // covariance path
+        let a = (self.cc * (2.0 - self.cc) * self.mueff).sqrt();
+        for (p, y) in self.pc.iter_mut().zip(y_w.iter()) {
+            *p = (1.0 - self.cc) * *p + hsig * a * y;
+        }
+
+        // rank-one and rank-mu covariance update
+        let ys: Vec<Vec<f64>> = order.iter().take(self.mu)
+            .map(|&idx| (0..n).map(|i| (candidates[idx][i] - old_mean[i]) / self.sigma).collect())
+            .collect();
+        let keep = 1.0 - self.c1 - self.cmu + (1.0 - hsig) * self.c1 * self.cc * (2.0 - self.cc);
+        for i in 0..n {
+            for j in 0..n {
+                let rank_mu: f64 = ys.iter().zip(self.weights.iter()).map(|(y, w)| w * y[i] * y[j]).sum();
+                self.cov[i * n + j] = keep * self.cov[i * n + j] + self.c1 * self.pc[i] * self.pc[j] + self.cmu * rank_mu;
+            }
+        }
+
+        self.sigma *= ((self.cs / self.damps) * (ps_norm / self.chi_n - 1.0)).exp();
+        self.mean = mean;
+        self.generation += 1;
+
+        // refresh B and D, symmetrizing away rounding noise first
+        for i in 0..n {
+            for j in 0..i {
+                let avg = 0.5 * (self.cov[i * n + j] + self.cov[j * n + i]);
+                self.cov[i * n + j] = avg;
+                self.cov[j * n + i] = avg;
+            }
+        }
+        let (eigvals, eigvecs) = Matrix::new(n, n, self.cov.clone()).eigh()?;
+        self.b = eigvecs.get_data();
+        self.d = eigvals.iter().map(|v| v.max(1e-20).sqrt()).collect();
+        return Ok(());
+    }
+
+    // one generation over a set of Value parameters, objective reads the current parameter values
+    // the parameters are left at the new mean, whose objective value is returned
+    pub fn step(&mut self, params: &[Value], mut objective: impl FnMut() -> f64) -> Result<f64> {
+        let candidates = self.ask();
+        let fitness: Vec<f64> = candidates.iter().map(|c| {
+            set_flat_params(params, c);
+            objective()
+        }).collect();
+        self.tell(&candidates, &fitness)?;
+        set_flat_params(params, &self.mean);
+        return Ok(objective());
+    }
+}
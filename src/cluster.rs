@@ -0,0 +1,325 @@
+use crate::error::{Result, RustMlError};
+
+// clustering rows of features (rows are samples, like the Trainer takes)
+// labels are cluster indices from 0 in order of first appearance (dbscan marks noise with None)
+
+fn n_cols(xs: &[Vec<f64>]) -> Result<usize> {
+    let d = xs.first().map_or(0, |x| x.len());
+    if let Some((i, x)) = xs.iter().enumerate().find(|(_, x)| x.len() != d) {
+        return Err(RustMlError::Shape(format!("row {} has {} columns, row 0 has {}", i, x.len(), d)));
+    }
+    return Ok(d);
+}
+
+pub fn euclidean(a: &[f64], b: &[f64]) -> f64 {
+    return a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt();
+}
+
+// density based clustering (ester et al. 1996): a point with at least min_samples points
+// (itself included) within eps is a core point, clusters are core points connected through
+// each other's neighbourhoods plus the border points they reach, everything else is noise
+pub struct Dbscan {
+    pub eps: f64,
+    pub min_samples: usize,
+
+    // set by fit
+    pub labels: Vec<Option<usize>>,
+    pub core: Vec<bool>,
+}
+
+impl Dbscan {
+    pub fn new(eps: f64, min_samples: usize) -> Self {
+        Dbscan {
+            eps,
+            min_samples,
+            labels: vec![],
+            core: vec![],
+        }
+    }
+
+    pub fn n_clusters(&self) -> usize {
+        return self.labels.iter().flatten().max().map_or(0, |c| c + 1);
+    }
+
+    pub fn fit(&mut self, xs: &[Vec<f64>]) -> Result<()> {
+        if self.eps <= 0.0 || self.min_samples == 0 {
+            return Err(RustMlError::Config(format!("dbscan needs eps > 0 and min_samples > 0, got eps {} and min_samples {}", self.eps, self.min_samples)));
+        }
+        n_cols(xs)?;
+        let n = xs.len();
+        let neighbours: Vec<Vec<usize>> = (0..n)
+            .map(|i| (0..n).filter(|&j| euclidean(&xs[i], &xs[j]) <= self.eps).collect())
+            .collect();
+        self.core = neighbours.iter().map(|nb| nb.len() >= self.min_samples).collect();
+        self.labels = vec![None; n];
+
+        let mut next = 0;
+        for i in 0..n {
+            if !self.core[i] || self.labels[i].is_some() {
+                continue;
+            }
+            // grow the cluster out from core point i, only core points expand it further
+            self.labels[i] = Some(next);
+            let mut stack = vec![i];
+            while let Some(p) = stack.pop() {
+                for &q in neighbours[p].iter() {
+                    if self.labels[q].is_none() {
+                        self.labels[q] = Some(next);
+                        if self.core[q] {
+                            stack.push(q);
+                        }
+                    }
+                }
+            }
+            next += 1;
+        }
+        tracing::debug!(clusters = next, noise = self.labels.iter().filter(|l| l.is_none()).count(), "dbscan fit");
+        return Ok(());
+    }
+
+    pub fn fit_predict(&mut self, xs: &[Vec<f64>]) -> Result<Vec<Option<usize>>> {
+        self.fit(xs)?;
+        return Ok(self.labels.clone());
+    }
+}
+
+// how the distance between two clusters comes from the distances between their points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linkage {
+    // closest pair
+    Single,
+    // farthest pair
+    Complete,
+    // mean over all pairs
+    Average,
+    // increase in within-cluster sum of squares when merging, tends to give even sized clusters
+    Ward,
+}
+
+// one step of the dendrogram, scipy style: clusters 0..n are the points themselves and
+// the cluster made by merge i is n + i
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Merge {
+    pub a: usize,
+    pub b: usize,
+    pub distance: f64,
+    // number of points in the merged cluster
+    pub size: usize,
+}
+
+// bottom up: start from every point on its own and merge the two closest clusters until one
+// is left, the full merge history is kept so the tree can be cut at any number of clusters
+pub struct Agglomerative {
+    pub n_clusters: usize,
+    pub linkage: Linkage,
+
+    // set by fit, the n - 1 merges in order, heights never decrease with these linkages
+    pub merges: Vec<Merge>,
+    pub labels: Vec<usize>,
+}
+
+impl Agglomerative {
+    // ward linkage by default
+    pub fn new(n_clusters: usize) -> Self {
+        Agglomerative {
+            n_clusters,
+            linkage: Linkage::Ward,
+            merges: vec![],
+            labels: vec![],
+        }
+    }
+
+    pub fn with_linkage(mut self, linkage: Linkage) -> Self {
+        self.linkage = linkage;
+        self
+    }
+
+    pub fn dendrogram(&self) -> &[Merge] {
+        return &self.merges;
+    }
+
+    pub fn fit(&mut self, xs: &[Vec<f64>]) -> Result<()> {
+        let n = xs.len();
+        if self.n_clusters == 0 || self.n_clusters > n {
+            return Err(RustMlError::Config(format!("agglomerative clustering needs 1 <= n_clusters <= {} samples, got {}", n, self.n_clusters)));
+        }
+        n_cols(xs)?;
+
+        // distances between the live clusters, lance-williams updates after every merge
+        let mut dist: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| euclidean(&xs[i], &xs[j])).collect()).collect();
+        let mut size = vec![1usize; n];
+        // id of the cluster currently sitting in each slot, None once merged away
+        let mut id: Vec<Option<usize>> = (0..n).map(Some).collect();
+        self.merges = Vec::with_capacity(n.saturating_sub(1));
+
+        for step in 0..n.saturating_sub(1) {
+            let live: Vec<usize> = (0..n).filter(|&i| id[i].is_some()).collect();
+            let mut best = (f64::INFINITY, 0, 0);
+            for (k, &i) in live.iter().enumerate() {
+                for &j in live[k + 1..].iter() {
+                    if dist[i][j] < best.0 {
+                        best = (dist[i][j], i, j);
+                    }
+                }
+            }
+            let (d, i, j) = best;
+            let (ni, nj) = (size[i] as f64, size[j] as f64);
+            for &k in live.iter().filter(|&&k| k != i && k != j) {
+                let (dki, dkj) = (dist[k][i], dist[k][j]);
+                let nk = size[k] as f64;
+                let merged = match self.linkage {
+                    Linkage::Single => dki.min(dkj),
+                    Linkage::Complete => dki.max(dkj),
+                    Linkage::Average => (ni * dki + nj * dkj) / (ni + nj),
+                    Linkage::Ward => (((ni + nk) * dki * dki + (nj + nk) * dkj * dkj - nk * d * d) / (ni + nj + nk)).max(0.0).sqrt(),
+                };
+                dist[k][i] = merged;
+                dist[i][k] = merged;
+            }
+            self.merges.push(Merge {
+                a: id[i].unwrap(),
+                b: id[j].unwrap(),
+                distance: d,
+                size: size[i] + size[j],
+            });
+            // the merged cluster lives on in slot i
+            size[i] += size[j];
+            id[i] = Some(n + step);
+            id[j] = None;
+        }
+        self.labels = self.cut(self.n_clusters);
+        return Ok(());
+    }
+
+    // labels with the tree cut at k clusters, i.e. the last k - 1 merges undone
+    pub fn cut(&self, k: usize) -> Vec<usize> {
+        let n = self.merges.len() + 1;
+        assert!(k >= 1 && k <= n, "can cut into 1 to {} clusters, got {}", n, k);
+        // union find over the first n - k merges
+        let mut parent: Vec<usize> = (0..2 * n - 1).collect();
+        fn root(parent: &[usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                i = parent[i];
+            }
+            return i;
+        }
+        for (step, m) in self.merges[..n - k].iter().enumerate() {
+            parent[m.a] = n + step;
+            parent[m.b] = n + step;
+        }
+        let mut ids: Vec<usize> = vec![];
+        return (0..n).map(|i| {
+            let r = root(&parent, i);
+            ids.iter().position(|&c| c == r).unwrap_or_else(|| {
+                ids.push(r);
+                ids.len() - 1
+            })
+        }).collect();
+    }
+
+    pub fn fit_predict(&mut self, xs: &[Vec<f64>]) -> Result<Vec<usize>> {
+        self.fit(xs)?;
+        return Ok(self.labels.clone());
+    }
+}
+
+// internal evaluation: how well a labelling fits the data, no ground truth needed
+// (for dbscan, leave the noise points out first)
+
+fn check_labels(xs: &[Vec<f64>], labels: &[usize]) -> Result<usize> {
+    if xs.len() != labels.len() {
+        return Err(RustMlError::Shape(format!("{} rows but {} labels", xs.len(), labels.len())));
+    }
+    n_cols(xs)?;
+    return Ok(labels.iter().max().map_or(0, |l| l + 1));
+}
+
+fn centroids(xs: &[Vec<f64>], labels: &[usize], k: usize) -> Vec<Option<Vec<f64>>> {
+    let d = xs.first().map_or(0, |x| x.len());
+    let mut sums = vec![vec![0.0; d]; k];
+    let mut counts = vec![0usize; k];
+    for (x, &l) in xs.iter().zip(labels.iter()) {
+        counts[l] += 1;
+        for (s, v) in sums[l].iter_mut().zip(x.iter()) {
+            *s += v;
+        }
+    }
+    return sums.into_iter().zip(counts).map(|(s, c)| if c == 0 { None } else { Some(s.iter().map(|v| v / c as f64).collect()) }).collect();
+}
+
+// per sample (b - a) / max(a, b), a the mean distance to its own cluster, b the mean distance to the
+// nearest other cluster; from -1 (wrong cluster) to 1 (well inside its own), 0 for points alone in their cluster
+pub fn silhouette_samples(xs: &[Vec<f64>], labels: &[usize]) -> Result<Vec<f64>> {
+    let k = check_labels(xs, labels)?;
+    let used = (0..k).filter(|c| labels.contains(c)).count();
+    if used < 2 || used >= xs.len() {
+        return Err(RustMlError::Config(format!("silhouette needs 2 to n - 1 clusters, got {} for {} samples", used, xs.len())));
+    }
+    return Ok((0..xs.len()).map(|i| {
+        let mut sums = vec![0.0; k];
+        let mut counts = vec![0usize; k];
+        for (j, &l) in labels.iter().enumerate() {
+            if j != i {
+                sums[l] += euclidean(&xs[i], &xs[j]);
+                counts[l] += 1;
+            }
+        }
+        let own = labels[i];
+        if counts[own] == 0 {
+            return 0.0;
+        }
+        let a = sums[own] / counts[own] as f64;
+        let b = (0..k).filter(|&c| c != own && counts[c] > 0).map(|c| sums[c] / counts[c] as f64).fold(f64::INFINITY, f64::min);
+        if a.max(b) == 0.0 { 0.0 } else { (b - a) / a.max(b) }
+    }).collect());
+}
+
+// mean silhouette over all samples, higher is better
+pub fn silhouette_score(xs: &[Vec<f64>], labels: &[usize]) -> Result<f64> {
+    let s = silhouette_samples(xs, labels)?;
+    return Ok(s.iter().sum::<f64>() / s.len() as f64);
+}
+
+// davies-bouldin index: mean over clusters of the worst (s_i + s_j) / d(c_i, c_j), s the mean distance
+// to the centroid and d the centroid distance; lower is better, 0 is the minimum
+pub fn davies_bouldin(xs: &[Vec<f64>], labels: &[usize]) -> Result<f64> {
+    let k = check_labels(xs, labels)?;
+    let centers: Vec<(usize, Vec<f64>)> = centroids(xs, labels, k).into_iter().enumerate().filter_map(|(c, m)| m.map(|m| (c, m))).collect();
+    if centers.len() < 2 {
+        return Err(RustMlError::Config("davies-bouldin needs at least 2 clusters".to_string()));
+    }
+    let spread: Vec<f64> = centers.iter().map(|(c, m)| {
+        let members: Vec<f64> = xs.iter().zip(labels.iter()).filter(|(_, l)| *l == c).map(|(x, _)| euclidean(x, m)).collect();
+        members.iter().sum::<f64>() / members.len() as f64
+    }).collect();
+    let worst = (0..centers.len()).map(|i| {
+        (0..centers.len()).filter(|&j| j != i).map(|j| {
+            let d = euclidean(&centers[i].1, &centers[j].1);
+            if d > 0.0 { (spread[i] + spread[j]) / d } else { f64::INFINITY }
+        }).fold(0.0, f64::max)
+    });
+    return Ok(worst.sum::<f64>() / centers.len() as f64);
+}
+
+// within-cluster sum of squared distances to the centroids
+pub fn inertia(xs: &[Vec<f64>], labels: &[usize]) -> Result<f64> {
+    let k = check_labels(xs, labels)?;
+    let centers = centroids(xs, labels, k);
+    return Ok(xs.iter().zip(labels.iter()).map(|(x, &l)| {
+        let m = centers[l].as_ref().unwrap();
+        x.iter().zip(m.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f64>()
+    }).sum());
+}
+
+// (k, inertia) for every k in ks, clustering with ward linkage; the tree is built once and cut at each k
+// inertia always falls with k, the elbow where it stops falling fast is the usual pick
+pub fn elbow_curve(xs: &[Vec<f64>], ks: &[usize]) -> Result<Vec<(usize, f64)>> {
+    let mut model = Agglomerative::new(1);
+    model.fit(xs)?;
+    return ks.iter().map(|&k| {
+        if k == 0 || k > xs.len() {
+            return Err(RustMlError::Config(format!("can cut into 1 to {} clusters, got {}", xs.len(), k)));
+        }
+        Ok((k, inertia(xs, &model.cut(k))?))
+    }).collect();
+}
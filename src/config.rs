@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{self, Samples};
+use crate::datasets;
+use crate::error::{Result, RustMlError};
+use crate::nn::{Activation, Module, MLP};
+use crate::optim::Schedule;
+use crate::random;
+use crate::stats;
+use crate::serialize::{ModelBundle, ModelInfo};
+use crate::train::{self, Trainer};
+
+// a training run written down as data: model, optimizer, learning rate schedule, data and seed,
+// so experiments are declarative and two runs can be diffed. toml (or json) like
+//
+//     seed = 42
+//     output = "moons.json"
+//
+//     [model]
+//     layers = [2, 16, 16, 1]
+//
+//     [optimizer]
+//     type = "adam"
+//     lr = 0.01
+//
+//     [scheduler]
+//     type = "cosine"
+//     min_lr = 0.001
+//
+//     [data]
+//     source = "moons"
+//     n = 200
+//     noise = 0.1
+//
+//     [training]
+//     epochs = 100
+//     batch_size = 32
+//
+// run() trains it, Trainer::from_config sets up a trainer from it for a custom loop
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrainConfig {
+    // passed to random::seed before anything is built, for a reproducible run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    // where run() saves the trained model (as a ModelBundle), nowhere if None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    pub model: ModelConfig,
+    pub optimizer: OptimizerConfig,
+    #[serde(default)]
+    pub scheduler: Schedule,
+    pub data: DataConfig,
+    pub training: TrainingConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfig {
+    // layer sizes of an MLP, inputs first
+    pub layers: Vec<usize>,
+    #[serde(default = "tanh")]
+    pub activation: Activation,
+}
+
+fn tanh() -> Activation {
+    return Activation::Tanh;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OptimizerConfig {
+    Sgd {
+        lr: f64,
+        #[serde(default)]
+        momentum: f64,
+        #[serde(default)]
+        weight_decay: f64,
+    },
+    Adam {
+        lr: f64,
+    },
+}
+
+impl OptimizerConfig {
+    pub fn lr(&self) -> f64 {
+        return match *self {
+            OptimizerConfig::Sgd { lr, .. } | OptimizerConfig::Adam { lr } => lr,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum DataConfig {
+    // data::read_csv, a relative path is relative to the config file when loaded with TrainConfig::load
+    Csv {
+        path: PathBuf,
+        #[serde(default = "one")]
+        targets: usize,
+        #[serde(default)]
+        header: bool,
+    },
+    // the synthetic datasets, targets are signs for a model with one output and one-hot otherwise
+    Moons {
+        n: usize,
+        #[serde(default)]
+        noise: f64,
+    },
+    Circles {
+        n: usize,
+        #[serde(default)]
+        noise: f64,
+        #[serde(default = "half")]
+        factor: f64,
+    },
+    Blobs {
+        n: usize,
+        centers: Vec<(f64, f64)>,
+        #[serde(default = "one_f64")]
+        std: f64,
+    },
+}
+
+fn one() -> usize {
+    return 1;
+}
+
+fn half() -> f64 {
+    return 0.5;
+}
+
+fn one_f64() -> f64 {
+    return 1.0;
+}
+
+impl DataConfig {
+    // features and target rows, outputs is the model's output size
+    pub fn load(&self, outputs: usize) -> Result<Samples> {
+        let dataset = match self {
+            DataConfig::Csv { path, targets, header } => return data::read_csv(path, *targets, *header),
+            DataConfig::Moons { n, noise } => datasets::moons(*n, *noise),
+            DataConfig::Circles { n, noise, factor } => datasets::circles(*n, *noise, *factor),
+            DataConfig::Blobs { n, centers, std } => datasets::blobs(*n, centers, *std),
+        };
+        let ys = if outputs == 1 { dataset.signs() } else { dataset.one_hot() };
+        return Ok((dataset.xs, ys));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LossConfig {
+    #[default]
+    Mse,
+    // the model outputs are taken as logits
+    CrossEntropy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrainingConfig {
+    pub epochs: usize,
+    // full batch if None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    #[serde(default)]
+    pub loss: LossConfig,
+    // wall-clock budget, training stops after the batch that runs past it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_seconds: Option<f64>,
+    // saved when training stops (finished, out of time or ctrl-c); run() resumes from it if it exists,
+    // relative to the config file like the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<PathBuf>,
+}
+
+impl TrainConfig {
+    pub fn from_toml(text: &str) -> Result<TrainConfig> {
+        let config: TrainConfig = toml::from_str(text).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        config.validate()?;
+        return Ok(config);
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        return toml::to_string(self).map_err(|e| RustMlError::Serialization(e.to_string()));
+    }
+
+    pub fn from_json(text: &str) -> Result<TrainConfig> {
+        let config: TrainConfig = serde_json::from_str(text).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        config.validate()?;
+        return Ok(config);
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        return serde_json::to_string_pretty(self).map_err(|e| RustMlError::Serialization(e.to_string()));
+    }
+
+    // json for a .json file, toml for anything else; relative data, output and checkpoint paths are taken
+    // relative to the config file's directory
+    pub fn load(path: impl AsRef<Path>) -> Result<TrainConfig> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut config = if path.extension().is_some_and(|e| e == "json") {
+            TrainConfig::from_json(&text)?
+        } else {
+            TrainConfig::from_toml(&text)?
+        };
+        let base = path.parent().unwrap_or(Path::new(""));
+        if let DataConfig::Csv { path, .. } = &mut config.data {
+            *path = base.join(&*path);
+        }
+        for path in [&mut config.output, &mut config.training.checkpoint].into_iter().flatten() {
+            *path = base.join(&*path);
+        }
+        return Ok(config);
+    }
+
+    // everything serde can't check on its own
+    pub fn validate(&self) -> Result<()> {
+        let bad = |msg: String| Err(RustMlError::Config(msg));
+        if self.model.layers.len() < 2 || self.model.layers.contains(&0) {
+            return bad(format!("model needs at least an input and an output size, all positive, got {:?}", self.model.layers));
+        }
+        if self.optimizer.lr().is_nan() || self.optimizer.lr() <= 0.0 {
+            return bad(format!("learning rate must be positive, got {}", self.optimizer.lr()));
+        }
+        if let Schedule::Step { every: 0, .. } = self.scheduler {
+            return bad("step schedule needs every > 0".to_string());
+        }
+        if let Schedule::Cyclic { period: 0, .. } = self.scheduler {
+            return bad("cyclic schedule needs period > 0".to_string());
+        }
+        if let Schedule::OneCycle { warmup, div, final_div, .. } = self.scheduler {
+            if !(0.0..1.0).contains(&warmup) || div.is_nan() || div <= 0.0 || final_div.is_nan() || final_div <= 0.0 {
+                return bad(format!("one-cycle schedule needs warmup in [0, 1) and positive divisors, got {}, {} and {}", warmup, div, final_div));
+            }
+        }
+        if self.training.epochs == 0 {
+            return bad("training needs at least one epoch".to_string());
+        }
+        if let Some(t) = self.training.max_seconds.filter(|t| t.is_nan() || *t <= 0.0) {
+            return bad(format!("max_seconds must be positive, got {}", t));
+        }
+        if self.training.batch_size == Some(0) {
+            return bad("batch size must be positive".to_string());
+        }
+        if let DataConfig::Csv { targets: 0, .. } = self.data {
+            return bad("csv data needs at least one target column".to_string());
+        }
+        return Ok(());
+    }
+
+    // the hyperparameters worth keeping with the trained model
+    fn info(&self) -> ModelInfo {
+        let info = ModelInfo::new()
+            .with_hyperparameter("layers", format!("{:?}", self.model.layers))
+            .with_hyperparameter("activation", format!("{:?}", self.model.activation))
+            .with_hyperparameter("optimizer", format!("{:?}", self.optimizer))
+            .with_hyperparameter("scheduler", format!("{:?}", self.scheduler))
+            .with_hyperparameter("epochs", self.training.epochs)
+            .with_hyperparameter("loss", format!("{:?}", self.training.loss));
+        return match self.training.batch_size {
+            Some(b) => info.with_hyperparameter("batch_size", b),
+            None => info,
+        };
+    }
+}
+
+// path with -seed<seed> added before the extension, so runs of different seeds don't overwrite each other
+fn seed_path(path: &Path, seed: u64) -> PathBuf {
+    let stem = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let name = match path.extension() {
+        Some(ext) => format!("{}-seed{}.{}", stem, seed, ext.to_string_lossy()),
+        None => format!("{}-seed{}", stem, seed),
+    };
+    return path.with_file_name(name);
+}
+
+// one final metric across seeds
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSummary {
+    pub mean: f64,
+    // sample standard deviation, 0 for a single seed
+    pub std: f64,
+    // in the order of the seeds
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedRuns {
+    pub seeds: Vec<u64>,
+    pub metrics: BTreeMap<String, MetricSummary>,
+}
+
+// one line per metric, e.g. "loss: 0.0123 ± 0.0045 (5 seeds)"
+impl Display for SeedRuns {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (name, m) in self.metrics.iter() {
+            writeln!(f, "{}: {:.4} ± {:.4} ({} seeds)", name, m.mean, m.std, self.seeds.len())?;
+        }
+        Ok(())
+    }
+}
+
+// the same run once per seed and the mean ± std of its final metrics, since one seed of a tiny network
+// says little; outputs and checkpoints get the seed added to their file names
+// with parallel set the seeds run on the rayon pool, each in its own thread with its own generator,
+// the results don't depend on it
+pub fn run_seeds(config: &TrainConfig, seeds: &[u64], parallel: bool) -> Result<SeedRuns> {
+    if seeds.is_empty() {
+        return Err(RustMlError::Config("no seeds to run".to_string()));
+    }
+    let run_one = |seed: &u64| -> Result<BTreeMap<String, f64>> {
+        let mut config = config.clone();
+        config.seed = Some(*seed);
+        for path in [&mut config.output, &mut config.training.checkpoint].into_iter().flatten() {
+            *path = seed_path(path, *seed);
+        }
+        let (bundle, _) = run(&config)?;
+        return Ok(bundle.info().metrics.clone());
+    };
+    let results: Vec<BTreeMap<String, f64>> = if parallel {
+        seeds.par_iter().map(run_one).collect::<Result<_>>()?
+    } else {
+        seeds.iter().map(run_one).collect::<Result<_>>()?
+    };
+
+    let mut metrics = BTreeMap::new();
+    for name in results[0].keys() {
+        let values: Vec<f64> = results.iter().filter_map(|r| r.get(name).copied()).collect();
+        let std = if values.len() > 1 { stats::std(&values, 1) } else { 0.0 };
+        metrics.insert(name.clone(), MetricSummary { mean: stats::mean(&values), std, values });
+    }
+    return Ok(SeedRuns {
+        seeds: seeds.to_vec(),
+        metrics,
+    });
+}
+
+// trains the run the config describes: seeds, loads the data, builds the model and fits it, carrying on
+// from the checkpoint if there is one; returns the model with its ModelInfo (also saved to config.output
+// if set) and the losses of the epochs trained in this call
+pub fn run(config: &TrainConfig) -> Result<(ModelBundle, Vec<f64>)> {
+    config.validate()?;
+    if let Some(seed) = config.seed {
+        random::seed(seed);
+    }
+    let layers = &config.model.layers;
+    let (xs, ys) = config.data.load(layers[layers.len() - 1])?;
+    if xs.is_empty() {
+        return Err(RustMlError::Config("the data has no samples".to_string()));
+    }
+    if xs[0].len() != layers[0] || ys[0].len() != layers[layers.len() - 1] {
+        return Err(RustMlError::Shape(format!("model is {:?} but the data has {} features and {} targets", layers, xs[0].len(), ys[0].len())));
+    }
+
+    let _span = tracing::info_span!("run", seed = config.seed).entered();
+    let model = MLP::with_activation(layers, config.model.activation);
+    let mut trainer = Trainer::from_config(&model, config);
+    if let Some(checkpoint) = config.training.checkpoint.as_ref().filter(|p| p.exists()) {
+        trainer.resume(checkpoint)?;
+    }
+    let losses = trainer.fit(&xs, &ys, config.training.epochs);
+
+    let mut info = config.info().with_dataset(&xs, &ys);
+    if let Some(loss) = trainer.history.last() {
+        info = info.with_metric("loss", *loss);
+    }
+    drop(trainer);
+    // the synthetic datasets are classification problems
+    if !matches!(config.data, DataConfig::Csv { .. }) {
+        let correct = model.predict_batch(&xs).iter().zip(ys.iter()).filter(|(out, y)| train::is_correct(out, y)).count();
+        info = info.with_metric("accuracy", correct as f64 / xs.len() as f64);
+    }
+    let bundle = ModelBundle::new(model, info);
+    if let Some(path) = &config.output {
+        bundle.save(path)?;
+        tracing::info!(path = %path.display(), "saved model");
+    }
+    return Ok((bundle, losses));
+}
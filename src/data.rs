@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Result, RustMlError};
+use crate::random;
+
+// feeding a dataset to training in batches
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // every sample once per epoch, rows as they are
+    Samples,
+    // every sample paired with a random partner: x = [x_a, x_b], y = [1] if both have the same
+    // target row (same class), [0] if not, about half of each
+    Pairs,
+    // every sample as an anchor: x = [anchor, positive, negative], y = the anchor's target row,
+    // positive from the same class, negative from another one
+    Triplets,
+}
+
+// batch-level augmentations, applied in order to every batch the loader yields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Augment {
+    // mixup (zhang et al. 2018): every sample is blended with another one from the same batch,
+    // inputs and targets alike, with one weight ~ beta(alpha, alpha) per batch; the targets
+    // become soft, so pair it with a loss that takes them (mse, cross entropy on probabilities)
+    Mixup { alpha: f64 },
+    // with probability p a target row is swapped for the row of a uniformly drawn other class,
+    // for studying robustness to mislabelled data
+    LabelNoise { p: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Batch {
+    pub xs: Vec<Vec<f64>>,
+    pub ys: Vec<Vec<f64>>,
+}
+
+pub struct DataLoader<'d> {
+    pub xs: &'d [Vec<f64>],
+    pub ys: &'d [Vec<f64>],
+    pub batch_size: usize,
+    pub shuffle: bool,
+    pub mode: Mode,
+    pub augment: Vec<Augment>,
+}
+
+impl<'d> DataLoader<'d> {
+    // shuffled batches of single samples by default
+    pub fn new(xs: &'d [Vec<f64>], ys: &'d [Vec<f64>], batch_size: usize) -> Self {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        assert!(batch_size > 0, "batch size must be positive");
+        DataLoader {
+            xs,
+            ys,
+            batch_size,
+            shuffle: true,
+            mode: Mode::Samples,
+            augment: vec![],
+        }
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_augment(mut self, augment: Augment) -> Self {
+        match augment {
+            Augment::Mixup { alpha } => assert!(alpha > 0.0, "mixup alpha must be positive, got {}", alpha),
+            Augment::LabelNoise { p } => assert!((0.0..=1.0).contains(&p), "label noise probability must be in [0, 1], got {}", p),
+        }
+        self.augment.push(augment);
+        self
+    }
+
+    // one epoch worth of batches, a fresh shuffle and fresh partners every call
+    pub fn batches(&self) -> Vec<Batch> {
+        let mut order: Vec<usize> = (0..self.xs.len()).collect();
+        if self.shuffle {
+            random::shuffle(&mut order);
+        }
+        let rows: Vec<(Vec<f64>, Vec<f64>)> = match self.mode {
+            Mode::Samples => order.iter().map(|&i| (self.xs[i].clone(), self.ys[i].clone())).collect(),
+            Mode::Pairs => {
+                let (classes, class_of) = group_by_target(self.ys);
+                order.iter().filter_map(|&i| {
+                    let same = pick_same(&classes, class_of[i], i);
+                    let other = pick_other(&classes, class_of[i]);
+                    let (j, similar) = match (same, other) {
+                        (Some(j), Some(k)) => if random::bernoulli(0.5) { (j, true) } else { (k, false) },
+                        (Some(j), None) => (j, true),
+                        (None, Some(k)) => (k, false),
+                        (None, None) => return None,
+                    };
+                    Some(([self.xs[i].as_slice(), &self.xs[j]].concat(), vec![if similar { 1.0 } else { 0.0 }]))
+                }).collect()
+            },
+            Mode::Triplets => {
+                let (classes, class_of) = group_by_target(self.ys);
+                // anchors that are alone in their class, or in the only class, have no triplet
+                order.iter().filter_map(|&i| {
+                    let p = pick_same(&classes, class_of[i], i)?;
+                    let n = pick_other(&classes, class_of[i])?;
+                    Some(([self.xs[i].as_slice(), &self.xs[p], &self.xs[n]].concat(), self.ys[i].clone()))
+                }).collect()
+            },
+        };
+        // the classes label noise draws from are those of the original target rows
+        let targets: Vec<Vec<f64>> = if self.augment.iter().any(|a| matches!(a, Augment::LabelNoise { .. })) {
+            group_by_target(self.ys).0.iter().map(|members| self.ys[members[0]].clone()).collect()
+        } else {
+            vec![]
+        };
+        return rows.chunks(self.batch_size).map(|chunk| {
+            let mut batch = Batch {
+                xs: chunk.iter().map(|(x, _)| x.clone()).collect(),
+                ys: chunk.iter().map(|(_, y)| y.clone()).collect(),
+            };
+            for a in self.augment.iter() {
+                match *a {
+                    Augment::Mixup { alpha } => mixup(&mut batch, alpha),
+                    Augment::LabelNoise { p } => label_noise(&mut batch, &targets, p),
+                }
+            }
+            batch
+        }).collect();
+    }
+}
+
+// samples grouped by target row (i.e. by class), in order of first appearance, and the class of each sample
+fn group_by_target(ys: &[Vec<f64>]) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut ids: HashMap<Vec<u64>, usize> = HashMap::new();
+    let mut classes: Vec<Vec<usize>> = vec![];
+    let mut class_of = Vec::with_capacity(ys.len());
+    for (i, y) in ys.iter().enumerate() {
+        let key: Vec<u64> = y.iter().map(|v| v.to_bits()).collect();
+        let c = *ids.entry(key).or_insert_with(|| {
+            classes.push(vec![]);
+            classes.len() - 1
+        });
+        classes[c].push(i);
+        class_of.push(c);
+    }
+    return (classes, class_of);
+}
+
+// another member of class c than i
+fn pick_same(classes: &[Vec<usize>], c: usize, i: usize) -> Option<usize> {
+    let members = &classes[c];
+    if members.len() < 2 {
+        return None;
+    }
+    let k = random::index(members.len() - 1);
+    // skip over i by shifting everything at or after its position
+    let pos = members.iter().position(|&m| m == i).unwrap_or(members.len());
+    return Some(members[if k >= pos { k + 1 } else { k }]);
+}
+
+// a member of any class other than c
+fn pick_other(classes: &[Vec<usize>], c: usize) -> Option<usize> {
+    if classes.len() < 2 {
+        return None;
+    }
+    let k = random::index(classes.len() - 1);
+    let other = &classes[if k >= c { k + 1 } else { k }];
+    return Some(other[random::index(other.len())]);
+}
+
+fn blend(a: &[f64], b: &[f64], lambda: f64) -> Vec<f64> {
+    return a.iter().zip(b.iter()).map(|(x, y)| lambda * x + (1.0 - lambda) * y).collect();
+}
+
+fn mixup(batch: &mut Batch, alpha: f64) {
+    let lambda = random::beta(alpha, alpha);
+    let mut partner: Vec<usize> = (0..batch.xs.len()).collect();
+    random::shuffle(&mut partner);
+    let xs = partner.iter().enumerate().map(|(i, &j)| blend(&batch.xs[i], &batch.xs[j], lambda)).collect();
+    let ys = partner.iter().enumerate().map(|(i, &j)| blend(&batch.ys[i], &batch.ys[j], lambda)).collect();
+    batch.xs = xs;
+    batch.ys = ys;
+}
+
+fn label_noise(batch: &mut Batch, targets: &[Vec<f64>], p: f64) {
+    if targets.len() < 2 {
+        return;
+    }
+    for y in batch.ys.iter_mut() {
+        if !random::bernoulli(p) {
+            continue;
+        }
+        let others: Vec<&Vec<f64>> = targets.iter().filter(|t| *t != y).collect();
+        if !others.is_empty() {
+            *y = others[random::index(others.len())].clone();
+        }
+    }
+}
+
+// splitting a dataset, by sample index so xs, ys and anything else per sample split the same way
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Split {
+    pub train: Vec<usize>,
+    pub test: Vec<usize>,
+}
+
+// the rows at the given indices
+pub fn select<T: Clone>(rows: &[T], idx: &[usize]) -> Vec<T> {
+    return idx.iter().map(|&i| rows[i].clone()).collect();
+}
+
+// random split with round(n * test_fraction) samples in the test set
+pub fn train_test_split(n: usize, test_fraction: f64) -> Split {
+    assert!((0.0..=1.0).contains(&test_fraction), "test fraction must be in [0, 1], got {}", test_fraction);
+    let mut idx: Vec<usize> = (0..n).collect();
+    random::shuffle(&mut idx);
+    let test = idx.split_off(n - (n as f64 * test_fraction).round() as usize);
+    return Split { train: idx, test };
+}
+
+// same, but every class (distinct target row) is split on its own, so both sides keep the class proportions
+// up to rounding within each class
+pub fn stratified_split(ys: &[Vec<f64>], test_fraction: f64) -> Split {
+    assert!((0.0..=1.0).contains(&test_fraction), "test fraction must be in [0, 1], got {}", test_fraction);
+    let mut split = Split { train: vec![], test: vec![] };
+    for mut members in group_by_target(ys).0 {
+        random::shuffle(&mut members);
+        let n = members.len();
+        split.test.extend(members.split_off(n - (n as f64 * test_fraction).round() as usize));
+        split.train.extend(members);
+    }
+    random::shuffle(&mut split.train);
+    random::shuffle(&mut split.test);
+    return split;
+}
+
+fn folds_from(groups: Vec<Vec<usize>>, n: usize, k: usize) -> Vec<Split> {
+    assert!(k >= 2 && k <= n, "k-fold needs 2 <= k <= {} samples, got k = {}", n, k);
+    let mut folds: Vec<Vec<usize>> = vec![vec![]; k];
+    // dealt round robin, carrying on across groups so the folds end up within one sample of each other
+    let mut next = 0;
+    for mut members in groups {
+        random::shuffle(&mut members);
+        for i in members {
+            folds[next].push(i);
+            next = (next + 1) % k;
+        }
+    }
+    return (0..k).map(|f| Split {
+        train: (0..k).filter(|g| *g != f).flat_map(|g| folds[g].iter().cloned()).collect(),
+        test: folds[f].clone(),
+    }).collect();
+}
+
+// k train/test splits whose test sets partition the samples
+pub fn k_fold(n: usize, k: usize) -> Vec<Split> {
+    return folds_from(vec![(0..n).collect()], n, k);
+}
+
+// k-fold where every fold gets its share of every class
+pub fn stratified_k_fold(ys: &[Vec<f64>], k: usize) -> Vec<Split> {
+    return folds_from(group_by_target(ys).0, ys.len(), k);
+}
+
+// feature rows and their target rows
+pub type Samples = (Vec<Vec<f64>>, Vec<Vec<f64>>);
+
+// numeric csv, one sample per line: the last `targets` columns are the target row, the rest the features
+// with header set the first line is skipped
+pub fn read_csv(path: impl AsRef<Path>, targets: usize, header: bool) -> Result<Samples> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let (mut xs, mut ys) = (vec![], vec![]);
+    for (i, line) in text.lines().enumerate().skip(if header { 1 } else { 0 }).filter(|(_, l)| !l.trim().is_empty()) {
+        let row = line.split(',').map(|v| v.trim().parse::<f64>()).collect::<std::result::Result<Vec<f64>, _>>()
+            .map_err(|e| RustMlError::Serialization(format!("{}: line {}: {}", path.display(), i + 1, e)))?;
+        if row.len() <= targets {
+            return Err(RustMlError::Shape(format!("{}: line {} has {} columns, need more than the {} targets", path.display(), i + 1, row.len(), targets)));
+        }
+        if let Some(first) = xs.first().map(|x: &Vec<f64>| x.len() + targets) {
+            if row.len() != first {
+                return Err(RustMlError::Shape(format!("{}: line {} has {} columns, expected {}", path.display(), i + 1, row.len(), first)));
+            }
+        }
+        let (x, y) = row.split_at(row.len() - targets);
+        xs.push(x.to_vec());
+        ys.push(y.to_vec());
+    }
+    return Ok((xs, ys));
+}
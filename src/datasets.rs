@@ -0,0 +1,85 @@
+use std::f64::consts::PI;
+
+use crate::random;
+
+// small synthetic 2-d classification datasets for trying models out
+
+pub struct Dataset {
+    pub xs: Vec<Vec<f64>>,
+    pub labels: Vec<usize>,
+}
+
+impl Dataset {
+    pub fn len(&self) -> usize {
+        return self.xs.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.xs.is_empty();
+    }
+
+    pub fn n_classes(&self) -> usize {
+        return self.labels.iter().max().map_or(0, |m| m + 1);
+    }
+
+    // labels as one-hot target rows, for cross entropy
+    pub fn one_hot(&self) -> Vec<Vec<f64>> {
+        let k = self.n_classes();
+        return self.labels.iter().map(|l| {
+            let mut y = vec![0.0; k];
+            y[*l] = 1.0;
+            y
+        }).collect();
+    }
+
+    // labels as +1 / -1 targets, for a single tanh output
+    pub fn signs(&self) -> Vec<Vec<f64>> {
+        return self.labels.iter().map(|l| vec![if *l == 0 { -1.0 } else { 1.0 }]).collect();
+    }
+}
+
+fn jitter(x: f64, noise: f64) -> f64 {
+    return if noise > 0.0 { x + random::normal(0.0, noise) } else { x };
+}
+
+// two interleaving half circles, n points split between them
+pub fn moons(n: usize, noise: f64) -> Dataset {
+    let mut xs = vec![];
+    let mut labels = vec![];
+    for (label, count) in [(0, n.div_ceil(2)), (1, n / 2)] {
+        for j in 0..count {
+            let t = PI * j as f64 / (count.max(2) - 1) as f64;
+            let (x, y) = if label == 0 { (t.cos(), t.sin()) } else { (1.0 - t.cos(), 0.5 - t.sin()) };
+            xs.push(vec![jitter(x, noise), jitter(y, noise)]);
+            labels.push(label);
+        }
+    }
+    return Dataset { xs, labels };
+}
+
+// a small circle (label 1) inside a big one (label 0), factor is the ratio of their radii
+pub fn circles(n: usize, noise: f64, factor: f64) -> Dataset {
+    let mut xs = vec![];
+    let mut labels = vec![];
+    for i in 0..n {
+        let label = i % 2;
+        let t = 2.0 * PI * random::uniform(0.0, 1.0);
+        let r = if label == 0 { 1.0 } else { factor };
+        xs.push(vec![jitter(r * t.cos(), noise), jitter(r * t.sin(), noise)]);
+        labels.push(label);
+    }
+    return Dataset { xs, labels };
+}
+
+// isotropic gaussian blobs around the given centers, label k for centers[k]
+pub fn blobs(n: usize, centers: &[(f64, f64)], std: f64) -> Dataset {
+    let mut xs = vec![];
+    let mut labels = vec![];
+    for i in 0..n {
+        let label = i % centers.len();
+        let (cx, cy) = centers[label];
+        xs.push(vec![random::normal(cx, std), random::normal(cy, std)]);
+        labels.push(label);
+    }
+    return Dataset { xs, labels };
+}
@@ -0,0 +1,133 @@
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::random;
+
+// decoding helpers for sequence models
+// `step` takes the tokens generated so far and returns the logits for the next token,
+// so any model can be plugged in, e.g. |seq| model.forward(..).iter().map(|v| v.get_data()).collect()
+
+// log softmax with the max-shift trick so large logits don't overflow
+pub fn log_softmax(logits: &[f64]) -> Vec<f64> {
+    let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let sum = logits.iter().map(|l| (l - max).exp()).sum::<f64>();
+    let log_sum = max + sum.ln();
+    return logits.iter().map(|l| l - log_sum).collect();
+}
+
+fn argmax(xs: &[f64]) -> usize {
+    let mut best = 0;
+    for i in 1..xs.len() {
+        if xs[i] > xs[best] {
+            best = i;
+        }
+    }
+    return best;
+}
+
+// greedy decoding: always take the highest scoring token
+// stops after max_len new tokens or when eos is produced
+pub fn greedy<F>(mut step: F, start: &[usize], max_len: usize, eos: Option<usize>) -> Vec<usize>
+where
+    F: FnMut(&[usize]) -> Vec<f64>,
+{
+    let mut seq = start.to_vec();
+    for _ in 0..max_len {
+        let token = argmax(&step(&seq));
+        seq.push(token);
+        if Some(token) == eos {
+            break;
+        }
+    }
+    return seq;
+}
+
+// top-k sampling: sample the next token from the k most likely ones,
+// after dividing the logits by the temperature
+pub fn sample_top_k<F>(
+    mut step: F,
+    start: &[usize],
+    max_len: usize,
+    k: usize,
+    temperature: f64,
+    eos: Option<usize>,
+) -> Vec<usize>
+where
+    F: FnMut(&[usize]) -> Vec<f64>,
+{
+    assert!(k > 0, "top-k sampling needs k > 0");
+    assert!(temperature > 0.0, "temperature must be positive");
+
+    let mut seq = start.to_vec();
+    for _ in 0..max_len {
+        let logits = step(&seq);
+
+        // keep the k best candidates
+        let mut candidates: Vec<(usize, f64)> = logits.iter().map(|l| l / temperature).enumerate().collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.truncate(k);
+
+        // softmax over the candidates, then sample
+        let max = candidates[0].1;
+        let weights: Vec<f64> = candidates.iter().map(|c| (c.1 - max).exp()).collect();
+        let token = candidates[random::categorical(&weights)].0;
+
+        seq.push(token);
+        if Some(token) == eos {
+            break;
+        }
+    }
+    return seq;
+}
+
+// beam search: keep the beam_width best partial sequences by total log probability
+// returns the finished beams sorted from best to worst, with their scores
+pub fn beam_search<F>(
+    mut step: F,
+    start: &[usize],
+    max_len: usize,
+    beam_width: usize,
+    eos: Option<usize>,
+) -> Vec<(Vec<usize>, f64)>
+where
+    F: FnMut(&[usize]) -> Vec<f64>,
+{
+    assert!(beam_width > 0, "beam search needs beam_width > 0");
+
+    let mut beams: Vec<(Vec<usize>, f64)> = vec![(start.to_vec(), 0.0)];
+    let mut finished: Vec<(Vec<usize>, f64)> = vec![];
+
+    for _ in 0..max_len {
+        let mut candidates: Vec<(Vec<usize>, f64)> = vec![];
+        for (seq, score) in beams.iter() {
+            let log_probs = log_softmax(&step(seq));
+            for (token, lp) in log_probs.iter().enumerate() {
+                let mut next = seq.clone();
+                next.push(token);
+                candidates.push((next, score + lp));
+            }
+        }
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // finished sequences leave the beam, the rest compete for the open slots
+        beams = vec![];
+        for (seq, score) in candidates {
+            if beams.len() + finished.len() >= beam_width {
+                break;
+            }
+            if eos.is_some() && seq.last().cloned() == eos {
+                finished.push((seq, score));
+            } else {
+                beams.push((seq, score));
+            }
+        }
+        if beams.is_empty() {
+            break;
+        }
+    }
+
+    finished.extend(beams);
+    finished.sort_by(|a, b| b.1.total_cmp(&a.1));
+    return finished;
+}
@@ -0,0 +1,239 @@
+use crate::nn::Module;
+use crate::stats;
+
+// noticing when the data a deployed model sees has moved away from what it was trained on
+// Ddm watches the model's error stream, the two-sample statistics and DriftMonitor compare
+// batches of inputs and predictions against a reference sample
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftState {
+    Stable,
+    Warning,
+    Drift,
+}
+
+// drift detection method (gama et al. 2004): the error rate p of a model on a stationary stream only
+// goes down as it sees more samples, so p + s rising well above its lowest point p_min + s_min
+// (s = sqrt(p (1 - p) / n)) means the concept changed; warning at 2 of those standard deviations,
+// drift at 3, after which it starts over for the new concept
+pub struct Ddm {
+    pub warning_level: f64,
+    pub drift_level: f64,
+    // no verdicts before this many samples
+    pub min_samples: usize,
+
+    n: usize,
+    p: f64,
+    p_min: f64,
+    s_min: f64,
+}
+
+impl Ddm {
+    pub fn new() -> Self {
+        Ddm {
+            warning_level: 2.0,
+            drift_level: 3.0,
+            min_samples: 30,
+            n: 0,
+            p: 1.0,
+            p_min: f64::INFINITY,
+            s_min: f64::INFINITY,
+        }
+    }
+
+    pub fn with_levels(mut self, warning_level: f64, drift_level: f64) -> Self {
+        self.warning_level = warning_level;
+        self.drift_level = drift_level;
+        self
+    }
+
+    pub fn reset(&mut self) {
+        self.n = 0;
+        self.p = 1.0;
+        self.p_min = f64::INFINITY;
+        self.s_min = f64::INFINITY;
+    }
+
+    // feed whether the model got the latest sample wrong
+    pub fn update(&mut self, error: bool) -> DriftState {
+        self.n += 1;
+        self.p += (if error { 1.0 } else { 0.0 } - self.p) / self.n as f64;
+        let s = (self.p * (1.0 - self.p) / self.n as f64).sqrt();
+        if self.n < self.min_samples {
+            return DriftState::Stable;
+        }
+        if self.p + s < self.p_min + self.s_min {
+            self.p_min = self.p;
+            self.s_min = s;
+        }
+        if self.p + s > self.p_min + self.drift_level * self.s_min {
+            tracing::info!(samples = self.n, error_rate = self.p, "drift detected");
+            self.reset();
+            return DriftState::Drift;
+        }
+        if self.p + s > self.p_min + self.warning_level * self.s_min {
+            return DriftState::Warning;
+        }
+        return DriftState::Stable;
+    }
+}
+
+impl Default for Ddm {
+    fn default() -> Self {
+        Ddm::new()
+    }
+}
+
+// two-sample kolmogorov-smirnov statistic, the largest gap between the empirical cdfs
+pub fn ks_statistic(a: &[f64], b: &[f64]) -> f64 {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(|x, y| x.total_cmp(y));
+    b.sort_by(|x, y| x.total_cmp(y));
+    let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+    while i < a.len() && j < b.len() {
+        let v = a[i].min(b[j]);
+        while i < a.len() && a[i] <= v {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= v {
+            j += 1;
+        }
+        d = d.max((i as f64 / a.len() as f64 - j as f64 / b.len() as f64).abs());
+    }
+    return d;
+}
+
+// asymptotic p-value of a ks statistic d between samples of n and m values (numerical recipes' form
+// of the kolmogorov distribution, good for n m / (n + m) above about 4)
+pub fn ks_p_value(d: f64, n: usize, m: usize) -> f64 {
+    let en = (n as f64 * m as f64 / (n + m) as f64).sqrt();
+    let lambda = (en + 0.12 + 0.11 / en) * d;
+    if lambda < 1e-3 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    for j in 1..=100 {
+        let term = (-2.0 * (j * j) as f64 * lambda * lambda).exp();
+        sum += if j % 2 == 1 { term } else { -term };
+        if term < 1e-12 {
+            break;
+        }
+    }
+    return (2.0 * sum).clamp(0.0, 1.0);
+}
+
+// population stability index of current against reference, over bins holding equal shares of the
+// reference; rule of thumb: under 0.1 stable, 0.1 to 0.25 some shift, above 0.25 a major shift
+pub fn psi(reference: &[f64], current: &[f64], bins: usize) -> f64 {
+    assert!(bins >= 2, "psi needs at least 2 bins, got {}", bins);
+    let edges: Vec<f64> = (1..bins).map(|k| stats::quantile(reference, k as f64 / bins as f64)).collect();
+    let shares = |xs: &[f64]| -> Vec<f64> {
+        let mut counts = vec![0.0; bins];
+        for x in xs {
+            counts[edges.iter().filter(|e| x > *e).count()] += 1.0;
+        }
+        // empty bins would make the log blow up
+        counts.iter().map(|c| (c / xs.len() as f64).max(1e-4)).collect()
+    };
+    let (r, c) = (shares(reference), shares(current));
+    return r.iter().zip(c.iter()).map(|(r, c)| (c - r) * (c / r).ln()).sum();
+}
+
+// shift statistics of one column (an input feature or a model output)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDrift {
+    pub column: usize,
+    pub ks: f64,
+    pub p_value: f64,
+    pub psi: f64,
+    pub drifted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftReport {
+    pub features: Vec<ColumnDrift>,
+    pub predictions: Vec<ColumnDrift>,
+}
+
+impl DriftReport {
+    pub fn drifted(&self) -> bool {
+        return self.features.iter().chain(self.predictions.iter()).any(|c| c.drifted);
+    }
+}
+
+// wraps a deployed model: predictions go through as usual, and every batch's inputs and outputs
+// are compared column by column with a reference sample (e.g. the training data) and its outputs
+// a column has drifted if the ks test rejects at alpha (bonferroni corrected over all the columns)
+// or its psi is above psi_threshold
+pub struct DriftMonitor<'m> {
+    pub model: &'m dyn Module,
+    pub alpha: f64,
+    pub psi_threshold: f64,
+    pub bins: usize,
+
+    reference: Vec<Vec<f64>>,
+    reference_outputs: Vec<Vec<f64>>,
+}
+
+impl<'m> DriftMonitor<'m> {
+    // alpha 0.01, psi threshold 0.25 and 10 psi bins by default
+    pub fn new(model: &'m dyn Module, reference: &[Vec<f64>]) -> Self {
+        assert!(!reference.is_empty(), "drift monitor needs a reference sample");
+        DriftMonitor {
+            model,
+            alpha: 0.01,
+            psi_threshold: 0.25,
+            bins: 10,
+            reference: columns(reference),
+            reference_outputs: columns(&model.predict_batch(reference)),
+        }
+    }
+
+    pub fn with_alpha(mut self, alpha: f64) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn with_psi_threshold(mut self, psi_threshold: f64) -> Self {
+        self.psi_threshold = psi_threshold;
+        self
+    }
+
+    // the model's predictions for the batch and how far the batch has moved from the reference
+    pub fn predict_batch(&self, xs: &[Vec<f64>]) -> (Vec<Vec<f64>>, DriftReport) {
+        let out = self.model.predict_batch(xs);
+        let report = self.check(xs, &out);
+        if report.drifted() {
+            tracing::warn!(features = report.features.iter().filter(|c| c.drifted).count(), predictions = report.predictions.iter().filter(|c| c.drifted).count(), "input drift");
+        }
+        return (out, report);
+    }
+
+    fn check(&self, xs: &[Vec<f64>], out: &[Vec<f64>]) -> DriftReport {
+        let tests = (self.reference.len() + self.reference_outputs.len()) as f64;
+        let compare = |reference: &[Vec<f64>], current: Vec<Vec<f64>>| -> Vec<ColumnDrift> {
+            reference.iter().zip(current.iter()).enumerate().map(|(column, (r, c))| {
+                let ks = ks_statistic(r, c);
+                let p_value = ks_p_value(ks, r.len(), c.len());
+                let psi = psi(r, c, self.bins);
+                ColumnDrift {
+                    column,
+                    ks,
+                    p_value,
+                    psi,
+                    drifted: p_value < self.alpha / tests || psi > self.psi_threshold,
+                }
+            }).collect()
+        };
+        return DriftReport {
+            features: compare(&self.reference, columns(xs)),
+            predictions: compare(&self.reference_outputs, columns(out)),
+        };
+    }
+}
+
+fn columns(rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let d = rows.first().map_or(0, |r| r.len());
+    return (0..d).map(|j| rows.iter().map(|r| r[j]).collect()).collect();
+}
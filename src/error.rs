@@ -0,0 +1,31 @@
+use alloc::string::String;
+
+use thiserror::Error;
+
+// the one error type returned by every fallible public function in the crate
+#[derive(Debug, Error)]
+pub enum RustMlError {
+    // inputs whose dimensions don't fit together
+    #[error("shape mismatch: {0}")]
+    Shape(String),
+
+    // singular or indefinite matrices, non-convergence, non-finite results
+    #[error("numerical error: {0}")]
+    Numerical(String),
+
+    // bad hyperparameters or settings
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    #[error("model is not fit yet")]
+    NotFitted,
+
+    #[error("serialization error: {0}")]
+    Serialization(String),
+
+    #[cfg(feature = "std")]
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = core::result::Result<T, RustMlError>;
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{Result, RustMlError};
+use crate::nn::MLP;
+use crate::plot::{self, Series};
+use crate::serialize;
+
+// lightweight experiment bookkeeping on the filesystem, one directory per training run:
+//
+//   <root>/<run>/config.json       hyperparameters and anything else worth remembering, as strings
+//   <root>/<run>/metrics.csv       step,name,value rows appended as training goes
+//   <root>/<run>/checkpoints/      step_000100.json etc., regular saved models (serialize::load)
+//   <root>/<run>/plots/            svgs, metrics.svg from plot_metrics()
+//
+// everything is plain text so runs can be read back (compare_runs) or by any other tool
+
+pub struct Run {
+    pub name: String,
+    pub dir: PathBuf,
+    pub config: BTreeMap<String, String>,
+}
+
+impl Run {
+    // a fresh run directory under root, named after the run with -2, -3, ... appended if it's taken;
+    // the name has to be a single plain directory name, so "..", "a/.." or "" are refused
+    pub fn create(root: impl AsRef<Path>, name: &str) -> Result<Run> {
+        if !matches!(Path::new(name).components().collect::<Vec<_>>()[..], [Component::Normal(_)]) {
+            return Err(RustMlError::Config(format!("run name {:?} isn't a plain directory name", name)));
+        }
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+        let mut dir = root.join(name);
+        let mut k = 2;
+        while dir.exists() {
+            dir = root.join(format!("{}-{}", name, k));
+            k += 1;
+        }
+        fs::create_dir_all(dir.join("checkpoints"))?;
+        fs::create_dir_all(dir.join("plots"))?;
+        fs::write(dir.join("metrics.csv"), "step,name,value\n")?;
+        let run = Run {
+            name: dir.file_name().unwrap().to_string_lossy().into_owned(),
+            dir,
+            config: BTreeMap::new(),
+        };
+        run.write_config()?;
+        tracing::info!(dir = %run.dir.display(), "created run");
+        return Ok(run);
+    }
+
+    // an existing run directory, e.g. to keep logging after a restart
+    pub fn open(dir: impl AsRef<Path>) -> Result<Run> {
+        let dir = dir.as_ref().to_path_buf();
+        let config = serde_json::from_str(&fs::read_to_string(dir.join("config.json"))?).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        return Ok(Run {
+            name: dir.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned()),
+            dir,
+            config,
+        });
+    }
+
+    fn write_config(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.config).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        fs::write(self.dir.join("config.json"), json)?;
+        return Ok(());
+    }
+
+    // sets one config entry and rewrites config.json
+    pub fn log_config(&mut self, key: &str, value: impl ToString) -> Result<()> {
+        self.config.insert(key.to_string(), value.to_string());
+        return self.write_config();
+    }
+
+    // appends one row per metric to metrics.csv
+    pub fn log_metrics(&self, step: usize, metrics: &[(&str, f64)]) -> Result<()> {
+        if let Some((name, _)) = metrics.iter().find(|(name, _)| name.contains(',') || name.contains('\n')) {
+            return Err(RustMlError::Config(format!("metric name '{}' can't contain commas or newlines", name)));
+        }
+        let mut file = OpenOptions::new().append(true).open(self.dir.join("metrics.csv"))?;
+        for (name, value) in metrics {
+            writeln!(file, "{},{},{}", step, name, value)?;
+        }
+        return Ok(());
+    }
+
+    // every metric logged so far, as (step, value) pairs in logging order
+    pub fn metrics(&self) -> Result<BTreeMap<String, Vec<(usize, f64)>>> {
+        return read_metrics(&self.dir);
+    }
+
+    pub fn save_checkpoint(&self, model: &MLP, step: usize) -> Result<PathBuf> {
+        let path = self.dir.join("checkpoints").join(format!("step_{:06}.json", step));
+        serialize::save(model, &path)?;
+        return Ok(path);
+    }
+
+    // the checkpoint with the highest step, if any
+    pub fn latest_checkpoint(&self) -> Result<Option<PathBuf>> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(self.dir.join("checkpoints"))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .collect();
+        // zero padded steps sort in order
+        paths.sort();
+        return Ok(paths.pop());
+    }
+
+    pub fn save_plot(&self, name: &str, svg: &str) -> Result<PathBuf> {
+        let path = self.dir.join("plots").join(format!("{}.svg", name));
+        plot::save(svg, &path)?;
+        return Ok(path);
+    }
+
+    // every metric against its step in one line chart, plots/metrics.svg
+    pub fn plot_metrics(&self) -> Result<PathBuf> {
+        let series: Vec<Series> = self.metrics()?.into_iter()
+            .map(|(name, points)| Series::new(&name, points.into_iter().map(|(s, v)| (s as f64, v)).collect()))
+            .collect();
+        return self.save_plot("metrics", &plot::line_chart(&self.name, &series));
+    }
+}
+
+fn read_metrics(dir: &Path) -> Result<BTreeMap<String, Vec<(usize, f64)>>> {
+    let csv = fs::read_to_string(dir.join("metrics.csv"))?;
+    let mut metrics: BTreeMap<String, Vec<(usize, f64)>> = BTreeMap::new();
+    for (i, line) in csv.lines().enumerate().skip(1).filter(|(_, l)| !l.trim().is_empty()) {
+        let bad = || RustMlError::Serialization(format!("{}: line {} is not step,name,value", dir.join("metrics.csv").display(), i + 1));
+        let mut parts = line.splitn(3, ',');
+        let (Some(step), Some(name), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(bad());
+        };
+        let step: usize = step.parse().map_err(|_| bad())?;
+        let value: f64 = value.parse().map_err(|_| bad())?;
+        metrics.entry(name.to_string()).or_default().push((step, value));
+    }
+    return Ok(metrics);
+}
+
+// one run boiled down for comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub name: String,
+    pub config: BTreeMap<String, String>,
+    // value at the highest step of every metric
+    pub last: BTreeMap<String, f64>,
+    // lowest and highest value every metric reached
+    pub min: BTreeMap<String, f64>,
+    pub max: BTreeMap<String, f64>,
+    // highest step any metric was logged at
+    pub steps: usize,
+}
+
+// reads the runs back and summarizes each one, in the order given
+pub fn compare_runs(dirs: &[impl AsRef<Path>]) -> Result<Vec<RunSummary>> {
+    return dirs.iter().map(|dir| {
+        let run = Run::open(dir)?;
+        let metrics = run.metrics()?;
+        let mut summary = RunSummary {
+            name: run.name,
+            config: run.config,
+            last: BTreeMap::new(),
+            min: BTreeMap::new(),
+            max: BTreeMap::new(),
+            steps: 0,
+        };
+        for (name, points) in metrics {
+            let last = points.iter().fold(points[0], |best, p| if p.0 >= best.0 { *p } else { best });
+            summary.steps = summary.steps.max(last.0);
+            summary.last.insert(name.clone(), last.1);
+            summary.min.insert(name.clone(), points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min));
+            summary.max.insert(name, points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max));
+        }
+        Ok(summary)
+    }).collect();
+}
+
+// the runs side by side as a markdown table: one column per run, a row for every config key
+// any run has and for the last value of every metric, blank where a run doesn't have it
+pub fn comparison_table(runs: &[RunSummary]) -> String {
+    let mut keys: Vec<&String> = runs.iter().flat_map(|r| r.config.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    let mut metrics: Vec<&String> = runs.iter().flat_map(|r| r.last.keys()).collect();
+    metrics.sort();
+    metrics.dedup();
+
+    let mut table = String::new();
+    let _ = writeln!(table, "| | {} |", runs.iter().map(|r| r.name.as_str()).collect::<Vec<&str>>().join(" | "));
+    let _ = writeln!(table, "|---|{}", "---|".repeat(runs.len()));
+    for key in keys {
+        let cells: Vec<&str> = runs.iter().map(|r| r.config.get(key).map_or("", |v| v.as_str())).collect();
+        let _ = writeln!(table, "| {} | {} |", key, cells.join(" | "));
+    }
+    for metric in metrics {
+        let cells: Vec<String> = runs.iter().map(|r| r.last.get(metric).map_or(String::new(), |v| format!("{:.4}", v))).collect();
+        let _ = writeln!(table, "| {} | {} |", metric, cells.join(" | "));
+    }
+    return table;
+}
@@ -0,0 +1,252 @@
+use crate::nn::Module;
+use crate::plot::{self, Series};
+use crate::random;
+use crate::stats;
+use crate::train;
+use crate::value::Value;
+
+// model interpretation: which inputs drive a prediction
+
+// gradient of one model output with respect to every input, at x
+pub fn output_gradient(model: &dyn Module, x: &[f64], output: usize) -> Vec<f64> {
+    let xv: Vec<Value> = x.iter().map(|xi| Value::new(*xi)).collect();
+    model.forward(&xv)[output].backward();
+    let grad = xv.iter().map(|v| v.get_grad()).collect();
+    model.zero_grad();
+    return grad;
+}
+
+// vanilla saliency (simonyan et al.): absolute input gradient of the chosen output
+pub fn saliency(model: &dyn Module, x: &[f64], output: usize) -> Vec<f64> {
+    return output_gradient(model, x, output).iter().map(|g| g.abs()).collect();
+}
+
+// integrated gradients (sundararajan et al.): (x - baseline) times the average gradient along the
+// straight path from baseline to x, approximated with the midpoint rule over `steps` points
+// the attributions add up to f(x) - f(baseline), up to the approximation error
+pub fn integrated_gradients(model: &dyn Module, x: &[f64], baseline: &[f64], output: usize, steps: usize) -> Vec<f64> {
+    assert_eq!(x.len(), baseline.len(), "baseline must have as many features as x");
+    assert!(steps > 0, "integrated gradients needs at least one step");
+    let mut avg = vec![0.0; x.len()];
+    for k in 0..steps {
+        let t = (k as f64 + 0.5) / steps as f64;
+        let point: Vec<f64> = x.iter().zip(baseline.iter()).map(|(xi, bi)| bi + t * (xi - bi)).collect();
+        for (a, g) in avg.iter_mut().zip(output_gradient(model, &point, output).iter()) {
+            *a += g / steps as f64;
+        }
+    }
+    return avg.iter().zip(x.iter().zip(baseline.iter())).map(|(a, (xi, bi))| a * (xi - bi)).collect();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureImportance {
+    pub feature: usize,
+    // mean drop in score when the feature is shuffled
+    pub mean: f64,
+    pub std: f64,
+    // 95% confidence interval of the mean (normal approximation over the repeats)
+    pub ci: (f64, f64),
+}
+
+// permutation importance (breiman): how much the score drops when one feature column is shuffled,
+// breaking its relation to the target while keeping its distribution
+// score(xs) evaluates any model on the given inputs against fixed targets, higher is better
+// (e.g. accuracy or negative mse), results are sorted from most to least important
+pub fn permutation_importance(xs: &[Vec<f64>], score: impl Fn(&[Vec<f64>]) -> f64, repeats: usize) -> Vec<FeatureImportance> {
+    assert!(!xs.is_empty(), "permutation importance needs at least one sample");
+    assert!(repeats > 1, "permutation importance needs at least 2 repeats for a confidence interval");
+    let base = score(xs);
+    let mut importances: Vec<FeatureImportance> = (0..xs[0].len()).map(|j| {
+        let drops: Vec<f64> = (0..repeats).map(|_| {
+            let mut col: Vec<f64> = xs.iter().map(|x| x[j]).collect();
+            random::shuffle(&mut col);
+            let shuffled: Vec<Vec<f64>> = xs.iter().zip(col.iter()).map(|(x, c)| {
+                let mut x = x.clone();
+                x[j] = *c;
+                x
+            }).collect();
+            base - score(&shuffled)
+        }).collect();
+        let mean = stats::mean(&drops);
+        let std = stats::std(&drops, 1);
+        let half = 1.96 * std / (repeats as f64).sqrt();
+        FeatureImportance {
+            feature: j,
+            mean,
+            std,
+            ci: (mean - half, mean + half),
+        }
+    }).collect();
+    importances.sort_by(|a, b| b.mean.total_cmp(&a.mean));
+    return importances;
+}
+
+// evenly spaced values from the smallest to the largest value of one feature
+pub fn feature_grid(xs: &[Vec<f64>], feature: usize, n: usize) -> Vec<f64> {
+    assert!(n > 1, "a grid needs at least 2 points");
+    let lo = xs.iter().map(|x| x[feature]).fold(f64::INFINITY, f64::min);
+    let hi = xs.iter().map(|x| x[feature]).fold(f64::NEG_INFINITY, f64::max);
+    return (0..n).map(|k| lo + (hi - lo) * k as f64 / (n - 1) as f64).collect();
+}
+
+// partial dependence of one output on one feature
+pub struct PartialDependence {
+    pub feature: usize,
+    pub grid: Vec<f64>,
+    // prediction averaged over the samples at every grid value
+    pub average: Vec<f64>,
+    // individual conditional expectation: ice[i][k] is sample i's prediction with the feature set to grid[k]
+    pub ice: Vec<Vec<f64>>,
+}
+
+impl PartialDependence {
+    // the average curve over the (thin) ice curves
+    pub fn plot(&self, title: &str) -> String {
+        let mut series: Vec<Series> = vec![Series::new("partial dependence", self.grid.iter().cloned().zip(self.average.iter().cloned()).collect())];
+        for (i, curve) in self.ice.iter().enumerate() {
+            series.push(Series::new(&format!("sample {}", i), self.grid.iter().cloned().zip(curve.iter().cloned()).collect()));
+        }
+        return plot::line_chart(title, &series);
+    }
+}
+
+// sets the feature of every sample to each grid value in turn and records the predictions
+pub fn partial_dependence(model: &dyn Module, xs: &[Vec<f64>], feature: usize, grid: &[f64], output: usize) -> PartialDependence {
+    let mut ice = vec![vec![0.0; grid.len()]; xs.len()];
+    for (k, g) in grid.iter().enumerate() {
+        let batch: Vec<Vec<f64>> = xs.iter().map(|x| {
+            let mut x = x.clone();
+            x[feature] = *g;
+            x
+        }).collect();
+        for (i, y) in model.predict_batch(&batch).iter().enumerate() {
+            ice[i][k] = y[output];
+        }
+    }
+    let average = (0..grid.len()).map(|k| ice.iter().map(|c| c[k]).sum::<f64>() / xs.len() as f64).collect();
+    return PartialDependence {
+        feature,
+        grid: grid.to_vec(),
+        average,
+        ice,
+    };
+}
+
+// two-feature partial dependence: pd[a][b] is the average prediction with the features set to
+// (grid_a[a], grid_b[b]), render it with plot::heatmap(title, &pd, range of grid_b, range of grid_a)
+pub fn partial_dependence_2d(
+    model: &dyn Module,
+    xs: &[Vec<f64>],
+    features: (usize, usize),
+    grid_a: &[f64],
+    grid_b: &[f64],
+    output: usize,
+) -> Vec<Vec<f64>> {
+    return grid_a.iter().map(|ga| {
+        grid_b.iter().map(|gb| {
+            let batch: Vec<Vec<f64>> = xs.iter().map(|x| {
+                let mut x = x.clone();
+                x[features.0] = *ga;
+                x[features.1] = *gb;
+                x
+            }).collect();
+            model.predict_batch(&batch).iter().map(|y| y[output]).sum::<f64>() / xs.len() as f64
+        }).collect()
+    }).collect();
+}
+
+// data attribution with influence functions (koh & liang 2017): how much the loss on a test point
+// would change if one training point were weighted up, without retraining
+// assumes the model is trained to (near) a minimum of the mean training loss
+
+// loss(outputs, target) of one sample, e.g. the one a Trainer was built with
+pub type SampleLoss<'a> = &'a dyn Fn(&[Value], &[f64]) -> Value;
+
+fn sample_losses(model: &dyn Module, loss: SampleLoss, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> Vec<Value> {
+    return xs.iter().zip(ys.iter()).map(|(x, y)| {
+        let xv: Vec<Value> = x.iter().map(|xi| Value::constant(*xi)).collect();
+        loss(&model.forward(&xv), y)
+    }).collect();
+}
+
+// gradient of the mean loss over the samples, in parameters() order
+fn mean_gradient(model: &dyn Module, loss: SampleLoss, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> Vec<f64> {
+    model.zero_grad();
+    Value::mean(&sample_losses(model, loss, xs, ys)).backward();
+    let grad = model.parameters().iter().map(|p| p.get_grad()).collect();
+    model.zero_grad();
+    return grad;
+}
+
+// hessian of the mean training loss times v, by central differences of the gradient along v
+// (the graphs aren't differentiable twice), the parameters are restored afterwards
+pub fn hessian_vector_product(model: &dyn Module, loss: SampleLoss, xs: &[Vec<f64>], ys: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+    let theta = model.get_flat_params();
+    assert_eq!(theta.len(), v.len(), "expected one entry of v per parameter");
+    let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        return vec![0.0; v.len()];
+    }
+    // step scaled so the parameters move by about the same amount whatever |v| is
+    let h = 1e-5 / norm;
+    let shifted = |sign: f64| -> Vec<f64> {
+        let p: Vec<f64> = theta.iter().zip(v.iter()).map(|(t, vi)| t + sign * h * vi).collect();
+        model.set_flat_params(&p);
+        mean_gradient(model, loss, xs, ys)
+    };
+    let (plus, minus) = (shifted(1.0), shifted(-1.0));
+    model.set_flat_params(&theta);
+    return plus.iter().zip(minus.iter()).map(|(a, b)| (a - b) / (2.0 * h)).collect();
+}
+
+// solves (H + damping I) s = g by conjugate gradients, H only seen through hessian-vector products
+// the damping keeps the system positive definite when the model isn't exactly at a minimum
+fn inverse_hvp(model: &dyn Module, loss: SampleLoss, xs: &[Vec<f64>], ys: &[Vec<f64>], g: &[f64], damping: f64) -> Vec<f64> {
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b.iter()).map(|(x, y)| x * y).sum::<f64>();
+    let apply = |v: &[f64]| -> Vec<f64> {
+        let hv = hessian_vector_product(model, loss, xs, ys, v);
+        hv.iter().zip(v.iter()).map(|(a, b)| a + damping * b).collect()
+    };
+    let mut s = vec![0.0; g.len()];
+    let mut r = g.to_vec();
+    let mut p = r.clone();
+    let mut rr = dot(&r, &r);
+    let tol = 1e-20 * dot(g, g).max(1e-300);
+    for _ in 0..g.len() {
+        if rr <= tol {
+            break;
+        }
+        let ap = apply(&p);
+        let pap = dot(&p, &ap);
+        if pap <= 0.0 {
+            tracing::debug!(pap, "influence: hessian not positive definite along a search direction, stopping early");
+            break;
+        }
+        let alpha = rr / pap;
+        for i in 0..s.len() {
+            s[i] += alpha * p[i];
+            r[i] -= alpha * ap[i];
+        }
+        let next = dot(&r, &r);
+        for i in 0..p.len() {
+            p[i] = r[i] + next / rr * p[i];
+        }
+        rr = next;
+    }
+    return s;
+}
+
+// influence of every training sample on the loss at (x_test, y_test):
+// d loss_test / d weight_i = -grad_test^T (H + damping I)^-1 grad_i
+// positive means weighting the sample up would increase the test loss (it hurts this prediction),
+// negative means it helps; sort by it to find the most harmful or helpful training points
+pub fn influence(model: &dyn Module, loss: SampleLoss, xs: &[Vec<f64>], ys: &[Vec<f64>], x_test: &[f64], y_test: &[f64], damping: f64) -> Vec<f64> {
+    assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+    assert!(damping >= 0.0, "damping can't be negative, got {}", damping);
+    let g_test = mean_gradient(model, loss, &[x_test.to_vec()], &[y_test.to_vec()]);
+    let s_test = inverse_hvp(model, loss, xs, ys, &g_test, damping);
+    let params = model.parameters();
+    let grads = train::per_sample_gradients(&params, &sample_losses(model, loss, xs, ys));
+    model.zero_grad();
+    return grads.iter().map(|g| -g.iter().zip(s_test.iter()).map(|(a, b)| a * b).sum::<f64>()).collect();
+}
@@ -0,0 +1,63 @@
+use crate::nn::{DeepClone, Module};
+use crate::optim::Sgd;
+use crate::random;
+use crate::train::Trainer;
+
+// federated learning simulation: the dataset is split across clients that each train a local
+// copy of the global model, and the server averages their parameters (FedAvg, mcmahan et al.)
+
+pub struct Client {
+    pub xs: Vec<Vec<f64>>,
+    pub ys: Vec<Vec<f64>>,
+}
+
+// shuffles the samples and deals them out to n clients as evenly as possible (iid split)
+pub fn split_clients(xs: &[Vec<f64>], ys: &[Vec<f64>], n: usize) -> Vec<Client> {
+    assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+    assert!(n > 0 && n <= xs.len(), "need between 1 and {} clients, got {}", xs.len(), n);
+    let mut idx: Vec<usize> = (0..xs.len()).collect();
+    random::shuffle(&mut idx);
+    let mut clients: Vec<Client> = (0..n).map(|_| Client { xs: vec![], ys: vec![] }).collect();
+    for (k, &i) in idx.iter().enumerate() {
+        clients[k % n].xs.push(xs[i].clone());
+        clients[k % n].ys.push(ys[i].clone());
+    }
+    return clients;
+}
+
+// average of flat parameter vectors, each weighted by its client's number of samples
+pub fn fed_avg(updates: &[(Vec<f64>, usize)]) -> Vec<f64> {
+    assert!(!updates.is_empty(), "fed_avg needs at least one update");
+    let total: usize = updates.iter().map(|u| u.1).sum();
+    let mut avg = vec![0.0; updates[0].0.len()];
+    for (params, n) in updates.iter() {
+        let w = *n as f64 / total as f64;
+        for (a, p) in avg.iter_mut().zip(params.iter()) {
+            *a += w * p;
+        }
+    }
+    return avg;
+}
+
+// runs `rounds` rounds of FedAvg: every client trains a copy of the global model for
+// local_epochs full-batch sgd steps, then the global model is set to the weighted average
+// returns the sample-weighted mean of the clients' last local losses for each round
+pub fn train<M: Module + DeepClone>(global: &M, clients: &[Client], rounds: usize, local_epochs: usize, lr: f64) -> Vec<f64> {
+    let total: usize = clients.iter().map(|c| c.xs.len()).sum();
+    let mut history = vec![];
+    for round in 0..rounds {
+        let mut updates = vec![];
+        let mut round_loss = 0.0;
+        for client in clients.iter().filter(|c| !c.xs.is_empty()) {
+            let local = global.deep_clone();
+            let mut trainer = Trainer::new(&local, Sgd::new(local.parameters(), lr));
+            let losses = trainer.fit(&client.xs, &client.ys, local_epochs);
+            round_loss += losses.last().cloned().unwrap_or(0.0) * client.xs.len() as f64 / total as f64;
+            updates.push((local.get_flat_params(), client.xs.len()));
+        }
+        global.set_flat_params(&fed_avg(&updates));
+        tracing::info!(round, loss = round_loss, "federated round done");
+        history.push(round_loss);
+    }
+    return history;
+}
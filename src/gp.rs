@@ -0,0 +1,198 @@
+use crate::error::{Result, RustMlError};
+use crate::matrix::Matrix;
+use crate::value::Value;
+
+// gaussian process regression for small datasets (exact inference, O(n^3) fitting)
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    Rbf,
+    Matern32,
+    Matern52,
+}
+
+impl Kernel {
+    // covariance between two points at squared distance r2
+    pub fn eval(&self, r2: f64, lengthscale: f64, variance: f64) -> f64 {
+        let r = r2.sqrt();
+        match self {
+            Kernel::Rbf => variance * (-0.5 * r2 / (lengthscale * lengthscale)).exp(),
+            Kernel::Matern32 => {
+                let s = 3f64.sqrt() * r / lengthscale;
+                variance * (1.0 + s) * (-s).exp()
+            },
+            Kernel::Matern52 => {
+                let s = 5f64.sqrt() * r / lengthscale;
+                variance * (1.0 + s + s * s / 3.0) * (-s).exp()
+            },
+        }
+    }
+
+    // same as eval but as a graph over the (Value) hyperparameters, the distance is a constant
+    fn eval_value(&self, r2: f64, lengthscale: &Value, variance: &Value) -> Value {
+        let k = match self {
+            Kernel::Rbf => {
+                let inv_ls2 = Value::reciprocal(&Value::square(lengthscale));
+                Value::exp(&Value::mul(&Value::constant(-0.5 * r2), &inv_ls2))
+            },
+            Kernel::Matern32 | Kernel::Matern52 => {
+                let c = if *self == Kernel::Matern32 { 3f64.sqrt() } else { 5f64.sqrt() };
+                let s = Value::mul(&Value::constant(c * r2.sqrt()), &Value::reciprocal(lengthscale));
+                let mut poly = Value::add(&Value::constant(1.0), &s);
+                if *self == Kernel::Matern52 {
+                    poly = Value::add(&poly, &Value::mul(&Value::square(&s), &Value::constant(1.0 / 3.0)));
+                }
+                Value::mul(&poly, &Value::exp(&Value::neg(&s)))
+            },
+        };
+        return Value::mul(variance, &k);
+    }
+}
+
+fn sq_dist(a: &Matrix, i: usize, b: &Matrix, j: usize) -> f64 {
+    return (0..a.cols()).map(|k| (a.get(i, k) - b.get(j, k)).powi(2)).sum();
+}
+
+pub struct GaussianProcess {
+    pub kernel: Kernel,
+    pub lengthscale: f64,
+    pub variance: f64,
+    // observation noise variance, added to the diagonal
+    pub noise: f64,
+
+    x_train: Option<Matrix>,
+    y_mean: f64,
+    // K^-1 (y - mean) and the cholesky factor of K, cached by fit
+    alpha: Vec<f64>,
+    chol: Option<Matrix>,
+}
+
+impl GaussianProcess {
+    pub fn new(kernel: Kernel) -> Self {
+        GaussianProcess {
+            kernel,
+            lengthscale: 1.0,
+            variance: 1.0,
+            noise: 1e-2,
+            x_train: None,
+            y_mean: 0.0,
+            alpha: vec![],
+            chol: None,
+        }
+    }
+
+    fn gram(&self, x: &Matrix) -> Matrix {
+        let n = x.rows();
+        let mut k = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                k[i * n + j] = self.kernel.eval(sq_dist(x, i, x, j), self.lengthscale, self.variance);
+            }
+            k[i * n + i] += self.noise;
+        }
+        return Matrix::new(n, n, k);
+    }
+
+    // fits with the current hyperparameters, the targets are centered on their mean
+    pub fn fit(&mut self, x: &Matrix, y: &[f64]) -> Result<()> {
+        if x.rows() != y.len() {
+            return Err(RustMlError::Shape(format!("x has {} rows but y has {} values", x.rows(), y.len())));
+        }
+        let n = y.len();
+        self.y_mean = y.iter().sum::<f64>() / n as f64;
+        let centered: Vec<f64> = y.iter().map(|v| v - self.y_mean).collect();
+
+        let l = self.gram(x).cholesky()?;
+        self.alpha = Matrix::cholesky_solve(&l, &Matrix::new(n, 1, centered))?.get_data();
+        self.chol = Some(l);
+        self.x_train = Some(x.clone_rc());
+        return Ok(());
+    }
+
+    // log p(y | X, hyperparameters) of the fitted data
+    pub fn log_marginal_likelihood(&self, y: &[f64]) -> Result<f64> {
+        let l = self.chol.as_ref().ok_or(RustMlError::NotFitted)?;
+        let n = y.len();
+        let fit: f64 = y.iter().zip(self.alpha.iter()).map(|(v, a)| (v - self.y_mean) * a).sum();
+        let log_det: f64 = (0..n).map(|i| l.get(i, i).ln()).sum::<f64>() * 2.0;
+        return Ok(-0.5 * fit - 0.5 * log_det - 0.5 * n as f64 * (2.0 * std::f64::consts::PI).ln());
+    }
+
+    // gradient descent on the negative log marginal likelihood over log(lengthscale), log(variance),
+    // log(noise), through the autograd engine
+    // -dL/dtheta = -0.5 tr(W dK/dtheta) with W = alpha alpha^T - K^-1, so with W held constant the
+    // graph -sum_ij 0.5 W_ij K_ij(theta) has the right gradient and backward() gives all three
+    // the graph is built and run one row of K at a time, so it holds O(n) nodes rather than O(n^2);
+    // the hyperparameter leaves add up their gradients over the rows
+    // steps that lower the likelihood are undone and the learning rate is halved
+    // returns the final log marginal likelihood
+    pub fn optimize(&mut self, x: &Matrix, y: &[f64], iters: usize, lr: f64) -> Result<f64> {
+        let n = x.rows();
+        let mut lr = lr;
+        self.fit(x, y)?;
+        let mut best = self.log_marginal_likelihood(y)?;
+        for _ in 0..iters {
+            let l = self.chol.as_ref().unwrap();
+            let k_inv = Matrix::cholesky_solve(l, &Matrix::identity(n))?;
+
+            let log_ls = Value::new(self.lengthscale.ln());
+            let log_var = Value::new(self.variance.ln());
+            let log_noise = Value::new(self.noise.ln());
+            for i in 0..n {
+                // fresh intermediate nodes per row, so no gradient is passed down twice
+                let (ls, var, noise) = (Value::exp(&log_ls), Value::exp(&log_var), Value::exp(&log_noise));
+                let mut terms = Vec::with_capacity(n - i + 1);
+                for j in i..n {
+                    let w = self.alpha[i] * self.alpha[j] - k_inv.get(i, j);
+                    // off-diagonal pairs appear twice in the trace
+                    let scale = if i == j { 0.5 * w } else { w };
+                    let k = self.kernel.eval_value(sq_dist(x, i, x, j), &ls, &var);
+                    terms.push(Value::mul(&k, &Value::constant(-scale)));
+                }
+                let w = self.alpha[i] * self.alpha[i] - k_inv.get(i, i);
+                terms.push(Value::mul(&noise, &Value::constant(-0.5 * w)));
+                Value::sum(&terms).backward();
+            }
+
+            let old = (self.lengthscale, self.variance, self.noise);
+            self.lengthscale = (log_ls.get_data() - lr * log_ls.get_grad()).exp();
+            self.variance = (log_var.get_data() - lr * log_var.get_grad()).exp();
+            self.noise = (log_noise.get_data() - lr * log_noise.get_grad()).exp().max(1e-6);
+
+            let lml = self.fit(x, y).and_then(|_| self.log_marginal_likelihood(y));
+            tracing::debug!(lml = ?lml, lengthscale = self.lengthscale, variance = self.variance, noise = self.noise, "gp hyperparameter step");
+            match lml {
+                Ok(v) if v >= best => best = v,
+                _ => {
+                    (self.lengthscale, self.variance, self.noise) = old;
+                    self.fit(x, y)?;
+                    lr *= 0.5;
+                },
+            }
+        }
+        return Ok(best);
+    }
+
+    // predictive mean and variance of the latent function at every row of x
+    // (add self.noise to the variance for the distribution of new noisy observations)
+    pub fn predict(&self, x: &Matrix) -> Result<(Vec<f64>, Vec<f64>)> {
+        let (x_train, l) = match (&self.x_train, &self.chol) {
+            (Some(xt), Some(l)) => (xt, l),
+            _ => return Err(RustMlError::NotFitted),
+        };
+        if x.cols() != x_train.cols() {
+            return Err(RustMlError::Shape(format!("model was fit on {} features, got {}", x_train.cols(), x.cols())));
+        }
+        let n = x_train.rows();
+        let mut means = vec![];
+        let mut vars = vec![];
+        for i in 0..x.rows() {
+            let k_star: Vec<f64> = (0..n).map(|j| self.kernel.eval(sq_dist(x, i, x_train, j), self.lengthscale, self.variance)).collect();
+            means.push(self.y_mean + k_star.iter().zip(self.alpha.iter()).map(|(a, b)| a * b).sum::<f64>());
+            let v = Matrix::cholesky_solve(l, &Matrix::new(n, 1, k_star.clone()))?.get_data();
+            let reduction: f64 = k_star.iter().zip(v.iter()).map(|(a, b)| a * b).sum();
+            vars.push((self.variance - reduction).max(0.0));
+        }
+        return Ok((means, vars));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::autograd::Node;
+use crate::error::{Result, RustMlError};
+use crate::value::Value;
+
+// exporting Value graphs for inspection: graphviz dot for quick pictures,
+// json for external tools (node ids are positions in the topological order, children first)
+
+// every node reachable from root, each once, children before their parents
+pub fn topo_order(root: &Value) -> Vec<Value> {
+    let mut order: Vec<Value> = vec![];
+    let mut placed: BTreeSet<usize> = BTreeSet::new();
+    let mut stack: Vec<(Value, bool)> = vec![(root.clone_rc(), false)];
+    while let Some((node, expanded)) = stack.pop() {
+        if placed.contains(&node.id()) {
+            continue;
+        }
+        if expanded {
+            placed.insert(node.id());
+            order.push(node);
+            continue;
+        }
+        stack.push((node.clone_rc(), true));
+        for child in node.get_children().into_iter().rev() {
+            if !placed.contains(&child.id()) {
+                stack.push((child, false));
+            }
+        }
+    }
+    return order;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonNode {
+    pub id: usize,
+    // empty for leaves
+    pub op: String,
+    pub data: f64,
+    pub grad: f64,
+    pub label: String,
+    pub requires_grad: bool,
+}
+
+// child `from` is argument number `arg` of node `to`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonEdge {
+    pub from: usize,
+    pub to: usize,
+    pub arg: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonGraph {
+    pub root: usize,
+    pub nodes: Vec<JsonNode>,
+    pub edges: Vec<JsonEdge>,
+}
+
+impl JsonGraph {
+    pub fn from_value(root: &Value) -> JsonGraph {
+        let order = topo_order(root);
+        let ids: BTreeMap<usize, usize> = order.iter().enumerate().map(|(i, v)| (v.id(), i)).collect();
+        let mut nodes = vec![];
+        let mut edges = vec![];
+        for (id, v) in order.iter().enumerate() {
+            let raw = v.0.borrow();
+            nodes.push(JsonNode {
+                id,
+                op: raw.op.to_string(),
+                data: raw.data,
+                grad: raw.grad,
+                label: raw.label.clone(),
+                requires_grad: raw.requires_grad,
+            });
+            for (arg, child) in raw.children.iter().enumerate() {
+                edges.push(JsonEdge { from: ids[&child.id()], to: id, arg });
+            }
+        }
+        return JsonGraph {
+            root: order.len() - 1,
+            nodes,
+            edges,
+        };
+    }
+}
+
+pub fn to_json(root: &Value) -> Result<String> {
+    return serde_json::to_string_pretty(&JsonGraph::from_value(root)).map_err(|e| RustMlError::Serialization(e.to_string()));
+}
+
+// graphviz source, render with e.g. `dot -Tsvg graph.dot -o graph.svg`
+// every node is a record (label | data | grad), op nodes get an extra circle naming the op
+pub fn to_dot(root: &Value) -> String {
+    let g = JsonGraph::from_value(root);
+    let mut dot = String::from("digraph G {\n    rankdir=LR;\n");
+    for n in g.nodes.iter() {
+        let label = n.label.replace('"', "\\\"");
+        let _ = writeln!(dot, "    n{} [shape=record, label=\"{{ {} | data {:.4} | grad {:.4} }}\"];", n.id, label, n.data, n.grad);
+        if !n.op.is_empty() {
+            let _ = writeln!(dot, "    n{}_op [label=\"{}\"];", n.id, n.op.replace('"', "\\\""));
+            let _ = writeln!(dot, "    n{}_op -> n{};", n.id, n.id);
+        }
+    }
+    for e in g.edges.iter() {
+        let _ = writeln!(dot, "    n{} -> n{}_op;", e.from, e.to);
+    }
+    dot.push_str("}\n");
+    return dot;
+}
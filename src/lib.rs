@@ -0,0 +1,72 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(feature = "std"))]
+mod math;
+pub mod error;
+pub mod autograd;
+pub mod value;
+pub mod matrix;
+pub mod nn;
+pub mod decoding;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod linalg;
+#[cfg(feature = "std")]
+pub mod stats;
+pub mod random;
+pub mod loss;
+pub mod uncertainty;
+#[cfg(feature = "std")]
+pub mod models;
+#[cfg(feature = "std")]
+pub mod gp;
+#[cfg(feature = "std")]
+pub mod blackbox;
+#[cfg(feature = "std")]
+pub mod optim;
+#[cfg(feature = "std")]
+pub mod ode;
+pub mod profiler;
+#[cfg(feature = "std")]
+pub mod serialize;
+#[cfg(feature = "std")]
+pub mod experiments;
+#[cfg(feature = "std")]
+pub mod prune;
+#[cfg(feature = "std")]
+pub mod train;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+pub mod federated;
+#[cfg(feature = "std")]
+pub mod privacy;
+#[cfg(feature = "std")]
+pub mod attacks;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "std")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod datasets;
+#[cfg(feature = "std")]
+pub mod data;
+#[cfg(feature = "std")]
+pub mod preprocessing;
+#[cfg(feature = "std")]
+pub mod anomaly;
+#[cfg(feature = "std")]
+pub mod cluster;
+#[cfg(feature = "std")]
+pub mod mixture;
+#[cfg(feature = "std")]
+pub mod manifold;
+#[cfg(feature = "std")]
+pub mod drift;
+#[cfg(feature = "std")]
+pub mod viz;
+#[cfg(feature = "std")]
+pub mod graph;
@@ -0,0 +1,410 @@
+use crate::error::{Result, RustMlError};
+use crate::matrix::Matrix;
+
+// small dense linear algebra on Matrix data
+// these work on the values only, the results are new leaf matrices without gradients
+
+// pivots smaller than this are treated as zero
+const PIVOT_EPS: f64 = 1e-12;
+
+// LU decomposition with partial pivoting: P A = L U
+// L (unit diagonal) and U are packed into one row-major n x n buffer
+pub struct LU {
+    pub n: usize,
+    pub lu: Vec<f64>,
+    pub perm: Vec<usize>,
+    // +1 or -1 depending on the number of row swaps
+    pub sign: f64,
+    pub singular: bool,
+}
+
+impl LU {
+    // solves A x = b for a single right hand side
+    pub fn solve_vec(&self, b: &[f64]) -> Vec<f64> {
+        let n = self.n;
+        // forward substitution with L on the permuted b
+        let mut y: Vec<f64> = self.perm.iter().map(|&p| b[p]).collect();
+        for i in 0..n {
+            for k in 0..i {
+                y[i] -= self.lu[i * n + k] * y[k];
+            }
+        }
+        // back substitution with U
+        for i in (0..n).rev() {
+            for k in i + 1..n {
+                y[i] -= self.lu[i * n + k] * y[k];
+            }
+            y[i] /= self.lu[i * n + i];
+        }
+        return y;
+    }
+}
+
+fn check_square(m: &Matrix, what: &str) -> Result<usize> {
+    if m.rows() != m.cols() {
+        return Err(RustMlError::Shape(format!("{} needs a square matrix, got {}x{}", what, m.rows(), m.cols())));
+    }
+    return Ok(m.rows());
+}
+
+impl Matrix {
+    pub fn lu(&self) -> Result<LU> {
+        let n = check_square(self, "lu")?;
+        let mut lu = self.get_data();
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+        let mut singular = false;
+
+        for k in 0..n {
+            // pick the largest pivot in the column to keep things stable
+            let mut p = k;
+            for i in k + 1..n {
+                if lu[i * n + k].abs() > lu[p * n + k].abs() {
+                    p = i;
+                }
+            }
+            if lu[p * n + k].abs() < PIVOT_EPS {
+                singular = true;
+                continue;
+            }
+            if p != k {
+                for j in 0..n {
+                    lu.swap(k * n + j, p * n + j);
+                }
+                perm.swap(k, p);
+                sign = -sign;
+            }
+            for i in k + 1..n {
+                let factor = lu[i * n + k] / lu[k * n + k];
+                lu[i * n + k] = factor;
+                for j in k + 1..n {
+                    lu[i * n + j] -= factor * lu[k * n + j];
+                }
+            }
+        }
+
+        return Ok(LU { n, lu, perm, sign, singular });
+    }
+
+    // determinant, 0 for singular matrices
+    pub fn det(&self) -> Result<f64> {
+        let lu = self.lu()?;
+        if lu.singular {
+            return Ok(0.0);
+        }
+        let n = lu.n;
+        return Ok(lu.sign * (0..n).map(|i| lu.lu[i * n + i]).product::<f64>());
+    }
+
+    pub fn inverse(&self) -> Result<Matrix> {
+        let n = check_square(self, "inverse")?;
+        return Matrix::solve(self, &Matrix::identity(n));
+    }
+
+    // solves A X = B, where B can have several columns
+    pub fn solve(a: &Matrix, b: &Matrix) -> Result<Matrix> {
+        let n = check_square(a, "solve")?;
+        if b.rows() != n {
+            return Err(RustMlError::Shape(format!("solve: A is {}x{} but B has {} rows", n, n, b.rows())));
+        }
+        let lu = a.lu()?;
+        if lu.singular {
+            return Err(RustMlError::Numerical("solve: matrix is singular".to_string()));
+        }
+
+        let k = b.cols();
+        let mut x = vec![0.0; n * k];
+        for j in 0..k {
+            let col: Vec<f64> = (0..n).map(|i| b.get(i, j)).collect();
+            let sol = lu.solve_vec(&col);
+            for i in 0..n {
+                x[i * k + j] = sol[i];
+            }
+        }
+        return Ok(Matrix::new(n, k, x));
+    }
+}
+
+impl Matrix {
+    // cholesky decomposition A = L L^T of a symmetric positive definite matrix, returns L
+    pub fn cholesky(&self) -> Result<Matrix> {
+        let n = check_square(self, "cholesky")?;
+        let a = self.get_data();
+        for i in 0..n {
+            for j in 0..i {
+                if (a[i * n + j] - a[j * n + i]).abs() > 1e-9 * (1.0 + a[i * n + j].abs()) {
+                    return Err(RustMlError::Numerical(format!("cholesky: matrix is not symmetric at ({}, {})", i, j)));
+                }
+            }
+        }
+
+        let mut l = vec![0.0; n * n];
+        for j in 0..n {
+            let mut d = a[j * n + j];
+            for k in 0..j {
+                d -= l[j * n + k] * l[j * n + k];
+            }
+            if d <= 0.0 {
+                return Err(RustMlError::Numerical(format!("cholesky: matrix is not positive definite (pivot {} is {})", j, d)));
+            }
+            let d = d.sqrt();
+            l[j * n + j] = d;
+            for i in j + 1..n {
+                let mut s = a[i * n + j];
+                for k in 0..j {
+                    s -= l[i * n + k] * l[j * n + k];
+                }
+                l[i * n + j] = s / d;
+            }
+        }
+        return Ok(Matrix::new(n, n, l));
+    }
+
+    // solves A X = B given the cholesky factor L of A
+    pub fn cholesky_solve(l: &Matrix, b: &Matrix) -> Result<Matrix> {
+        let n = check_square(l, "cholesky_solve")?;
+        if b.rows() != n {
+            return Err(RustMlError::Shape(format!("cholesky_solve: L is {}x{} but B has {} rows", n, n, b.rows())));
+        }
+        let ld = l.get_data();
+        let k = b.cols();
+        let mut x = b.get_data();
+        for j in 0..k {
+            // L y = b
+            for i in 0..n {
+                for p in 0..i {
+                    x[i * k + j] -= ld[i * n + p] * x[p * k + j];
+                }
+                x[i * k + j] /= ld[i * n + i];
+            }
+            // L^T x = y
+            for i in (0..n).rev() {
+                for p in i + 1..n {
+                    x[i * k + j] -= ld[p * n + i] * x[p * k + j];
+                }
+                x[i * k + j] /= ld[i * n + i];
+            }
+        }
+        return Ok(Matrix::new(n, k, x));
+    }
+
+    // thin QR decomposition A = Q R of an m x n matrix with m >= n (modified gram-schmidt)
+    // Q is m x n with orthonormal columns, R is n x n upper triangular
+    pub fn qr(&self) -> Result<(Matrix, Matrix)> {
+        let (m, n) = (self.rows(), self.cols());
+        if m < n {
+            return Err(RustMlError::Shape(format!("qr needs at least as many rows as columns, got {}x{}", m, n)));
+        }
+        // work column by column
+        let mut cols: Vec<Vec<f64>> = (0..n).map(|j| (0..m).map(|i| self.get(i, j)).collect()).collect();
+        let mut r = vec![0.0; n * n];
+
+        for j in 0..n {
+            let original = cols[j].iter().map(|x| x * x).sum::<f64>().sqrt();
+            for k in 0..j {
+                let proj: f64 = cols[k].iter().zip(cols[j].iter()).map(|(a, b)| a * b).sum();
+                r[k * n + j] = proj;
+                let (done, rest) = cols.split_at_mut(j);
+                for (x, q) in rest[0].iter_mut().zip(done[k].iter()) {
+                    *x -= proj * q;
+                }
+            }
+            let norm = cols[j].iter().map(|x| x * x).sum::<f64>().sqrt();
+            if norm <= PIVOT_EPS * original.max(1.0) {
+                return Err(RustMlError::Numerical(format!("qr: matrix is rank deficient (column {} is linearly dependent)", j)));
+            }
+            r[j * n + j] = norm;
+            for x in cols[j].iter_mut() {
+                *x /= norm;
+            }
+        }
+
+        let mut q = vec![0.0; m * n];
+        for (j, col) in cols.iter().enumerate() {
+            for i in 0..m {
+                q[i * n + j] = col[i];
+            }
+        }
+        return Ok((Matrix::new(m, n, q), Matrix::new(n, n, r)));
+    }
+
+    // least squares solution of A X ~= B through QR: R X = Q^T B
+    pub fn lstsq(a: &Matrix, b: &Matrix) -> Result<Matrix> {
+        if b.rows() != a.rows() {
+            return Err(RustMlError::Shape(format!("lstsq: A has {} rows but B has {}", a.rows(), b.rows())));
+        }
+        let (q, r) = a.qr()?;
+        let (m, n, k) = (a.rows(), a.cols(), b.cols());
+        let mut x = vec![0.0; n * k];
+        for j in 0..k {
+            let mut y: Vec<f64> = (0..n).map(|i| (0..m).map(|p| q.get(p, i) * b.get(p, j)).sum()).collect();
+            for i in (0..n).rev() {
+                for p in i + 1..n {
+                    y[i] -= r.get(i, p) * y[p];
+                }
+                y[i] /= r.get(i, i);
+            }
+            for i in 0..n {
+                x[i * k + j] = y[i];
+            }
+        }
+        return Ok(Matrix::new(n, k, x));
+    }
+}
+
+// max number of jacobi sweeps before giving up on convergence
+const MAX_SWEEPS: usize = 100;
+
+fn transpose_data(rows: usize, cols: usize, data: &[f64]) -> Vec<f64> {
+    let mut t = vec![0.0; data.len()];
+    for i in 0..rows {
+        for j in 0..cols {
+            t[j * rows + i] = data[i * cols + j];
+        }
+    }
+    return t;
+}
+
+impl Matrix {
+    // eigendecomposition of a symmetric matrix with the cyclic jacobi method
+    // returns the eigenvalues in descending order and the matching eigenvectors as columns
+    pub fn eigh(&self) -> Result<(Vec<f64>, Matrix)> {
+        let n = check_square(self, "eigh")?;
+        let mut a = self.get_data();
+        for i in 0..n {
+            for j in 0..i {
+                if (a[i * n + j] - a[j * n + i]).abs() > 1e-9 * (1.0 + a[i * n + j].abs()) {
+                    return Err(RustMlError::Numerical(format!("eigh: matrix is not symmetric at ({}, {})", i, j)));
+                }
+            }
+        }
+        let mut v = Matrix::identity(n).get_data();
+
+        let scale: f64 = a.iter().map(|x| x * x).sum::<f64>().max(f64::MIN_POSITIVE);
+        let mut converged = false;
+        for _ in 0..MAX_SWEEPS {
+            let off: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+                .map(|(i, j)| a[i * n + j] * a[i * n + j]).sum();
+            if off <= 1e-22 * scale {
+                converged = true;
+                break;
+            }
+            for p in 0..n {
+                for q in p + 1..n {
+                    let apq = a[p * n + q];
+                    if apq == 0.0 {
+                        continue;
+                    }
+                    // rotation angle that zeroes a[p][q]
+                    let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let t = if theta == 0.0 { 1.0 } else { t };
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+
+                    // A = J^T A J, applied to columns then rows
+                    for k in 0..n {
+                        let (akp, akq) = (a[k * n + p], a[k * n + q]);
+                        a[k * n + p] = c * akp - s * akq;
+                        a[k * n + q] = s * akp + c * akq;
+                    }
+                    for k in 0..n {
+                        let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                        a[p * n + k] = c * apk - s * aqk;
+                        a[q * n + k] = s * apk + c * aqk;
+                    }
+                    for k in 0..n {
+                        let (vkp, vkq) = (v[k * n + p], v[k * n + q]);
+                        v[k * n + p] = c * vkp - s * vkq;
+                        v[k * n + q] = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+        if !converged {
+            return Err(RustMlError::Numerical(format!("eigh: jacobi iteration did not converge in {} sweeps", MAX_SWEEPS)));
+        }
+
+        // sort by eigenvalue, largest first
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| a[j * n + j].total_cmp(&a[i * n + i]));
+        let values = order.iter().map(|&i| a[i * n + i]).collect();
+        let mut vectors = vec![0.0; n * n];
+        for (new_j, &old_j) in order.iter().enumerate() {
+            for i in 0..n {
+                vectors[i * n + new_j] = v[i * n + old_j];
+            }
+        }
+        return Ok((values, Matrix::new(n, n, vectors)));
+    }
+
+    // thin SVD A = U diag(s) V^T with one-sided jacobi rotations
+    // for an m x n matrix and k = min(m, n): U is m x k, s has k values (descending), V is n x k
+    // columns of U belonging to zero singular values are left as zeros
+    pub fn svd(&self) -> Result<(Matrix, Vec<f64>, Matrix)> {
+        let (m, n) = (self.rows(), self.cols());
+        if m < n {
+            // svd of the transpose, with U and V swapped
+            let t = Matrix::new(n, m, transpose_data(m, n, &self.get_data()));
+            let (u, s, v) = t.svd()?;
+            return Ok((v, s, u));
+        }
+
+        // orthogonalize the columns of A, the rotations accumulate into V
+        let mut u: Vec<Vec<f64>> = (0..n).map(|j| (0..m).map(|i| self.get(i, j)).collect()).collect();
+        let mut v: Vec<Vec<f64>> = (0..n).map(|j| (0..n).map(|i| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+        let mut converged = false;
+        for _ in 0..MAX_SWEEPS {
+            let mut rotated = false;
+            for p in 0..n {
+                for q in p + 1..n {
+                    let alpha: f64 = u[p].iter().map(|x| x * x).sum();
+                    let beta: f64 = u[q].iter().map(|x| x * x).sum();
+                    let gamma: f64 = u[p].iter().zip(u[q].iter()).map(|(a, b)| a * b).sum();
+                    if gamma.abs() <= 1e-15 * (alpha * beta).sqrt() {
+                        continue;
+                    }
+                    rotated = true;
+                    let zeta = (beta - alpha) / (2.0 * gamma);
+                    let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                    let t = if zeta == 0.0 { 1.0 } else { t };
+                    let c = 1.0 / (1.0 + t * t).sqrt();
+                    let s = c * t;
+                    for cols in [&mut u, &mut v] {
+                        let (left, right) = cols.split_at_mut(q);
+                        for (xp, xq) in left[p].iter_mut().zip(right[0].iter_mut()) {
+                            let (a, b) = (*xp, *xq);
+                            *xp = c * a - s * b;
+                            *xq = s * a + c * b;
+                        }
+                    }
+                }
+            }
+            if !rotated {
+                converged = true;
+                break;
+            }
+        }
+        if !converged {
+            return Err(RustMlError::Numerical(format!("svd: jacobi iteration did not converge in {} sweeps", MAX_SWEEPS)));
+        }
+
+        // singular values are the column norms, sort them largest first
+        let norms: Vec<f64> = u.iter().map(|col| col.iter().map(|x| x * x).sum::<f64>().sqrt()).collect();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+
+        let mut ud = vec![0.0; m * n];
+        let mut vd = vec![0.0; n * n];
+        for (new_j, &old_j) in order.iter().enumerate() {
+            for i in 0..m {
+                ud[i * n + new_j] = if norms[old_j] > 0.0 { u[old_j][i] / norms[old_j] } else { 0.0 };
+            }
+            for i in 0..n {
+                vd[i * n + new_j] = v[old_j][i];
+            }
+        }
+        let s = order.iter().map(|&j| norms[j]).collect();
+        return Ok((Matrix::new(m, n, ud), s, Matrix::new(n, n, vd)));
+    }
+}
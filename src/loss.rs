@@ -0,0 +1,89 @@
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::value::Value;
+
+// loss functions, each returns a single scalar node to call backward() on
+
+// mean squared error
+pub fn mse(ypred: &[Value], ys: &[Value]) -> Value {
+    assert_eq!(ypred.len(), ys.len(), "mse needs as many predictions as targets");
+    let errors: Vec<Value> = ypred.iter().zip(ys.iter()).map(|(yp, y)| Value::square(&Value::sub(yp, y))).collect();
+    return Value::mean(&errors);
+}
+
+// gaussian negative log likelihood for one target, given the predicted mean and log variance:
+// 0.5 * (log_var + (y - mean)^2 / exp(log_var)), leaving out the constant 0.5 * ln(2 pi)
+// predicting the log variance keeps the variance positive without any clamping
+pub fn gaussian_nll(mean: &Value, log_var: &Value, target: &Value) -> Value {
+    let sq = Value::square(&Value::sub(target, mean));
+    let scaled = Value::mul(&sq, &Value::exp(&Value::neg(log_var)));
+    return Value::mul(&Value::add(log_var, &scaled), &Value::constant(0.5));
+}
+
+// gaussian nll averaged over a batch
+pub fn gaussian_nll_mean(means: &[Value], log_vars: &[Value], targets: &[Value]) -> Value {
+    assert!(means.len() == log_vars.len() && means.len() == targets.len(), "gaussian_nll_mean needs inputs of the same length");
+    let losses: Vec<Value> = (0..targets.len()).map(|i| gaussian_nll(&means[i], &log_vars[i], &targets[i])).collect();
+    return Value::mean(&losses);
+}
+
+// log softmax over a vector of logits, x_i - logsumexp(x), which can't overflow
+pub fn log_softmax(logits: &[Value]) -> Vec<Value> {
+    let log_sum = Value::logsumexp(logits);
+    return logits.iter().map(|l| Value::sub(l, &log_sum)).collect();
+}
+
+// cross entropy between softmax(logits) and a target distribution (one-hot or soft)
+pub fn cross_entropy(logits: &[Value], target: &[f64]) -> Value {
+    assert_eq!(logits.len(), target.len(), "cross_entropy needs one target probability per logit");
+    let terms: Vec<Value> = log_softmax(logits).iter().zip(target.iter())
+        .filter(|(_, p)| **p != 0.0)
+        .map(|(lp, p)| Value::mul(lp, &Value::constant(-*p)))
+        .collect();
+    return Value::sum(&terms);
+}
+
+// soft-target distillation loss (hinton et al.): KL(softmax(teacher / T) || softmax(student / T)) * T^2
+// the T^2 keeps the gradient scale comparable to the hard-label loss as T changes
+pub fn distillation(student_logits: &[Value], teacher_logits: &[f64], temperature: f64) -> Value {
+    assert!(temperature > 0.0, "temperature must be positive");
+    let max = teacher_logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = teacher_logits.iter().map(|l| ((l - max) / temperature).exp()).collect();
+    let total: f64 = weights.iter().sum();
+    let soft: Vec<f64> = weights.iter().map(|w| w / total).collect();
+
+    // the teacher's entropy is a constant, adding it turns the cross entropy into the KL divergence
+    let entropy: f64 = soft.iter().filter(|p| **p > 0.0).map(|p| -p * p.ln()).sum();
+    let scaled: Vec<Value> = student_logits.iter().map(|l| Value::mul(l, &Value::constant(1.0 / temperature))).collect();
+    let kl = Value::sub(&cross_entropy(&scaled, &soft), &Value::constant(entropy));
+    return Value::mul(&kl, &Value::constant(temperature * temperature));
+}
+
+// squared euclidean distance between two embeddings
+pub fn squared_distance(a: &[Value], b: &[Value]) -> Value {
+    assert_eq!(a.len(), b.len(), "embeddings must have the same length");
+    let sq: Vec<Value> = a.iter().zip(b.iter()).map(|(x, y)| Value::square(&Value::sub(x, y))).collect();
+    return Value::sum(&sq);
+}
+
+// contrastive loss (hadsell et al. 2006) for one pair of embeddings: similar pairs are pulled
+// together (d^2), dissimilar ones pushed apart until they're at least margin away (max(0, margin - d)^2)
+pub fn contrastive(a: &[Value], b: &[Value], similar: bool, margin: f64) -> Value {
+    let d2 = squared_distance(a, b);
+    if similar {
+        return d2;
+    }
+    let d = Value::pow(&Value::add_eps(&d2, 1e-12), 0.5);
+    let gap = Value::sub(&Value::constant(margin), &d);
+    return Value::where_(gap.get_data() > 0.0, &Value::square(&gap), &Value::constant(0.0));
+}
+
+// triplet loss (schroff et al. 2015): the anchor should be closer to the positive than to the
+// negative by at least margin, in squared distance; max(0, d(a, p)^2 - d(a, n)^2 + margin)
+pub fn triplet(anchor: &[Value], positive: &[Value], negative: &[Value], margin: f64) -> Value {
+    let diff = Value::sub(&squared_distance(anchor, positive), &squared_distance(anchor, negative));
+    let excess = Value::add(&diff, &Value::constant(margin));
+    return Value::where_(excess.get_data() > 0.0, &excess, &Value::constant(0.0));
+}
@@ -1,11 +1,44 @@
-mod value;
-mod matrix;
-mod nn;
-
-use value::Value;
-use nn::MLP;
+use rust_ml::value::Value;
+use rust_ml::nn::{Module, MLP};
+use rust_ml::config::{self, TrainConfig};
+use rust_ml::profiler;
+use rust_ml::train;
 
 fn main() {
+    tracing_subscriber::fmt().with_max_level(tracing::Level::INFO).init();
+
+    // given a config file (see config::TrainConfig), train that run instead of the demo below;
+    // seeds after the path run it once per seed, in parallel, and print the mean ± std of the metrics
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(path) = args.first() {
+        // ctrl-c finishes the batch, saves the checkpoint and still saves the model
+        if let Err(e) = train::handle_interrupts() {
+            tracing::warn!(error = %e, "training can't be interrupted cleanly");
+        }
+        let seeds: Vec<u64> = match args[1..].iter().map(|s| s.parse()).collect() {
+            Ok(seeds) => seeds,
+            Err(e) => {
+                eprintln!("seeds must be non-negative integers: {}", e);
+                std::process::exit(1);
+            },
+        };
+        let result = TrainConfig::load(path).and_then(|c| {
+            if seeds.is_empty() {
+                config::run(&c).map(|(_, losses)| format!("final loss {}", losses.last().copied().unwrap_or(f64::NAN)))
+            } else {
+                config::run_seeds(&c, &seeds, true).map(|runs| runs.to_string().trim_end().to_string())
+            }
+        });
+        match result {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+
     // testing the value library
     let a = Value::new(1.0);
     let b = Value::new(2.0);
@@ -35,15 +68,15 @@ fn main() {
 
     // real neural network
     println!("real nn stuff");
-    let mlp = MLP::new(&vec![3, 4, 4, 1]);
+    let mlp = MLP::new(&[3, 4, 4, 1]);
 
     // defining data and labels
-    let xs = vec![
+    let xs = [
         vec![Value::new(2.0), Value::new(3.0), Value::new(-1.0)],
         vec![Value::new(3.0), Value::new(-1.0), Value::new(0.5)],
         vec![Value::new(0.5), Value::new(1.0), Value::new(1.0)],
         vec![Value::new(1.0), Value::new(1.0), Value::new(-1.0)],];
-    let ys = vec![Value::new(1.0), Value::new(-1.0), Value::new(-1.0), Value::new(1.0)];
+    let ys = [Value::new(1.0), Value::new(-1.0), Value::new(-1.0), Value::new(1.0)];
 
     // testing initial prediction (spoiler: it's bad)
     let ypred = xs.iter().map(|x| mlp.forward(x)).collect::<Vec<Vec<Value>>>();
@@ -54,15 +87,13 @@ fn main() {
     // training
     let max_epoch = 100;
     let lr = 0.1;
+    let train_span = tracing::info_span!("train", max_epoch, lr).entered();
     for epoch in 0..max_epoch {
         // forward pass
         let ypred = xs.iter().map(|x| mlp.forward(x)[0].clone_rc()).collect::<Vec<Value>>();
 
-        // calculating the loss, specifically MSE
-        let mut loss = Value::new(0.0);
-        for i in 0..ypred.len() {
-            loss = Value::add(&loss, &Value::pow(&Value::sub(&ypred[i], &ys[i]), 2.0));
-        }
+        // calculating the loss, specifically MSE, as one sum node instead of a chain of adds
+        let loss = squared_error(&ypred, &ys);
 
         // backward pass
         mlp.zero_grad();
@@ -73,11 +104,26 @@ fn main() {
             p.update_data(-lr * p.get_grad());
         }
 
-        println!("epoch: {} loss: {}", epoch, loss.get_data());
+        tracing::info!(epoch, loss = loss.get_data(), "epoch done");
     }
+    drop(train_span);
 
     let ypred = xs.iter().map(|x| mlp.forward(x)[0].clone_rc()).collect::<Vec<Value>>();
     for y in ypred.iter() {
         println!("{}", y);
     }
+
+    // where does the time go in one training step
+    profiler::enable();
+    let ypred = xs.iter().map(|x| mlp.forward(x)[0].clone_rc()).collect::<Vec<Value>>();
+    let loss = squared_error(&ypred, &ys);
+    mlp.zero_grad();
+    loss.backward();
+    profiler::disable();
+    profiler::print_report();
+}
+
+fn squared_error(ypred: &[Value], ys: &[Value]) -> Value {
+    let errs: Vec<Value> = ypred.iter().zip(ys.iter()).map(|(p, y)| Value::pow(&Value::sub(p, y), 2.0)).collect();
+    return Value::sum(&errs);
 }
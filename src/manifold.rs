@@ -0,0 +1,255 @@
+use crate::error::{Result, RustMlError};
+use crate::random;
+
+// nonlinear embeddings into 2-d for plotting (plot::scatter), from rows of features: a dataset's
+// inputs or a network's hidden activations
+
+// t-sne (van der maaten & hinton 2008): neighbour probabilities from gaussians in the input space,
+// matched by probabilities from a student-t in the embedding by gradient descent on the kl divergence
+// theta > 0 approximates the repulsive forces with a barnes-hut quadtree (van der maaten 2014):
+// cells that look small from a point (width / distance < theta) act as one point at their center of mass,
+// theta = 0 is exact; either way the input affinities are exact, so this is O(n^2) memory
+pub struct Tsne {
+    // effective number of neighbours each point's gaussian is tuned to
+    pub perplexity: f64,
+    // None picks max(n / early_exaggeration / 4, 50) (belkina et al. 2019), large fixed rates fling points off on small datasets
+    pub learning_rate: Option<f64>,
+    pub n_iter: usize,
+    // p is multiplied by this for the first 250 iterations, pulling clusters apart early on
+    pub early_exaggeration: f64,
+    pub theta: f64,
+
+    // kl(p || q) of the final embedding
+    pub kl_divergence: f64,
+}
+
+impl Tsne {
+    // perplexity 30, automatic learning rate, 1000 iterations, exaggeration 12, barnes-hut with theta 0.5 by default
+    pub fn new() -> Self {
+        Tsne {
+            perplexity: 30.0,
+            learning_rate: None,
+            n_iter: 1000,
+            early_exaggeration: 12.0,
+            theta: 0.5,
+            kl_divergence: f64::NAN,
+        }
+    }
+
+    pub fn with_perplexity(mut self, perplexity: f64) -> Self {
+        self.perplexity = perplexity;
+        self
+    }
+
+    pub fn with_learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = Some(learning_rate);
+        self
+    }
+
+    pub fn with_iterations(mut self, n_iter: usize) -> Self {
+        self.n_iter = n_iter;
+        self
+    }
+
+    pub fn with_theta(mut self, theta: f64) -> Self {
+        self.theta = theta;
+        self
+    }
+
+    // one (x, y) point per row
+    pub fn fit_transform(&mut self, xs: &[Vec<f64>]) -> Result<Vec<(f64, f64)>> {
+        let n = xs.len();
+        let d = xs.first().map_or(0, |x| x.len());
+        if let Some(i) = xs.iter().position(|x| x.len() != d) {
+            return Err(RustMlError::Shape(format!("row {} has {} columns, row 0 has {}", i, xs[i].len(), d)));
+        }
+        if self.perplexity <= 0.0 || (n as f64) <= 3.0 * self.perplexity {
+            return Err(RustMlError::Config(format!("t-sne needs more than 3 * perplexity rows, got {} rows for perplexity {}", n, self.perplexity)));
+        }
+        if self.theta < 0.0 {
+            return Err(RustMlError::Config(format!("theta must be non-negative, got {}", self.theta)));
+        }
+        let p = affinities(xs, self.perplexity);
+        let lr = self.learning_rate.unwrap_or((n as f64 / self.early_exaggeration / 4.0).max(50.0));
+
+        let mut y: Vec<(f64, f64)> = (0..n).map(|_| (random::normal(0.0, 1e-4), random::normal(0.0, 1e-4))).collect();
+        let mut velocity = vec![(0.0, 0.0); n];
+        // per coordinate step size multipliers, grown while the gradient keeps its sign (jacobs 1988)
+        let mut gains = vec![(1.0, 1.0); n];
+        let exaggeration_iters = 250.min(self.n_iter);
+        for iter in 0..self.n_iter {
+            let exaggeration = if iter < exaggeration_iters { self.early_exaggeration } else { 1.0 };
+            let momentum = if iter < exaggeration_iters { 0.5 } else { 0.8 };
+
+            let tree = Cell::build(&y, (0..n).collect());
+            let repulsion: Vec<(f64, f64, f64)> = (0..n).map(|i| tree.repulsion(&y, i, self.theta)).collect();
+            let z: f64 = repulsion.iter().map(|r| r.2).sum();
+            for i in 0..n {
+                // attractive part exactly, over the neighbours p puts weight on
+                let mut attract = (0.0, 0.0);
+                for (j, pij) in p[i].iter().enumerate() {
+                    if *pij > 0.0 {
+                        let (dx, dy) = (y[i].0 - y[j].0, y[i].1 - y[j].1);
+                        let w = pij / (1.0 + dx * dx + dy * dy);
+                        attract = (attract.0 + w * dx, attract.1 + w * dy);
+                    }
+                }
+                let grad = (
+                    4.0 * (exaggeration * attract.0 - repulsion[i].0 / z),
+                    4.0 * (exaggeration * attract.1 - repulsion[i].1 / z),
+                );
+                velocity[i] = step(velocity[i], &mut gains[i], grad, momentum, lr);
+            }
+            for i in 0..n {
+                y[i] = (y[i].0 + velocity[i].0, y[i].1 + velocity[i].1);
+            }
+            // keep the embedding centered
+            let (mx, my) = y.iter().fold((0.0, 0.0), |a, p| (a.0 + p.0 / n as f64, a.1 + p.1 / n as f64));
+            for p in y.iter_mut() {
+                *p = (p.0 - mx, p.1 - my);
+            }
+        }
+        self.kl_divergence = kl_divergence(&p, &y);
+        tracing::debug!(kl = self.kl_divergence, iterations = self.n_iter, "t-sne fit");
+        return Ok(y);
+    }
+}
+
+impl Default for Tsne {
+    fn default() -> Self {
+        Tsne::new()
+    }
+}
+
+// one momentum step for a point, with the gains updated from the gradient's sign
+fn step(velocity: (f64, f64), gains: &mut (f64, f64), grad: (f64, f64), momentum: f64, lr: f64) -> (f64, f64) {
+    let gain = |g: f64, grad: f64, v: f64| if (grad > 0.0) != (v > 0.0) { g + 0.2 } else { (g * 0.8).max(0.01) };
+    *gains = (gain(gains.0, grad.0, velocity.0), gain(gains.1, grad.1, velocity.1));
+    return (
+        momentum * velocity.0 - lr * gains.0 * grad.0,
+        momentum * velocity.1 - lr * gains.1 * grad.1,
+    );
+}
+
+fn squared_distances(xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    return xs.iter().map(|a| xs.iter().map(|b| a.iter().zip(b.iter()).map(|(u, v)| (u - v) * (u - v)).sum()).collect()).collect();
+}
+
+// symmetrized joint probabilities p_ij = (p_j|i + p_i|j) / 2n, where p_j|i is a gaussian around x_i
+// with its precision found by bisection so the conditional distribution has the target perplexity
+fn affinities(xs: &[Vec<f64>], perplexity: f64) -> Vec<Vec<f64>> {
+    let n = xs.len();
+    let d2 = squared_distances(xs);
+    let target = perplexity.ln();
+    let mut cond = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        // shift by the nearest distance so the exponentials don't all underflow
+        let nearest = (0..n).filter(|&j| j != i).map(|j| d2[i][j]).fold(f64::INFINITY, f64::min);
+        let (mut lo, mut hi, mut beta) = (0.0, f64::INFINITY, 1.0);
+        for _ in 0..100 {
+            let mut sum = 0.0;
+            for j in 0..n {
+                cond[i][j] = if j == i { 0.0 } else { (-beta * (d2[i][j] - nearest)).exp() };
+                sum += cond[i][j];
+            }
+            // shannon entropy of the normalized row, in nats
+            let mut entropy = 0.0;
+            for c in cond[i].iter_mut() {
+                *c /= sum;
+                if *c > 0.0 {
+                    entropy -= *c * c.ln();
+                }
+            }
+            if (entropy - target).abs() < 1e-5 {
+                break;
+            }
+            // too spread out: sharpen
+            if entropy > target {
+                lo = beta;
+                beta = if hi.is_finite() { (beta + hi) / 2.0 } else { beta * 2.0 };
+            } else {
+                hi = beta;
+                beta = (beta + lo) / 2.0;
+            }
+        }
+    }
+    return (0..n).map(|i| (0..n).map(|j| (cond[i][j] + cond[j][i]) / (2.0 * n as f64)).collect()).collect();
+}
+
+fn kl_divergence(p: &[Vec<f64>], y: &[(f64, f64)]) -> f64 {
+    let n = y.len();
+    let w = |i: usize, j: usize| 1.0 / (1.0 + (y[i].0 - y[j].0).powi(2) + (y[i].1 - y[j].1).powi(2));
+    let z: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j))).map(|(i, j)| w(i, j)).sum();
+    let mut kl = 0.0;
+    for (i, row) in p.iter().enumerate() {
+        for (j, pij) in row.iter().enumerate() {
+            if i != j && *pij > 0.0 {
+                kl += pij * (pij / (w(i, j) / z)).ln();
+            }
+        }
+    }
+    return kl;
+}
+
+// quadtree over the embedding, a leaf holds the points that ended up alone (or stacked on top of each other)
+struct Cell {
+    center_of_mass: (f64, f64),
+    count: usize,
+    width: f64,
+    points: Vec<usize>,
+    children: Vec<Cell>,
+}
+
+impl Cell {
+    fn build(y: &[(f64, f64)], idx: Vec<usize>) -> Cell {
+        let (mut lo, mut hi) = ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY));
+        for &i in idx.iter() {
+            lo = (lo.0.min(y[i].0), lo.1.min(y[i].1));
+            hi = (hi.0.max(y[i].0), hi.1.max(y[i].1));
+        }
+        return Cell::grow(y, idx, lo, (hi.0 - lo.0).max(hi.1 - lo.1));
+    }
+
+    fn grow(y: &[(f64, f64)], idx: Vec<usize>, corner: (f64, f64), width: f64) -> Cell {
+        let count = idx.len();
+        let center_of_mass = idx.iter().fold((0.0, 0.0), |a, &i| (a.0 + y[i].0 / count as f64, a.1 + y[i].1 / count as f64));
+        if count <= 1 || width < 1e-12 {
+            return Cell { center_of_mass, count, width, points: idx, children: vec![] };
+        }
+        let half = width / 2.0;
+        let mut quadrants: Vec<Vec<usize>> = vec![vec![]; 4];
+        for i in idx {
+            let q = (y[i].0 >= corner.0 + half) as usize + 2 * (y[i].1 >= corner.1 + half) as usize;
+            quadrants[q].push(i);
+        }
+        let children = quadrants.into_iter().enumerate()
+            .filter(|(_, members)| !members.is_empty())
+            .map(|(q, members)| {
+                let c = (corner.0 + half * (q % 2) as f64, corner.1 + half * (q / 2) as f64);
+                Cell::grow(y, members, c, half)
+            })
+            .collect();
+        return Cell { center_of_mass, count, width, points: vec![], children };
+    }
+
+    // sum over j != i of (w_ij^2 (y_i - y_j), w_ij) with w_ij = 1 / (1 + |y_i - y_j|^2)
+    fn repulsion(&self, y: &[(f64, f64)], i: usize, theta: f64) -> (f64, f64, f64) {
+        let (dx, dy) = (y[i].0 - self.center_of_mass.0, y[i].1 - self.center_of_mass.1);
+        let d2 = dx * dx + dy * dy;
+        if self.children.is_empty() {
+            let mut out = (0.0, 0.0, 0.0);
+            for &j in self.points.iter().filter(|&&j| j != i) {
+                let (dx, dy) = (y[i].0 - y[j].0, y[i].1 - y[j].1);
+                let w = 1.0 / (1.0 + dx * dx + dy * dy);
+                out = (out.0 + w * w * dx, out.1 + w * w * dy, out.2 + w);
+            }
+            return out;
+        }
+        if d2 > 0.0 && self.width < theta * d2.sqrt() {
+            let w = 1.0 / (1.0 + d2);
+            let m = self.count as f64;
+            return (m * w * w * dx, m * w * w * dy, m * w);
+        }
+        return self.children.iter().map(|c| c.repulsion(y, i, theta)).fold((0.0, 0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+    }
+}
@@ -0,0 +1,67 @@
+// float functions for no_std builds, where f64 has no exp / ln / sqrt / ... of its own
+// modules import Float when std is off, so x.exp() reads the same either way
+
+pub trait Float {
+    fn exp(self) -> f64;
+    fn exp_m1(self) -> f64;
+    fn ln(self) -> f64;
+    fn ln_1p(self) -> f64;
+    fn log(self, base: f64) -> f64;
+    fn sqrt(self) -> f64;
+    fn powf(self, p: f64) -> f64;
+    fn powi(self, n: i32) -> f64;
+    fn sin(self) -> f64;
+    fn cos(self) -> f64;
+    fn tan(self) -> f64;
+    fn tanh(self) -> f64;
+}
+
+impl Float for f64 {
+    fn exp(self) -> f64 {
+        return libm::exp(self);
+    }
+
+    fn exp_m1(self) -> f64 {
+        return libm::expm1(self);
+    }
+
+    fn ln(self) -> f64 {
+        return libm::log(self);
+    }
+
+    fn ln_1p(self) -> f64 {
+        return libm::log1p(self);
+    }
+
+    fn log(self, base: f64) -> f64 {
+        return libm::log(self) / libm::log(base);
+    }
+
+    fn sqrt(self) -> f64 {
+        return libm::sqrt(self);
+    }
+
+    fn powf(self, p: f64) -> f64 {
+        return libm::pow(self, p);
+    }
+
+    fn powi(self, n: i32) -> f64 {
+        return libm::pow(self, n as f64);
+    }
+
+    fn sin(self) -> f64 {
+        return libm::sin(self);
+    }
+
+    fn cos(self) -> f64 {
+        return libm::cos(self);
+    }
+
+    fn tan(self) -> f64 {
+        return libm::tan(self);
+    }
+
+    fn tanh(self) -> f64 {
+        return libm::tanh(self);
+    }
+}
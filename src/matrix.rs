@@ -1,25 +1,40 @@
-use std::{
-    cell::RefCell, collections::HashSet, rc::Rc,
+use alloc::{
+    boxed::Box, format, rc::Rc,
+    string::{String, ToString}, vec, vec::Vec,
+};
+use core::{
+    any::Any, cell::RefCell,
     hash::{Hash, Hasher},
     fmt::{self, Display, Formatter},
 };
 
+use crate::autograd::{self, Node};
+use crate::error::{Result, RustMlError};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::profiler::{self, Phase};
+use crate::value::Value;
+
+// Matrix struct for automatic differentiation over whole matrices, same idea as Value
 #[derive(Debug, Clone)]
 pub struct Matrix(pub Rc<RefCell<RawMatrix>>);
 
+// RawMatrix struct for the actual data, stored row-major
 #[derive(Debug, Clone)]
 pub struct RawMatrix {
-    rows: usize,
-    cols: usize,
-
-    data: Vec<f64>,
-    grad: Vec<f64>,
-    op: String,
-    label: String,
-    children: Vec<Matrix>,
+    pub rows: usize,
+    pub cols: usize,
+
+    pub data: Vec<f64>,
+    pub grad: Vec<f64>,
+    pub op: String,
+    pub label: String,
+    pub children: Vec<Matrix>,
+    // set on matrices made by Matrix::from_values: the scalar nodes behind each element
+    pub values: Vec<Value>,
 }
 
-// implement hash, eq, and display for Value
+// implement hash, eq, and display for Matrix
 impl Hash for Matrix {
     fn hash<H: Hasher>(&self, state: &mut H) {
         let ptr = Rc::as_ptr(&self.0);
@@ -33,4 +48,256 @@ impl PartialEq for Matrix {
     }
 }
 
-impl Eq for Matrix {}
\ No newline at end of file
+impl Eq for Matrix {}
+
+impl Display for Matrix {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let m = self.0.borrow();
+        write!(f, "Matrix({}x{} [", m.rows, m.cols)?;
+        for i in 0..m.rows {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            let row: Vec<String> = m.data[i * m.cols..(i + 1) * m.cols].iter().map(|x| x.to_string()).collect();
+            write!(f, "{}", row.join(" "))?;
+        }
+        write!(f, "])")
+    }
+}
+
+impl Matrix {
+    // constructor for Matrix from row-major data
+    pub fn new(rows: usize, cols: usize, data: Vec<f64>) -> Matrix {
+        assert_eq!(data.len(), rows * cols, "expected {} elements for a {}x{} matrix, got {}", rows * cols, rows, cols, data.len());
+        return Matrix(Rc::new(RefCell::new(RawMatrix {
+            rows,
+            cols,
+            grad: vec![0.0; data.len()],
+            data,
+            op: "".to_string(),
+            label: "".to_string(),
+            children: vec![],
+            values: vec![],
+        })));
+    }
+
+    // same as new but returns an error instead of panicking when the data doesn't fit the shape
+    pub fn try_new(rows: usize, cols: usize, data: Vec<f64>) -> Result<Matrix> {
+        if data.len() != rows * cols {
+            return Err(RustMlError::Shape(format!("expected {} elements for a {}x{} matrix, got {}", rows * cols, rows, cols, data.len())));
+        }
+        return Ok(Matrix::new(rows, cols, data));
+    }
+
+    // a matrix whose elements are scalar Values (row-major), the gradient of each element is
+    // routed back to its Value and on through the scalar graph when backward() reaches it
+    pub fn from_values(rows: usize, cols: usize, values: &[Value]) -> Matrix {
+        let m = Matrix::new_for_op(rows, cols, values.iter().map(|v| v.get_data()).collect(), "from values", vec![]);
+        m.0.borrow_mut().values = values.iter().map(|v| v.clone_rc()).collect();
+        return m;
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        return Matrix::new(rows, cols, vec![0.0; rows * cols]);
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        let mut data = vec![0.0; n * n];
+        for i in 0..n {
+            data[i * n + i] = 1.0;
+        }
+        return Matrix::new(n, n, data);
+    }
+
+    // constructor for Matrix when made from an operator
+    pub fn new_for_op(rows: usize, cols: usize, data: Vec<f64>, op: &str, children: Vec<Matrix>) -> Matrix {
+        let t = profiler::start();
+        let m = Matrix(Rc::new(RefCell::new(RawMatrix {
+            rows,
+            cols,
+            grad: vec![0.0; data.len()],
+            data,
+            op: op.to_string(),
+            label: "".to_string(),
+            children,
+            values: vec![],
+        })));
+        profiler::record(&format!("matrix {}", op), Phase::Forward, t);
+        return m;
+    }
+
+    // getters and setters
+    pub fn rows(&self) -> usize {
+        return self.0.borrow().rows;
+    }
+
+    pub fn cols(&self) -> usize {
+        return self.0.borrow().cols;
+    }
+
+    pub fn get_data(&self) -> Vec<f64> {
+        return self.0.borrow().data.clone();
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let m = self.0.borrow();
+        return m.data[i * m.cols + j];
+    }
+
+    pub fn get_grad(&self) -> Vec<f64> {
+        return self.0.borrow().grad.clone();
+    }
+
+    pub fn zero_grad(&self) {
+        let mut m = self.0.borrow_mut();
+        m.grad = vec![0.0; m.data.len()];
+    }
+
+    pub fn update_grad(&self, grad: &[f64]) {
+        let mut m = self.0.borrow_mut();
+        for (g, d) in m.grad.iter_mut().zip(grad.iter()) {
+            *g += d;
+        }
+    }
+
+    pub fn update_grad_at(&self, k: usize, grad: f64) {
+        self.0.borrow_mut().grad[k] += grad;
+    }
+
+    pub fn get_children(&self) -> Vec<Matrix> {
+        return self.0.borrow().children.clone();
+    }
+
+    // get an rc pointer to the matrix, not cloning the data
+    pub fn clone_rc(&self) -> Matrix {
+        return Matrix(Rc::clone(&self.0));
+    }
+
+    // elementwise op helper, applies f to every element
+    fn map_op(m: &Matrix, op: &str, f: impl Fn(f64) -> f64) -> Matrix {
+        let data = m.get_data().into_iter().map(f).collect();
+        return Matrix::new_for_op(m.rows(), m.cols(), data, op, vec![m.clone_rc()]);
+    }
+
+    pub fn square(m: &Matrix) -> Matrix {
+        return Self::map_op(m, "square", |x| x * x);
+    }
+
+    pub fn cube(m: &Matrix) -> Matrix {
+        return Self::map_op(m, "cube", |x| x * x * x);
+    }
+
+    pub fn reciprocal(m: &Matrix) -> Matrix {
+        return Self::map_op(m, "reciprocal", |x| 1.0 / x);
+    }
+
+    // 1 / sqrt(x)
+    pub fn rsqrt(m: &Matrix) -> Matrix {
+        return Self::map_op(m, "rsqrt", |x| 1.0 / x.sqrt());
+    }
+
+    // non-differentiable helpers, they return plain indices/masks and block the gradient
+
+    // index of the largest element in each row (first one on ties)
+    pub fn argmax_rows(&self) -> Vec<usize> {
+        let m = self.0.borrow();
+        return (0..m.rows).map(|i| {
+            let row = &m.data[i * m.cols..(i + 1) * m.cols];
+            let mut best = 0;
+            for j in 1..row.len() {
+                if row[j] > row[best] {
+                    best = j;
+                }
+            }
+            best
+        }).collect();
+    }
+
+    // column indices of the k largest elements in each row, largest first
+    pub fn topk_rows(&self, k: usize) -> Vec<Vec<usize>> {
+        let m = self.0.borrow();
+        return (0..m.rows).map(|i| {
+            let row = &m.data[i * m.cols..(i + 1) * m.cols];
+            let mut idx: Vec<usize> = (0..m.cols).collect();
+            idx.sort_by(|&a, &b| row[b].total_cmp(&row[a]));
+            idx.truncate(k);
+            idx
+        }).collect();
+    }
+
+    // row-major mask of which elements are strictly greater than the threshold
+    pub fn greater_than(&self, threshold: f64) -> Vec<bool> {
+        return self.0.borrow().data.iter().map(|x| *x > threshold).collect();
+    }
+
+    // backward pass for the current node
+    pub fn _backward(&self) {
+        let m = self.0.borrow();
+        let operation = m.op.as_str();
+        match operation {
+            // elementwise ops, the local derivative only depends on the input x and output y
+            "square" | "cube" | "reciprocal" | "rsqrt" => {
+                let x = m.children[0].get_data();
+                let local = |i: usize| -> f64 {
+                    match operation {
+                        "square" => 2.0 * x[i],
+                        "cube" => 3.0 * x[i] * x[i],
+                        "reciprocal" => -m.data[i] * m.data[i],
+                        _ => -0.5 * m.data[i] * m.data[i] * m.data[i],
+                    }
+                };
+                let grad: Vec<f64> = (0..m.data.len()).map(|i| m.grad[i] * local(i)).collect();
+                m.children[0].update_grad(&grad);
+            },
+
+            "from values" => {
+                for (v, g) in m.values.iter().zip(m.grad.iter()) {
+                    v.update_grad(*g);
+                }
+            },
+
+            _ => {},
+        }
+    }
+
+    // backward pass for the entire graph, seeding every output element with gradient 1
+    // (i.e. the gradient of the sum of the output)
+    pub fn backward(&self) {
+        {
+            let mut m = self.0.borrow_mut();
+            m.grad = vec![1.0; m.data.len()];
+        }
+        Matrix::propagate(&[self.clone_rc()]);
+    }
+
+    // backward from gradients already sitting on the roots, without seeding them
+    // used when gradients arrive from the scalar engine (Value::from_matrix)
+    pub fn propagate(roots: &[Matrix]) {
+        autograd::propagate(roots.iter().map(|r| Box::new(r.clone_rc()) as Box<dyn Node>).collect());
+    }
+}
+
+impl Node for Matrix {
+    fn id(&self) -> usize {
+        return Rc::as_ptr(&self.0) as usize;
+    }
+
+    fn inputs(&self) -> Vec<Box<dyn Node>> {
+        let m = self.0.borrow();
+        let children = m.children.iter().map(|c| Box::new(c.clone_rc()) as Box<dyn Node>);
+        let values = m.values.iter().map(|v| Box::new(v.clone_rc()) as Box<dyn Node>);
+        return children.chain(values).collect();
+    }
+
+    fn backward_local(&self) {
+        self._backward();
+    }
+
+    fn op_name(&self) -> String {
+        return format!("matrix {}", self.0.borrow().op);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+}
@@ -0,0 +1,211 @@
+use std::f64::consts::PI;
+
+use crate::error::{Result, RustMlError};
+use crate::matrix::Matrix;
+use crate::random;
+use crate::stats;
+
+// gaussian mixture models fitted with expectation maximization (rows are samples, columns are features)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CovarianceType {
+    // every component has its own full covariance matrix
+    Full,
+    // every component has its own diagonal covariance (independent features within a component)
+    Diag,
+}
+
+pub struct GaussianMixture {
+    pub n_components: usize,
+    pub covariance_type: CovarianceType,
+    pub max_iter: usize,
+    // stop once the mean log-likelihood improves by less than this
+    pub tol: f64,
+    // added to the covariance diagonals so components can't collapse onto a single point
+    pub reg: f64,
+
+    // set by fit
+    pub weights: Vec<f64>,
+    pub means: Vec<Vec<f64>>,
+    // d x d per component, diagonal for CovarianceType::Diag
+    pub covariances: Vec<Matrix>,
+    // mean log-likelihood of the training data after every em iteration
+    pub log_likelihoods: Vec<f64>,
+    pub converged: bool,
+
+    // cholesky factors of the covariances, cached for the densities
+    chol: Vec<Matrix>,
+}
+
+impl GaussianMixture {
+    // full covariances, up to 100 iterations with tolerance 1e-4 by default
+    pub fn new(n_components: usize) -> Self {
+        GaussianMixture {
+            n_components,
+            covariance_type: CovarianceType::Full,
+            max_iter: 100,
+            tol: 1e-4,
+            reg: 1e-6,
+            weights: vec![],
+            means: vec![],
+            covariances: vec![],
+            log_likelihoods: vec![],
+            converged: false,
+            chol: vec![],
+        }
+    }
+
+    pub fn with_covariance_type(mut self, covariance_type: CovarianceType) -> Self {
+        self.covariance_type = covariance_type;
+        self
+    }
+
+    pub fn with_max_iter(mut self, max_iter: usize) -> Self {
+        self.max_iter = max_iter;
+        self
+    }
+
+    pub fn with_tol(mut self, tol: f64) -> Self {
+        self.tol = tol;
+        self
+    }
+
+    // means start at distinct random rows, covariances at the data covariance, weights uniform
+    pub fn fit(&mut self, x: &Matrix) -> Result<()> {
+        let (n, d) = (x.rows(), x.cols());
+        let k = self.n_components;
+        if k == 0 || k > n || n < 2 {
+            return Err(RustMlError::Config(format!("a mixture of {} components needs at least {} rows, got {}", k, k.max(2), n)));
+        }
+        let mut idx: Vec<usize> = (0..n).collect();
+        random::shuffle(&mut idx);
+        self.means = idx[..k].iter().map(|&i| (0..d).map(|j| x.get(i, j)).collect()).collect();
+        let cov = self.regularize(stats::covariance(x).get_data(), d);
+        self.covariances = vec![cov; k].into_iter().map(|c| Matrix::new(d, d, c)).collect();
+        self.weights = vec![1.0 / k as f64; k];
+        self.chol = self.covariances.iter().map(|c| c.cholesky()).collect::<Result<_>>()?;
+        self.log_likelihoods = vec![];
+        self.converged = false;
+
+        for _ in 0..self.max_iter {
+            // e step: responsibilities and the log-likelihood of the current parameters
+            let (resp, ll) = self.responsibilities(x)?;
+            if let Some(prev) = self.log_likelihoods.last() {
+                if (ll - prev).abs() < self.tol {
+                    self.log_likelihoods.push(ll);
+                    self.converged = true;
+                    break;
+                }
+            }
+            self.log_likelihoods.push(ll);
+
+            // m step: weighted means and covariances
+            for c in 0..k {
+                let r: Vec<f64> = (0..n).map(|i| resp.get(i, c)).collect();
+                let nk = r.iter().sum::<f64>() + 10.0 * f64::EPSILON;
+                self.weights[c] = nk / n as f64;
+                let mean: Vec<f64> = (0..d).map(|j| (0..n).map(|i| r[i] * x.get(i, j)).sum::<f64>() / nk).collect();
+                let mut cov = vec![0.0; d * d];
+                for a in 0..d {
+                    for b in a..d {
+                        if self.covariance_type == CovarianceType::Diag && a != b {
+                            continue;
+                        }
+                        let s = (0..n).map(|i| r[i] * (x.get(i, a) - mean[a]) * (x.get(i, b) - mean[b])).sum::<f64>() / nk;
+                        cov[a * d + b] = s;
+                        cov[b * d + a] = s;
+                    }
+                }
+                self.means[c] = mean;
+                self.covariances[c] = Matrix::new(d, d, self.regularize(cov, d));
+            }
+            self.chol = self.covariances.iter().map(|c| c.cholesky()).collect::<Result<_>>()?;
+        }
+        tracing::debug!(iterations = self.log_likelihoods.len(), converged = self.converged, "gaussian mixture fit");
+        return Ok(());
+    }
+
+    // diag keeps only the diagonal, both get reg added to it
+    fn regularize(&self, mut cov: Vec<f64>, d: usize) -> Vec<f64> {
+        for a in 0..d {
+            for b in 0..d {
+                if a == b {
+                    cov[a * d + b] += self.reg;
+                } else if self.covariance_type == CovarianceType::Diag {
+                    cov[a * d + b] = 0.0;
+                }
+            }
+        }
+        return cov;
+    }
+
+    // log(weight_c) + log N(x_i | mean_c, cov_c), n x k
+    fn weighted_log_densities(&self, x: &Matrix) -> Result<Vec<Vec<f64>>> {
+        if self.chol.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        let (n, d) = (x.rows(), x.cols());
+        if d != self.means[0].len() {
+            return Err(RustMlError::Shape(format!("fit on {} columns, got {}", self.means[0].len(), d)));
+        }
+        let mut out = vec![vec![0.0; self.n_components]; n];
+        for (c, l) in self.chol.iter().enumerate() {
+            let log_det = 2.0 * (0..d).map(|j| l.get(j, j).ln()).sum::<f64>();
+            // columns are the centered samples, cov^-1 applied to all of them in one solve
+            let mut diff = vec![0.0; d * n];
+            for i in 0..n {
+                for j in 0..d {
+                    diff[j * n + i] = x.get(i, j) - self.means[c][j];
+                }
+            }
+            let solved = Matrix::cholesky_solve(l, &Matrix::new(d, n, diff.clone()))?.get_data();
+            for (i, row) in out.iter_mut().enumerate() {
+                let mahalanobis = (0..d).map(|j| diff[j * n + i] * solved[j * n + i]).sum::<f64>();
+                row[c] = self.weights[c].ln() - 0.5 * (d as f64 * (2.0 * PI).ln() + log_det + mahalanobis);
+            }
+        }
+        return Ok(out);
+    }
+
+    // posterior component probabilities per sample and the mean log-likelihood
+    fn responsibilities(&self, x: &Matrix) -> Result<(Matrix, f64)> {
+        let logs = self.weighted_log_densities(x)?;
+        let k = self.n_components;
+        let mut resp = Vec::with_capacity(logs.len() * k);
+        let mut total = 0.0;
+        for row in logs.iter() {
+            let lse = log_sum_exp(row);
+            total += lse;
+            resp.extend(row.iter().map(|v| (v - lse).exp()));
+        }
+        return Ok((Matrix::new(logs.len(), k, resp), total / logs.len() as f64));
+    }
+
+    // soft assignments, n x n_components with rows summing to 1
+    pub fn predict_proba(&self, x: &Matrix) -> Result<Matrix> {
+        return Ok(self.responsibilities(x)?.0);
+    }
+
+    // most probable component per sample
+    pub fn predict(&self, x: &Matrix) -> Result<Vec<usize>> {
+        return Ok(self.predict_proba(x)?.argmax_rows());
+    }
+
+    // log p(x_i) under the mixture, per sample
+    pub fn score_samples(&self, x: &Matrix) -> Result<Vec<f64>> {
+        return Ok(self.weighted_log_densities(x)?.iter().map(|row| log_sum_exp(row)).collect());
+    }
+
+    // mean log-likelihood
+    pub fn score(&self, x: &Matrix) -> Result<f64> {
+        return Ok(self.responsibilities(x)?.1);
+    }
+}
+
+fn log_sum_exp(xs: &[f64]) -> f64 {
+    let m = xs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if m == f64::NEG_INFINITY {
+        return m;
+    }
+    return m + xs.iter().map(|v| (v - m).exp()).sum::<f64>().ln();
+}
@@ -0,0 +1,311 @@
+use crate::error::{Result, RustMlError};
+use crate::matrix::Matrix;
+use crate::random;
+
+// classical (non-neural) models working directly on Matrix data, rows are samples
+
+// adds a leading column of ones so the first weight acts as the intercept
+fn with_bias(x: &Matrix) -> Matrix {
+    let (n, d) = (x.rows(), x.cols());
+    let mut data = Vec::with_capacity(n * (d + 1));
+    for i in 0..n {
+        data.push(1.0);
+        for j in 0..d {
+            data.push(x.get(i, j));
+        }
+    }
+    return Matrix::new(n, d + 1, data);
+}
+
+// bayesian linear regression with a gaussian prior w ~ N(0, alpha^-1 I)
+// and gaussian observation noise with known precision beta
+// the posterior over the weights stays gaussian, so every update is exact (conjugate)
+pub struct BayesianLinearRegression {
+    pub alpha: f64,
+    pub beta: f64,
+    pub fit_intercept: bool,
+
+    // posterior mean and precision (inverse covariance) of the weights,
+    // the intercept is the first weight when fit_intercept is set
+    pub mean: Vec<f64>,
+    pub precision: Option<Matrix>,
+}
+
+impl BayesianLinearRegression {
+    pub fn new(alpha: f64, beta: f64) -> Result<Self> {
+        if alpha <= 0.0 || beta <= 0.0 {
+            return Err(RustMlError::Config(format!("alpha and beta must be positive, got {} and {}", alpha, beta)));
+        }
+        return Ok(BayesianLinearRegression {
+            alpha,
+            beta,
+            fit_intercept: true,
+            mean: vec![],
+            precision: None,
+        });
+    }
+
+    fn design(&self, x: &Matrix) -> Matrix {
+        if self.fit_intercept { with_bias(x) } else { x.clone_rc() }
+    }
+
+    // resets to the prior and conditions on (x, y)
+    pub fn fit(&mut self, x: &Matrix, y: &[f64]) -> Result<()> {
+        self.mean = vec![];
+        self.precision = None;
+        return self.update(x, y);
+    }
+
+    // conditions the current posterior on more data, the old posterior acts as the prior:
+    // P_new = P_old + beta X^T X, m_new = P_new^-1 (P_old m_old + beta X^T y)
+    pub fn update(&mut self, x: &Matrix, y: &[f64]) -> Result<()> {
+        if x.rows() != y.len() {
+            return Err(RustMlError::Shape(format!("x has {} rows but y has {} values", x.rows(), y.len())));
+        }
+        let phi = self.design(x);
+        let (n, d) = (phi.rows(), phi.cols());
+
+        let (prior_p, prior_m) = match &self.precision {
+            Some(p) => {
+                if p.rows() != d {
+                    return Err(RustMlError::Shape(format!("model was fit on {} features, got {}", p.rows(), d)));
+                }
+                (p.get_data(), self.mean.clone())
+            },
+            None => {
+                let mut p = vec![0.0; d * d];
+                for i in 0..d {
+                    p[i * d + i] = self.alpha;
+                }
+                (p, vec![0.0; d])
+            },
+        };
+
+        let mut p = prior_p.clone();
+        let mut rhs = vec![0.0; d];
+        for a in 0..d {
+            for b in 0..d {
+                p[a * d + b] += self.beta * (0..n).map(|i| phi.get(i, a) * phi.get(i, b)).sum::<f64>();
+                rhs[a] += prior_p[a * d + b] * prior_m[b];
+            }
+            rhs[a] += self.beta * (0..n).map(|i| phi.get(i, a) * y[i]).sum::<f64>();
+        }
+
+        let precision = Matrix::new(d, d, p);
+        let l = precision.cholesky()?;
+        self.mean = Matrix::cholesky_solve(&l, &Matrix::new(d, 1, rhs))?.get_data();
+        self.precision = Some(precision);
+        return Ok(());
+    }
+
+    // posterior covariance of the weights
+    pub fn covariance(&self) -> Result<Matrix> {
+        let p = self.precision.as_ref().ok_or(RustMlError::NotFitted)?;
+        return p.inverse();
+    }
+
+    // predictive mean and variance for every row, the variance includes the noise 1 / beta
+    pub fn predict(&self, x: &Matrix) -> Result<(Vec<f64>, Vec<f64>)> {
+        let p = self.precision.as_ref().ok_or(RustMlError::NotFitted)?;
+        let phi = self.design(x);
+        let d = phi.cols();
+        if d != self.mean.len() {
+            return Err(RustMlError::Shape(format!("model was fit on {} features, got {}", self.mean.len(), d)));
+        }
+
+        // x^T S x with S = P^-1, done as a solve per row through the cholesky factor
+        let l = p.cholesky()?;
+        let mut means = vec![];
+        let mut vars = vec![];
+        for i in 0..phi.rows() {
+            let row: Vec<f64> = (0..d).map(|j| phi.get(i, j)).collect();
+            means.push(row.iter().zip(self.mean.iter()).map(|(a, b)| a * b).sum());
+            let s_row = Matrix::cholesky_solve(&l, &Matrix::new(d, 1, row.clone()))?.get_data();
+            let quad: f64 = row.iter().zip(s_row.iter()).map(|(a, b)| a * b).sum();
+            vars.push(1.0 / self.beta + quad);
+        }
+        return Ok((means, vars));
+    }
+}
+
+// one shuffled pass over the rows, step gets each row's index and features and updates the model
+fn sgd_pass(x: &Matrix, mut step: impl FnMut(usize, &[f64])) {
+    let mut order: Vec<usize> = (0..x.rows()).collect();
+    random::shuffle(&mut order);
+    for i in order {
+        let row: Vec<f64> = (0..x.cols()).map(|j| x.get(i, j)).collect();
+        step(i, &row);
+    }
+}
+
+fn check_features(fitted: usize, x: &Matrix) -> Result<()> {
+    if fitted != x.cols() {
+        return Err(RustMlError::Shape(format!("model was fit on {} features, got {}", fitted, x.cols())));
+    }
+    return Ok(());
+}
+
+// linear regression on squared error with l2 penalty, fitted by stochastic gradient descent
+// partial_fit continues from the current weights, for data that arrives in chunks
+pub struct SgdRegressor {
+    pub lr: f64,
+    pub l2: f64,
+    // passes over the data in fit()
+    pub epochs: usize,
+
+    pub weights: Vec<f64>,
+    pub intercept: f64,
+}
+
+impl SgdRegressor {
+    pub fn new(lr: f64, l2: f64) -> Self {
+        SgdRegressor {
+            lr,
+            l2,
+            epochs: 10,
+            weights: vec![],
+            intercept: 0.0,
+        }
+    }
+
+    pub fn with_epochs(mut self, epochs: usize) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    // starts from zero weights and runs epochs passes
+    pub fn fit(&mut self, x: &Matrix, y: &[f64]) -> Result<()> {
+        self.weights = vec![];
+        self.intercept = 0.0;
+        for _ in 0..self.epochs {
+            self.partial_fit(x, y)?;
+        }
+        return Ok(());
+    }
+
+    // one pass over (x, y) from wherever the model is, the first call sets the number of features
+    pub fn partial_fit(&mut self, x: &Matrix, y: &[f64]) -> Result<()> {
+        if x.rows() != y.len() {
+            return Err(RustMlError::Shape(format!("x has {} rows but y has {} values", x.rows(), y.len())));
+        }
+        if self.weights.is_empty() {
+            self.weights = vec![0.0; x.cols()];
+        }
+        check_features(self.weights.len(), x)?;
+        let (lr, l2) = (self.lr, self.l2);
+        let SgdRegressor { weights, intercept, .. } = self;
+        sgd_pass(x, |i, row| {
+            let err = row.iter().zip(weights.iter()).map(|(a, w)| a * w).sum::<f64>() + *intercept - y[i];
+            for (w, a) in weights.iter_mut().zip(row.iter()) {
+                *w -= lr * (err * a + l2 * *w);
+            }
+            *intercept -= lr * err;
+        });
+        return Ok(());
+    }
+
+    pub fn predict(&self, x: &Matrix) -> Result<Vec<f64>> {
+        if self.weights.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_features(self.weights.len(), x)?;
+        return Ok((0..x.rows()).map(|i| (0..x.cols()).map(|j| x.get(i, j) * self.weights[j]).sum::<f64>() + self.intercept).collect());
+    }
+}
+
+// multinomial logistic regression (softmax over one linear score per class) with l2 penalty, fitted by sgd
+// classes are the labels 0..k; partial_fit on a batch with a label it hasn't seen grows the model by that
+// class (zero weights, so it starts out no more likely than the others) and carries on
+pub struct SgdClassifier {
+    pub lr: f64,
+    pub l2: f64,
+    pub epochs: usize,
+
+    // one row of weights and one intercept per class
+    pub weights: Vec<Vec<f64>>,
+    pub intercepts: Vec<f64>,
+}
+
+impl SgdClassifier {
+    pub fn new(lr: f64, l2: f64) -> Self {
+        SgdClassifier {
+            lr,
+            l2,
+            epochs: 10,
+            weights: vec![],
+            intercepts: vec![],
+        }
+    }
+
+    pub fn with_epochs(mut self, epochs: usize) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    pub fn n_classes(&self) -> usize {
+        return self.weights.len();
+    }
+
+    pub fn fit(&mut self, x: &Matrix, labels: &[usize]) -> Result<()> {
+        self.weights = vec![];
+        self.intercepts = vec![];
+        for _ in 0..self.epochs {
+            self.partial_fit(x, labels)?;
+        }
+        return Ok(());
+    }
+
+    pub fn partial_fit(&mut self, x: &Matrix, labels: &[usize]) -> Result<()> {
+        if x.rows() != labels.len() {
+            return Err(RustMlError::Shape(format!("x has {} rows but there are {} labels", x.rows(), labels.len())));
+        }
+        if let Some(w) = self.weights.first() {
+            check_features(w.len(), x)?;
+        }
+        let k = labels.iter().map(|l| l + 1).max().unwrap_or(0).max(self.n_classes());
+        if k > self.n_classes() {
+            tracing::debug!(from = self.n_classes(), to = k, "sgd classifier grew new classes");
+        }
+        self.weights.resize(k, vec![0.0; x.cols()]);
+        self.intercepts.resize(k, 0.0);
+
+        let (lr, l2) = (self.lr, self.l2);
+        let SgdClassifier { weights, intercepts, .. } = self;
+        sgd_pass(x, |i, row| {
+            let p = softmax_scores(weights, intercepts, row);
+            // d(cross entropy)/d(score_c) = p_c - [c == label]
+            for (c, (w, b)) in weights.iter_mut().zip(intercepts.iter_mut()).enumerate() {
+                let g = p[c] - if c == labels[i] { 1.0 } else { 0.0 };
+                for (wj, a) in w.iter_mut().zip(row.iter()) {
+                    *wj -= lr * (g * a + l2 * *wj);
+                }
+                *b -= lr * g;
+            }
+        });
+        return Ok(());
+    }
+
+    // class probabilities, rows x n_classes
+    pub fn predict_proba(&self, x: &Matrix) -> Result<Matrix> {
+        let w = self.weights.first().ok_or(RustMlError::NotFitted)?;
+        check_features(w.len(), x)?;
+        let mut data = Vec::with_capacity(x.rows() * self.n_classes());
+        for i in 0..x.rows() {
+            let row: Vec<f64> = (0..x.cols()).map(|j| x.get(i, j)).collect();
+            data.extend(softmax_scores(&self.weights, &self.intercepts, &row));
+        }
+        return Ok(Matrix::new(x.rows(), self.n_classes(), data));
+    }
+
+    pub fn predict(&self, x: &Matrix) -> Result<Vec<usize>> {
+        return Ok(self.predict_proba(x)?.argmax_rows());
+    }
+}
+
+fn softmax_scores(weights: &[Vec<f64>], intercepts: &[f64], row: &[f64]) -> Vec<f64> {
+    let scores: Vec<f64> = weights.iter().zip(intercepts.iter()).map(|(w, b)| w.iter().zip(row.iter()).map(|(a, c)| a * c).sum::<f64>() + b).collect();
+    let m = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<f64> = scores.iter().map(|s| (s - m).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    return exps.iter().map(|e| e / total).collect();
+}
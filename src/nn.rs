@@ -1,30 +1,283 @@
+use alloc::{boxed::Box, collections::BTreeSet, format, rc::Rc, string::{String, ToString}, vec, vec::Vec};
+use core::cell::{Cell, RefCell};
+
+#[cfg(feature = "std")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::autograd::Node;
+use crate::error::{Result, RustMlError};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
 use crate::value::*;
+#[cfg(feature = "std")]
+use crate::parallel;
+use crate::random;
+
+// common interface for everything that can be stacked into a model
+pub trait Module {
+    fn forward(&self, x: &[Value]) -> Vec<Value>;
+
+    fn parameters(&self) -> Vec<Value>;
+
+    // inference on plain floats, no graph is built so nothing can be backpropagated
+    // the default goes through forward() on constants, modules override it to skip the Values
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let x: Vec<Value> = x.iter().map(|xi| Value::constant(*xi)).collect();
+        self.forward(&x).iter().map(|y| y.get_data()).collect()
+    }
+
+    // predict over a batch of rows
+    // Values can't cross threads, so this is sequential unless a module copies its weights out first
+    fn predict_batch(&self, xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        xs.iter().map(|x| self.predict(x)).collect()
+    }
+
+    // number of inputs the module expects, None if it takes any length (activations, dropout)
+    fn input_shape(&self) -> Option<usize> {
+        None
+    }
+
+    // length of the output for an input of the given length, or an error if the module can't take it
+    // the default finds out by running predict() on zeros, modules with known shapes override it
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        Ok(self.predict(&vec![0.0; input]).len())
+    }
+
+    fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.set_grad(0.0);
+        }
+    }
+
+    // switch between training and evaluation behaviour, only matters for layers like dropout
+    fn train(&self, _mode: bool) {}
+
+    // the current mode, None if nothing in the module depends on it
+    // containers report the first child that has a mode
+    fn training_mode(&self) -> Option<bool> {
+        None
+    }
+
+    // the output of the given layer (inclusive) for one input, i.e. the model cut after that layer
+    // containers look layers up by position or name, a plain module is a single layer 0
+    fn features(&self, x: &[f64], up_to_layer: LayerId) -> Result<Vec<f64>> {
+        match up_to_layer {
+            LayerId::Index(0) => Ok(self.predict(x)),
+            id => Err(RustMlError::Config(format!("no layer {} in a single layer module", id))),
+        }
+    }
+
+    // parameters split by layer, named like features() looks them up; a plain module is one layer "0"
+    fn layer_parameters(&self) -> Vec<(String, Vec<Value>)> {
+        vec![("0".to_string(), self.parameters())]
+    }
+
+    // all parameter values in parameters() order
+    fn get_flat_params(&self) -> Vec<f64> {
+        get_flat_params(&self.parameters())
+    }
+
+    fn set_flat_params(&self, flat: &[f64]) {
+        set_flat_params(&self.parameters(), flat);
+    }
+}
+
+// a layer inside a container, by position or by name
+// layers are named after their position ("0", "1", ...) unless given names (Sequential::named)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerId {
+    Index(usize),
+    Name(String),
+}
+
+impl From<usize> for LayerId {
+    fn from(i: usize) -> Self {
+        LayerId::Index(i)
+    }
+}
 
-use rand::prelude::*;
+impl From<&str> for LayerId {
+    fn from(name: &str) -> Self {
+        LayerId::Name(name.to_string())
+    }
+}
+
+impl core::fmt::Display for LayerId {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            LayerId::Index(i) => write!(f, "{}", i),
+            LayerId::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+// position of a layer given the container's layer names
+fn resolve_layer(names: &[String], id: &LayerId) -> Result<usize> {
+    let found = match id {
+        LayerId::Index(i) => Some(*i).filter(|i| *i < names.len()),
+        LayerId::Name(name) => names.iter().position(|n| n == name),
+    };
+    found.ok_or_else(|| RustMlError::Config(format!("no layer {} among {} layers", id, names.len())))
+}
+
+fn index_names(n: usize) -> Vec<String> {
+    (0..n).map(|i| i.to_string()).collect()
+}
+
+// message of a shape error without the "shape mismatch:" prefix, for wrapping it with more context
+fn shape_message(e: RustMlError) -> String {
+    match e {
+        RustMlError::Shape(msg) => msg,
+        e => e.to_string(),
+    }
+}
+
+// copies a module with fresh parameter nodes holding the same values, so the copy can be
+// trained without touching the original (clone_rc would share the nodes)
+// weights tied inside the original become separate weights in the copy
+pub trait DeepClone {
+    fn deep_clone(&self) -> Self;
+}
+
+// drops repeated parameters (same node, not same value), keeping the first occurrence
+// so tied weights shared between modules are only returned, and updated, once
+pub fn unique_params(params: Vec<Value>) -> Vec<Value> {
+    let mut seen: BTreeSet<usize> = BTreeSet::new();
+    params.into_iter().filter(|p| seen.insert(p.id())).collect()
+}
+
+// read a list of parameters into one plain vector
+pub fn get_flat_params(params: &[Value]) -> Vec<f64> {
+    params.iter().map(|p| p.get_data()).collect()
+}
+
+// write a plain vector back into a list of parameters, the lengths must match
+pub fn set_flat_params(params: &[Value], flat: &[f64]) {
+    assert_eq!(params.len(), flat.len(), "expected {} parameter values, got {}", params.len(), flat.len());
+    for (p, x) in params.iter().zip(flat.iter()) {
+        p.set_data(*x);
+    }
+}
+
+// polyak averaging of a target network towards its source, in place: target = tau * source +
+// (1 - tau) * target for every parameter, e.g. the slowly moving target of dqn or actor-critic
+// built with deep_clone; tau = 1 copies the source over
+// the two need the same parameters in the same order (a deep clone of a model with tied weights
+// has more of them than the original)
+pub fn soft_update(target: &dyn Module, source: &dyn Module, tau: f64) {
+    assert!((0.0..=1.0).contains(&tau), "tau must be in [0, 1], got {}", tau);
+    let (t, s) = (target.parameters(), source.parameters());
+    assert_eq!(t.len(), s.len(), "target has {} parameters, source has {}", t.len(), s.len());
+    for (t, s) in t.iter().zip(s.iter()) {
+        t.set_data(tau * s.get_data() + (1.0 - tau) * t.get_data());
+    }
+}
+
+// elementwise activation functions
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    Linear,
+    Tanh,
+    Softplus,
+    Gelu,
+    Silu,
+    Elu(f64),
+    Relu,
+    LeakyRelu(f64),
+    Sigmoid,
+}
+
+impl Activation {
+    pub fn apply(&self, x: &Value) -> Value {
+        match self {
+            Activation::Linear => x.clone_rc(),
+            Activation::Tanh => Value::tanh(x),
+            Activation::Softplus => Value::softplus(x),
+            Activation::Gelu => Value::gelu(x),
+            Activation::Silu => Value::silu(x),
+            Activation::Elu(alpha) => Value::elu(x, *alpha),
+            Activation::Relu => Value::relu(x),
+            Activation::LeakyRelu(alpha) => Value::leaky_relu(x, *alpha),
+            Activation::Sigmoid => Value::sigmoid(x),
+        }
+    }
+
+    pub fn apply_f64(&self, x: f64) -> f64 {
+        match self {
+            Activation::Linear => x,
+            Activation::Tanh => x.tanh(),
+            Activation::Softplus => softplus_f64(x),
+            Activation::Gelu => gelu_f64(x),
+            Activation::Silu => silu_f64(x),
+            Activation::Elu(alpha) => elu_f64(x, *alpha),
+            Activation::Relu => x.max(0.0),
+            Activation::LeakyRelu(alpha) => leaky_relu_f64(x, *alpha),
+            Activation::Sigmoid => stable_sigmoid(x),
+        }
+    }
+}
+
+impl DeepClone for Activation {
+    fn deep_clone(&self) -> Self {
+        *self
+    }
+}
+
+// used as a standalone layer, applies the activation to every input
+impl Module for Activation {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        x.iter().map(|v| self.apply(v)).collect()
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        vec![]
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        x.iter().map(|xi| self.apply_f64(*xi)).collect()
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        Ok(input)
+    }
+}
 
 // a single neuron
 pub struct Neuron {
     pub w: Vec<Value>,
     pub b: Value,
+    pub act: Activation,
 }
 
 impl Neuron {
     pub fn new(nin: usize) -> Self {
-        let w = (0..nin).map(|_| Value::new(thread_rng().gen_range(-1.0..1.0))).collect();
-        let b = Value::new(thread_rng().gen_range(-1.0..1.0));
+        Self::with_activation(nin, Activation::Tanh)
+    }
+
+    pub fn with_activation(nin: usize, act: Activation) -> Self {
+        let w = (0..nin).map(|_| Value::new(random::uniform(-1.0, 1.0))).collect();
+        let b = Value::new(random::uniform(-1.0, 1.0));
         Neuron {
             w,
-            b
+            b,
+            act
         }
     }
 
-    pub fn forward(&self, x: &Vec<Value>) -> Value {
-        let mut y = Value::new(self.b.get_data());
-        for i in 0..self.w.len() {
-            y = Value::add(&y, &Value::mul(&self.w[i], &x[i]));
+    // one fused node for the weighted sum, plus the activation
+    pub fn forward(&self, x: &[Value]) -> Value {
+        let y = Value::fma_sum(&self.w, x, &self.b);
+        return self.act.apply(&y);
+    }
+
+    // same as forward but on plain floats
+    pub fn predict(&self, x: &[f64]) -> f64 {
+        let mut y = self.b.get_data();
+        for (wi, xi) in self.w.iter().zip(x.iter()) {
+            y += wi.get_data() * xi;
         }
-        y = Value::tanh(&y);
-        return y;
+        return self.act.apply_f64(y);
     }
 
     pub fn parameters(&self) -> Vec<Value> {
@@ -34,6 +287,35 @@ impl Neuron {
     }
 }
 
+impl DeepClone for Neuron {
+    fn deep_clone(&self) -> Self {
+        Neuron {
+            w: self.w.iter().map(|w| Value::new(w.get_data())).collect(),
+            b: Value::new(self.b.get_data()),
+            act: self.act,
+        }
+    }
+}
+
+// plain float copy of a layer's weights, Send + Sync so batches can be split across threads
+struct DenseWeights {
+    w: Vec<Vec<f64>>,
+    b: Vec<f64>,
+    act: Vec<Activation>,
+}
+
+impl DenseWeights {
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = Vec::with_capacity(self.b.len());
+        for ((w, b), act) in self.w.iter().zip(self.b.iter()).zip(self.act.iter()) {
+            // same accumulation order as Neuron::predict so both paths agree exactly
+            let z = w.iter().zip(x.iter()).fold(*b, |z, (wi, xi)| z + wi * xi);
+            y.push(act.apply_f64(z));
+        }
+        return y;
+    }
+}
+
 // a layer of neurons
 pub struct Layer {
     neurons: Vec<Neuron>,
@@ -41,49 +323,667 @@ pub struct Layer {
 
 impl Layer {
     pub fn new(nin: usize, nout: usize) -> Self {
-        let neurons = (0..nout).map(|_| Neuron::new(nin)).collect();
+        Self::with_activation(nin, nout, Activation::Tanh)
+    }
+
+    pub fn with_activation(nin: usize, nout: usize, act: Activation) -> Self {
+        let neurons = (0..nout).map(|_| Neuron::with_activation(nin, act)).collect();
         Layer {
             neurons
         }
     }
 
-    pub fn forward(&self, x: &Vec<Value>) -> Vec<Value> {
-        self.neurons.iter().map(|n| n.forward(&x)).collect()
+    // build a layer from existing neurons, e.g. ones sharing weights with another layer
+    pub fn from_neurons(neurons: Vec<Neuron>) -> Self {
+        Layer {
+            neurons
+        }
     }
 
-    pub fn parameters(&self) -> Vec<Value> {
-        self.neurons.iter().flat_map(|n| n.parameters()).collect()
+    pub fn neurons(&self) -> &[Neuron] {
+        &self.neurons
+    }
+
+    fn dense_weights(&self) -> DenseWeights {
+        DenseWeights {
+            w: self.neurons.iter().map(|n| n.w.iter().map(|w| w.get_data()).collect()).collect(),
+            b: self.neurons.iter().map(|n| n.b.get_data()).collect(),
+            act: self.neurons.iter().map(|n| n.act).collect(),
+        }
+    }
+}
+
+impl DeepClone for Layer {
+    fn deep_clone(&self) -> Self {
+        Layer::from_neurons(self.neurons.iter().map(|n| n.deep_clone()).collect())
+    }
+}
+
+impl Module for Layer {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        self.neurons.iter().map(|n| n.forward(x)).collect()
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        self.neurons.iter().map(|n| n.predict(x)).collect()
+    }
+
+    fn predict_batch(&self, xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let weights = self.dense_weights();
+        // deterministic mode and no_std builds stay on this thread
+        #[cfg(feature = "std")]
+        if !parallel::is_deterministic() {
+            return xs.par_iter().map(|x| weights.predict(x)).collect();
+        }
+        xs.iter().map(|x| weights.predict(x)).collect()
+    }
+
+    fn input_shape(&self) -> Option<usize> {
+        self.neurons.first().map(|n| n.w.len())
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        match self.input_shape() {
+            Some(nin) if nin != input => Err(RustMlError::Shape(format!("dense layer {} -> {} got {} inputs", nin, self.neurons.len(), input))),
+            _ => Ok(self.neurons.len()),
+        }
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        unique_params(self.neurons.iter().flat_map(|n| n.parameters()).collect())
+    }
+}
+
+// called with the index of a layer inside a container and that layer's output, on every forward or predict
+// through the container, e.g. to record hidden activations
+pub type ForwardHook = Box<dyn Fn(usize, &[f64])>;
+
+// returned by register_forward_hook, hands the hook back to remove_forward_hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HookHandle(usize);
+
+// the forward hooks of one container, behind a RefCell so they can be added through &self like the rest of Module
+#[derive(Default)]
+struct Hooks {
+    hooks: RefCell<Vec<(HookHandle, usize, ForwardHook)>>,
+    next: Cell<usize>,
+}
+
+impl Hooks {
+    fn register(&self, layer: usize, hook: ForwardHook) -> HookHandle {
+        let handle = HookHandle(self.next.get());
+        self.next.set(handle.0 + 1);
+        self.hooks.borrow_mut().push((handle, layer, hook));
+        handle
+    }
+
+    fn remove(&self, handle: HookHandle) -> bool {
+        let mut hooks = self.hooks.borrow_mut();
+        let before = hooks.len();
+        hooks.retain(|(h, _, _)| *h != handle);
+        hooks.len() < before
+    }
+
+    fn is_empty(&self) -> bool {
+        self.hooks.borrow().is_empty()
+    }
+
+    fn run(&self, layer: usize, y: &[f64]) {
+        for (_, l, hook) in self.hooks.borrow().iter() {
+            if *l == layer {
+                hook(layer, y);
+            }
+        }
+    }
+
+    fn run_values(&self, layer: usize, y: &[Value]) {
+        if !self.is_empty() {
+            self.run(layer, &y.iter().map(|v| v.get_data()).collect::<Vec<f64>>());
+        }
+    }
+}
+
+// output of one layer of a container for every row, e.g. hidden features for a classical model or manifold::Tsne
+fn layer_outputs(model: &dyn Module, hooks: &Hooks, n_layers: usize, xs: &[Vec<f64>], layer: usize) -> Vec<Vec<f64>> {
+    assert!(layer < n_layers, "layer {} out of range for {} layers", layer, n_layers);
+    let outputs: Rc<RefCell<Vec<Vec<f64>>>> = Default::default();
+    let sink = outputs.clone();
+    let handle = hooks.register(layer, Box::new(move |_, y| sink.borrow_mut().push(y.to_vec())));
+    for x in xs {
+        model.predict(x);
     }
+    hooks.remove(handle);
+    outputs.take()
 }
 
 // multiple layers of neurons
 pub struct MLP {
     layers: Vec<Layer>,
+    hooks: Hooks,
 }
 
 impl MLP {
-    pub fn new(sz: &Vec<usize>) -> Self {
-        let layers = sz.windows(2).map(|n| Layer::new(n[0], n[1])).collect();
+    pub fn new(sz: &[usize]) -> Self {
+        Self::with_activation(sz, Activation::Tanh)
+    }
+
+    pub fn with_activation(sz: &[usize], act: Activation) -> Self {
+        let layers = sz.windows(2).map(|n| Layer::with_activation(n[0], n[1], act)).collect();
+        MLP::from_layers(layers)
+    }
+
+    pub fn from_layers(layers: Vec<Layer>) -> Self {
         MLP {
-            layers
+            layers,
+            hooks: Hooks::default()
         }
     }
 
-    pub fn forward(&self, x: &Vec<Value>) -> Vec<Value> {
-        let mut y = x.clone();
-        for l in &self.layers {
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    // number of inputs the first layer expects
+    pub fn nin(&self) -> usize {
+        self.layers.first().and_then(|l| l.neurons.first()).map_or(0, |n| n.w.len())
+    }
+
+    pub fn register_forward_hook(&self, layer: usize, hook: ForwardHook) -> HookHandle {
+        self.hooks.register(layer, hook)
+    }
+
+    // false if the hook was already removed
+    pub fn remove_forward_hook(&self, handle: HookHandle) -> bool {
+        self.hooks.remove(handle)
+    }
+
+    // what layer `layer` outputs for every row
+    pub fn hidden_states(&self, xs: &[Vec<f64>], layer: usize) -> Vec<Vec<f64>> {
+        layer_outputs(self, &self.hooks, self.layers.len(), xs, layer)
+    }
+}
+
+impl DeepClone for MLP {
+    fn deep_clone(&self) -> Self {
+        MLP::from_layers(self.layers.iter().map(|l| l.deep_clone()).collect())
+    }
+}
+
+impl Module for MLP {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        let mut y = x.to_vec();
+        for (i, l) in self.layers.iter().enumerate() {
             y = l.forward(&y);
+            self.hooks.run_values(i, &y);
         }
         y
     }
 
-    pub fn parameters(&self) -> Vec<Value> {
-        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = x.to_vec();
+        for (i, l) in self.layers.iter().enumerate() {
+            y = l.predict(&y);
+            self.hooks.run(i, &y);
+        }
+        y
     }
 
-    pub fn zero_grad(&self) {
-        for p in self.parameters() {
-            p.set_grad(0.0);
+    fn features(&self, x: &[f64], up_to_layer: LayerId) -> Result<Vec<f64>> {
+        let last = resolve_layer(&index_names(self.layers.len()), &up_to_layer)?;
+        let mut y = x.to_vec();
+        for (i, l) in self.layers[..=last].iter().enumerate() {
+            y = l.predict(&y);
+            self.hooks.run(i, &y);
+        }
+        Ok(y)
+    }
+
+    fn predict_batch(&self, xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        // hooks can't run on other threads, deterministic mode stays on this one too
+        if !self.hooks.is_empty() {
+            return xs.iter().map(|x| self.predict(x)).collect();
+        }
+        let weights: Vec<DenseWeights> = self.layers.iter().map(|l| l.dense_weights()).collect();
+        let run = |x: &Vec<f64>| {
+            let mut y = x.clone();
+            for w in weights.iter() {
+                y = w.predict(&y);
+            }
+            y
+        };
+        #[cfg(feature = "std")]
+        if !parallel::is_deterministic() {
+            return xs.par_iter().map(run).collect();
         }
+        xs.iter().map(run).collect()
     }
-}
\ No newline at end of file
+
+    fn input_shape(&self) -> Option<usize> {
+        self.layers.first().and_then(|l| l.input_shape())
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        let mut shape = input;
+        for (i, l) in self.layers.iter().enumerate() {
+            shape = l.output_shape(shape).map_err(|e| RustMlError::Shape(format!("layer {}: {}", i, shape_message(e))))?;
+        }
+        Ok(shape)
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        unique_params(self.layers.iter().flat_map(|l| l.parameters()).collect())
+    }
+
+    fn layer_parameters(&self) -> Vec<(String, Vec<Value>)> {
+        index_names(self.layers.len()).into_iter().zip(self.layers.iter().map(|l| l.parameters())).collect()
+    }
+}
+
+// lookup table of learnable vectors, one row per token id
+// forward takes token ids (as Values) and returns their rows concatenated
+// only a few rows get gradients per step, pair it with a sparse optimizer
+pub struct Embedding {
+    pub weights: Vec<Vec<Value>>,
+}
+
+impl Embedding {
+    pub fn new(num: usize, dim: usize) -> Self {
+        let weights = (0..num).map(|_| (0..dim).map(|_| Value::new(random::normal(0.0, 1.0))).collect()).collect();
+        Embedding {
+            weights
+        }
+    }
+
+    pub fn lookup(&self, id: usize) -> Vec<Value> {
+        self.weights[id].iter().map(|w| w.clone_rc()).collect()
+    }
+}
+
+impl DeepClone for Embedding {
+    fn deep_clone(&self) -> Self {
+        Embedding {
+            weights: self.weights.iter().map(|row| row.iter().map(|w| Value::new(w.get_data())).collect()).collect()
+        }
+    }
+}
+
+impl Module for Embedding {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        x.iter().flat_map(|id| self.lookup(id.get_data() as usize)).collect()
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        x.iter().flat_map(|id| self.weights[*id as usize].iter().map(|w| w.get_data())).collect()
+    }
+
+    // one row per token id
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        Ok(input * self.weights.first().map_or(0, |row| row.len()))
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        self.weights.iter().flatten().map(|w| w.clone_rc()).collect()
+    }
+}
+
+// inverted dropout: in training mode each input is zeroed with probability p
+// and the rest are scaled by 1 / (1 - p), in eval mode it does nothing
+pub struct Dropout {
+    pub p: f64,
+    training: Cell<bool>,
+}
+
+impl Dropout {
+    pub fn new(p: f64) -> Self {
+        assert!((0.0..1.0).contains(&p), "dropout probability must be in [0, 1), got {}", p);
+        Dropout {
+            p,
+            training: Cell::new(true)
+        }
+    }
+
+    pub fn is_training(&self) -> bool {
+        self.training.get()
+    }
+}
+
+impl DeepClone for Dropout {
+    fn deep_clone(&self) -> Self {
+        Dropout {
+            p: self.p,
+            training: Cell::new(self.training.get())
+        }
+    }
+}
+
+impl Module for Dropout {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        if !self.training.get() || self.p == 0.0 {
+            return x.to_vec();
+        }
+        let scale = 1.0 / (1.0 - self.p);
+        x.iter().map(|v| {
+            let keep = if random::bernoulli(self.p) { 0.0 } else { scale };
+            Value::mul(v, &Value::constant(keep))
+        }).collect()
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        vec![]
+    }
+
+    // inference never drops anything, whatever the training mode
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        x.to_vec()
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        Ok(input)
+    }
+
+    fn train(&self, mode: bool) {
+        self.training.set(mode);
+    }
+
+    fn training_mode(&self) -> Option<bool> {
+        Some(self.training.get())
+    }
+}
+
+// a stack of arbitrary modules applied one after another
+pub struct Sequential {
+    pub layers: Vec<Box<dyn Module>>,
+    // one per layer, their positions unless built with named()
+    pub names: Vec<String>,
+    hooks: Hooks,
+}
+
+impl Sequential {
+    // panics if consecutive layers don't fit together, see try_new
+    pub fn new(layers: Vec<Box<dyn Module>>) -> Self {
+        match Sequential::try_new(layers) {
+            Ok(seq) => seq,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // checks that every layer accepts what the one before it produces, starting from the first
+    // layer with a known input size, so a mismatch fails here instead of in the middle of forward
+    pub fn try_new(layers: Vec<Box<dyn Module>>) -> Result<Self> {
+        let mut shape: Option<usize> = None;
+        for (i, l) in layers.iter().enumerate() {
+            let Some(input) = shape.or_else(|| l.input_shape()) else {
+                continue;
+            };
+            let output = l.output_shape(input).map_err(|e| RustMlError::Shape(format!("layer {}: {}", i, shape_message(e))))?;
+            shape = Some(output);
+        }
+        Ok(Sequential {
+            names: index_names(layers.len()),
+            layers,
+            hooks: Hooks::default()
+        })
+    }
+
+    // layers with names to look them up by in features(), e.g. "body" and "head"
+    pub fn named(layers: Vec<(&str, Box<dyn Module>)>) -> Result<Self> {
+        let names: Vec<String> = layers.iter().map(|(name, _)| name.to_string()).collect();
+        if let Some(dup) = names.iter().enumerate().find(|(i, n)| names[..*i].contains(n)) {
+            return Err(RustMlError::Config(format!("layer name '{}' is used twice", dup.1)));
+        }
+        let mut seq = Sequential::try_new(layers.into_iter().map(|(_, l)| l).collect())?;
+        seq.names = names;
+        Ok(seq)
+    }
+
+    pub fn register_forward_hook(&self, layer: usize, hook: ForwardHook) -> HookHandle {
+        self.hooks.register(layer, hook)
+    }
+
+    // false if the hook was already removed
+    pub fn remove_forward_hook(&self, handle: HookHandle) -> bool {
+        self.hooks.remove(handle)
+    }
+
+    // what layer `layer` outputs for every row
+    pub fn hidden_states(&self, xs: &[Vec<f64>], layer: usize) -> Vec<Vec<f64>> {
+        layer_outputs(self, &self.hooks, self.layers.len(), xs, layer)
+    }
+}
+
+impl Module for Sequential {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        let mut y = x.to_vec();
+        for (i, l) in self.layers.iter().enumerate() {
+            y = l.forward(&y);
+            self.hooks.run_values(i, &y);
+        }
+        y
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let mut y = x.to_vec();
+        for (i, l) in self.layers.iter().enumerate() {
+            y = l.predict(&y);
+            self.hooks.run(i, &y);
+        }
+        y
+    }
+
+    fn input_shape(&self) -> Option<usize> {
+        self.layers.first().and_then(|l| l.input_shape())
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        let mut shape = input;
+        for (i, l) in self.layers.iter().enumerate() {
+            shape = l.output_shape(shape).map_err(|e| RustMlError::Shape(format!("layer {}: {}", i, shape_message(e))))?;
+        }
+        Ok(shape)
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        unique_params(self.layers.iter().flat_map(|l| l.parameters()).collect())
+    }
+
+    fn layer_parameters(&self) -> Vec<(String, Vec<Value>)> {
+        self.names.iter().cloned().zip(self.layers.iter().map(|l| l.parameters())).collect()
+    }
+
+    fn train(&self, mode: bool) {
+        for l in &self.layers {
+            l.train(mode);
+        }
+    }
+
+    fn training_mode(&self) -> Option<bool> {
+        self.layers.iter().find_map(|l| l.training_mode())
+    }
+
+    fn features(&self, x: &[f64], up_to_layer: LayerId) -> Result<Vec<f64>> {
+        let last = resolve_layer(&self.names, &up_to_layer)?;
+        let mut y = x.to_vec();
+        for (i, l) in self.layers[..=last].iter().enumerate() {
+            y = l.predict(&y);
+            self.hooks.run(i, &y);
+        }
+        Ok(y)
+    }
+}
+
+// a trained model cut after one of its layers, used as a fixed input transformation for a new head
+// (transfer learning): forward computes the features on plain floats, so nothing flows back into
+// the body and it has no parameters to train
+pub struct FeatureExtractor {
+    pub body: Box<dyn Module>,
+    pub up_to_layer: LayerId,
+}
+
+impl FeatureExtractor {
+    // fails if the body has no such layer (checked with a zero input when the body's input size is known)
+    pub fn new(body: Box<dyn Module>, up_to_layer: impl Into<LayerId>) -> Result<Self> {
+        let up_to_layer = up_to_layer.into();
+        if let Some(nin) = body.input_shape() {
+            body.features(&vec![0.0; nin], up_to_layer.clone())?;
+        }
+        Ok(FeatureExtractor {
+            body,
+            up_to_layer
+        })
+    }
+}
+
+impl Module for FeatureExtractor {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        let x: Vec<f64> = x.iter().map(|v| v.get_data()).collect();
+        self.predict(&x).into_iter().map(Value::constant).collect()
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        self.body.features(x, self.up_to_layer.clone()).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        vec![]
+    }
+
+    fn input_shape(&self) -> Option<usize> {
+        self.body.input_shape()
+    }
+
+    fn train(&self, mode: bool) {
+        self.body.train(mode);
+    }
+
+    fn training_mode(&self) -> Option<bool> {
+        self.body.training_mode()
+    }
+}
+
+// a shared trunk feeding several heads, e.g. one regression and one classification output
+// forward returns the heads' outputs concatenated in order, forward_heads keeps them apart
+pub struct MultiHead {
+    pub trunk: Box<dyn Module>,
+    pub heads: Vec<Box<dyn Module>>,
+}
+
+impl MultiHead {
+    pub fn new(trunk: Box<dyn Module>, heads: Vec<Box<dyn Module>>) -> Self {
+        MultiHead {
+            trunk,
+            heads
+        }
+    }
+
+    // the trunk runs once, every head reads its output
+    pub fn forward_heads(&self, x: &[Value]) -> Vec<Vec<Value>> {
+        let shared = self.trunk.forward(x);
+        self.heads.iter().map(|h| h.forward(&shared)).collect()
+    }
+
+    // where each head's outputs sit in forward()'s result, for an input of the given length
+    pub fn head_ranges(&self, input: usize) -> Result<Vec<core::ops::Range<usize>>> {
+        let shared = self.trunk.output_shape(input)?;
+        let mut start = 0;
+        let mut ranges = vec![];
+        for (i, h) in self.heads.iter().enumerate() {
+            let n = h.output_shape(shared).map_err(|e| RustMlError::Shape(format!("head {}: {}", i, shape_message(e))))?;
+            ranges.push(start..start + n);
+            start += n;
+        }
+        Ok(ranges)
+    }
+}
+
+impl Module for MultiHead {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        self.forward_heads(x).into_iter().flatten().collect()
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let shared = self.trunk.predict(x);
+        self.heads.iter().flat_map(|h| h.predict(&shared)).collect()
+    }
+
+    fn input_shape(&self) -> Option<usize> {
+        self.trunk.input_shape()
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        Ok(self.head_ranges(input)?.last().map_or(0, |r| r.end))
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        let heads = self.heads.iter().flat_map(|h| h.parameters());
+        unique_params(self.trunk.parameters().into_iter().chain(heads).collect())
+    }
+
+    fn train(&self, mode: bool) {
+        self.trunk.train(mode);
+        for h in &self.heads {
+            h.train(mode);
+        }
+    }
+
+    fn training_mode(&self) -> Option<bool> {
+        self.trunk.training_mode().or_else(|| self.heads.iter().find_map(|h| h.training_mode()))
+    }
+}
+
+// one encoder shared by several inputs laid side by side, for similarity learning:
+// the input is `arity` equal parts (e.g. a pair or a triplet from data::DataLoader),
+// each is encoded by the same weights and the embeddings are concatenated
+pub struct Siamese {
+    pub encoder: Box<dyn Module>,
+    pub arity: usize,
+}
+
+impl Siamese {
+    pub fn new(encoder: Box<dyn Module>, arity: usize) -> Self {
+        assert!(arity > 0, "a siamese model needs at least one input");
+        Siamese {
+            encoder,
+            arity
+        }
+    }
+
+    fn part_len(&self, n: usize) -> usize {
+        assert_eq!(n % self.arity, 0, "input of length {} can't be split into {} equal parts", n, self.arity);
+        n / self.arity
+    }
+}
+
+impl Module for Siamese {
+    fn forward(&self, x: &[Value]) -> Vec<Value> {
+        let k = self.part_len(x.len());
+        x.chunks(k.max(1)).flat_map(|part| self.encoder.forward(part)).collect()
+    }
+
+    fn predict(&self, x: &[f64]) -> Vec<f64> {
+        let k = self.part_len(x.len());
+        x.chunks(k.max(1)).flat_map(|part| self.encoder.predict(part)).collect()
+    }
+
+    fn input_shape(&self) -> Option<usize> {
+        self.encoder.input_shape().map(|n| n * self.arity)
+    }
+
+    fn output_shape(&self, input: usize) -> Result<usize> {
+        if !input.is_multiple_of(self.arity) {
+            return Err(RustMlError::Shape(format!("input of length {} can't be split into {} equal parts", input, self.arity)));
+        }
+        Ok(self.encoder.output_shape(input / self.arity)? * self.arity)
+    }
+
+    fn parameters(&self) -> Vec<Value> {
+        self.encoder.parameters()
+    }
+
+    fn train(&self, mode: bool) {
+        self.encoder.train(mode);
+    }
+
+    fn training_mode(&self) -> Option<bool> {
+        self.encoder.training_mode()
+    }
+}
@@ -0,0 +1,36 @@
+use crate::nn::Module;
+use crate::value::Value;
+
+// fixed-step ode integration where the dynamics dy/dt = f(y) are a Module,
+// every step is ordinary graph ops so gradients flow back through the whole unrolled trajectory
+// (for time-dependent dynamics, carry t as an extra state with dt/dt = 1)
+
+// y + c * k, elementwise
+fn axpy(y: &[Value], c: f64, k: &[Value]) -> Vec<Value> {
+    let c = Value::constant(c);
+    return y.iter().zip(k.iter()).map(|(yi, ki)| Value::add(yi, &Value::mul(&c, ki))).collect();
+}
+
+// one classic runge-kutta 4 step
+pub fn rk4_step(f: &dyn Module, y: &[Value], dt: f64) -> Vec<Value> {
+    let k1 = f.forward(y);
+    let k2 = f.forward(&axpy(y, dt / 2.0, &k1));
+    let k3 = f.forward(&axpy(y, dt / 2.0, &k2));
+    let k4 = f.forward(&axpy(y, dt, &k3));
+    assert_eq!(k1.len(), y.len(), "dynamics must return as many values as the state has");
+
+    let mut next = axpy(y, dt / 6.0, &k1);
+    next = axpy(&next, dt / 3.0, &k2);
+    next = axpy(&next, dt / 3.0, &k3);
+    return axpy(&next, dt / 6.0, &k4);
+}
+
+// integrates from y0 for the given number of steps, returns the states at every step including y0
+pub fn odeint(f: &dyn Module, y0: &[Value], dt: f64, steps: usize) -> Vec<Vec<Value>> {
+    let mut traj = vec![y0.to_vec()];
+    for _ in 0..steps {
+        let next = rk4_step(f, &traj[traj.len() - 1], dt);
+        traj.push(next);
+    }
+    return traj;
+}
@@ -0,0 +1,571 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, RustMlError};
+use crate::nn::{get_flat_params, set_flat_params, unique_params};
+use crate::value::Value;
+
+// optimizers are driven by a closure that zeroes the gradients, computes the loss,
+// calls backward() on it and returns its value, e.g.
+//     opt.step(&mut || { model.zero_grad(); let loss = ...; loss.backward(); loss.get_data() })
+// so optimizers that need several evaluations per step (line searches) can re-run it
+pub trait Optimizer {
+    // one optimization step, returns the loss at the updated parameters
+    // (or the last loss evaluated if computing it again would cost an extra pass)
+    fn step(&mut self, closure: &mut dyn FnMut() -> f64) -> f64;
+
+    fn parameters(&self) -> &[Value];
+
+    // change the learning rate between steps, e.g. for schedules or training stages
+    fn set_lr(&mut self, lr: f64);
+
+    // change the momentum between steps (sgd's momentum, adam's beta1), for the one-cycle schedule
+    // optimizers without one ignore it
+    fn set_momentum(&mut self, _momentum: f64) {}
+}
+
+// learning rate as a function of the epoch, applied through set_lr by Trainer::fit
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Schedule {
+    #[default]
+    Constant,
+    // multiplied by gamma every `every` epochs
+    Step { every: usize, gamma: f64 },
+    // multiplied by gamma every epoch
+    Exponential { gamma: f64 },
+    // half a cosine from the base lr down to min_lr over the run
+    Cosine { min_lr: f64 },
+    // one-cycle (smith 2018): the base lr is the peak, warmed up to from base_lr / div over the first
+    // `warmup` fraction of the run, then annealed to base_lr / (div * final_div), both halves cosine
+    // shaped; momentum, if given as (high, low), goes the other way: high to low and back up
+    OneCycle {
+        #[serde(default = "one_cycle_warmup")]
+        warmup: f64,
+        #[serde(default = "one_cycle_div")]
+        div: f64,
+        #[serde(default = "one_cycle_final_div")]
+        final_div: f64,
+        #[serde(default)]
+        momentum: Option<(f64, f64)>,
+    },
+    // straight down from the base lr to min_lr over every `period` epochs, then back up, reaching
+    // min_lr on each cycle's last epoch (the schedule stochastic weight averaging samples on)
+    Cyclic { min_lr: f64, period: usize },
+}
+
+impl Schedule {
+    pub fn lr(&self, base_lr: f64, epoch: usize, epochs: usize) -> f64 {
+        return match *self {
+            Schedule::Constant => base_lr,
+            Schedule::Step { every, gamma } => base_lr * gamma.powi((epoch / every.max(1)) as i32),
+            Schedule::Exponential { gamma } => base_lr * gamma.powi(epoch as i32),
+            Schedule::Cosine { min_lr } => {
+                let t = if epochs > 1 { epoch as f64 / (epochs - 1) as f64 } else { 0.0 };
+                min_lr + 0.5 * (base_lr - min_lr) * (1.0 + (std::f64::consts::PI * t).cos())
+            },
+            Schedule::OneCycle { warmup, div, final_div, .. } => {
+                let start = base_lr / div;
+                match one_cycle_phase(warmup, epoch, epochs) {
+                    (true, p) => anneal(start, base_lr, p),
+                    (false, p) => anneal(base_lr, start / final_div, p),
+                }
+            },
+            Schedule::Cyclic { min_lr, period } => {
+                let t = (epoch % period.max(1) + 1) as f64 / period.max(1) as f64;
+                (1.0 - t) * base_lr + t * min_lr
+            },
+        };
+    }
+
+    // momentum for the epoch, None for schedules that leave it alone
+    pub fn momentum(&self, epoch: usize, epochs: usize) -> Option<f64> {
+        let Schedule::OneCycle { warmup, momentum: Some((high, low)), .. } = *self else {
+            return None;
+        };
+        return Some(match one_cycle_phase(warmup, epoch, epochs) {
+            (true, p) => anneal(high, low, p),
+            (false, p) => anneal(low, high, p),
+        });
+    }
+}
+
+fn one_cycle_warmup() -> f64 {
+    return 0.3;
+}
+
+fn one_cycle_div() -> f64 {
+    return 25.0;
+}
+
+fn one_cycle_final_div() -> f64 {
+    return 1e4;
+}
+
+// whether the epoch is in the warmup, and how far through its phase it is, in [0, 1]
+fn one_cycle_phase(warmup: f64, epoch: usize, epochs: usize) -> (bool, f64) {
+    let t = if epochs > 1 { epoch as f64 / (epochs - 1) as f64 } else { 1.0 };
+    if t < warmup {
+        return (true, t / warmup);
+    }
+    return (false, if warmup < 1.0 { (t - warmup) / (1.0 - warmup) } else { 1.0 });
+}
+
+// half a cosine from a (p = 0) to b (p = 1)
+fn anneal(a: f64, b: f64, p: f64) -> f64 {
+    return b + 0.5 * (a - b) * (1.0 + (std::f64::consts::PI * p).cos());
+}
+
+fn get_flat_grads(params: &[Value]) -> Vec<f64> {
+    return params.iter().map(|p| p.get_grad()).collect();
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    return a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+}
+
+// indices of the parameters that got a gradient since the last sparse step, filled in by
+// update_grad through a log attached to every parameter
+#[derive(Default)]
+struct DirtyLog(Option<Rc<RefCell<Vec<usize>>>>);
+
+impl DirtyLog {
+    // the dirty parameters, in index order, with their dirty flags cleared
+    fn take(&mut self, params: &[Value]) -> Vec<usize> {
+        let mut dirty = match &self.0 {
+            Some(log) => std::mem::take(&mut *log.borrow_mut()),
+            None => {
+                // first sparse step: hook the log up, one scan catches what was touched before
+                let log = Rc::new(RefCell::new(vec![]));
+                for (i, p) in params.iter().enumerate() {
+                    p.set_dirty_log(Some((log.clone(), i)));
+                }
+                self.0 = Some(log);
+                (0..params.len()).filter(|&i| params[i].is_dirty()).collect()
+            },
+        };
+        dirty.sort_unstable();
+        dirty.dedup();
+        for &i in &dirty {
+            params[i].clear_dirty();
+        }
+        return dirty;
+    }
+}
+
+// stochastic gradient descent with optional momentum and weight decay
+pub struct Sgd {
+    pub params: Vec<Value>,
+    pub lr: f64,
+    pub momentum: f64,
+    pub weight_decay: f64,
+    // only update parameters that received a gradient in the last backward pass
+    pub sparse: bool,
+
+    velocity: Vec<f64>,
+    dirty: DirtyLog,
+}
+
+impl Sgd {
+    pub fn new(params: Vec<Value>, lr: f64) -> Self {
+        let params = unique_params(params);
+        let n = params.len();
+        Sgd {
+            params,
+            lr,
+            momentum: 0.0,
+            weight_decay: 0.0,
+            sparse: false,
+            velocity: vec![0.0; n],
+            dirty: DirtyLog::default(),
+        }
+    }
+
+    pub fn with_momentum(mut self, momentum: f64) -> Self {
+        self.momentum = momentum;
+        self
+    }
+
+    // sparse updates for embedding-style parameters where each step only touches a few rows,
+    // untouched parameters (and their momentum) are left as they are
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, closure: &mut dyn FnMut() -> f64) -> f64 {
+        let loss = closure();
+        let indices: Vec<usize> = if self.sparse { self.dirty.take(&self.params) } else { (0..self.params.len()).collect() };
+        for i in indices {
+            let p = &self.params[i];
+            let g = p.get_grad() + self.weight_decay * p.get_data();
+            self.velocity[i] = self.momentum * self.velocity[i] + g;
+            p.update_data(-self.lr * self.velocity[i]);
+        }
+        tracing::trace!(loss, lr = self.lr, "sgd step");
+        return loss;
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.params
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        self.momentum = momentum;
+    }
+}
+
+// adam: per-parameter step sizes from running averages of the gradient and its square
+pub struct Adam {
+    pub params: Vec<Value>,
+    pub lr: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub eps: f64,
+    // lazy adam: only parameters touched by the last backward pass are updated, and each keeps
+    // its own step count for the bias correction
+    pub sparse: bool,
+
+    m: Vec<f64>,
+    v: Vec<f64>,
+    t: Vec<i32>,
+    dirty: DirtyLog,
+}
+
+impl Adam {
+    pub fn new(params: Vec<Value>, lr: f64) -> Self {
+        let params = unique_params(params);
+        let n = params.len();
+        Adam {
+            params,
+            lr,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            sparse: false,
+            m: vec![0.0; n],
+            v: vec![0.0; n],
+            t: vec![0; n],
+            dirty: DirtyLog::default(),
+        }
+    }
+
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, closure: &mut dyn FnMut() -> f64) -> f64 {
+        let loss = closure();
+        let indices: Vec<usize> = if self.sparse { self.dirty.take(&self.params) } else { (0..self.params.len()).collect() };
+        for i in indices {
+            self.t[i] += 1;
+            let bias1 = 1.0 - self.beta1.powi(self.t[i]);
+            let bias2 = 1.0 - self.beta2.powi(self.t[i]);
+            let g = self.params[i].get_grad();
+            self.m[i] = self.beta1 * self.m[i] + (1.0 - self.beta1) * g;
+            self.v[i] = self.beta2 * self.v[i] + (1.0 - self.beta2) * g * g;
+            let m_hat = self.m[i] / bias1;
+            let v_hat = self.v[i] / bias2;
+            self.params[i].update_data(-self.lr * m_hat / (v_hat.sqrt() + self.eps));
+        }
+        tracing::trace!(loss, lr = self.lr, "adam step");
+        return loss;
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.params
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        self.beta1 = momentum;
+    }
+}
+
+// box constraints through projection: after every step of the wrapped optimizer each
+// parameter is clamped back into its [lower, upper] interval
+pub struct Projected<O: Optimizer> {
+    pub inner: O,
+    pub bounds: Vec<(f64, f64)>,
+}
+
+impl<O: Optimizer> Projected<O> {
+    // one (lower, upper) pair per parameter, use f64::INFINITY / NEG_INFINITY for open sides
+    pub fn new(inner: O, bounds: Vec<(f64, f64)>) -> Result<Self> {
+        if inner.parameters().len() != bounds.len() {
+            return Err(RustMlError::Shape(format!("expected one bound per parameter, got {} bounds for {} parameters", bounds.len(), inner.parameters().len())));
+        }
+        for (lo, hi) in bounds.iter() {
+            if lo > hi {
+                return Err(RustMlError::Config(format!("lower bound {} is above upper bound {}", lo, hi)));
+            }
+        }
+        let opt = Projected {
+            inner,
+            bounds
+        };
+        opt.project();
+        Ok(opt)
+    }
+
+    fn project(&self) {
+        for (p, (lo, hi)) in self.inner.parameters().iter().zip(self.bounds.iter()) {
+            p.set_data(p.get_data().clamp(*lo, *hi));
+        }
+    }
+}
+
+impl<O: Optimizer> Optimizer for Projected<O> {
+    fn step(&mut self, closure: &mut dyn FnMut() -> f64) -> f64 {
+        let loss = self.inner.step(closure);
+        self.project();
+        return loss;
+    }
+
+    fn parameters(&self) -> &[Value] {
+        self.inner.parameters()
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.inner.set_lr(lr);
+    }
+
+    fn set_momentum(&mut self, momentum: f64) {
+        self.inner.set_momentum(momentum);
+    }
+}
+
+// constraints through reparameterization: the optimizer works on an unconstrained raw
+// parameter and the model uses transform(raw), which can never leave the allowed range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constraint {
+    // exp(raw)
+    Positive,
+    // lower + exp(raw)
+    GreaterThan(f64),
+    // lower + (upper - lower) * sigmoid(raw)
+    Interval(f64, f64),
+}
+
+impl Constraint {
+    pub fn transform(&self, raw: &Value) -> Value {
+        match self {
+            Constraint::Positive => Value::exp(raw),
+            Constraint::GreaterThan(lo) => Value::add(&Value::exp(raw), &Value::constant(*lo)),
+            Constraint::Interval(lo, hi) => {
+                let sigmoid = Value::reciprocal(&Value::add(&Value::constant(1.0), &Value::exp(&Value::neg(raw))));
+                Value::add(&Value::mul(&sigmoid, &Value::constant(hi - lo)), &Value::constant(*lo))
+            },
+        }
+    }
+
+    // raw value that maps to the given constrained value, for initialization
+    pub fn inverse(&self, value: f64) -> f64 {
+        match self {
+            Constraint::Positive => {
+                assert!(value > 0.0, "{} is not positive", value);
+                value.ln()
+            },
+            Constraint::GreaterThan(lo) => {
+                assert!(value > *lo, "{} is not above {}", value, lo);
+                (value - lo).ln()
+            },
+            Constraint::Interval(lo, hi) => {
+                assert!(value > *lo && value < *hi, "{} is not inside ({}, {})", value, lo, hi);
+                let u = (value - lo) / (hi - lo);
+                (u / (1.0 - u)).ln()
+            },
+        }
+    }
+
+    // new raw parameter starting at the given constrained value
+    pub fn init(&self, value: f64) -> Value {
+        Value::new(self.inverse(value))
+    }
+}
+
+// minimizes any scalar objective built from the optimizer's parameters, not just a network loss
+// objective builds the graph from scratch on every call; stops after max_steps or once the loss
+// changes by less than tolerance between steps, returns the loss after every step
+pub fn fit_function(opt: &mut dyn Optimizer, objective: impl Fn() -> Value, max_steps: usize, tolerance: f64) -> Vec<f64> {
+    let _span = tracing::info_span!("fit_function", max_steps).entered();
+    let params: Vec<Value> = opt.parameters().to_vec();
+    let mut closure = || {
+        for p in params.iter() {
+            p.set_grad(0.0);
+        }
+        let loss = objective();
+        loss.backward();
+        loss.get_data()
+    };
+
+    let mut losses: Vec<f64> = vec![];
+    for _ in 0..max_steps {
+        let loss = opt.step(&mut closure);
+        tracing::debug!(step = losses.len(), loss, "fit_function step");
+        let done = match losses.last() {
+            Some(prev) => (prev - loss).abs() < tolerance,
+            None => false,
+        };
+        losses.push(loss);
+        if done {
+            break;
+        }
+    }
+    return losses;
+}
+
+// limited-memory BFGS: builds a quasi-newton direction from the last `history` steps
+// with the two-loop recursion, and picks the step length with a backtracking (armijo) line search
+pub struct Lbfgs {
+    pub params: Vec<Value>,
+    pub lr: f64,
+    pub history: usize,
+    // iterations per call to step()
+    pub max_iter: usize,
+    pub tolerance_grad: f64,
+    pub tolerance_change: f64,
+
+    s_hist: Vec<Vec<f64>>,
+    y_hist: Vec<Vec<f64>>,
+}
+
+impl Lbfgs {
+    pub fn new(params: Vec<Value>, lr: f64) -> Self {
+        let params = unique_params(params);
+        Lbfgs {
+            params,
+            lr,
+            history: 10,
+            max_iter: 20,
+            tolerance_grad: 1e-7,
+            tolerance_change: 1e-9,
+            s_hist: vec![],
+            y_hist: vec![],
+        }
+    }
+
+    // -H g with the two-loop recursion, plain steepest descent when there's no history yet
+    fn direction(&self, g: &[f64]) -> Vec<f64> {
+        let mut q = g.to_vec();
+        let k = self.s_hist.len();
+        let mut alphas = vec![0.0; k];
+        for i in (0..k).rev() {
+            let rho = 1.0 / dot(&self.y_hist[i], &self.s_hist[i]);
+            alphas[i] = rho * dot(&self.s_hist[i], &q);
+            for (qj, yj) in q.iter_mut().zip(self.y_hist[i].iter()) {
+                *qj -= alphas[i] * yj;
+            }
+        }
+        // initial hessian guess s^T y / y^T y from the latest pair
+        if k > 0 {
+            let gamma = dot(&self.s_hist[k - 1], &self.y_hist[k - 1]) / dot(&self.y_hist[k - 1], &self.y_hist[k - 1]);
+            for qj in q.iter_mut() {
+                *qj *= gamma;
+            }
+        }
+        for (i, alpha) in alphas.iter().enumerate() {
+            let rho = 1.0 / dot(&self.y_hist[i], &self.s_hist[i]);
+            let beta = rho * dot(&self.y_hist[i], &q);
+            for (qj, sj) in q.iter_mut().zip(self.s_hist[i].iter()) {
+                *qj += (alpha - beta) * sj;
+            }
+        }
+        return q.iter().map(|x| -x).collect();
+    }
+}
+
+impl Optimizer for Lbfgs {
+    fn step(&mut self, closure: &mut dyn FnMut() -> f64) -> f64 {
+        let mut loss = closure();
+        let mut x = get_flat_params(&self.params);
+        let mut g = get_flat_grads(&self.params);
+
+        for _ in 0..self.max_iter {
+            if g.iter().all(|v| v.abs() <= self.tolerance_grad) {
+                break;
+            }
+            let mut d = self.direction(&g);
+            let mut slope = dot(&g, &d);
+            if slope >= 0.0 {
+                // not a descent direction (bad curvature info), start over from the gradient
+                self.s_hist.clear();
+                self.y_hist.clear();
+                d = g.iter().map(|v| -v).collect();
+                slope = dot(&g, &d);
+            }
+
+            // the first step has no curvature info to scale it, keep it small
+            let mut t = if self.s_hist.is_empty() {
+                self.lr * (1.0 / g.iter().map(|v| v.abs()).sum::<f64>()).min(1.0)
+            } else {
+                self.lr
+            };
+
+            // backtracking until the armijo condition holds
+            let mut new_loss = loss;
+            let mut accepted = false;
+            for _ in 0..30 {
+                let trial: Vec<f64> = x.iter().zip(d.iter()).map(|(xi, di)| xi + t * di).collect();
+                set_flat_params(&self.params, &trial);
+                new_loss = closure();
+                if new_loss.is_finite() && new_loss <= loss + 1e-4 * t * slope {
+                    accepted = true;
+                    break;
+                }
+                t *= 0.5;
+            }
+            if !accepted {
+                tracing::debug!(loss, "l-bfgs line search failed, keeping the last point");
+                set_flat_params(&self.params, &x);
+                closure();
+                break;
+            }
+
+            let new_x = get_flat_params(&self.params);
+            let new_g = get_flat_grads(&self.params);
+            let s: Vec<f64> = new_x.iter().zip(x.iter()).map(|(a, b)| a - b).collect();
+            let y: Vec<f64> = new_g.iter().zip(g.iter()).map(|(a, b)| a - b).collect();
+            if dot(&s, &y) > 1e-10 {
+                self.s_hist.push(s);
+                self.y_hist.push(y);
+                if self.s_hist.len() > self.history {
+                    self.s_hist.remove(0);
+                    self.y_hist.remove(0);
+                }
+            }
+
+            tracing::trace!(loss = new_loss, step = t, history = self.s_hist.len(), "l-bfgs iteration");
+            let change = (loss - new_loss).abs();
+            x = new_x;
+            g = new_g;
+            loss = new_loss;
+            if change < self.tolerance_change {
+                break;
+            }
+        }
+        return loss;
+    }
+
+    fn parameters(&self) -> &[Value] {
+        &self.params
+    }
+
+    fn set_lr(&mut self, lr: f64) {
+        self.lr = lr;
+    }
+}
@@ -0,0 +1,306 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use rand::Rng;
+
+use crate::loss;
+use crate::nn::Module;
+use crate::optim::Optimizer;
+use crate::random;
+use crate::value::Value;
+
+// switch for bit-reproducible results once work is split across threads
+// floating point addition isn't associative, so the order partial results are combined in matters
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+pub fn set_deterministic(on: bool) {
+    DETERMINISTIC.store(on, Ordering::SeqCst);
+}
+
+pub fn is_deterministic() -> bool {
+    return DETERMINISTIC.load(Ordering::SeqCst);
+}
+
+// pairwise (tree) summation, always combines in the same order for the same input
+pub fn tree_sum(xs: &[f64]) -> f64 {
+    match xs.len() {
+        0 => 0.0,
+        1 => xs[0],
+        n => tree_sum(&xs[..n / 2]) + tree_sum(&xs[n / 2..]),
+    }
+}
+
+// combine per-chunk partial results tagged with their chunk index
+// in deterministic mode they're put back in chunk order first, otherwise they're summed as they arrived
+pub fn sum_partials(mut partials: Vec<(usize, f64)>) -> f64 {
+    if is_deterministic() {
+        partials.sort_by_key(|p| p.0);
+        let xs: Vec<f64> = partials.iter().map(|p| p.1).collect();
+        return tree_sum(&xs);
+    }
+    return partials.iter().map(|p| p.1).sum();
+}
+
+// train::LossFn that can be shared with worker threads
+pub type SyncLossFn<'a> = Box<dyn Fn(&[Value], &[f64]) -> Value + Sync + 'a>;
+
+// data-parallel training: the graph is single threaded (Rc), so every worker thread builds its own
+// replica of the model, gets the current parameters with its shard of each batch, and sends back
+// the gradient of its summed loss; the shard gradients are added up with sum_partials (as they
+// arrive, or in worker order in deterministic mode), averaged over the batch, and the optimizer
+// takes one step on the main model, the same step a single-threaded Trainer would take up to rounding
+pub struct DataParallelTrainer<'a> {
+    pub model: &'a dyn Module,
+    pub optimizer: Box<dyn Optimizer + 'a>,
+    pub workers: usize,
+    // None trains full batch
+    pub batch_size: Option<usize>,
+    // mean loss of every epoch trained so far
+    pub history: Vec<f64>,
+    replica: Box<dyn Fn() -> Box<dyn Module> + Sync + 'a>,
+    loss: SyncLossFn<'a>,
+}
+
+// one shard of a batch for a worker, at the given parameters
+struct Job {
+    params: Arc<Vec<f64>>,
+    rows: Vec<usize>,
+}
+
+// what a worker sends back: its index, the gradient of its shard's summed loss and that sum
+type ShardResult = (usize, Vec<f64>, f64);
+
+impl<'a> DataParallelTrainer<'a> {
+    // replica builds a model with the same parameters() layout as model (e.g. || Box::new(MLP::new(&sizes))),
+    // its initial values don't matter; defaults to mean squared error like Trainer
+    pub fn new(model: &'a dyn Module, replica: impl Fn() -> Box<dyn Module> + Sync + 'a, optimizer: impl Optimizer + 'a, workers: usize) -> Self {
+        assert!(workers > 0, "need at least one worker");
+        let n = replica().parameters().len();
+        assert_eq!(n, model.parameters().len(), "replicas have {} parameters, the model has {}", n, model.parameters().len());
+        DataParallelTrainer {
+            model,
+            optimizer: Box::new(optimizer),
+            workers,
+            batch_size: None,
+            history: vec![],
+            replica: Box::new(replica),
+            loss: Box::new(|ypred, y| {
+                let y: Vec<Value> = y.iter().map(|yi| Value::constant(*yi)).collect();
+                loss::mse(ypred, &y)
+            }),
+        }
+    }
+
+    pub fn with_loss(mut self, loss: impl Fn(&[Value], &[f64]) -> Value + Sync + 'a) -> Self {
+        self.loss = Box::new(loss);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    // returns the mean loss of every epoch, like Trainer::fit
+    // no rows means nothing to train on, the model is left alone and no epochs are recorded
+    pub fn fit(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], epochs: usize) -> Vec<f64> {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        if xs.is_empty() {
+            tracing::warn!(epochs, "data parallel fit called without any rows");
+            return vec![];
+        }
+        let _span = tracing::info_span!("fit", epochs, samples = xs.len(), workers = self.workers).entered();
+        let DataParallelTrainer { model, optimizer, workers, batch_size, history, replica, loss } = self;
+        let (replica, loss) = (&**replica, &**loss);
+        let mut losses = vec![];
+        // worker rngs (dropout in the replicas) come from the caller's, so random::seed covers them too
+        let base_seed: u64 = random::with_rng(|r| r.gen());
+        thread::scope(|scope| {
+            let (results_tx, results) = mpsc::channel::<ShardResult>();
+            let jobs: Vec<mpsc::Sender<Job>> = (0..*workers).map(|w| {
+                let (tx, rx) = mpsc::channel::<Job>();
+                let results_tx = results_tx.clone();
+                scope.spawn(move || {
+                    random::seed(base_seed.wrapping_add(w as u64));
+                    let local = replica();
+                    local.train(true);
+                    let params = local.parameters();
+                    for job in rx {
+                        local.set_flat_params(&job.params);
+                        local.zero_grad();
+                        let shard: Vec<Value> = job.rows.iter().map(|&i| loss(&local.forward(&to_values(&xs[i])), &ys[i])).collect();
+                        let total = Value::sum(&shard);
+                        total.backward();
+                        let grads = params.iter().map(|p| p.get_grad()).collect();
+                        if results_tx.send((w, grads, total.get_data())).is_err() {
+                            break;
+                        }
+                    }
+                });
+                tx
+            }).collect();
+            drop(results_tx);
+
+            // one step on the mean loss over the rows, gradients from the workers
+            let mut step = |rows: &[usize]| -> f64 {
+                let shard_size = rows.len().div_ceil(jobs.len());
+                let mut first: Option<f64> = None;
+                optimizer.step(&mut || {
+                    let params = Arc::new(model.get_flat_params());
+                    let shards: Vec<&[usize]> = rows.chunks(shard_size).collect();
+                    for (job, shard) in jobs.iter().zip(shards.iter()) {
+                        job.send(Job { params: params.clone(), rows: shard.to_vec() }).expect("data parallel worker died");
+                    }
+                    let parts: Vec<ShardResult> = shards.iter().map(|_| results.recv().expect("data parallel worker died")).collect();
+                    for (j, p) in model.parameters().iter().enumerate() {
+                        let g = sum_partials(parts.iter().map(|(w, g, _)| (*w, g[j])).collect());
+                        p.set_grad(g / rows.len() as f64);
+                    }
+                    let total = sum_partials(parts.iter().map(|(w, _, l)| (*w, *l)).collect());
+                    let mean = total / rows.len() as f64;
+                    *first.get_or_insert(mean)
+                });
+                first.unwrap_or(f64::NAN)
+            };
+
+            for epoch in 0..epochs {
+                let mut idx: Vec<usize> = (0..xs.len()).collect();
+                let epoch_loss = match *batch_size {
+                    Some(k) if k < xs.len() => {
+                        random::shuffle(&mut idx);
+                        let total: f64 = idx.chunks(k).map(|batch| step(batch) * batch.len() as f64).sum();
+                        total / xs.len() as f64
+                    },
+                    _ => step(&idx),
+                };
+                tracing::info!(epoch, loss = epoch_loss, "epoch done");
+                history.push(epoch_loss);
+                losses.push(epoch_loss);
+            }
+            // dropping the job senders ends the workers
+        });
+        return losses;
+    }
+}
+
+fn to_values(x: &[f64]) -> Vec<Value> {
+    return x.iter().map(|xi| Value::constant(*xi)).collect();
+}
+
+// asynchronous sgd without locks (hogwild, niu et al. 2011): the parameters live in shared atomics
+// and every worker loops over its own shard of the epoch, reading whatever the parameters are at that
+// moment into its replica, computing a mini-batch gradient and writing its update straight back
+//
+// trade-offs against DataParallelTrainer:
+// - no worker waits for another, so cores stay busy even when shards take uneven time, and there
+//   are workers times more (smaller) steps per epoch
+// - gradients are computed on parameters that other workers may already have moved (stale), and two
+//   workers updating the same parameter at once can lose one of the updates (plain loads and stores,
+//   no compare-and-swap); with small learning rates and sparse-ish updates this costs little, with
+//   large ones it adds noise and can diverge where synchronous training wouldn't
+// - plain sgd only, there's no place to keep optimizer state (momentum, adam moments) consistent
+// - not reproducible, the interleaving of the updates depends on thread timing, so fit refuses to
+//   run in deterministic mode
+pub struct Hogwild<'a> {
+    pub model: &'a dyn Module,
+    pub lr: f64,
+    pub workers: usize,
+    // samples per step of one worker
+    pub batch_size: usize,
+    // mean loss of every epoch trained so far
+    pub history: Vec<f64>,
+    replica: Box<dyn Fn() -> Box<dyn Module> + Sync + 'a>,
+    loss: SyncLossFn<'a>,
+}
+
+impl<'a> Hogwild<'a> {
+    // replica as for DataParallelTrainer::new, batch size 1 and mean squared error by default
+    pub fn new(model: &'a dyn Module, replica: impl Fn() -> Box<dyn Module> + Sync + 'a, lr: f64, workers: usize) -> Self {
+        assert!(workers > 0, "need at least one worker");
+        let n = replica().parameters().len();
+        assert_eq!(n, model.parameters().len(), "replicas have {} parameters, the model has {}", n, model.parameters().len());
+        Hogwild {
+            model,
+            lr,
+            workers,
+            batch_size: 1,
+            history: vec![],
+            replica: Box::new(replica),
+            loss: Box::new(|ypred, y| {
+                let y: Vec<Value> = y.iter().map(|yi| Value::constant(*yi)).collect();
+                loss::mse(ypred, &y)
+            }),
+        }
+    }
+
+    pub fn with_loss(mut self, loss: impl Fn(&[Value], &[f64]) -> Value + Sync + 'a) -> Self {
+        self.loss = Box::new(loss);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    // every epoch deals a fresh shuffle of the rows out to the workers, returns the mean loss of every
+    // epoch (each step's loss before its update, averaged over the samples)
+    // no rows means nothing to train on, the model is left alone and no epochs are recorded
+    pub fn fit(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], epochs: usize) -> Vec<f64> {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        assert!(!is_deterministic(), "hogwild can't be reproducible, turn deterministic mode off or use DataParallelTrainer");
+        if xs.is_empty() {
+            tracing::warn!(epochs, "hogwild fit called without any rows");
+            return vec![];
+        }
+        let _span = tracing::info_span!("hogwild", epochs, samples = xs.len(), workers = self.workers).entered();
+        let shared: Vec<AtomicU64> = self.model.get_flat_params().iter().map(|p| AtomicU64::new(p.to_bits())).collect();
+        let (replica, loss) = (&*self.replica, &*self.loss);
+        let (lr, batch_size) = (self.lr, self.batch_size);
+        let mut losses = vec![];
+        // worker rngs (dropout in the replicas) come from the caller's, one draw up front and an offset per epoch and worker
+        let base_seed: u64 = random::with_rng(|r| r.gen());
+        for epoch in 0..epochs {
+            let mut idx: Vec<usize> = (0..xs.len()).collect();
+            random::shuffle(&mut idx);
+            let shards: Vec<Vec<usize>> = (0..self.workers).map(|w| idx.iter().skip(w).step_by(self.workers).cloned().collect()).collect();
+            let shared = &shared;
+            let epoch_seed = base_seed.wrapping_add((epoch * self.workers) as u64);
+            let total: f64 = thread::scope(|scope| {
+                let handles: Vec<_> = shards.iter().enumerate().filter(|(_, s)| !s.is_empty()).map(|(w, shard)| scope.spawn(move || {
+                    random::seed(epoch_seed.wrapping_add(w as u64));
+                    let local = replica();
+                    local.train(true);
+                    let params = local.parameters();
+                    let mut total = 0.0;
+                    for batch in shard.chunks(batch_size) {
+                        let current: Vec<f64> = shared.iter().map(|p| f64::from_bits(p.load(Ordering::Relaxed))).collect();
+                        local.set_flat_params(&current);
+                        local.zero_grad();
+                        let batch_losses: Vec<Value> = batch.iter().map(|&i| loss(&local.forward(&to_values(&xs[i])), &ys[i])).collect();
+                        let mean = Value::mean(&batch_losses);
+                        mean.backward();
+                        total += mean.get_data() * batch.len() as f64;
+                        // racy on purpose: another worker's update between the load and the store is lost
+                        for (p, s) in params.iter().zip(shared.iter()) {
+                            let value = f64::from_bits(s.load(Ordering::Relaxed));
+                            s.store((value - lr * p.get_grad()).to_bits(), Ordering::Relaxed);
+                        }
+                    }
+                    total
+                })).collect();
+                handles.into_iter().map(|h| h.join().expect("hogwild worker panicked")).sum()
+            });
+            let epoch_loss = total / xs.len() as f64;
+            tracing::info!(epoch, loss = epoch_loss, "epoch done");
+            self.history.push(epoch_loss);
+            losses.push(epoch_loss);
+        }
+        let params: Vec<f64> = shared.iter().map(|p| f64::from_bits(p.load(Ordering::Relaxed))).collect();
+        self.model.set_flat_params(&params);
+        return losses;
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::error::Result;
+
+// minimal svg charts, no dependencies: each function returns the svg as a string, save() writes it
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 420.0;
+const MARGIN: f64 = 50.0;
+const COLORS: &[&str] = &["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f"];
+
+pub struct Series {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Series {
+    pub fn new(name: &str, points: Vec<(f64, f64)>) -> Self {
+        Series {
+            name: name.to_string(),
+            points
+        }
+    }
+
+    // y values against their index, e.g. a loss history
+    pub fn from_values(name: &str, ys: &[f64]) -> Self {
+        Series::new(name, ys.iter().enumerate().map(|(i, y)| (i as f64, *y)).collect())
+    }
+}
+
+pub fn save(svg: &str, path: impl AsRef<Path>) -> Result<()> {
+    fs::write(path, svg)?;
+    return Ok(());
+}
+
+// maps data coordinates onto the plotting area inside the margins
+struct Frame {
+    x: (f64, f64),
+    y: (f64, f64),
+}
+
+impl Frame {
+    fn fit(points: impl Iterator<Item = (f64, f64)>) -> Frame {
+        let mut f = Frame { x: (f64::INFINITY, f64::NEG_INFINITY), y: (f64::INFINITY, f64::NEG_INFINITY) };
+        for (x, y) in points.filter(|(x, y)| x.is_finite() && y.is_finite()) {
+            f.x = (f.x.0.min(x), f.x.1.max(x));
+            f.y = (f.y.0.min(y), f.y.1.max(y));
+        }
+        if !f.x.0.is_finite() {
+            return Frame { x: (0.0, 1.0), y: (0.0, 1.0) };
+        }
+        // flat ranges get some room so nothing divides by zero
+        if f.x.0 == f.x.1 {
+            f.x = (f.x.0 - 0.5, f.x.1 + 0.5);
+        }
+        if f.y.0 == f.y.1 {
+            f.y = (f.y.0 - 0.5, f.y.1 + 0.5);
+        }
+        return f;
+    }
+
+    fn px(&self, x: f64) -> f64 {
+        return MARGIN + (x - self.x.0) / (self.x.1 - self.x.0) * (WIDTH - 2.0 * MARGIN);
+    }
+
+    fn py(&self, y: f64) -> f64 {
+        return HEIGHT - MARGIN - (y - self.y.0) / (self.y.1 - self.y.0) * (HEIGHT - 2.0 * MARGIN);
+    }
+}
+
+fn header(svg: &mut String, title: &str) {
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#, WIDTH, HEIGHT);
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(svg, r#"<text x="{}" y="25" text-anchor="middle" font-size="15">{}</text>"#, WIDTH / 2.0, escape(title));
+}
+
+// axes with the data range written at the ends
+fn axes(svg: &mut String, f: &Frame) {
+    let (left, right, top, bottom) = (MARGIN, WIDTH - MARGIN, MARGIN, HEIGHT - MARGIN);
+    let _ = writeln!(svg, r#"<path d="M{left} {top} V{bottom} H{right}" fill="none" stroke="black"/>"#);
+    let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, left, bottom + 16.0, tick(f.x.0));
+    let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, right, bottom + 16.0, tick(f.x.1));
+    let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, left - 4.0, bottom, tick(f.y.0));
+    let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, left - 4.0, top + 4.0, tick(f.y.1));
+}
+
+fn tick(v: f64) -> String {
+    if v != 0.0 && (v.abs() >= 1e4 || v.abs() < 1e-2) {
+        return format!("{:.2e}", v);
+    }
+    return format!("{:.3}", v);
+}
+
+fn escape(s: &str) -> String {
+    return s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+}
+
+// one polyline per series, with a legend in the top right corner
+pub fn line_chart(title: &str, series: &[Series]) -> String {
+    let f = Frame::fit(series.iter().flat_map(|s| s.points.iter().cloned()));
+    let mut svg = String::new();
+    header(&mut svg, title);
+    axes(&mut svg, &f);
+    for (k, s) in series.iter().enumerate() {
+        let color = COLORS[k % COLORS.len()];
+        let points: Vec<String> = s.points.iter()
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .map(|(x, y)| format!("{:.2},{:.2}", f.px(*x), f.py(*y)))
+            .collect();
+        let _ = writeln!(svg, r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#, points.join(" "), color);
+        let ly = MARGIN + 14.0 * k as f64;
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end" fill="{}">{}</text>"#, WIDTH - MARGIN, ly, color, escape(&s.name));
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+// blue (low) to red (high) color for t in [0, 1]
+fn heat(t: f64) -> String {
+    let t = if t.is_finite() { t.clamp(0.0, 1.0) } else { 0.5 };
+    return format!("rgb({},{},{})", (255.0 * t) as u8, (80.0 * (1.0 - (2.0 * t - 1.0).abs())) as u8, (255.0 * (1.0 - t)) as u8);
+}
+
+// values[i][j] is drawn as a cell at x = x_range lerp j, y = y_range lerp i (row 0 at the bottom)
+pub fn heatmap(title: &str, values: &[Vec<f64>], x_range: (f64, f64), y_range: (f64, f64)) -> String {
+    return heatmap_with_points(title, values, x_range, y_range, &[]);
+}
+
+// heatmap with a scatter of (x, y, group) points on top, colored by group
+pub fn heatmap_with_points(title: &str, values: &[Vec<f64>], x_range: (f64, f64), y_range: (f64, f64), points: &[(f64, f64, usize)]) -> String {
+    let f = Frame { x: x_range, y: y_range };
+    let finite = values.iter().flatten().filter(|v| v.is_finite());
+    let lo = finite.clone().cloned().fold(f64::INFINITY, f64::min);
+    let hi = finite.cloned().fold(f64::NEG_INFINITY, f64::max);
+    let span = if hi > lo { hi - lo } else { 1.0 };
+
+    let mut svg = String::new();
+    header(&mut svg, title);
+    let rows = values.len();
+    for (i, row) in values.iter().enumerate() {
+        let cols = row.len();
+        let w = (WIDTH - 2.0 * MARGIN) / cols as f64;
+        let h = (HEIGHT - 2.0 * MARGIN) / rows as f64;
+        for (j, v) in row.iter().enumerate() {
+            let x = MARGIN + j as f64 * w;
+            let y = HEIGHT - MARGIN - (i + 1) as f64 * h;
+            let _ = writeln!(svg, r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}" fill-opacity="0.6"/>"#, x, y, w + 0.5, h + 0.5, heat((v - lo) / span));
+        }
+    }
+    for (x, y, group) in points.iter() {
+        let _ = writeln!(svg, r#"<circle cx="{:.2}" cy="{:.2}" r="3" fill="{}" stroke="black" stroke-width="0.5"/>"#, f.px(*x), f.py(*y), COLORS[group % COLORS.len()]);
+    }
+    axes(&mut svg, &f);
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+// (x, y, group) points colored by group, e.g. a 2-d embedding from manifold::Tsne with the class labels
+pub fn scatter(title: &str, points: &[(f64, f64, usize)]) -> String {
+    let f = Frame::fit(points.iter().map(|(x, y, _)| (*x, *y)));
+    let mut svg = String::new();
+    header(&mut svg, title);
+    axes(&mut svg, &f);
+    for (x, y, group) in points.iter().filter(|(x, y, _)| x.is_finite() && y.is_finite()) {
+        let _ = writeln!(svg, r#"<circle cx="{:.2}" cy="{:.2}" r="3" fill="{}" fill-opacity="0.8"/>"#, f.px(*x), f.py(*y), COLORS[group % COLORS.len()]);
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
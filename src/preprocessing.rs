@@ -0,0 +1,544 @@
+use crate::data;
+use crate::error::{Result, RustMlError};
+use crate::stats;
+
+// feature transformers for tabular data, rows are samples and columns are features (the same
+// layout the Trainer takes), missing values are NaN
+// fit() learns from the training rows, transform() applies what was learned to any rows
+
+pub trait Transformer {
+    // ys are the training targets, for the transformers that need them (None is fine otherwise)
+    fn fit(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<()>;
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>>;
+
+    // fit, then transform the same rows; transformers whose output on their own training rows
+    // would leak the targets override it
+    fn fit_transform(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<Vec<Vec<f64>>> {
+        self.fit(xs, ys)?;
+        return self.transform(xs);
+    }
+}
+
+// transformers applied one after another, each fit on what the previous ones produce
+pub struct Pipeline {
+    pub steps: Vec<Box<dyn Transformer>>,
+}
+
+impl Pipeline {
+    pub fn new(steps: Vec<Box<dyn Transformer>>) -> Self {
+        Pipeline {
+            steps
+        }
+    }
+}
+
+impl Transformer for Pipeline {
+    fn fit(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<()> {
+        self.fit_transform(xs, ys)?;
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        let mut out = xs.to_vec();
+        for step in self.steps.iter() {
+            out = step.transform(&out)?;
+        }
+        return Ok(out);
+    }
+
+    fn fit_transform(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<Vec<Vec<f64>>> {
+        let mut out = xs.to_vec();
+        for step in self.steps.iter_mut() {
+            out = step.fit_transform(&out, ys)?;
+        }
+        return Ok(out);
+    }
+}
+
+// number of columns, checking every row has it
+fn n_cols(xs: &[Vec<f64>]) -> Result<usize> {
+    let d = xs.first().map_or(0, |x| x.len());
+    if let Some((i, x)) = xs.iter().enumerate().find(|(_, x)| x.len() != d) {
+        return Err(RustMlError::Shape(format!("row {} has {} columns, row 0 has {}", i, x.len(), d)));
+    }
+    return Ok(d);
+}
+
+fn check_cols(xs: &[Vec<f64>], expected: usize) -> Result<()> {
+    let d = n_cols(xs)?;
+    if !xs.is_empty() && d != expected {
+        return Err(RustMlError::Shape(format!("fit on {} columns, got {}", expected, d)));
+    }
+    return Ok(());
+}
+
+// the non-missing values of column j
+fn observed(xs: &[Vec<f64>], j: usize) -> Vec<f64> {
+    return xs.iter().map(|x| x[j]).filter(|v| !v.is_nan()).collect();
+}
+
+// most frequent value, the smallest one on ties
+fn mode(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let (mut best, mut best_count) = (f64::NAN, 0);
+    let mut i = 0;
+    while i < sorted.len() {
+        let j = sorted[i..].iter().position(|v| *v != sorted[i]).map_or(sorted.len(), |k| i + k);
+        if j - i > best_count {
+            best = sorted[i];
+            best_count = j - i;
+        }
+        i = j;
+    }
+    return best;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImputeStrategy {
+    Mean,
+    Median,
+    // most frequent value, for categorical columns
+    MostFrequent,
+    Constant(f64),
+}
+
+// replaces NaNs column by column with a statistic of the observed training values
+pub struct SimpleImputer {
+    pub strategy: ImputeStrategy,
+    // also append one 0/1 column per feature that had missing values at fit time, so a model can
+    // still tell imputed values from real ones
+    pub add_indicator: bool,
+
+    // fill value per column, empty until fit
+    pub statistics: Vec<f64>,
+    // columns that get an indicator
+    pub indicator_columns: Vec<usize>,
+}
+
+impl SimpleImputer {
+    pub fn new(strategy: ImputeStrategy) -> Self {
+        SimpleImputer {
+            strategy,
+            add_indicator: false,
+            statistics: vec![],
+            indicator_columns: vec![],
+        }
+    }
+
+    pub fn with_indicator(mut self, add_indicator: bool) -> Self {
+        self.add_indicator = add_indicator;
+        self
+    }
+}
+
+impl Transformer for SimpleImputer {
+    fn fit(&mut self, xs: &[Vec<f64>], _ys: Option<&[f64]>) -> Result<()> {
+        let d = n_cols(xs)?;
+        let mut statistics = Vec::with_capacity(d);
+        for j in 0..d {
+            let values = observed(xs, j);
+            if values.is_empty() && !matches!(self.strategy, ImputeStrategy::Constant(_)) {
+                return Err(RustMlError::Numerical(format!("column {} has no observed values to impute from", j)));
+            }
+            statistics.push(match self.strategy {
+                ImputeStrategy::Mean => stats::mean(&values),
+                ImputeStrategy::Median => stats::quantile(&values, 0.5),
+                ImputeStrategy::MostFrequent => mode(&values),
+                ImputeStrategy::Constant(c) => c,
+            });
+        }
+        self.statistics = statistics;
+        self.indicator_columns = (0..d).filter(|&j| xs.iter().any(|x| x[j].is_nan())).collect();
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.statistics.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_cols(xs, self.statistics.len())?;
+        return Ok(xs.iter().map(|x| {
+            let mut row: Vec<f64> = x.iter().zip(self.statistics.iter()).map(|(v, s)| if v.is_nan() { *s } else { *v }).collect();
+            if self.add_indicator {
+                row.extend(self.indicator_columns.iter().map(|&j| if x[j].is_nan() { 1.0 } else { 0.0 }));
+            }
+            row
+        }).collect());
+    }
+}
+
+// categorical columns hold category codes as floats; NaN is always treated as missing and left alone
+
+// category code -> its position among the sorted categories seen at fit time, for the given columns
+// categories never seen at fit time become unknown_value (NaN by default, so an imputer after
+// this step can deal with them)
+pub struct OrdinalEncoder {
+    pub columns: Vec<usize>,
+    pub unknown_value: f64,
+
+    // sorted distinct categories of every encoded column, empty until fit
+    pub categories: Vec<Vec<f64>>,
+}
+
+impl OrdinalEncoder {
+    pub fn new(columns: Vec<usize>) -> Self {
+        OrdinalEncoder {
+            columns,
+            unknown_value: f64::NAN,
+            categories: vec![],
+        }
+    }
+
+    pub fn with_unknown_value(mut self, unknown_value: f64) -> Self {
+        self.unknown_value = unknown_value;
+        self
+    }
+}
+
+fn check_columns(columns: &[usize], d: usize) -> Result<()> {
+    if let Some(c) = columns.iter().find(|c| **c >= d) {
+        return Err(RustMlError::Shape(format!("column {} is out of range for {} columns", c, d)));
+    }
+    return Ok(());
+}
+
+impl Transformer for OrdinalEncoder {
+    fn fit(&mut self, xs: &[Vec<f64>], _ys: Option<&[f64]>) -> Result<()> {
+        check_columns(&self.columns, n_cols(xs)?)?;
+        self.categories = self.columns.iter().map(|&j| {
+            let mut values = observed(xs, j);
+            values.sort_by(f64::total_cmp);
+            values.dedup();
+            values
+        }).collect();
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.categories.len() != self.columns.len() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_columns(&self.columns, n_cols(xs)?)?;
+        let mut out = xs.to_vec();
+        for row in out.iter_mut() {
+            for (&j, cats) in self.columns.iter().zip(self.categories.iter()) {
+                if row[j].is_nan() {
+                    continue;
+                }
+                row[j] = match cats.binary_search_by(|c| c.total_cmp(&row[j])) {
+                    Ok(k) => k as f64,
+                    Err(_) => self.unknown_value,
+                };
+            }
+        }
+        return Ok(out);
+    }
+}
+
+// smoothed target encoding: every category code is replaced by the mean target of its rows,
+// shrunk towards the overall mean, (n * category mean + smoothing * overall mean) / (n + smoothing),
+// so rare categories don't get extreme values; unknown or missing categories get the overall mean
+// fit_transform encodes the training rows out of fold (each fold with statistics from the others),
+// otherwise every row's own target would leak into its feature
+pub struct TargetEncoder {
+    pub columns: Vec<usize>,
+    pub smoothing: f64,
+    pub folds: usize,
+
+    // overall target mean, and (category, encoding) pairs sorted by category per column
+    pub prior: f64,
+    pub encodings: Vec<Vec<(f64, f64)>>,
+}
+
+impl TargetEncoder {
+    pub fn new(columns: Vec<usize>, smoothing: f64) -> Self {
+        TargetEncoder {
+            columns,
+            smoothing,
+            folds: 5,
+            prior: 0.0,
+            encodings: vec![],
+        }
+    }
+
+    pub fn with_folds(mut self, folds: usize) -> Self {
+        self.folds = folds;
+        self
+    }
+
+    fn encode(&self, j: usize, v: f64) -> f64 {
+        if v.is_nan() {
+            return self.prior;
+        }
+        return match self.encodings[j].binary_search_by(|(c, _)| c.total_cmp(&v)) {
+            Ok(k) => self.encodings[j][k].1,
+            Err(_) => self.prior,
+        };
+    }
+}
+
+fn targets(ys: Option<&[f64]>, n: usize) -> Result<&[f64]> {
+    let ys = ys.ok_or(RustMlError::Config("target encoding needs the targets".to_string()))?;
+    if ys.len() != n {
+        return Err(RustMlError::Shape(format!("expected {} targets, got {}", n, ys.len())));
+    }
+    return Ok(ys);
+}
+
+impl Transformer for TargetEncoder {
+    fn fit(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<()> {
+        if self.smoothing < 0.0 {
+            return Err(RustMlError::Config(format!("smoothing can't be negative, got {}", self.smoothing)));
+        }
+        check_columns(&self.columns, n_cols(xs)?)?;
+        let ys = targets(ys, xs.len())?;
+        if ys.is_empty() {
+            return Err(RustMlError::Config("target encoding needs at least one row".to_string()));
+        }
+        self.prior = stats::mean(ys);
+        self.encodings = self.columns.iter().map(|&j| {
+            let mut pairs: Vec<(f64, f64)> = xs.iter().zip(ys.iter()).map(|(x, y)| (x[j], *y)).filter(|(c, _)| !c.is_nan()).collect();
+            pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+            pairs.chunk_by(|a, b| a.0 == b.0).map(|group| {
+                let n = group.len() as f64;
+                let sum: f64 = group.iter().map(|(_, y)| y).sum();
+                (group[0].0, (sum + self.smoothing * self.prior) / (n + self.smoothing))
+            }).collect()
+        }).collect();
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.encodings.len() != self.columns.len() {
+            return Err(RustMlError::NotFitted);
+        }
+        check_columns(&self.columns, n_cols(xs)?)?;
+        let mut out = xs.to_vec();
+        for row in out.iter_mut() {
+            for (k, &j) in self.columns.iter().enumerate() {
+                row[j] = self.encode(k, row[j]);
+            }
+        }
+        return Ok(out);
+    }
+
+    fn fit_transform(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<Vec<Vec<f64>>> {
+        let y = targets(ys, xs.len())?;
+        if self.folds < 2 || self.folds > xs.len() {
+            return Err(RustMlError::Config(format!("out-of-fold encoding needs 2 <= folds <= {} rows, got {}", xs.len(), self.folds)));
+        }
+        let mut out = xs.to_vec();
+        for split in data::k_fold(xs.len(), self.folds) {
+            let mut fold = TargetEncoder::new(self.columns.clone(), self.smoothing);
+            fold.fit(&data::select(xs, &split.train), Some(&data::select(y, &split.train)))?;
+            for (i, row) in split.test.iter().zip(fold.transform(&data::select(xs, &split.test))?) {
+                out[*i] = row;
+            }
+        }
+        // later transform() calls use statistics from all the rows
+        self.fit(xs, ys)?;
+        return Ok(out);
+    }
+}
+
+// feature selection: each selector keeps a subset of the columns, listed in `selected` after fit
+
+fn keep_columns(xs: &[Vec<f64>], fitted_cols: usize, selected: &[usize]) -> Result<Vec<Vec<f64>>> {
+    check_cols(xs, fitted_cols)?;
+    return Ok(xs.iter().map(|x| selected.iter().map(|&j| x[j]).collect()).collect());
+}
+
+fn column(xs: &[Vec<f64>], j: usize) -> Vec<f64> {
+    return xs.iter().map(|x| x[j]).collect();
+}
+
+// drops columns whose (population) variance is not above the threshold, 0 drops constant columns
+pub struct VarianceThreshold {
+    pub threshold: f64,
+
+    pub variances: Vec<f64>,
+    pub selected: Vec<usize>,
+}
+
+impl VarianceThreshold {
+    pub fn new(threshold: f64) -> Self {
+        VarianceThreshold {
+            threshold,
+            variances: vec![],
+            selected: vec![],
+        }
+    }
+}
+
+impl Transformer for VarianceThreshold {
+    fn fit(&mut self, xs: &[Vec<f64>], _ys: Option<&[f64]>) -> Result<()> {
+        if xs.is_empty() {
+            return Err(RustMlError::Config("variance threshold needs at least one row".to_string()));
+        }
+        let d = n_cols(xs)?;
+        self.variances = (0..d).map(|j| stats::var(&column(xs, j), 0)).collect();
+        self.selected = (0..d).filter(|&j| self.variances[j] > self.threshold).collect();
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.variances.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        return keep_columns(xs, self.variances.len(), &self.selected);
+    }
+}
+
+// pearson correlation of two columns, 0 if either is constant
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let (ma, mb) = (stats::mean(a), stats::mean(b));
+    let cov: f64 = a.iter().zip(b.iter()).map(|(x, y)| (x - ma) * (y - mb)).sum();
+    let va: f64 = a.iter().map(|x| (x - ma) * (x - ma)).sum();
+    let vb: f64 = b.iter().map(|y| (y - mb) * (y - mb)).sum();
+    if va == 0.0 || vb == 0.0 {
+        return 0.0;
+    }
+    return cov / (va * vb).sqrt();
+}
+
+// keeps the k columns most correlated with the target (by absolute pearson correlation),
+// each judged on its own
+pub struct SelectKBest {
+    pub k: usize,
+
+    // |correlation| of every column with the target
+    pub scores: Vec<f64>,
+    // kept columns, in their original order
+    pub selected: Vec<usize>,
+}
+
+impl SelectKBest {
+    pub fn new(k: usize) -> Self {
+        SelectKBest {
+            k,
+            scores: vec![],
+            selected: vec![],
+        }
+    }
+}
+
+impl Transformer for SelectKBest {
+    fn fit(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<()> {
+        let d = n_cols(xs)?;
+        let ys = targets(ys, xs.len())?;
+        if self.k > d {
+            return Err(RustMlError::Config(format!("can't keep {} of {} columns", self.k, d)));
+        }
+        self.scores = (0..d).map(|j| correlation(&column(xs, j), ys).abs()).collect();
+        let mut order: Vec<usize> = (0..d).collect();
+        order.sort_by(|a, b| self.scores[*b].total_cmp(&self.scores[*a]));
+        self.selected = order[..self.k].to_vec();
+        self.selected.sort();
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.scores.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        return keep_columns(xs, self.scores.len(), &self.selected);
+    }
+}
+
+// importance of every column given the training rows and targets, higher is more important
+pub type Importance = Box<dyn Fn(&[Vec<f64>], &[f64]) -> Result<Vec<f64>>>;
+
+// model-based selection: keeps the columns whose importance is above the threshold,
+// with importances from any model, e.g. lasso() below or explain::permutation_importance
+pub struct SelectFromModel {
+    pub importance: Importance,
+    pub threshold: f64,
+
+    pub importances: Vec<f64>,
+    pub selected: Vec<usize>,
+}
+
+impl SelectFromModel {
+    pub fn new(importance: Importance, threshold: f64) -> Self {
+        SelectFromModel {
+            importance,
+            threshold,
+            importances: vec![],
+            selected: vec![],
+        }
+    }
+
+    // l1-regularized linear regression: keeps the columns the lasso doesn't zero out
+    pub fn lasso(alpha: f64) -> Self {
+        return SelectFromModel::new(Box::new(move |xs, ys| {
+            Ok(lasso(xs, ys, alpha)?.iter().map(|w| w.abs()).collect())
+        }), 1e-10);
+    }
+}
+
+impl Transformer for SelectFromModel {
+    fn fit(&mut self, xs: &[Vec<f64>], ys: Option<&[f64]>) -> Result<()> {
+        let d = n_cols(xs)?;
+        let ys = targets(ys, xs.len())?;
+        let importances = (self.importance)(xs, ys)?;
+        if importances.len() != d {
+            return Err(RustMlError::Shape(format!("expected {} importances, got {}", d, importances.len())));
+        }
+        self.selected = (0..d).filter(|&j| importances[j] > self.threshold).collect();
+        self.importances = importances;
+        return Ok(());
+    }
+
+    fn transform(&self, xs: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        if self.importances.is_empty() {
+            return Err(RustMlError::NotFitted);
+        }
+        return keep_columns(xs, self.importances.len(), &self.selected);
+    }
+}
+
+// lasso by cyclic coordinate descent on standardized columns and a centered target:
+// minimizes 1 / (2n) |y - X w|^2 + alpha |w|_1, returns the weights of the standardized columns
+// (so their sizes are comparable), constant columns get 0
+pub fn lasso(xs: &[Vec<f64>], ys: &[f64], alpha: f64) -> Result<Vec<f64>> {
+    if alpha < 0.0 {
+        return Err(RustMlError::Config(format!("alpha can't be negative, got {}", alpha)));
+    }
+    let d = n_cols(xs)?;
+    let ys = targets(Some(ys), xs.len())?;
+    let n = xs.len() as f64;
+    let cols: Vec<Vec<f64>> = (0..d).map(|j| {
+        let c = column(xs, j);
+        let (m, s) = (stats::mean(&c), stats::std(&c, 0));
+        c.iter().map(|x| if s > 0.0 { (x - m) / s } else { 0.0 }).collect()
+    }).collect();
+    let my = stats::mean(ys);
+    let mut residual: Vec<f64> = ys.iter().map(|y| y - my).collect();
+    let mut w = vec![0.0; d];
+    for _ in 0..1000 {
+        let mut max_change: f64 = 0.0;
+        for j in 0..d {
+            // standardized columns have sum of squares n, unless they're constant
+            if cols[j].iter().all(|x| *x == 0.0) {
+                continue;
+            }
+            let rho: f64 = cols[j].iter().zip(residual.iter()).map(|(x, r)| x * r).sum::<f64>() / n + w[j];
+            let new = rho.signum() * (rho.abs() - alpha).max(0.0);
+            let delta = new - w[j];
+            if delta != 0.0 {
+                for (r, x) in residual.iter_mut().zip(cols[j].iter()) {
+                    *r -= delta * x;
+                }
+                w[j] = new;
+                max_change = max_change.max(delta.abs());
+            }
+        }
+        if max_change < 1e-10 {
+            return Ok(w);
+        }
+    }
+    tracing::debug!(alpha, "lasso: coordinate descent hit the iteration limit");
+    return Ok(w);
+}
@@ -0,0 +1,90 @@
+use crate::nn::unique_params;
+use crate::optim::Optimizer;
+use crate::random;
+use crate::value::Value;
+
+// differentially private sgd (abadi et al. 2016): every sample's gradient is clipped to a fixed
+// l2 norm before averaging, then gaussian noise scaled to that norm is added, so no single
+// sample can move the parameters by much
+
+pub struct DpSgd<O: Optimizer> {
+    pub inner: O,
+    // per-sample gradient norm bound C
+    pub clip_norm: f64,
+    // noise standard deviation as a multiple of C
+    pub noise_multiplier: f64,
+    // steps taken so far, for the privacy accounting
+    pub steps: usize,
+}
+
+impl<O: Optimizer> DpSgd<O> {
+    pub fn new(inner: O, clip_norm: f64, noise_multiplier: f64) -> Self {
+        assert!(clip_norm > 0.0, "clip_norm must be positive, got {}", clip_norm);
+        assert!(noise_multiplier >= 0.0, "noise_multiplier can't be negative, got {}", noise_multiplier);
+        DpSgd {
+            inner,
+            clip_norm,
+            noise_multiplier,
+            steps: 0,
+        }
+    }
+
+    pub fn parameters(&self) -> &[Value] {
+        self.inner.parameters()
+    }
+
+    // one private step over a batch of n samples
+    // sample(i) must build the loss of sample i alone, call backward() on it and return its value
+    // (the gradients are zeroed before every call), returns the mean loss of the batch
+    // the inner optimizer sees the noisy mean gradient, it should only evaluate the closure once (sgd, adam)
+    pub fn step(&mut self, n: usize, sample: &mut dyn FnMut(usize) -> f64) -> f64 {
+        assert!(n > 0, "a private step needs at least one sample");
+        let params = unique_params(self.inner.parameters().to_vec());
+        let mut sum = vec![0.0; params.len()];
+        let mut loss = 0.0;
+        for i in 0..n {
+            for p in params.iter() {
+                p.set_grad(0.0);
+            }
+            loss += sample(i);
+            let g: Vec<f64> = params.iter().map(|p| p.get_grad()).collect();
+            let norm = g.iter().map(|x| x * x).sum::<f64>().sqrt();
+            let scale = (self.clip_norm / norm.max(1e-12)).min(1.0);
+            for (s, gi) in sum.iter_mut().zip(g.iter()) {
+                *s += scale * gi;
+            }
+        }
+        let std = self.noise_multiplier * self.clip_norm;
+        for (p, s) in params.iter().zip(sum.iter()) {
+            p.set_grad((s + random::normal(0.0, std)) / n as f64);
+        }
+        let loss = loss / n as f64;
+        self.steps += 1;
+        tracing::trace!(loss, steps = self.steps, "dp-sgd step");
+        self.inner.step(&mut || loss);
+        return loss;
+    }
+
+    // privacy spent so far, see epsilon()
+    pub fn epsilon(&self, delta: f64) -> f64 {
+        return epsilon(self.noise_multiplier, self.steps, delta);
+    }
+}
+
+// (epsilon, delta) guarantee of `steps` gaussian mechanism steps with the given noise multiplier,
+// from renyi differential privacy: each step is (a, a / (2 sigma^2))-rdp, rdp adds up over steps,
+// and (a, r)-rdp implies (r + ln(1/delta) / (a - 1), delta)-dp, minimized over the order a
+// this ignores the amplification from sampling batches, so it's an upper bound (exact for full-batch training)
+pub fn epsilon(noise_multiplier: f64, steps: usize, delta: f64) -> f64 {
+    assert!(delta > 0.0 && delta < 1.0, "delta must be in (0, 1), got {}", delta);
+    if noise_multiplier == 0.0 {
+        return f64::INFINITY;
+    }
+    if steps == 0 {
+        return 0.0;
+    }
+    // the bound is convex in a with its minimum at a = 1 + sqrt(2 sigma^2 ln(1/delta) / steps)
+    let rdp_rate = steps as f64 / (2.0 * noise_multiplier * noise_multiplier);
+    let a = 1.0 + ((1.0 / delta).ln() / rdp_rate).sqrt();
+    return rdp_rate * a + (1.0 / delta).ln() / (a - 1.0);
+}
@@ -0,0 +1,126 @@
+use alloc::string::String;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    time::Instant,
+};
+
+// opt-in per-op timing for the autograd engines
+// forward time is the time spent building each node, backward time is each node's _backward,
+// and the topological sort is recorded as its own "graph bookkeeping" entry
+// when disabled the only cost is a thread-local bool check per node
+// without std there's no clock, profiling stays off and start() / record() do nothing
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpStats {
+    pub op: String,
+    pub phase: Phase,
+    pub count: u64,
+    pub total: Duration,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static STATS: RefCell<HashMap<(String, Phase), (u64, Duration)>> = RefCell::new(HashMap::new());
+}
+
+// when start() was called, nothing to hold without a clock
+#[cfg(feature = "std")]
+pub type Start = Instant;
+#[cfg(not(feature = "std"))]
+pub type Start = ();
+
+#[cfg(feature = "std")]
+pub fn enable() {
+    ENABLED.with(|e| e.set(true));
+}
+
+#[cfg(feature = "std")]
+pub fn disable() {
+    ENABLED.with(|e| e.set(false));
+}
+
+#[cfg(feature = "std")]
+pub fn is_enabled() -> bool {
+    return ENABLED.with(|e| e.get());
+}
+
+#[cfg(not(feature = "std"))]
+pub fn is_enabled() -> bool {
+    return false;
+}
+
+#[cfg(feature = "std")]
+pub fn reset() {
+    STATS.with(|s| s.borrow_mut().clear());
+}
+
+// start timing, None when profiling is off so record() becomes a no-op
+#[cfg(feature = "std")]
+pub fn start() -> Option<Start> {
+    if is_enabled() { Some(Instant::now()) } else { None }
+}
+
+#[cfg(not(feature = "std"))]
+pub fn start() -> Option<Start> {
+    return None;
+}
+
+#[cfg(not(feature = "std"))]
+pub fn record(_op: &str, _phase: Phase, _start: Option<Start>) {}
+
+#[cfg(feature = "std")]
+pub fn record(op: &str, phase: Phase, start: Option<Start>) {
+    if let Some(t) = start {
+        let elapsed = t.elapsed();
+        let op = if op.is_empty() { "leaf" } else { op };
+        STATS.with(|s| {
+            let mut s = s.borrow_mut();
+            let entry = s.entry((op.to_string(), phase)).or_insert((0, Duration::ZERO));
+            entry.0 += 1;
+            entry.1 += elapsed;
+        });
+    }
+}
+
+// everything recorded so far, most expensive first
+#[cfg(feature = "std")]
+pub fn report() -> Vec<OpStats> {
+    let mut stats: Vec<OpStats> = STATS.with(|s| {
+        s.borrow().iter().map(|((op, phase), (count, total))| OpStats {
+            op: op.clone(),
+            phase: *phase,
+            count: *count,
+            total: *total,
+        }).collect()
+    });
+    stats.sort_by_key(|s| std::cmp::Reverse(s.total));
+    return stats;
+}
+
+#[cfg(feature = "std")]
+pub fn print_report() {
+    let stats = report();
+    let grand_total: Duration = stats.iter().map(|s| s.total).sum();
+    println!("{:<24} {:<9} {:>10} {:>12} {:>10} {:>7}", "op", "phase", "count", "total (ms)", "avg (ns)", "%");
+    for s in stats.iter() {
+        println!(
+            "{:<24} {:<9} {:>10} {:>12.3} {:>10.1} {:>6.1}%",
+            s.op,
+            format!("{:?}", s.phase),
+            s.count,
+            s.total.as_secs_f64() * 1e3,
+            s.total.as_nanos() as f64 / s.count as f64,
+            100.0 * s.total.as_secs_f64() / grand_total.as_secs_f64().max(f64::MIN_POSITIVE),
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use crate::nn::Module;
+use crate::value::Value;
+
+// magnitude pruning: zero out the parameters closest to zero to shrink a trained model
+// works on module.parameters(), so biases are pruned together with the weights
+
+// which parameters survived pruning, reapply after every optimizer step while fine-tuning
+// so the pruned ones stay at zero
+pub struct PruneMask {
+    pub params: Vec<Value>,
+    pub keep: Vec<bool>,
+}
+
+impl PruneMask {
+    pub fn apply(&self) {
+        for (p, keep) in self.params.iter().zip(self.keep.iter()) {
+            if !keep {
+                p.set_data(0.0);
+                p.set_grad(0.0);
+            }
+        }
+    }
+
+    // fraction of parameters removed by this mask
+    pub fn sparsity(&self) -> f64 {
+        if self.keep.is_empty() {
+            return 0.0;
+        }
+        return self.keep.iter().filter(|k| !**k).count() as f64 / self.keep.len() as f64;
+    }
+}
+
+// zeroes the given fraction of the module's parameters, smallest absolute values first
+// (ties broken by parameter order), and returns the mask that was applied
+pub fn magnitude(module: &dyn Module, sparsity: f64) -> PruneMask {
+    assert!((0.0..=1.0).contains(&sparsity), "sparsity must be between 0 and 1, got {}", sparsity);
+    let params = module.parameters();
+    let n_prune = (sparsity * params.len() as f64).round() as usize;
+
+    let mut order: Vec<usize> = (0..params.len()).collect();
+    order.sort_by(|&a, &b| params[a].get_data().abs().total_cmp(&params[b].get_data().abs()));
+    let mut keep = vec![true; params.len()];
+    for &i in order.iter().take(n_prune) {
+        keep[i] = false;
+    }
+
+    let mask = PruneMask { params, keep };
+    mask.apply();
+    return mask;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SparsityReport {
+    pub total: usize,
+    pub zeros: usize,
+    pub sparsity: f64,
+}
+
+// how many of the module's parameters are exactly zero
+pub fn sparsity(module: &dyn Module) -> SparsityReport {
+    let params = module.parameters();
+    let zeros = params.iter().filter(|p| p.get_data() == 0.0).count();
+    let sparsity = if params.is_empty() { 0.0 } else { zeros as f64 / params.len() as f64 };
+    return SparsityReport {
+        total: params.len(),
+        zeros,
+        sparsity,
+    };
+}
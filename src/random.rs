@@ -0,0 +1,144 @@
+#[cfg(feature = "std")]
+use std::cell::{Cell, RefCell};
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+// one place for all the randomness in the crate (init, dropout, sampling, data generators)
+// calling seed() makes everything that goes through here reproducible
+#[cfg(feature = "std")]
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+    static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// without std there are no thread locals and no entropy: one global stream starting from a fixed
+// state, every with_rng() call gets a generator seeded from the next position in it
+#[cfg(not(feature = "std"))]
+static STATE: AtomicU64 = AtomicU64::new(0x853c_49e6_748f_ea9b);
+#[cfg(not(feature = "std"))]
+static SEEDED: AtomicBool = AtomicBool::new(false);
+#[cfg(not(feature = "std"))]
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "std")]
+pub fn seed(seed: u64) {
+    RNG.with(|r| *r.borrow_mut() = StdRng::seed_from_u64(seed));
+    SEED.with(|s| s.set(Some(seed)));
+}
+
+#[cfg(not(feature = "std"))]
+pub fn seed(seed: u64) {
+    STATE.store(seed, Ordering::Relaxed);
+    SEED.store(seed, Ordering::Relaxed);
+    SEEDED.store(true, Ordering::Relaxed);
+}
+
+// the last seed() on this thread, None while the generator is still seeded from entropy
+#[cfg(feature = "std")]
+pub fn last_seed() -> Option<u64> {
+    return SEED.with(|s| s.get());
+}
+
+#[cfg(not(feature = "std"))]
+pub fn last_seed() -> Option<u64> {
+    if SEEDED.load(Ordering::Relaxed) { Some(SEED.load(Ordering::Relaxed)) } else { None }
+}
+
+// run f with the shared generator, for anything not covered by the helpers below
+#[cfg(feature = "std")]
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    return RNG.with(|r| f(&mut r.borrow_mut()));
+}
+
+#[cfg(not(feature = "std"))]
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    // load and store rather than fetch_add, so targets without atomic read-modify-write still build
+    let state = STATE.load(Ordering::Relaxed);
+    STATE.store(state.wrapping_add(0x9e37_79b9_7f4a_7c15), Ordering::Relaxed);
+    return f(&mut StdRng::seed_from_u64(state));
+}
+
+// uniform in [lo, hi)
+pub fn uniform(lo: f64, hi: f64) -> f64 {
+    return with_rng(|r| r.gen_range(lo..hi));
+}
+
+// normal distribution through the box-muller transform
+pub fn normal(mean: f64, std: f64) -> f64 {
+    let (u1, u2) = with_rng(|r| (1.0 - r.gen::<f64>(), r.gen::<f64>()));
+    let z = (-2.0 * u1.ln()).sqrt() * (2.0 * core::f64::consts::PI * u2).cos();
+    return mean + std * z;
+}
+
+// gamma(shape, 1) by marsaglia and tsang's method, shapes below 1 boosted by u^(1 / shape)
+pub fn gamma(shape: f64) -> f64 {
+    assert!(shape > 0.0, "gamma needs a positive shape, got {}", shape);
+    if shape < 1.0 {
+        let u = with_rng(|r| r.gen::<f64>()).max(f64::MIN_POSITIVE);
+        return gamma(shape + 1.0) * u.powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let z = normal(0.0, 1.0);
+        let v = (1.0 + c * z).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u = with_rng(|r| r.gen::<f64>()).max(f64::MIN_POSITIVE);
+        if u.ln() < 0.5 * z * z + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+// beta(a, b) from two gamma draws
+pub fn beta(a: f64, b: f64) -> f64 {
+    let x = gamma(a);
+    let y = gamma(b);
+    return x / (x + y);
+}
+
+// true with probability p
+pub fn bernoulli(p: f64) -> bool {
+    return with_rng(|r| r.gen::<f64>() < p);
+}
+
+// index sampled proportionally to the (non-negative, not necessarily normalized) weights
+pub fn categorical(weights: &[f64]) -> usize {
+    let total: f64 = weights.iter().sum();
+    assert!(total > 0.0, "categorical needs at least one positive weight");
+    let mut u = with_rng(|r| r.gen::<f64>()) * total;
+    for (i, w) in weights.iter().enumerate() {
+        if u < *w {
+            return i;
+        }
+        u -= w;
+    }
+    // rounding can leave u just above the last bucket, pick the last positive weight
+    return weights.iter().rposition(|w| *w > 0.0).unwrap();
+}
+
+// standard gumbel noise, -ln(-ln(u))
+pub fn gumbel() -> f64 {
+    let u = with_rng(|r| r.gen::<f64>()).max(f64::MIN_POSITIVE);
+    return -(-u.ln()).ln();
+}
+
+// random integer in [0, n)
+pub fn index(n: usize) -> usize {
+    return with_rng(|r| r.gen_range(0..n));
+}
+
+// in-place fisher-yates shuffle
+pub fn shuffle<T>(xs: &mut [T]) {
+    for i in (1..xs.len()).rev() {
+        let j = index(i + 1);
+        xs.swap(i, j);
+    }
+}
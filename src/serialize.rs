@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, RustMlError};
+use crate::nn::{Activation, Layer, Neuron, MLP};
+use crate::random;
+use crate::value::Value;
+
+// saving and loading trained models as json, only the parameter values are stored, not gradients or graphs
+
+// bumped whenever the layout of the saved json changes
+// files without a version were written before versioning existed and are read as version 0
+pub const FORMAT_VERSION: u32 = 1;
+
+// ops this build knows how to run, a model using anything else is rejected on load
+pub const SUPPORTED_OPS: &[&str] = &["dense", "linear", "tanh", "softplus", "gelu", "silu", "elu", "relu", "leaky_relu", "sigmoid"];
+
+fn op_name(act: &Activation) -> &'static str {
+    match act {
+        Activation::Linear => "linear",
+        Activation::Tanh => "tanh",
+        Activation::Softplus => "softplus",
+        Activation::Gelu => "gelu",
+        Activation::Silu => "silu",
+        Activation::Elu(_) => "elu",
+        Activation::Relu => "relu",
+        Activation::LeakyRelu(_) => "leaky_relu",
+        Activation::Sigmoid => "sigmoid",
+    }
+}
+
+// the metadata every format version carries, read on its own before the model itself
+// so an incompatible file gets a clear message instead of a serde error
+#[derive(Debug, Clone, Deserialize)]
+struct Header {
+    #[serde(default)]
+    format_version: u32,
+    #[serde(default)]
+    ops: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedNeuron {
+    pub w: Vec<f64>,
+    pub b: f64,
+    pub act: Activation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMLP {
+    #[serde(default)]
+    pub format_version: u32,
+    // every op the model uses, sorted
+    #[serde(default)]
+    pub ops: Vec<String>,
+    pub layers: Vec<Vec<SavedNeuron>>,
+    // only in files written through ModelBundle, plain loading ignores it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ModelInfo>,
+}
+
+impl SavedMLP {
+    pub fn from_mlp(mlp: &MLP) -> SavedMLP {
+        let layers = mlp.layers().iter().map(|l| {
+            l.neurons().iter().map(|n| SavedNeuron {
+                w: n.w.iter().map(|w| w.get_data()).collect(),
+                b: n.b.get_data(),
+                act: n.act,
+            }).collect()
+        }).collect::<Vec<Vec<SavedNeuron>>>();
+        let mut ops: Vec<String> = vec!["dense".to_string()];
+        for n in layers.iter().flatten() {
+            let op = op_name(&n.act).to_string();
+            if !ops.contains(&op) {
+                ops.push(op);
+            }
+        }
+        ops.sort();
+        return SavedMLP {
+            format_version: FORMAT_VERSION,
+            ops,
+            layers,
+            info: None,
+        };
+    }
+
+    // every layer must be non-empty, its neurons must agree on their input size,
+    // and that size must match the width of the previous layer
+    pub fn validate(&self) -> Result<()> {
+        let mut width: Option<usize> = None;
+        for (i, layer) in self.layers.iter().enumerate() {
+            if layer.is_empty() {
+                return Err(RustMlError::Shape(format!("layer {} has no neurons", i)));
+            }
+            let nin = width.unwrap_or(layer[0].w.len());
+            for (j, n) in layer.iter().enumerate() {
+                if n.w.len() != nin {
+                    return Err(RustMlError::Shape(format!("layer {} neuron {} has {} weights, expected {}", i, j, n.w.len(), nin)));
+                }
+            }
+            width = Some(layer.len());
+        }
+        return Ok(());
+    }
+
+    pub fn to_mlp(&self) -> Result<MLP> {
+        self.validate()?;
+        let layers = self.layers.iter().map(|l| {
+            Layer::from_neurons(l.iter().map(|n| Neuron {
+                w: n.w.iter().map(|w| Value::new(*w)).collect(),
+                b: Value::new(n.b),
+                act: n.act,
+            }).collect())
+        }).collect();
+        return Ok(MLP::from_layers(layers));
+    }
+}
+
+pub fn to_json(mlp: &MLP) -> Result<String> {
+    return serde_json::to_string(&SavedMLP::from_mlp(mlp)).map_err(|e| RustMlError::Serialization(e.to_string()));
+}
+
+// checks that this build can read a file with the given header
+fn check_compatible(header: &Header) -> Result<()> {
+    if header.format_version > FORMAT_VERSION {
+        return Err(RustMlError::Serialization(format!(
+            "model was saved with format version {}, this build reads up to version {}; upgrade rust-ml to load it",
+            header.format_version, FORMAT_VERSION
+        )));
+    }
+    let unknown: Vec<&str> = header.ops.iter().map(|o| o.as_str()).filter(|o| !SUPPORTED_OPS.contains(o)).collect();
+    if !unknown.is_empty() {
+        return Err(RustMlError::Serialization(format!(
+            "model uses ops this build doesn't support: {}; upgrade rust-ml or re-export the model without them",
+            unknown.join(", ")
+        )));
+    }
+    return Ok(());
+}
+
+pub fn from_json(json: &str) -> Result<MLP> {
+    let header: Header = serde_json::from_str(json).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+    check_compatible(&header)?;
+    let saved: SavedMLP = serde_json::from_str(json).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+    return saved.to_mlp();
+}
+
+pub fn save(mlp: &MLP, path: impl AsRef<Path>) -> Result<()> {
+    fs::write(path, to_json(mlp)?)?;
+    return Ok(());
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<MLP> {
+    return from_json(&fs::read_to_string(path)?);
+}
+
+// what a saved model was trained on and how, stored next to the weights so the file describes itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    // dataset_hash() of the training data
+    pub dataset_hash: Option<String>,
+    pub hyperparameters: BTreeMap<String, String>,
+    // final metrics, e.g. "loss" or "test accuracy"
+    pub metrics: BTreeMap<String, f64>,
+    // version of rust-ml that wrote the file
+    pub crate_version: String,
+    pub seed: Option<u64>,
+}
+
+impl ModelInfo {
+    // this build's version and the seed last passed to random::seed, if any
+    pub fn new() -> Self {
+        ModelInfo {
+            dataset_hash: None,
+            hyperparameters: BTreeMap::new(),
+            metrics: BTreeMap::new(),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: random::last_seed(),
+        }
+    }
+
+    pub fn with_dataset(mut self, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> Self {
+        self.dataset_hash = Some(dataset_hash(xs, ys));
+        self
+    }
+
+    pub fn with_hyperparameter(mut self, name: &str, value: impl ToString) -> Self {
+        self.hyperparameters.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_metric(mut self, name: &str, value: f64) -> Self {
+        self.metrics.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Default for ModelInfo {
+    fn default() -> Self {
+        ModelInfo::new()
+    }
+}
+
+// 64-bit fnv-1a over the exact bits of every value and the row boundaries, as 16 hex digits
+// not cryptographic, only meant to tell whether two models saw the same data
+pub fn dataset_hash(xs: &[Vec<f64>], ys: &[Vec<f64>]) -> String {
+    let mut h: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for b in bytes {
+            h ^= *b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+    };
+    for rows in [xs, ys] {
+        feed(&(rows.len() as u64).to_le_bytes());
+        for row in rows {
+            feed(&(row.len() as u64).to_le_bytes());
+            for v in row {
+                feed(&v.to_bits().to_le_bytes());
+            }
+        }
+    }
+    return format!("{:016x}", h);
+}
+
+// a model saved together with its ModelInfo; the file is a regular saved model, load() reads it too
+pub struct ModelBundle {
+    pub model: MLP,
+    info: ModelInfo,
+}
+
+impl ModelBundle {
+    pub fn new(model: MLP, info: ModelInfo) -> Self {
+        ModelBundle {
+            model,
+            info
+        }
+    }
+
+    pub fn info(&self) -> &ModelInfo {
+        return &self.info;
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let mut saved = SavedMLP::from_mlp(&self.model);
+        saved.info = Some(self.info.clone());
+        return serde_json::to_string(&saved).map_err(|e| RustMlError::Serialization(e.to_string()));
+    }
+
+    // files saved without a bundle load with an empty ModelInfo (no version or seed)
+    pub fn from_json(json: &str) -> Result<ModelBundle> {
+        let header: Header = serde_json::from_str(json).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        check_compatible(&header)?;
+        let saved: SavedMLP = serde_json::from_str(json).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        let info = saved.info.clone().unwrap_or(ModelInfo {
+            crate_version: String::new(),
+            seed: None,
+            ..ModelInfo::new()
+        });
+        return Ok(ModelBundle::new(saved.to_mlp()?, info));
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_json()?)?;
+        return Ok(());
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<ModelBundle> {
+        return ModelBundle::from_json(&fs::read_to_string(path)?);
+    }
+}
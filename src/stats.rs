@@ -0,0 +1,126 @@
+use crate::matrix::Matrix;
+
+// column-wise summary statistics over Matrix data (rows are samples, columns are features)
+
+fn column(x: &Matrix, j: usize) -> Vec<f64> {
+    return (0..x.rows()).map(|i| x.get(i, j)).collect();
+}
+
+pub fn mean(xs: &[f64]) -> f64 {
+    return xs.iter().sum::<f64>() / xs.len() as f64;
+}
+
+// variance with ddof delta degrees of freedom (0 = population, 1 = sample)
+pub fn var(xs: &[f64], ddof: usize) -> f64 {
+    assert!(xs.len() > ddof, "variance with ddof {} needs more than {} values, got {}", ddof, ddof, xs.len());
+    let m = mean(xs);
+    return xs.iter().map(|x| (x - m) * (x - m)).sum::<f64>() / (xs.len() - ddof) as f64;
+}
+
+pub fn std(xs: &[f64], ddof: usize) -> f64 {
+    return var(xs, ddof).sqrt();
+}
+
+// q-th quantile (0 <= q <= 1) with linear interpolation between the closest ranks
+pub fn quantile(xs: &[f64], q: f64) -> f64 {
+    assert!((0.0..=1.0).contains(&q), "quantile must be between 0 and 1, got {}", q);
+    assert!(!xs.is_empty(), "quantile of an empty slice");
+    let mut sorted = xs.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let pos = q * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    return sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64);
+}
+
+pub fn col_mean(x: &Matrix) -> Vec<f64> {
+    return (0..x.cols()).map(|j| mean(&column(x, j))).collect();
+}
+
+pub fn col_var(x: &Matrix, ddof: usize) -> Vec<f64> {
+    return (0..x.cols()).map(|j| var(&column(x, j), ddof)).collect();
+}
+
+pub fn col_std(x: &Matrix, ddof: usize) -> Vec<f64> {
+    return (0..x.cols()).map(|j| std(&column(x, j), ddof)).collect();
+}
+
+pub fn col_quantile(x: &Matrix, q: f64) -> Vec<f64> {
+    return (0..x.cols()).map(|j| quantile(&column(x, j), q)).collect();
+}
+
+// sample covariance matrix (n - 1 in the denominator), cols x cols
+pub fn covariance(x: &Matrix) -> Matrix {
+    let (n, d) = (x.rows(), x.cols());
+    assert!(n > 1, "covariance needs at least 2 rows, got {}", n);
+    let means = col_mean(x);
+    let mut cov = vec![0.0; d * d];
+    for a in 0..d {
+        for b in a..d {
+            let c = (0..n).map(|i| (x.get(i, a) - means[a]) * (x.get(i, b) - means[b])).sum::<f64>() / (n - 1) as f64;
+            cov[a * d + b] = c;
+            cov[b * d + a] = c;
+        }
+    }
+    return Matrix::new(d, d, cov);
+}
+
+// pearson correlation matrix, constant columns get 0 correlation with everything else
+pub fn correlation(x: &Matrix) -> Matrix {
+    let d = x.cols();
+    let cov = covariance(x);
+    let sd: Vec<f64> = (0..d).map(|j| cov.get(j, j).sqrt()).collect();
+    let mut corr = vec![0.0; d * d];
+    for a in 0..d {
+        for b in 0..d {
+            corr[a * d + b] = if a == b {
+                1.0
+            } else if sd[a] == 0.0 || sd[b] == 0.0 {
+                0.0
+            } else {
+                cov.get(a, b) / (sd[a] * sd[b])
+            };
+        }
+    }
+    return Matrix::new(d, d, corr);
+}
+
+// per-column summary, like a quick describe() for exploring a dataset
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub q25: f64,
+    pub median: f64,
+    pub q75: f64,
+    pub max: f64,
+    pub nan_count: usize,
+}
+
+// summary of every column, NaNs are counted and left out of the other statistics
+pub fn describe(x: &Matrix) -> Vec<Summary> {
+    return (0..x.cols()).map(|j| {
+        let all = column(x, j);
+        let xs: Vec<f64> = all.iter().cloned().filter(|v| !v.is_nan()).collect();
+        let nan_count = all.len() - xs.len();
+        if xs.is_empty() {
+            return Summary {
+                count: 0, mean: f64::NAN, std: f64::NAN, min: f64::NAN, q25: f64::NAN,
+                median: f64::NAN, q75: f64::NAN, max: f64::NAN, nan_count,
+            };
+        }
+        Summary {
+            count: xs.len(),
+            mean: mean(&xs),
+            std: if xs.len() > 1 { std(&xs, 1) } else { 0.0 },
+            min: quantile(&xs, 0.0),
+            q25: quantile(&xs, 0.25),
+            median: quantile(&xs, 0.5),
+            q75: quantile(&xs, 0.75),
+            max: quantile(&xs, 1.0),
+            nan_count,
+        }
+    }).collect();
+}
@@ -0,0 +1,959 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::attacks::Adversarial;
+use crate::config::{LossConfig, OptimizerConfig, TrainConfig};
+use crate::data::DataLoader;
+use crate::error::{Result, RustMlError};
+use crate::graph;
+use crate::loss;
+use crate::nn::{get_flat_params, Module};
+use crate::optim::{Adam, Optimizer, Schedule, Sgd};
+use crate::plot::{self, Series};
+use crate::random;
+use crate::value::Value;
+
+// per-sample loss between the model outputs and the target row
+pub type LossFn<'a> = Box<dyn Fn(&[Value], &[f64]) -> Value + 'a>;
+
+// one task of a multi-task model: its slice of the model outputs, its slice of the target row,
+// and the loss between the two
+pub struct Task<'a> {
+    pub name: String,
+    pub outputs: Range<usize>,
+    pub targets: Range<usize>,
+    pub loss: LossFn<'a>,
+}
+
+impl<'a> Task<'a> {
+    pub fn new(name: &str, outputs: Range<usize>, targets: Range<usize>, loss: impl Fn(&[Value], &[f64]) -> Value + 'a) -> Self {
+        Task {
+            name: name.to_string(),
+            outputs,
+            targets,
+            loss: Box::new(loss),
+        }
+    }
+}
+
+// how the per-task losses are added up
+#[derive(Debug, Clone)]
+pub enum TaskWeights {
+    Fixed(Vec<f64>),
+    // homoscedastic uncertainty weighting (kendall et al. 2018): one learnable log variance s_i per task,
+    // total = sum of exp(-s_i) L_i + s_i, so noisier tasks get weighted down
+    // the s_i are parameters, hand parameters() to the optimizer along with the model's
+    Uncertainty(Vec<Value>),
+}
+
+impl TaskWeights {
+    // learnable weights starting at 1 for every task
+    pub fn uncertainty(tasks: usize) -> Self {
+        TaskWeights::Uncertainty((0..tasks).map(|_| Value::new(0.0)).collect())
+    }
+
+    pub fn parameters(&self) -> Vec<Value> {
+        match self {
+            TaskWeights::Fixed(_) => vec![],
+            TaskWeights::Uncertainty(log_vars) => log_vars.iter().map(|s| s.clone_rc()).collect(),
+        }
+    }
+
+    // current weight of each task loss
+    pub fn weights(&self) -> Vec<f64> {
+        match self {
+            TaskWeights::Fixed(w) => w.clone(),
+            TaskWeights::Uncertainty(log_vars) => log_vars.iter().map(|s| (-s.get_data()).exp()).collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            TaskWeights::Fixed(w) => w.len(),
+            TaskWeights::Uncertainty(log_vars) => log_vars.len(),
+        }
+    }
+}
+
+// combines the task losses into one loss over the whole output and target rows
+pub fn multi_task_loss<'a>(tasks: Vec<Task<'a>>, weights: TaskWeights) -> LossFn<'a> {
+    assert_eq!(tasks.len(), weights.len(), "expected one weight per task");
+    return Box::new(move |out, y| {
+        let terms: Vec<Value> = tasks.iter().enumerate().map(|(i, t)| {
+            let l = (t.loss)(&out[t.outputs.clone()], &y[t.targets.clone()]);
+            match &weights {
+                TaskWeights::Fixed(w) => Value::mul(&l, &Value::constant(w[i])),
+                TaskWeights::Uncertainty(log_vars) => {
+                    let s = &log_vars[i];
+                    Value::add(&Value::mul(&l, &Value::exp(&Value::neg(s))), s)
+                },
+            }
+        }).collect();
+        Value::sum(&terms)
+    });
+}
+
+// soft targets from a bigger, already trained model
+// the teacher only runs through predict(), so it's never part of the graph and never updated
+pub struct Distillation<'a> {
+    pub teacher: &'a dyn Module,
+    pub temperature: f64,
+    // weight of the soft-target loss, the hard-label loss gets 1 - alpha
+    pub alpha: f64,
+}
+
+fn sample_loss(model: &dyn Module, loss: &LossFn, distillation: Option<&Distillation>, x: &[f64], y: &[f64]) -> Value {
+    let xv: Vec<Value> = x.iter().map(|xi| Value::constant(*xi)).collect();
+    let out = model.forward(&xv);
+    let hard = loss(&out, y);
+    match distillation {
+        None => hard,
+        Some(d) => {
+            let soft = loss::distillation(&out, &d.teacher.predict(x), d.temperature);
+            Value::add(
+                &Value::mul(&soft, &Value::constant(d.alpha)),
+                &Value::mul(&hard, &Value::constant(1.0 - d.alpha)),
+            )
+        },
+    }
+}
+
+// one stage of a multi-stage schedule (curriculum, pretraining then fine-tuning, ...)
+// settings left as None keep whatever the trainer had at the end of the previous stage
+pub struct Stage {
+    pub name: String,
+    pub xs: Vec<Vec<f64>>,
+    pub ys: Vec<Vec<f64>>,
+    pub epochs: usize,
+    pub lr: Option<f64>,
+    pub batch_size: Option<usize>,
+    // parameters held fixed during the stage, e.g. layer.parameters() of a pretrained trunk
+    pub frozen: Vec<Value>,
+}
+
+impl Stage {
+    pub fn new(name: &str, xs: Vec<Vec<f64>>, ys: Vec<Vec<f64>>, epochs: usize) -> Self {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        Stage {
+            name: name.to_string(),
+            xs,
+            ys,
+            epochs,
+            lr: None,
+            batch_size: None,
+            frozen: vec![],
+        }
+    }
+
+    pub fn with_lr(mut self, lr: f64) -> Self {
+        self.lr = Some(lr);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn with_frozen(mut self, params: Vec<Value>) -> Self {
+        self.frozen.extend(params);
+        self
+    }
+}
+
+// gradient of each loss separately, one row per loss in params order, instead of their sum
+// the losses are built once (e.g. from one forward pass over a batch) and each backward only
+// clears and walks its own loss's graph, so nothing is rebuilt per sample
+// the gradients already on params are left as they were
+pub fn per_sample_gradients(params: &[Value], losses: &[Value]) -> Vec<Vec<f64>> {
+    let saved: Vec<f64> = params.iter().map(|p| p.get_grad()).collect();
+    let grads = losses.iter().map(|l| {
+        for p in params.iter() {
+            p.set_grad(0.0);
+        }
+        // nodes shared with other samples' graphs still hold their gradient from the previous backward
+        for node in graph::topo_order(l) {
+            node.set_grad(0.0);
+        }
+        l.backward();
+        params.iter().map(|p| p.get_grad()).collect()
+    }).collect();
+    for (p, g) in params.iter().zip(saved) {
+        p.set_grad(g);
+    }
+    return grads;
+}
+
+// exponentially weighted running loss and accuracy over a stream, for watching a model learn online
+// (and degrade under drift): a sample k updates back weighs decay^k, the averages are divided by the
+// total weight so the first samples aren't pulled towards 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamMetrics {
+    pub decay: f64,
+    pub loss: f64,
+    pub accuracy: f64,
+    pub samples: usize,
+    weight: f64,
+}
+
+impl StreamMetrics {
+    // decay close to 1 averages over about 1 / (1 - decay) recent samples
+    pub fn new(decay: f64) -> Self {
+        assert!((0.0..1.0).contains(&decay), "decay must be in [0, 1), got {}", decay);
+        StreamMetrics {
+            decay,
+            loss: 0.0,
+            accuracy: 0.0,
+            samples: 0,
+            weight: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, loss: f64, correct: bool) {
+        self.weight = self.decay * self.weight + 1.0;
+        let a = 1.0 / self.weight;
+        self.loss += a * (loss - self.loss);
+        self.accuracy += a * (if correct { 1.0 } else { 0.0 } - self.accuracy);
+        self.samples += 1;
+    }
+}
+
+// gradient and update sizes of one layer at one optimizer step
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerGradStats {
+    pub layer: String,
+    pub grad_norm: f64,
+    pub weight_norm: f64,
+    // |update| / |weights|, around 1e-3 is healthy; much higher and the learning rate is too high
+    // (the weights get rewritten every few steps), much lower and it's too low to learn anything
+    pub update_ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradStats {
+    // optimizer steps the trainer had taken before this one
+    pub step: usize,
+    // l2 norm of the gradient over all the model's parameters
+    pub grad_norm: f64,
+    pub layers: Vec<LayerGradStats>,
+}
+
+// watches the optimizer steps of a trainer (Trainer::with_grad_monitor): the global gradient norm and
+// every layer's update-to-weight ratio, logged and kept in history
+// exploding or vanishing gradient norms and ratios far from 1e-3 are the usual signs of a bad learning rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradMonitor {
+    // record every this many steps, the snapshot of the weights isn't free on big models
+    pub every: usize,
+    pub history: Vec<GradStats>,
+    steps: usize,
+    // layer weights from before the step being watched
+    before: Option<Vec<Vec<f64>>>,
+}
+
+impl GradMonitor {
+    pub fn new(every: usize) -> Self {
+        assert!(every > 0, "monitor interval must be positive");
+        GradMonitor {
+            every,
+            history: vec![],
+            steps: 0,
+            before: None,
+        }
+    }
+
+    fn before_step(&mut self, model: &dyn Module) {
+        if self.steps.is_multiple_of(self.every) {
+            self.before = Some(model.layer_parameters().iter().map(|(_, params)| get_flat_params(params)).collect());
+        }
+    }
+
+    fn after_step(&mut self, model: &dyn Module) {
+        self.steps += 1;
+        let Some(before) = self.before.take() else {
+            return;
+        };
+        let norm = |xs: &mut dyn Iterator<Item = f64>| xs.map(|x| x * x).sum::<f64>().sqrt();
+        let grad_norm = norm(&mut model.parameters().iter().map(|p| p.get_grad()));
+        let layers: Vec<LayerGradStats> = model.layer_parameters().into_iter().zip(before.iter())
+            .filter(|((_, params), _)| !params.is_empty())
+            .map(|((layer, params), before)| {
+                let weight_norm = norm(&mut before.iter().cloned());
+                let update = norm(&mut params.iter().zip(before.iter()).map(|(p, b)| p.get_data() - b));
+                LayerGradStats {
+                    layer,
+                    grad_norm: norm(&mut params.iter().map(|p| p.get_grad())),
+                    weight_norm,
+                    update_ratio: if weight_norm > 0.0 { update / weight_norm } else { 0.0 },
+                }
+            })
+            .collect();
+        tracing::debug!(step = self.steps - 1, grad_norm, max_update_ratio = layers.iter().map(|l| l.update_ratio).fold(0.0, f64::max), "gradient stats");
+        self.history.push(GradStats { step: self.steps - 1, grad_norm, layers });
+    }
+
+    // the update ratio of one layer over the recorded steps
+    pub fn ratio_history(&self, layer: &str) -> Vec<f64> {
+        return self.history.iter().filter_map(|s| s.layers.iter().find(|l| l.layer == layer)).map(|l| l.update_ratio).collect();
+    }
+}
+
+// stochastic weight averaging (izmailov et al. 2018): a running mean of the weights from the tail of
+// training, taken every `every` epochs once `start` epochs are done; with a cyclic schedule whose
+// period is `every` the samples land on the low-lr end of each cycle, spread around a wide minimum,
+// and their average usually sits nearer its middle than any one of them (Trainer::with_swa)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Swa {
+    pub start: usize,
+    pub every: usize,
+    // number of weight snapshots in the mean so far
+    pub samples: usize,
+    mean: Vec<f64>,
+}
+
+impl Swa {
+    pub fn new(start: usize, every: usize) -> Self {
+        assert!(every > 0, "swa interval must be positive");
+        Swa {
+            start,
+            every,
+            samples: 0,
+            mean: vec![],
+        }
+    }
+
+    // called with the total number of epochs trained, after each one
+    fn update(&mut self, model: &dyn Module, epochs: usize) {
+        if epochs <= self.start || !(epochs - self.start).is_multiple_of(self.every) {
+            return;
+        }
+        let params = model.get_flat_params();
+        if self.samples == 0 {
+            self.mean = params;
+        } else {
+            let n = self.samples as f64;
+            for (m, p) in self.mean.iter_mut().zip(params.iter()) {
+                *m += (p - *m) / (n + 1.0);
+            }
+        }
+        self.samples += 1;
+        tracing::debug!(epochs, samples = self.samples, "swa snapshot");
+    }
+
+    // the averaged weights, None before the first snapshot
+    pub fn average(&self) -> Option<&[f64]> {
+        if self.samples == 0 {
+            return None;
+        }
+        return Some(&self.mean);
+    }
+}
+
+// loss against learning rate from Trainer::lr_finder (leslie smith's lr range test)
+#[derive(Debug, Clone, PartialEq)]
+pub struct LrFinder {
+    pub lrs: Vec<f64>,
+    // loss of every step's batch before the step
+    pub losses: Vec<f64>,
+    // exponential moving average of the losses (bias corrected), what the suggestion reads
+    pub smoothed: Vec<f64>,
+}
+
+impl LrFinder {
+    // (low, high): where the smoothed loss falls fastest against log lr and where it bottoms out
+    // a fixed lr around low is a safe pick, a one-cycle or cosine schedule can peak around high
+    // None if the sweep is too short to tell
+    pub fn suggestion(&self) -> Option<(f64, f64)> {
+        if self.smoothed.len() < 3 {
+            return None;
+        }
+        let best = (0..self.smoothed.len()).fold(0, |b, i| if self.smoothed[i] < self.smoothed[b] { i } else { b });
+        if best == 0 {
+            return None;
+        }
+        let slope = |i: usize| (self.smoothed[i + 1] - self.smoothed[i]) / (self.lrs[i + 1] / self.lrs[i]).ln();
+        let steepest = (0..best).fold(0, |b, i| if slope(i) < slope(b) { i } else { b });
+        return Some((self.lrs[steepest], self.lrs[best]));
+    }
+
+    // smoothed and raw loss against log10 of the learning rate, as an svg
+    pub fn plot(&self) -> String {
+        let against_lr = |ys: &[f64]| self.lrs.iter().zip(ys.iter()).map(|(lr, y)| (lr.log10(), *y)).collect();
+        return plot::line_chart("lr range test (loss vs log10 lr)", &[
+            Series::new("smoothed", against_lr(&self.smoothed)),
+            Series::new("loss", against_lr(&self.losses)),
+        ]);
+    }
+}
+
+// whether the model output gets the target's class, following the datasets::Dataset targets:
+// a single output is read as a sign (+1 / -1), several as scores against a one-hot row
+pub fn is_correct(out: &[f64], y: &[f64]) -> bool {
+    if out.len() == 1 {
+        return (out[0] > 0.0) == (y[0] > 0.0);
+    }
+    let argmax = |v: &[f64]| (0..v.len()).fold(0, |best, k| if v[k] > v[best] { k } else { best });
+    return argmax(out) == argmax(y);
+}
+
+// ctrl-c while training: the first one asks every trainer to stop once the batch it's on is done (and
+// save its checkpoint, if it has one), a second one exits straight away
+static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+// installs the ctrl-c handler (once per process, later calls return the same flag) and returns the
+// flag it sets; trainers made after this watch it, with_interrupt hands a trainer any other flag
+pub fn handle_interrupts() -> Result<Arc<AtomicBool>> {
+    if let Some(flag) = INTERRUPT.get() {
+        return Ok(flag.clone());
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    let handler_flag = flag.clone();
+    ctrlc::set_handler(move || {
+        if handler_flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        tracing::warn!("interrupted, stopping after the current batch (ctrl-c again to quit now)");
+    }).map_err(|e| RustMlError::Config(format!("can't install the ctrl-c handler: {}", e)))?;
+    return Ok(INTERRUPT.get_or_init(|| flag).clone());
+}
+
+// why fit() stopped before running all its epochs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    EpochBudget,
+    TimeBudget,
+    Interrupted,
+}
+
+// what a trainer saves to pick a run back up: the model parameters and the epoch losses so far
+// optimizer state (momentum, adam moments) isn't kept, it builds up again after a resume
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub params: Vec<f64>,
+    pub history: Vec<f64>,
+}
+
+// training loop: by default every epoch is one optimizer step on the mean loss over the dataset,
+// with a batch size it's one step per mini-batch over a fresh shuffle of the dataset
+pub struct Trainer<'a> {
+    pub model: &'a dyn Module,
+    pub optimizer: Box<dyn Optimizer + 'a>,
+    pub loss: LossFn<'a>,
+    pub distillation: Option<Distillation<'a>>,
+    pub adversarial: Option<Adversarial>,
+    // None trains full batch
+    pub batch_size: Option<usize>,
+    // mean loss of every epoch trained so far
+    pub history: Vec<f64>,
+    // parameters of the current stage held at these values
+    frozen: Vec<(Value, f64)>,
+    // base learning rate and how fit() moves it from epoch to epoch
+    schedule: Option<(f64, Schedule)>,
+    // why the last fit() stopped early, None if it ran all its epochs
+    pub stopped: Option<Stop>,
+    // limits on the total epochs trained (history included) and on the wall-clock time of one fit()
+    max_epochs: Option<usize>,
+    max_time: Option<Duration>,
+    deadline: Option<Instant>,
+    interrupt: Option<Arc<AtomicBool>>,
+    // saved to at the end of every fit(), finished or not
+    checkpoint: Option<PathBuf>,
+    pub grad_monitor: Option<GradMonitor>,
+    pub swa: Option<Swa>,
+}
+
+impl<'a> Trainer<'a> {
+    // defaults to mean squared error against the target rows
+    pub fn new(model: &'a dyn Module, optimizer: impl Optimizer + 'a) -> Self {
+        Trainer {
+            model,
+            optimizer: Box::new(optimizer),
+            loss: Box::new(|ypred, y| {
+                let y: Vec<Value> = y.iter().map(|yi| Value::constant(*yi)).collect();
+                loss::mse(ypred, &y)
+            }),
+            distillation: None,
+            adversarial: None,
+            batch_size: None,
+            history: vec![],
+            frozen: vec![],
+            schedule: None,
+            stopped: None,
+            max_epochs: None,
+            max_time: None,
+            deadline: None,
+            interrupt: INTERRUPT.get().cloned(),
+            checkpoint: None,
+            grad_monitor: None,
+            swa: None,
+        }
+    }
+
+    // optimizer over all the model's parameters, schedule, batch size, loss, budgets and checkpoint
+    // from a run config (the config's epochs are the epoch budget)
+    pub fn from_config(model: &'a dyn Module, config: &TrainConfig) -> Self {
+        let params = model.parameters();
+        let mut trainer = match config.optimizer {
+            OptimizerConfig::Sgd { lr, momentum, weight_decay } => {
+                let mut sgd = Sgd::new(params, lr).with_momentum(momentum);
+                sgd.weight_decay = weight_decay;
+                Trainer::new(model, sgd)
+            },
+            OptimizerConfig::Adam { lr } => Trainer::new(model, Adam::new(params, lr)),
+        };
+        trainer = trainer
+            .with_schedule(config.optimizer.lr(), config.scheduler)
+            .with_epoch_budget(config.training.epochs);
+        if let Some(batch_size) = config.training.batch_size {
+            trainer = trainer.with_batch_size(batch_size);
+        }
+        if let Some(seconds) = config.training.max_seconds {
+            trainer = trainer.with_time_budget(Duration::from_secs_f64(seconds));
+        }
+        if let Some(path) = &config.training.checkpoint {
+            trainer = trainer.with_checkpoint(path);
+        }
+        if config.training.loss == LossConfig::CrossEntropy {
+            trainer = trainer.with_loss(loss::cross_entropy);
+        }
+        trainer
+    }
+
+    pub fn with_loss(mut self, loss: impl Fn(&[Value], &[f64]) -> Value + 'a) -> Self {
+        self.loss = Box::new(loss);
+        self
+    }
+
+    // multi-task training, see multi_task_loss
+    pub fn with_tasks(mut self, tasks: Vec<Task<'a>>, weights: TaskWeights) -> Self {
+        self.loss = multi_task_loss(tasks, weights);
+        self
+    }
+
+    // train against a frozen teacher as well as the labels, the model outputs are treated as logits
+    pub fn with_distillation(mut self, teacher: &'a dyn Module, temperature: f64, alpha: f64) -> Self {
+        assert!((0.0..=1.0).contains(&alpha), "alpha must be between 0 and 1, got {}", alpha);
+        self.distillation = Some(Distillation {
+            teacher,
+            temperature,
+            alpha,
+        });
+        self
+    }
+
+    // adversarial training: every sample's loss is mixed with the loss on a perturbed copy of it,
+    // regenerated against the current model every time the loss is evaluated
+    pub fn with_adversarial(mut self, eps: f64, steps: usize, weight: f64) -> Self {
+        assert!((0.0..=1.0).contains(&weight), "weight must be between 0 and 1, got {}", weight);
+        self.adversarial = Some(Adversarial {
+            eps,
+            steps,
+            weight,
+        });
+        self
+    }
+
+    // mini-batch training, a batch size of at least the dataset size is the same as full batch
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    // fit() sets the optimizer's learning rate from the schedule at the start of every epoch
+    pub fn with_schedule(mut self, base_lr: f64, schedule: Schedule) -> Self {
+        self.schedule = Some((base_lr, schedule));
+        self
+    }
+
+    // fit() stops once this many epochs have been trained in total, counting the history
+    // (so a resumed run finishes the budget it started with)
+    pub fn with_epoch_budget(mut self, max_epochs: usize) -> Self {
+        self.max_epochs = Some(max_epochs);
+        self
+    }
+
+    // fit() stops after the first batch that ends past this much wall-clock time
+    pub fn with_time_budget(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    // fit() stops after the current batch once the flag is set, see handle_interrupts
+    pub fn with_interrupt(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(flag);
+        self
+    }
+
+    // fit() saves a Checkpoint here when it returns, however it stopped
+    pub fn with_checkpoint(mut self, path: impl AsRef<Path>) -> Self {
+        self.checkpoint = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // records gradient norms and update ratios every `every` optimizer steps, see GradMonitor
+    pub fn with_grad_monitor(mut self, every: usize) -> Self {
+        self.grad_monitor = Some(GradMonitor::new(every));
+        self
+    }
+
+    // averages the weights every `every` epochs after the first `start`, see Swa and finalize_swa
+    pub fn with_swa(mut self, start: usize, every: usize) -> Self {
+        self.swa = Some(Swa::new(start, every));
+        self
+    }
+
+    // puts the swa average into the model, false (model untouched) if nothing has been averaged yet
+    // there are no batch norm layers, so no running statistics need recomputing for the new weights;
+    // a model with them would need one pass over the training data in training mode here
+    pub fn finalize_swa(&mut self) -> bool {
+        let Some(mean) = self.swa.as_ref().and_then(|s| s.average()) else {
+            return false;
+        };
+        self.model.set_flat_params(mean);
+        tracing::info!(samples = self.swa.as_ref().map_or(0, |s| s.samples), "swa weights in place");
+        return true;
+    }
+
+    // written to a temporary file first, so stopping halfway through never leaves a broken checkpoint
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let checkpoint = Checkpoint {
+            params: self.model.get_flat_params(),
+            history: self.history.clone(),
+        };
+        let json = serde_json::to_string(&checkpoint).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)?;
+        return Ok(());
+    }
+
+    // loads a checkpoint's parameters into the model and its history into the trainer
+    pub fn resume(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let checkpoint: Checkpoint = serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| RustMlError::Serialization(e.to_string()))?;
+        let n = self.model.parameters().len();
+        if checkpoint.params.len() != n {
+            return Err(RustMlError::Shape(format!("checkpoint has {} parameters, the model has {}", checkpoint.params.len(), n)));
+        }
+        self.model.set_flat_params(&checkpoint.params);
+        self.history = checkpoint.history;
+        tracing::info!(epochs = self.history.len(), "resumed from checkpoint");
+        return Ok(());
+    }
+
+    // sets self.stopped if training should stop after the batch that just finished
+    fn should_stop(&mut self) -> bool {
+        let stop = if self.interrupt.as_ref().is_some_and(|f| f.load(Ordering::SeqCst)) {
+            Some(Stop::Interrupted)
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some(Stop::TimeBudget)
+        } else {
+            None
+        };
+        if stop.is_some() {
+            self.stopped = stop;
+        }
+        return stop.is_some();
+    }
+
+    // the epoch loop shared by fit() and fit_loader()
+    fn run_epochs(&mut self, epochs: usize, mut epoch_fn: impl FnMut(&mut Self) -> f64) -> Vec<f64> {
+        self.stopped = None;
+        self.deadline = self.max_time.map(|t| Instant::now() + t);
+        self.model.train(true);
+        let start = self.history.len();
+        let mut losses = vec![];
+        for epoch in 0..epochs {
+            if self.max_epochs.is_some_and(|m| self.history.len() >= m) {
+                self.stopped = Some(Stop::EpochBudget);
+                break;
+            }
+            if let Some((base_lr, schedule)) = self.schedule {
+                // over the whole budget if there is one, so a resumed run picks the schedule up where it was
+                let (epoch, total) = match self.max_epochs {
+                    Some(m) => (self.history.len(), m),
+                    None => (self.history.len() - start, epochs),
+                };
+                self.optimizer.set_lr(schedule.lr(base_lr, epoch, total));
+                if let Some(momentum) = schedule.momentum(epoch, total) {
+                    self.optimizer.set_momentum(momentum);
+                }
+            }
+            let loss = epoch_fn(self);
+            tracing::info!(epoch, loss, "epoch done");
+            losses.push(loss);
+            if let Some(swa) = self.swa.as_mut() {
+                swa.update(self.model, self.history.len());
+            }
+            if self.should_stop() {
+                break;
+            }
+        }
+        self.model.train(false);
+        self.deadline = None;
+        if let Some(stop) = self.stopped {
+            tracing::info!(reason = ?stop, epochs = losses.len(), "training stopped early");
+        }
+        if let Some(path) = self.checkpoint.clone() {
+            match self.save_checkpoint(&path) {
+                Ok(()) => tracing::info!(path = %path.display(), "saved checkpoint"),
+                Err(e) => tracing::warn!(path = %path.display(), error = %e, "couldn't save checkpoint"),
+            }
+        }
+        return losses;
+    }
+
+    // loss for one sample, including the distillation term if there is one
+    pub fn sample_loss(&self, x: &[f64], y: &[f64]) -> Value {
+        return sample_loss(self.model, &self.loss, self.distillation.as_ref(), x, y);
+    }
+
+    // one optimizer step per batch the loader yields, returns the mean loss over its samples
+    // (over the batches it got through, if it was told to stop)
+    pub fn train_loader_epoch(&mut self, loader: &DataLoader) -> f64 {
+        let batches = loader.batches();
+        let (mut total, mut n) = (0.0, 0);
+        for batch in batches.iter() {
+            let idx: Vec<usize> = (0..batch.xs.len()).collect();
+            total += self.step(&batch.xs, &batch.ys, &idx) * batch.xs.len() as f64;
+            n += batch.xs.len();
+            if self.should_stop() {
+                break;
+            }
+        }
+        let epoch_loss = total / n.max(1) as f64;
+        self.history.push(epoch_loss);
+        return epoch_loss;
+    }
+
+    // fit() over a data loader, e.g. one yielding pairs or triplets
+    pub fn fit_loader(&mut self, loader: &DataLoader, epochs: usize) -> Vec<f64> {
+        let _span = tracing::info_span!("fit", epochs, samples = loader.xs.len()).entered();
+        return self.run_epochs(epochs, |t| t.train_loader_epoch(loader));
+    }
+
+    // gradient of every sample's loss with respect to the model parameters, see per_sample_gradients
+    pub fn per_sample_gradients(&self, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        let losses: Vec<Value> = xs.iter().zip(ys.iter()).map(|(x, y)| self.sample_loss(x, y)).collect();
+        return per_sample_gradients(&self.model.parameters(), &losses);
+    }
+
+    // one pass over the dataset, full batch or in shuffled mini-batches,
+    // returns the mean loss before each step, averaged over the samples
+    // a pass told to stop (interrupt, time budget) ends after the batch it's on
+    pub fn train_epoch(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> f64 {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        let mut idx: Vec<usize> = (0..xs.len()).collect();
+        let epoch_loss = match self.batch_size {
+            Some(k) if k < xs.len() => {
+                random::shuffle(&mut idx);
+                let (mut total, mut n) = (0.0, 0);
+                for batch in idx.chunks(k) {
+                    total += self.step(xs, ys, batch) * batch.len() as f64;
+                    n += batch.len();
+                    if self.should_stop() {
+                        break;
+                    }
+                }
+                total / n as f64
+            },
+            _ => self.step(xs, ys, &idx),
+        };
+        self.history.push(epoch_loss);
+        return epoch_loss;
+    }
+
+    // one optimizer step on the mean loss over the samples in batch, returns that loss before the step
+    fn step(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], batch: &[usize]) -> f64 {
+        if let Some(monitor) = self.grad_monitor.as_mut() {
+            monitor.before_step(self.model);
+        }
+        // split the borrows so the closure can read the loss while the optimizer is borrowed mutably
+        let Trainer { model, optimizer, loss, distillation, adversarial, frozen, grad_monitor, .. } = self;
+        let mut first: Option<f64> = None;
+        let last = optimizer.step(&mut || {
+            // the attacks run their own backward passes, so they go before the gradients are zeroed
+            let adv_xs: Option<Vec<Vec<f64>>> = adversarial.map(|a| {
+                batch.iter().map(|&i| a.perturb(*model, loss, &xs[i], &ys[i])).collect()
+            });
+            model.zero_grad();
+            let mut losses = Vec::with_capacity(batch.len());
+            for (k, &i) in batch.iter().enumerate() {
+                let mut l = sample_loss(*model, loss, distillation.as_ref(), &xs[i], &ys[i]);
+                if let (Some(a), Some(adv_xs)) = (adversarial.as_ref(), adv_xs.as_ref()) {
+                    let adv = sample_loss(*model, loss, distillation.as_ref(), &adv_xs[k], &ys[i]);
+                    l = Value::add(
+                        &Value::mul(&adv, &Value::constant(a.weight)),
+                        &Value::mul(&l, &Value::constant(1.0 - a.weight)),
+                    );
+                }
+                losses.push(l);
+            }
+            let total = Value::mean(&losses);
+            total.backward();
+            *first.get_or_insert(total.get_data())
+        });
+        for (p, x) in frozen.iter() {
+            p.set_data(*x);
+        }
+        if let Some(monitor) = grad_monitor.as_mut() {
+            monitor.after_step(*model);
+        }
+        return first.unwrap_or(last);
+    }
+
+    // runs the stages one after another, returns the epoch losses of each stage (an interrupt ends
+    // the stage it's in and skips the rest)
+    // frozen parameters get no gradient and are put back after every step, so momentum or
+    // weight decay in the optimizer can't move them either
+    pub fn fit_stages(&mut self, stages: &[Stage]) -> Vec<Vec<f64>> {
+        self.stopped = None;
+        let mut losses = vec![];
+        for stage in stages {
+            let _span = tracing::info_span!("stage", name = stage.name.as_str(), epochs = stage.epochs).entered();
+            if let Some(lr) = stage.lr {
+                self.optimizer.set_lr(lr);
+            }
+            if stage.batch_size.is_some() {
+                self.batch_size = stage.batch_size;
+            }
+            let requires_grad: Vec<bool> = stage.frozen.iter().map(|p| p.requires_grad()).collect();
+            for p in stage.frozen.iter() {
+                p.set_requires_grad(false);
+            }
+            self.frozen = stage.frozen.iter().map(|p| (p.clone_rc(), p.get_data())).collect();
+
+            self.model.train(true);
+            let mut stage_losses = vec![];
+            for epoch in 0..stage.epochs {
+                let loss = self.train_epoch(&stage.xs, &stage.ys);
+                tracing::info!(epoch, loss, "epoch done");
+                stage_losses.push(loss);
+                if self.should_stop() {
+                    break;
+                }
+            }
+            self.model.train(false);
+            self.frozen.clear();
+
+            for (p, r) in stage.frozen.iter().zip(requires_grad) {
+                p.set_requires_grad(r);
+            }
+            losses.push(stage_losses);
+            if self.stopped.is_some() {
+                break;
+            }
+        }
+        return losses;
+    }
+
+    // one more epoch on new data, continuing from the current weights and optimizer state (momentum,
+    // adam moments), so a model can keep learning as data arrives without retraining from scratch
+    // the model's outputs are fixed, new classes need an output for them already
+    pub fn partial_fit(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>]) -> f64 {
+        self.model.train(true);
+        let loss = self.train_epoch(xs, ys);
+        self.model.train(false);
+        tracing::debug!(loss, samples = xs.len(), "partial fit");
+        return loss;
+    }
+
+    // online learning, test then train: the micro-batch is scored by the current model (into metrics)
+    // before one optimizer step on it, so the metrics always measure data the model hasn't trained on
+    // returns the micro-batch's mean loss before the step
+    pub fn online_step(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], metrics: &mut StreamMetrics) -> f64 {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let correct = is_correct(&self.model.predict(x), y);
+            metrics.update(self.sample_loss(x, y).get_data(), correct);
+        }
+        self.model.train(true);
+        let idx: Vec<usize> = (0..xs.len()).collect();
+        let loss = self.step(xs, ys, &idx);
+        self.model.train(false);
+        return loss;
+    }
+
+    // online_step over a stream of (x, y) samples in micro-batches as they arrive (the last one may be
+    // smaller), returns the running (loss, accuracy) after every update
+    pub fn fit_stream(&mut self, stream: impl IntoIterator<Item = (Vec<f64>, Vec<f64>)>, micro_batch: usize, metrics: &mut StreamMetrics) -> Vec<(f64, f64)> {
+        assert!(micro_batch > 0, "micro-batch size must be positive");
+        let mut trace = vec![];
+        let (mut xs, mut ys) = (vec![], vec![]);
+        let mut stream = stream.into_iter().peekable();
+        while let Some((x, y)) = stream.next() {
+            xs.push(x);
+            ys.push(y);
+            if xs.len() == micro_batch || stream.peek().is_none() {
+                self.online_step(&xs, &ys, metrics);
+                tracing::debug!(samples = metrics.samples, loss = metrics.loss, accuracy = metrics.accuracy, "online update");
+                trace.push((metrics.loss, metrics.accuracy));
+                xs.clear();
+                ys.clear();
+            }
+        }
+        return trace;
+    }
+
+    // lr range test: `iterations` steps over shuffled batches (of the batch size, the whole dataset
+    // without one) with the learning rate going up exponentially from min_lr to max_lr, recording the
+    // loss at every lr; stops early once the loss has risen above its best by more than 3 times the
+    // best's size (4 times the best for positive losses, and still sensible for negative ones)
+    // the model's weights are put back afterwards but the optimizer's state (momentum, adam moments,
+    // its lr) isn't, so train with a fresh optimizer once a learning rate is picked
+    pub fn lr_finder(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], min_lr: f64, max_lr: f64, iterations: usize) -> LrFinder {
+        assert_eq!(xs.len(), ys.len(), "expected as many targets as inputs");
+        assert!(!xs.is_empty(), "the lr range test needs at least one sample");
+        assert!(0.0 < min_lr && min_lr < max_lr, "need 0 < min_lr < max_lr, got {} and {}", min_lr, max_lr);
+        assert!(iterations >= 2, "the sweep needs at least 2 iterations");
+        let _span = tracing::info_span!("lr_finder", min_lr, max_lr, iterations).entered();
+        let saved = self.model.get_flat_params();
+        let batch_size = self.batch_size.unwrap_or(xs.len()).clamp(1, xs.len());
+        let mut finder = LrFinder { lrs: vec![], losses: vec![], smoothed: vec![] };
+        let (beta, mut avg, mut best) = (0.98, 0.0, f64::INFINITY);
+        let mut batches: Vec<Vec<usize>> = vec![];
+        self.model.train(true);
+        for i in 0..iterations {
+            if batches.is_empty() {
+                let mut idx: Vec<usize> = (0..xs.len()).collect();
+                random::shuffle(&mut idx);
+                batches = idx.chunks(batch_size).rev().map(|b| b.to_vec()).collect();
+            }
+            let batch = batches.pop().unwrap();
+            let lr = min_lr * (max_lr / min_lr).powf(i as f64 / (iterations - 1) as f64);
+            self.optimizer.set_lr(lr);
+            let loss = self.step(xs, ys, &batch);
+            avg = beta * avg + (1.0 - beta) * loss;
+            let smoothed = avg / (1.0 - beta.powi(i as i32 + 1));
+            finder.lrs.push(lr);
+            finder.losses.push(loss);
+            finder.smoothed.push(smoothed);
+            best = best.min(smoothed);
+            if !smoothed.is_finite() || smoothed - best > 3.0 * best.abs() {
+                tracing::info!(lr, iteration = i, "loss diverged, stopping the sweep");
+                break;
+            }
+        }
+        self.model.train(false);
+        self.model.set_flat_params(&saved);
+        if let Some((low, high)) = finder.suggestion() {
+            tracing::info!(low, high, "suggested learning rates");
+        }
+        return finder;
+    }
+
+    // trains up to epochs epochs, fewer if a budget runs out or it's interrupted (see stopped)
+    pub fn fit(&mut self, xs: &[Vec<f64>], ys: &[Vec<f64>], epochs: usize) -> Vec<f64> {
+        let _span = tracing::info_span!("fit", epochs, samples = xs.len()).entered();
+        return self.run_epochs(epochs, |t| t.train_epoch(xs, ys));
+    }
+}
@@ -0,0 +1,27 @@
+use alloc::vec::Vec;
+
+use crate::nn::Module;
+use crate::value::Value;
+
+// monte carlo dropout: run n forward passes with dropout left on and return the
+// predictive mean and variance of every output
+// the model is put in training mode for the passes and left in whatever mode it was in before
+pub fn mc_dropout(model: &dyn Module, x: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    assert!(n > 1, "mc_dropout needs at least 2 passes to estimate a variance");
+    let previous = model.training_mode();
+    model.train(true);
+    let samples: Vec<Vec<f64>> = (0..n).map(|_| {
+        let input: Vec<Value> = x.iter().map(|v| Value::constant(*v)).collect();
+        model.forward(&input).iter().map(|y| y.get_data()).collect()
+    }).collect();
+    if let Some(mode) = previous {
+        model.train(mode);
+    }
+
+    let nout = samples[0].len();
+    let mean: Vec<f64> = (0..nout).map(|j| samples.iter().map(|s| s[j]).sum::<f64>() / n as f64).collect();
+    let var: Vec<f64> = (0..nout).map(|j| {
+        samples.iter().map(|s| (s[j] - mean[j]) * (s[j] - mean[j])).sum::<f64>() / (n - 1) as f64
+    }).collect();
+    return (mean, var);
+}
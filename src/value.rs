@@ -1,9 +1,155 @@
-use std::{
-    cell::RefCell, collections::HashSet, rc::Rc,
+use alloc::{
+    boxed::Box, collections::BTreeMap, format, rc::Rc,
+    string::{String, ToString}, vec, vec::Vec,
+};
+use core::{
+    any::Any, cell::RefCell,
     hash::{Hash, Hasher},
     fmt::{self, Display, Formatter},
 };
 
+use crate::autograd::{self, Node};
+use crate::error::RustMlError;
+use crate::matrix::Matrix;
+use crate::profiler::{self, Phase};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+// sqrt(2 / pi), used by the gelu approximation
+const GELU_C: f64 = 0.7978845608028654;
+
+// sigmoid that doesn't overflow exp for large |x|
+pub(crate) fn stable_sigmoid(x: f64) -> f64 {
+    if x >= 0.0 {
+        return 1.0 / (1.0 + (-x).exp());
+    }
+    let e = x.exp();
+    return e / (1.0 + e);
+}
+
+// plain float versions of the activations, shared by the ops below and the inference path in nn
+pub(crate) fn softplus_f64(x: f64) -> f64 {
+    return x.max(0.0) + (-x.abs()).exp().ln_1p();
+}
+
+pub(crate) fn gelu_f64(x: f64) -> f64 {
+    let t = (GELU_C * (x + 0.044715 * x * x * x)).tanh();
+    return 0.5 * x * (1.0 + t);
+}
+
+pub(crate) fn silu_f64(x: f64) -> f64 {
+    return x * stable_sigmoid(x);
+}
+
+pub(crate) fn elu_f64(x: f64, alpha: f64) -> f64 {
+    return if x > 0.0 { x } else { alpha * x.exp_m1() };
+}
+
+pub(crate) fn leaky_relu_f64(x: f64, alpha: f64) -> f64 {
+    return if x > 0.0 { x } else { alpha * x };
+}
+
+// dot product and both norms, shared by the cosine similarity forward and backward
+fn cosine_parts(x: &[f64], y: &[f64]) -> (f64, f64, f64) {
+    let dot = x.iter().zip(y.iter()).map(|(a, b)| a * b).sum();
+    let nx = x.iter().map(|a| a * a).sum::<f64>().sqrt();
+    let ny = y.iter().map(|b| b * b).sum::<f64>().sqrt();
+    return (dot, nx, ny);
+}
+
+// the operation a node was made by, with the parameters its backward rule needs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Leaf,
+    Add,
+    Mul,
+    Pow(f64),
+    PowV,
+    Exp,
+    Ln,
+    Log(f64),
+    Sin,
+    Cos,
+    Tan,
+    Square,
+    Cube,
+    Reciprocal,
+    Rsqrt,
+    Sqrt,
+    Abs,
+    Tanh,
+    Softplus,
+    Gelu,
+    Silu,
+    Elu(f64),
+    Relu,
+    LeakyRelu(f64),
+    Sigmoid,
+    // which branch was picked, true for the first child
+    Where(bool),
+    Dot,
+    Sum,
+    Mean,
+    LogSumExp,
+    FmaSum,
+    NormL2,
+    Cosine,
+    MatrixElement,
+}
+
+impl Op {
+    // name without parameters, what the profiler groups by
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Op::Leaf => "",
+            Op::Add => "+",
+            Op::Mul => "*",
+            Op::Pow(_) => "pow",
+            Op::PowV => "powv",
+            Op::Exp => "exp",
+            Op::Ln => "ln",
+            Op::Log(_) => "log",
+            Op::Sin => "sin",
+            Op::Cos => "cos",
+            Op::Tan => "tan",
+            Op::Square => "square",
+            Op::Cube => "cube",
+            Op::Reciprocal => "reciprocal",
+            Op::Rsqrt => "rsqrt",
+            Op::Sqrt => "sqrt",
+            Op::Abs => "abs",
+            Op::Tanh => "tanh",
+            Op::Softplus => "softplus",
+            Op::Gelu => "gelu",
+            Op::Silu => "silu",
+            Op::Elu(_) => "elu",
+            Op::Relu => "relu",
+            Op::LeakyRelu(_) => "leaky_relu",
+            Op::Sigmoid => "sigmoid",
+            Op::Where(_) => "where",
+            Op::Dot => "dot",
+            Op::Sum => "sum",
+            Op::Mean => "mean",
+            Op::LogSumExp => "logsumexp",
+            Op::FmaSum => "fma_sum",
+            Op::NormL2 => "norm_l2",
+            Op::Cosine => "cosine",
+            Op::MatrixElement => "matrix element",
+        };
+    }
+}
+
+// the label graphs and traces show, e.g. "+", "tanh", "pow(2)" or "log(10)"; empty for leaves
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Op::Pow(p) => write!(f, "pow({})", p),
+            Op::Log(base) => write!(f, "log({})", base),
+            op => write!(f, "{}", op.name()),
+        }
+    }
+}
+
 // Value struct for automatic differentiation
 // using Rc and RefCell for sharing multiple pointers and mutable references
 #[derive(Debug, Clone)]
@@ -14,11 +160,21 @@ pub struct Value(pub Rc<RefCell<RawValue>>);
 pub struct RawValue {
     pub data: f64,
     pub grad: f64,
-    pub op: String,
+    pub op: Op,
     pub label: String,
     pub children: Vec<Value>,
 
-    pub extra: f64,
+    // set whenever a gradient is accumulated into this node, lets sparse optimizers
+    // skip parameters that weren't touched by the last backward pass
+    pub dirty: bool,
+    // a sparse optimizer's log and this parameter's index in it, the index is pushed when the
+    // node turns dirty so the optimizer doesn't have to scan all of its parameters
+    pub dirty_log: Option<(Rc<RefCell<Vec<usize>>>, usize)>,
+    // leaves: whether a gradient is wanted at all; op nodes: whether any child wants one
+    // computed once at construction so backward can skip whole constant subgraphs
+    pub requires_grad: bool,
+    // set on nodes made by Value::from_matrix: the matrix and the (row-major) element they read
+    pub matrix: Option<(Matrix, usize)>,
 }
 
 // implement hash, eq, and display for Value
@@ -49,23 +205,67 @@ impl Value {
         return Value(Rc::new(RefCell::new(RawValue {
             data,
             grad: 0.0,
-            op: "".to_string(),
+            op: Op::Leaf,
             label: "".to_string(),
             children: vec![],
-            extra: 0.0
+            dirty: false,
+            dirty_log: None,
+            requires_grad: true,
+            matrix: None
         })));
     }
 
     // constructor for Value when made from an operator
-    pub fn new_for_op(data: f64, op: &str, children: Vec<Value>, extra: f64) -> Value {
-        return Value(Rc::new(RefCell::new(RawValue {
+    pub fn new_for_op(data: f64, op: Op, children: Vec<Value>) -> Value {
+        let t = profiler::start();
+        let requires_grad = children.iter().any(|c| c.requires_grad());
+        let v = Value(Rc::new(RefCell::new(RawValue {
             data,
             grad: 0.0,
-            op: op.to_string(),
+            op,
             label: "".to_string(),
-            children: children,
-            extra
+            children,
+            dirty: false,
+            dirty_log: None,
+            requires_grad,
+            matrix: None
         })));
+        profiler::record(op.name(), Phase::Forward, t);
+        return v;
+    }
+
+    // constructor for a Value that never needs a gradient (constants, inputs, targets)
+    pub fn constant(data: f64) -> Value {
+        let v = Value::new(data);
+        v.0.borrow_mut().requires_grad = false;
+        return v;
+    }
+
+    // element (i, j) of a matrix as a scalar node, its gradient is routed back into the matrix
+    // and on through the matrix graph when backward() reaches it
+    pub fn from_matrix(m: &Matrix, i: usize, j: usize) -> Value {
+        let k = i * m.cols() + j;
+        let v = Value::new_for_op(m.get(i, j), Op::MatrixElement, vec![]);
+        {
+            let mut raw = v.0.borrow_mut();
+            raw.requires_grad = true;
+            raw.matrix = Some((m.clone_rc(), k));
+        }
+        return v;
+    }
+
+    // a constant copy of the current data, cut off from the graph
+    pub fn detach(&self) -> Value {
+        return Value::constant(self.get_data());
+    }
+
+    pub fn requires_grad(&self) -> bool {
+        return self.0.borrow().requires_grad;
+    }
+
+    // only meaningful on leaves, op nodes take it from their children when they're built
+    pub fn set_requires_grad(&self, requires_grad: bool) {
+        self.0.borrow_mut().requires_grad = requires_grad;
     }
 
     // getters and setters, and update
@@ -73,6 +273,10 @@ impl Value {
         return self.0.borrow().data;
     }
 
+    pub fn set_data(&self, data: f64) {
+        self.0.borrow_mut().data = data;
+    }
+
     pub fn update_data(&self, data: f64) {
         self.0.borrow_mut().data += data;
     }
@@ -86,7 +290,27 @@ impl Value {
     }
 
     pub fn update_grad(&self, grad: f64) {
-        self.0.borrow_mut().grad += grad;
+        let mut v = self.0.borrow_mut();
+        v.grad += grad;
+        if !v.dirty {
+            if let Some((log, index)) = &v.dirty_log {
+                log.borrow_mut().push(*index);
+            }
+        }
+        v.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        return self.0.borrow().dirty;
+    }
+
+    pub fn clear_dirty(&self) {
+        self.0.borrow_mut().dirty = false;
+    }
+
+    // have update_grad push index into log whenever this node turns dirty, None to stop
+    pub fn set_dirty_log(&self, log: Option<(Rc<RefCell<Vec<usize>>>, usize)>) {
+        self.0.borrow_mut().dirty_log = log;
     }
 
     pub fn get_children(&self) -> Vec<Value> {
@@ -101,113 +325,702 @@ impl Value {
     pub fn add(v1: &Value, v2: &Value) -> Value {
         return Value::new_for_op(
             v1.get_data() + v2.get_data(),
-            "+",
-            vec![v1.clone_rc(), v2.clone_rc()],
-            0.0
+            Op::Add,
+            vec![v1.clone_rc(), v2.clone_rc()]
         );
     }
 
     // a - b = a + (-b)
     pub fn sub(v1: &Value, v2: &Value) -> Value {
-        return Self::add(&v1, &Self::neg(&v2));
+        return Self::add(v1, &Self::neg(v2));
     }
 
     pub fn mul(v1: &Value, v2: &Value) -> Value {
         return Value::new_for_op(
             v1.get_data() * v2.get_data(),
-            "*",
-            vec![v1.clone_rc(), v2.clone_rc()],
-            0.0
+            Op::Mul,
+            vec![v1.clone_rc(), v2.clone_rc()]
         );
     }
 
-    // a / b = a * (1 / b) = a * b^-1
+    // a / b = a * (1 / b)
     pub fn div(v1: &Value, v2: &Value) -> Value {
-        return Self::mul(&v1, &Self::pow(&v2, -1.0));
+        return Self::mul(v1, &Self::reciprocal(v2));
+    }
+
+    // a / b with the denominator clamped away from zero: if |b| < eps, b is replaced by
+    // eps (keeping its sign) and treated as a constant, so no inf/nan leaks into the graph
+    pub fn safe_div(v1: &Value, v2: &Value, eps: f64) -> Value {
+        let d = v2.get_data();
+        if d.abs() >= eps {
+            return Self::div(v1, v2);
+        }
+        let clamped = if d < 0.0 { -eps } else { eps };
+        return Self::mul(v1, &Value::constant(1.0 / clamped));
+    }
+
+    // a / b that returns an error instead of silently producing inf/nan
+    pub fn checked_div(v1: &Value, v2: &Value) -> Result<Value, RustMlError> {
+        if v2.get_data() == 0.0 {
+            return Err(RustMlError::Numerical(format!("division by zero: {} / 0", v1.get_data())));
+        }
+        let res = Self::div(v1, v2);
+        if !res.get_data().is_finite() {
+            return Err(RustMlError::Numerical(format!("division produced a non-finite value: {} / {}", v1.get_data(), v2.get_data())));
+        }
+        return Ok(res);
+    }
+
+    // a + eps, used to keep normalization denominators and logs away from zero
+    pub fn add_eps(v1: &Value, eps: f64) -> Value {
+        return Self::add(v1, &Value::constant(eps));
     }
 
     pub fn neg(v1: &Value) -> Value {
-        return Value::mul(&v1, &Value::new(-1.0));
+        return Value::mul(v1, &Value::constant(-1.0));
     }
 
     pub fn pow(v1: &Value, p: f64) -> Value {
         return Value::new_for_op(
             v1.get_data().powf(p),
-            format!("pow({})", p).as_str(),
-            vec![v1.clone_rc()],
-            p
+            Op::Pow(p),
+            vec![v1.clone_rc()]
+        );
+    }
+
+    // base^exponent with the exponent a node too, e.g. a learnable one
+    // the exponent's gradient needs ln(base), so it only gets one where the base is positive
+    pub fn powv(base: &Value, exponent: &Value) -> Value {
+        return Value::new_for_op(
+            base.get_data().powf(exponent.get_data()),
+            Op::PowV,
+            vec![base.clone_rc(), exponent.clone_rc()]
         );
     }
 
     pub fn exp(val: &Value) -> Value {
         return Value::new_for_op(
             val.get_data().exp(),
-            "exp",
-            vec![val.clone_rc()],
-            0.0
+            Op::Exp,
+            vec![val.clone_rc()]
         );
     }
 
-    // tanh(x) = (exp(2x) - 1) / (exp(2x) + 1)
+    // natural log, only defined for positive inputs
+    pub fn ln(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().ln(), Op::Ln, vec![val.clone_rc()]);
+    }
+
+    // log in the given base (positive, not 1), ln(x) / ln(base) as one node
+    pub fn log(val: &Value, base: f64) -> Value {
+        assert!(base > 0.0 && base != 1.0, "log base must be positive and not 1, got {}", base);
+        return Value::new_for_op(val.get_data().log(base), Op::Log(base), vec![val.clone_rc()]);
+    }
+
+    pub fn sin(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().sin(), Op::Sin, vec![val.clone_rc()]);
+    }
+
+    pub fn cos(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().cos(), Op::Cos, vec![val.clone_rc()]);
+    }
+
+    // blows up near odd multiples of pi / 2, like the function
+    pub fn tan(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().tan(), Op::Tan, vec![val.clone_rc()]);
+    }
+
+    // cheaper special cases of pow with their own backward rules
+    pub fn square(val: &Value) -> Value {
+        let x = val.get_data();
+        return Value::new_for_op(x * x, Op::Square, vec![val.clone_rc()]);
+    }
+
+    pub fn cube(val: &Value) -> Value {
+        let x = val.get_data();
+        return Value::new_for_op(x * x * x, Op::Cube, vec![val.clone_rc()]);
+    }
+
+    pub fn reciprocal(val: &Value) -> Value {
+        return Value::new_for_op(1.0 / val.get_data(), Op::Reciprocal, vec![val.clone_rc()]);
+    }
+
+    // 1 / sqrt(x)
+    pub fn rsqrt(val: &Value) -> Value {
+        return Value::new_for_op(1.0 / val.get_data().sqrt(), Op::Rsqrt, vec![val.clone_rc()]);
+    }
+
+    // the gradient is infinite at 0, keep the input away from it (e.g. sqrt(x + eps) for an rms)
+    pub fn sqrt(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().sqrt(), Op::Sqrt, vec![val.clone_rc()]);
+    }
+
+    // |x|, the gradient at 0 is taken as 0 like relu's, so l1 terms leave exact zeros alone
+    pub fn abs(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().abs(), Op::Abs, vec![val.clone_rc()]);
+    }
+
+    // tanh as its own node: building it from (exp(2x) - 1) / (exp(2x) + 1)
+    // underflows in the backward pass for large inputs and gives wrong gradients
     pub fn tanh(val: &Value) -> Value {
-        let e = Value::exp(&Value::mul(&val, &Value::new(2.0)));
-        return Value::div(&Value::sub(&e, &Value::new(1.0)), &Value::add(&e, &Value::new(1.0)));
+        return Value::new_for_op(
+            val.get_data().tanh(),
+            Op::Tanh,
+            vec![val.clone_rc()]
+        );
+    }
+
+    // softplus(x) = ln(1 + exp(x)), written as max(x, 0) + ln(1 + exp(-|x|)) so exp can't overflow
+    pub fn softplus(val: &Value) -> Value {
+        return Value::new_for_op(softplus_f64(val.get_data()), Op::Softplus, vec![val.clone_rc()]);
+    }
+
+    // gelu, tanh approximation: 0.5x(1 + tanh(sqrt(2/pi)(x + 0.044715x^3)))
+    pub fn gelu(val: &Value) -> Value {
+        return Value::new_for_op(gelu_f64(val.get_data()), Op::Gelu, vec![val.clone_rc()]);
+    }
+
+    // silu / swish: x * sigmoid(x)
+    pub fn silu(val: &Value) -> Value {
+        return Value::new_for_op(silu_f64(val.get_data()), Op::Silu, vec![val.clone_rc()]);
+    }
+
+    // elu: x for x > 0, alpha(exp(x) - 1) otherwise
+    pub fn elu(val: &Value, alpha: f64) -> Value {
+        return Value::new_for_op(elu_f64(val.get_data(), alpha), Op::Elu(alpha), vec![val.clone_rc()]);
+    }
+
+    // max(x, 0), the gradient at 0 is taken as 0
+    pub fn relu(val: &Value) -> Value {
+        return Value::new_for_op(val.get_data().max(0.0), Op::Relu, vec![val.clone_rc()]);
+    }
+
+    // x for x > 0, alpha x otherwise
+    pub fn leaky_relu(val: &Value, alpha: f64) -> Value {
+        return Value::new_for_op(leaky_relu_f64(val.get_data(), alpha), Op::LeakyRelu(alpha), vec![val.clone_rc()]);
+    }
+
+    // 1 / (1 + exp(-x)) as one node, stable for large |x| where exp/div would overflow
+    pub fn sigmoid(val: &Value) -> Value {
+        return Value::new_for_op(stable_sigmoid(val.get_data()), Op::Sigmoid, vec![val.clone_rc()]);
+    }
+
+    // select: a if cond else b, the gradient only flows to the branch that was picked
+    // lets piecewise functions (e.g. huber loss) stay in the graph
+    pub fn where_(cond: bool, a: &Value, b: &Value) -> Value {
+        let data = if cond { a.get_data() } else { b.get_data() };
+        return Value::new_for_op(
+            data,
+            Op::Where(cond),
+            vec![a.clone_rc(), b.clone_rc()]
+        );
+    }
+
+    // the larger of a and b, with the gradient going to that one (to a on a tie)
+    // e.g. a hinge loss max(0, 1 - y f(x)), or a max-pool as a fold over a window
+    pub fn max(a: &Value, b: &Value) -> Value {
+        return Value::where_(a.get_data() >= b.get_data(), a, b);
+    }
+
+    // the smaller of a and b, same routing as max
+    pub fn min(a: &Value, b: &Value) -> Value {
+        return Value::where_(a.get_data() <= b.get_data(), a, b);
+    }
+
+    // elementwise select over slices using a boolean mask
+    pub fn where_mask(mask: &[bool], a: &[Value], b: &[Value]) -> Vec<Value> {
+        assert!(mask.len() == a.len() && a.len() == b.len(), "where_mask needs mask and values of the same length");
+        return mask.iter().zip(a.iter().zip(b.iter())).map(|(c, (x, y))| Self::where_(*c, x, y)).collect();
+    }
+
+    // fused vector ops: one node for the whole vector instead of O(n) add/mul nodes
+    // the children are xs followed by ys, so child i and child n + i are paired
+
+    // dot product: sum of x_i * y_i
+    pub fn dot(xs: &[Value], ys: &[Value]) -> Value {
+        assert_eq!(xs.len(), ys.len(), "dot needs vectors of the same length");
+        let data = xs.iter().zip(ys.iter()).map(|(x, y)| x.get_data() * y.get_data()).sum();
+        let children = xs.iter().chain(ys.iter()).map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::Dot, children);
+    }
+
+    // sum of all xs, 0 for none
+    pub fn sum(xs: &[Value]) -> Value {
+        let data = xs.iter().map(|x| x.get_data()).sum();
+        let children = xs.iter().map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::Sum, children);
+    }
+
+    // average of xs, needs at least one
+    pub fn mean(xs: &[Value]) -> Value {
+        assert!(!xs.is_empty(), "mean of no values");
+        let data = xs.iter().map(|x| x.get_data()).sum::<f64>() / xs.len() as f64;
+        let children = xs.iter().map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::Mean, children);
+    }
+
+    // ln(sum of exp(x_i)) in one node, computed as max + ln(sum of exp(x_i - max)) so nothing
+    // overflows however large the logits get; needs at least one
+    pub fn logsumexp(xs: &[Value]) -> Value {
+        assert!(!xs.is_empty(), "logsumexp of no values");
+        let max = xs.iter().map(|x| x.get_data()).fold(f64::NEG_INFINITY, f64::max);
+        let data = max + xs.iter().map(|x| (x.get_data() - max).exp()).sum::<f64>().ln();
+        let children = xs.iter().map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::LogSumExp, children);
+    }
+
+    // b + sum of w_i * x_i in one node, the pre-activation of a dense neuron
+    // children are ws, then xs, then b; accumulated from b in order, the same as Neuron::predict
+    pub fn fma_sum(ws: &[Value], xs: &[Value], b: &Value) -> Value {
+        assert_eq!(ws.len(), xs.len(), "fma_sum needs as many weights as inputs");
+        let data = ws.iter().zip(xs.iter()).fold(b.get_data(), |acc, (w, x)| acc + w.get_data() * x.get_data());
+        let children = ws.iter().chain(xs.iter()).chain(core::iter::once(b)).map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::FmaSum, children);
+    }
+
+    // euclidean norm: sqrt(sum of x_i^2)
+    pub fn norm_l2(xs: &[Value]) -> Value {
+        let data = xs.iter().map(|x| x.get_data() * x.get_data()).sum::<f64>().sqrt();
+        let children = xs.iter().map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::NormL2, children);
+    }
+
+    // cosine similarity: dot(x, y) / (|x| |y|)
+    pub fn cosine_similarity(xs: &[Value], ys: &[Value]) -> Value {
+        assert_eq!(xs.len(), ys.len(), "cosine_similarity needs vectors of the same length");
+        let x: Vec<f64> = xs.iter().map(|v| v.get_data()).collect();
+        let y: Vec<f64> = ys.iter().map(|v| v.get_data()).collect();
+        let (dot, nx, ny) = cosine_parts(&x, &y);
+        let data = if nx == 0.0 || ny == 0.0 { 0.0 } else { dot / (nx * ny) };
+        let children = xs.iter().chain(ys.iter()).map(|v| v.clone_rc()).collect();
+        return Value::new_for_op(data, Op::Cosine, children);
+    }
+
+    // running sum: out[i] = xs[0] + ... + xs[i], built from chained adds so backward comes for free
+    pub fn cumsum(xs: &[Value]) -> Vec<Value> {
+        let mut out: Vec<Value> = vec![];
+        for x in xs {
+            let next = match out.last() {
+                Some(prev) => Self::add(prev, x),
+                None => x.clone_rc(),
+            };
+            out.push(next);
+        }
+        return out;
+    }
+
+    // running product: out[i] = xs[0] * ... * xs[i]
+    pub fn cumprod(xs: &[Value]) -> Vec<Value> {
+        let mut out: Vec<Value> = vec![];
+        for x in xs {
+            let next = match out.last() {
+                Some(prev) => Self::mul(prev, x),
+                None => x.clone_rc(),
+            };
+            out.push(next);
+        }
+        return out;
+    }
+
+    // non-differentiable helpers: these only read the data and return plain indices/masks,
+    // so no gradient flows through them
+
+    // index of the largest value (first one on ties)
+    pub fn argmax(xs: &[Value]) -> usize {
+        let mut best = 0;
+        for i in 1..xs.len() {
+            if xs[i].get_data() > xs[best].get_data() {
+                best = i;
+            }
+        }
+        return best;
+    }
+
+    // indices of the k largest values, largest first
+    pub fn topk(xs: &[Value], k: usize) -> Vec<usize> {
+        let mut idx: Vec<usize> = (0..xs.len()).collect();
+        idx.sort_by(|&a, &b| xs[b].get_data().total_cmp(&xs[a].get_data()));
+        idx.truncate(k);
+        return idx;
+    }
+
+    // mask of which values are strictly greater than the threshold
+    pub fn greater_than(xs: &[Value], threshold: f64) -> Vec<bool> {
+        return xs.iter().map(|x| x.get_data() > threshold).collect();
     }
 
     // backward pass for the current node
     pub fn _backward(&self) {
         let val = self.0.borrow();
-        let operation = val.op.as_str();
-        match operation {
-            "+" => {
+        match val.op {
+            Op::Add => {
                 val.children[0].update_grad(val.grad);
                 val.children[1].update_grad(val.grad);
             },
-            "*" => {
+            Op::Mul => {
                 val.children[0].update_grad(val.grad * val.children[1].get_data());
                 val.children[1].update_grad(val.grad * val.children[0].get_data());
             },
-            "exp" => {
+            Op::Exp => {
                 val.children[0].update_grad(val.grad * val.data);
             },
-            // match with anything starts with pow(
-            s if s.starts_with("pow(") => {
-                let p: f64 = val.extra;
+            Op::Ln => {
+                val.children[0].update_grad(val.grad / val.children[0].get_data());
+            },
+            Op::Log(base) => {
+                val.children[0].update_grad(val.grad / (val.children[0].get_data() * base.ln()));
+            },
+            Op::Sin => {
+                val.children[0].update_grad(val.grad * val.children[0].get_data().cos());
+            },
+            Op::Cos => {
+                val.children[0].update_grad(val.grad * -val.children[0].get_data().sin());
+            },
+            // d tan = 1 + tan^2
+            Op::Tan => {
+                val.children[0].update_grad(val.grad * (1.0 + val.data * val.data));
+            },
+            Op::Square => {
+                val.children[0].update_grad(val.grad * 2.0 * val.children[0].get_data());
+            },
+            Op::Cube => {
+                let x = val.children[0].get_data();
+                val.children[0].update_grad(val.grad * 3.0 * x * x);
+            },
+            // d(1/x) = -1/x^2 = -y^2
+            Op::Reciprocal => {
+                val.children[0].update_grad(val.grad * -val.data * val.data);
+            },
+            // d(x^-1/2) = -1/2 x^-3/2 = -1/2 y^3
+            Op::Rsqrt => {
+                val.children[0].update_grad(val.grad * -0.5 * val.data * val.data * val.data);
+            },
+            // d sqrt = 1 / (2 y)
+            Op::Sqrt => {
+                val.children[0].update_grad(val.grad * 0.5 / val.data);
+            },
+            Op::Abs => {
+                let x = val.children[0].get_data();
+                if x != 0.0 {
+                    val.children[0].update_grad(val.grad * x.signum());
+                }
+            },
+            Op::Tanh => {
+                val.children[0].update_grad(val.grad * (1.0 - val.data * val.data));
+            },
+            // d softplus = sigmoid(x)
+            Op::Softplus => {
+                let x = val.children[0].get_data();
+                val.children[0].update_grad(val.grad * stable_sigmoid(x));
+            },
+            Op::Gelu => {
+                let x = val.children[0].get_data();
+                let t = (GELU_C * (x + 0.044715 * x * x * x)).tanh();
+                let dt = (1.0 - t * t) * GELU_C * (1.0 + 3.0 * 0.044715 * x * x);
+                val.children[0].update_grad(val.grad * (0.5 * (1.0 + t) + 0.5 * x * dt));
+            },
+            // d silu = s + x s (1 - s)
+            Op::Silu => {
+                let x = val.children[0].get_data();
+                let s = stable_sigmoid(x);
+                val.children[0].update_grad(val.grad * (s + x * s * (1.0 - s)));
+            },
+            // d elu = 1 for x > 0, alpha exp(x) = y + alpha otherwise
+            Op::Elu(alpha) => {
+                let x = val.children[0].get_data();
+                let d = if x > 0.0 { 1.0 } else { val.data + alpha };
+                val.children[0].update_grad(val.grad * d);
+            },
+            Op::Relu => {
+                if val.children[0].get_data() > 0.0 {
+                    val.children[0].update_grad(val.grad);
+                }
+            },
+            Op::LeakyRelu(alpha) => {
+                let d = if val.children[0].get_data() > 0.0 { 1.0 } else { alpha };
+                val.children[0].update_grad(val.grad * d);
+            },
+            // d sigmoid = y (1 - y)
+            Op::Sigmoid => {
+                val.children[0].update_grad(val.grad * val.data * (1.0 - val.data));
+            },
+            Op::Dot => {
+                let n = val.children.len() / 2;
+                for i in 0..n {
+                    val.children[i].update_grad(val.grad * val.children[n + i].get_data());
+                    val.children[n + i].update_grad(val.grad * val.children[i].get_data());
+                }
+            },
+            Op::FmaSum => {
+                let n = val.children.len() / 2;
+                for i in 0..n {
+                    val.children[i].update_grad(val.grad * val.children[n + i].get_data());
+                    val.children[n + i].update_grad(val.grad * val.children[i].get_data());
+                }
+                val.children[2 * n].update_grad(val.grad);
+            },
+            Op::Sum => {
+                for c in val.children.iter() {
+                    c.update_grad(val.grad);
+                }
+            },
+            Op::Mean => {
+                let g = val.grad / val.children.len() as f64;
+                for c in val.children.iter() {
+                    c.update_grad(g);
+                }
+            },
+            // the gradient is softmax(x), exp(x_i - y) with y the logsumexp itself
+            Op::LogSumExp => {
+                for c in val.children.iter() {
+                    c.update_grad(val.grad * (c.get_data() - val.data).exp());
+                }
+            },
+            // d|x|/dx_i = x_i / |x|, taken as 0 at the origin
+            Op::NormL2 if val.data != 0.0 => {
+                for c in val.children.iter() {
+                    c.update_grad(val.grad * c.get_data() / val.data);
+                }
+            },
+            // dc/dx_i = y_i / (|x||y|) - c x_i / |x|^2, and the same with x and y swapped
+            Op::Cosine => {
+                let n = val.children.len() / 2;
+                let x: Vec<f64> = val.children[..n].iter().map(|v| v.get_data()).collect();
+                let y: Vec<f64> = val.children[n..].iter().map(|v| v.get_data()).collect();
+                let (_, nx, ny) = cosine_parts(&x, &y);
+                if nx != 0.0 && ny != 0.0 {
+                    let c = val.data;
+                    for i in 0..n {
+                        val.children[i].update_grad(val.grad * (y[i] / (nx * ny) - c * x[i] / (nx * nx)));
+                        val.children[n + i].update_grad(val.grad * (x[i] / (nx * ny) - c * y[i] / (ny * ny)));
+                    }
+                }
+            },
+            Op::Where(cond) => {
+                let picked = if cond { 0 } else { 1 };
+                val.children[picked].update_grad(val.grad);
+            },
+            Op::Pow(p) => {
                 val.children[0].update_grad(val.grad * p * val.children[0].get_data().powf(p - 1.0));
             },
+            // d/da a^b = b a^(b - 1), d/db a^b = a^b ln a
+            Op::PowV => {
+                let (a, b) = (val.children[0].get_data(), val.children[1].get_data());
+                val.children[0].update_grad(val.grad * b * a.powf(b - 1.0));
+                if a > 0.0 {
+                    val.children[1].update_grad(val.grad * val.data * a.ln());
+                }
+            },
 
-            _ => {},
+            Op::MatrixElement => {
+                if let Some((m, k)) = &val.matrix {
+                    m.update_grad_at(*k, val.grad);
+                }
+            },
+
+            Op::Leaf | Op::NormL2 => {},
         }
     }
 
-    // backward pass for the entire graph
-    pub fn backward(&self) {
-        // find the topo sort
-        let mut topo_sort: Vec<Value> = vec![];
-        let mut visited: HashSet<Value> = HashSet::new();
-
-        // iterative dfs
-        let mut stack: Vec<Value> = vec![self.clone_rc()];
-        while !stack.is_empty() {
-            let node = stack[stack.len() - 1].clone_rc();
-            if !visited.contains(&node) {
-                visited.insert(node.clone());
-                for child in node.get_children() {
-                    if !visited.contains(&child) {
-                        stack.push(child);
-                    }
-                }
-            } else {
-                topo_sort.push(node);
-                stack.pop();
+    // structural equality of two graphs: same ops (with their parameters), same data (within tol)
+    // and the same wiring, including which nodes are shared, but not necessarily the same nodes
+    // labels, gradients and requires_grad are ignored
+    pub fn graph_equal(a: &Value, b: &Value, tol: f64) -> bool {
+        // matched pairs of node ids in both directions, so a shared node on one side must be shared on the other
+        let mut a_to_b: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut b_to_a: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut stack: Vec<(Value, Value)> = vec![(a.clone_rc(), b.clone_rc())];
+        while let Some((x, y)) = stack.pop() {
+            let (ix, iy) = (x.id(), y.id());
+            match (a_to_b.get(&ix), b_to_a.get(&iy)) {
+                (Some(my), Some(mx)) if *my == iy && *mx == ix => continue,
+                (None, None) => {},
+                _ => return false,
             }
+            let (rx, ry) = (x.0.borrow(), y.0.borrow());
+            if rx.op != ry.op || rx.children.len() != ry.children.len() {
+                return false;
+            }
+            if !((rx.data - ry.data).abs() <= tol || (rx.data.is_nan() && ry.data.is_nan())) {
+                return false;
+            }
+            for (cx, cy) in rx.children.iter().zip(ry.children.iter()) {
+                stack.push((cx.clone_rc(), cy.clone_rc()));
+            }
+            a_to_b.insert(ix, iy);
+            b_to_a.insert(iy, ix);
         }
+        return true;
+    }
+
+    // backward pass for the entire graph
+    pub fn backward(&self) {
+        self.set_grad(1.0);
+        Value::propagate(&[self.clone_rc()]);
+    }
+
+    // backward from gradients already sitting on the roots, without seeding them
+    // used when gradients arrive from the matrix engine (Matrix::from_values)
+    pub fn propagate(roots: &[Value]) {
+        autograd::propagate(roots.iter().map(|r| Box::new(r.clone_rc()) as Box<dyn Node>).collect());
+    }
 
+    // same as backward, but records every gradient handed from a node to a child, in the order
+    // the chain rule applies them, for showing how backpropagation works step by step
+    // matrix nodes reached through Value::from_matrix run their backward but aren't recorded
+    pub fn backward_traced(&self) -> Vec<BackwardStep> {
+        let topo_sort = autograd::topo_sort(vec![Box::new(self.clone_rc())]);
         self.set_grad(1.0);
-        // backward pass
+        let mut steps = vec![];
         for node in topo_sort.iter().rev() {
-            // println!("{} {} {}", node.0.borrow().label, node.get_data(), node.get_grad());
+            let Some(node) = node.as_any().downcast_ref::<Value>() else {
+                node.backward_local();
+                continue;
+            };
+            let children = node.get_children();
+            let before: Vec<f64> = children.iter().map(|c| c.get_grad()).collect();
             node._backward();
+            let grad = node.get_grad();
+            for (arg, child) in children.iter().enumerate() {
+                // an operand used twice (a + a) gets one step with both contributions
+                if !child.requires_grad() || children[..arg].contains(child) {
+                    continue;
+                }
+                let contribution = child.get_grad() - before[arg];
+                steps.push(BackwardStep {
+                    node: node.clone_rc(),
+                    child: child.clone_rc(),
+                    arg,
+                    contribution,
+                    local_grad: if grad != 0.0 { contribution / grad } else { 0.0 },
+                });
+            }
+        }
+        return steps;
+    }
+}
+
+impl Node for Value {
+    fn id(&self) -> usize {
+        return Rc::as_ptr(&self.0) as usize;
+    }
+
+    fn inputs(&self) -> Vec<Box<dyn Node>> {
+        let v = self.0.borrow();
+        let mut inputs: Vec<Box<dyn Node>> = v.children.iter().map(|c| Box::new(c.clone_rc()) as Box<dyn Node>).collect();
+        if let Some((m, _)) = &v.matrix {
+            inputs.push(Box::new(m.clone_rc()));
+        }
+        return inputs;
+    }
+
+    fn requires_grad(&self) -> bool {
+        return self.0.borrow().requires_grad;
+    }
+
+    fn backward_local(&self) {
+        self._backward();
+    }
+
+    fn op_name(&self) -> String {
+        return self.0.borrow().op.name().to_string();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+}
+
+// operators, so expressions read like math: &a * &(&b + &c) - 1.0
+// each one builds the same node as the named function (Value::add, Value::mul, ...), owned and
+// borrowed operands both work, and f64s on either side become constants
+macro_rules! value_op {
+    ($tr:ident, $method:ident, $f:path) => {
+        impl core::ops::$tr<&Value> for &Value {
+            type Output = Value;
+            fn $method(self, rhs: &Value) -> Value {
+                $f(self, rhs)
+            }
+        }
+
+        impl core::ops::$tr<Value> for &Value {
+            type Output = Value;
+            fn $method(self, rhs: Value) -> Value {
+                $f(self, &rhs)
+            }
+        }
+
+        impl core::ops::$tr<&Value> for Value {
+            type Output = Value;
+            fn $method(self, rhs: &Value) -> Value {
+                $f(&self, rhs)
+            }
+        }
+
+        impl core::ops::$tr<Value> for Value {
+            type Output = Value;
+            fn $method(self, rhs: Value) -> Value {
+                $f(&self, &rhs)
+            }
+        }
+
+        impl core::ops::$tr<f64> for &Value {
+            type Output = Value;
+            fn $method(self, rhs: f64) -> Value {
+                $f(self, &Value::constant(rhs))
+            }
+        }
+
+        impl core::ops::$tr<f64> for Value {
+            type Output = Value;
+            fn $method(self, rhs: f64) -> Value {
+                $f(&self, &Value::constant(rhs))
+            }
         }
+
+        impl core::ops::$tr<&Value> for f64 {
+            type Output = Value;
+            fn $method(self, rhs: &Value) -> Value {
+                $f(&Value::constant(self), rhs)
+            }
+        }
+
+        impl core::ops::$tr<Value> for f64 {
+            type Output = Value;
+            fn $method(self, rhs: Value) -> Value {
+                $f(&Value::constant(self), &rhs)
+            }
+        }
+    };
+}
+
+value_op!(Add, add, Value::add);
+value_op!(Sub, sub, Value::sub);
+value_op!(Mul, mul, Value::mul);
+value_op!(Div, div, Value::div);
+
+impl core::ops::Neg for &Value {
+    type Output = Value;
+    fn neg(self) -> Value {
+        Value::neg(self)
+    }
+}
+
+impl core::ops::Neg for Value {
+    type Output = Value;
+    fn neg(self) -> Value {
+        Value::neg(&self)
     }
 }
+
+// one application of the chain rule: node passed `contribution` = node.grad * local_grad to child
+#[derive(Debug, Clone)]
+pub struct BackwardStep {
+    pub node: Value,
+    pub child: Value,
+    // position of the child among the node's children
+    pub arg: usize,
+    // d node / d child, 0 when the node's own gradient is 0 and it can't be recovered
+    pub local_grad: f64,
+    pub contribution: f64,
+}
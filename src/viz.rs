@@ -0,0 +1,61 @@
+use crate::datasets::Dataset;
+use crate::nn::Module;
+use crate::plot::{self, Series};
+
+// ready-made plots for trained models
+
+// predicted class over a regular grid covering a 2-d dataset
+pub struct DecisionBoundary {
+    pub x_range: (f64, f64),
+    pub y_range: (f64, f64),
+    // classes[i][j] is the class at x = x_range lerp j, y = y_range lerp i
+    pub classes: Vec<Vec<usize>>,
+}
+
+impl DecisionBoundary {
+    // the class regions with the dataset drawn on top
+    pub fn svg(&self, title: &str, data: &Dataset) -> String {
+        let values: Vec<Vec<f64>> = self.classes.iter().map(|row| row.iter().map(|c| *c as f64).collect()).collect();
+        let points: Vec<(f64, f64, usize)> = data.xs.iter().zip(data.labels.iter()).map(|(x, l)| (x[0], x[1], *l)).collect();
+        return plot::heatmap_with_points(title, &values, self.x_range, self.y_range, &points);
+    }
+}
+
+// a model with one output is read as a sign classifier (class 1 when the output is > 0),
+// with several outputs the class is the largest one
+fn class_of(out: &[f64]) -> usize {
+    if out.len() == 1 {
+        return (out[0] > 0.0) as usize;
+    }
+    return (0..out.len()).fold(0, |best, k| if out[k] > out[best] { k } else { best });
+}
+
+// evaluates the model on a resolution x resolution grid spanning the data plus a 10% border
+pub fn decision_boundary(model: &dyn Module, data: &Dataset, resolution: usize) -> DecisionBoundary {
+    assert!(resolution > 1, "resolution must be at least 2");
+    let range = |d: usize| {
+        let lo = data.xs.iter().map(|x| x[d]).fold(f64::INFINITY, f64::min);
+        let hi = data.xs.iter().map(|x| x[d]).fold(f64::NEG_INFINITY, f64::max);
+        let pad = 0.1 * (hi - lo).max(1e-9);
+        (lo - pad, hi + pad)
+    };
+    let (x_range, y_range) = (range(0), range(1));
+    let lerp = |r: (f64, f64), k: usize| r.0 + (r.1 - r.0) * (k as f64 + 0.5) / resolution as f64;
+
+    let grid: Vec<Vec<f64>> = (0..resolution)
+        .flat_map(|i| (0..resolution).map(move |j| (i, j)))
+        .map(|(i, j)| vec![lerp(x_range, j), lerp(y_range, i)])
+        .collect();
+    let out = model.predict_batch(&grid);
+    let classes = out.chunks(resolution).map(|row| row.iter().map(|o| class_of(o)).collect()).collect();
+    return DecisionBoundary {
+        x_range,
+        y_range,
+        classes,
+    };
+}
+
+// loss per epoch, e.g. from Trainer::history
+pub fn learning_curve(history: &[f64]) -> String {
+    return plot::line_chart("learning curve", &[Series::from_values("loss", history)]);
+}
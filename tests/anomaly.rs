@@ -0,0 +1,59 @@
+use rust_ml::anomaly::{IsolationForest, OutlierDetector, ZScoreFilter};
+use rust_ml::error::RustMlError;
+use rust_ml::random;
+
+// a gaussian cloud around the origin plus a few far away points at the end
+fn cloud_with_outliers() -> Vec<Vec<f64>> {
+    let mut xs: Vec<Vec<f64>> = (0..200).map(|_| vec![random::normal(0.0, 1.0), random::normal(0.0, 1.0)]).collect();
+    xs.extend([vec![8.0, 8.0], vec![-9.0, 1.0], vec![0.5, 10.0]]);
+    xs
+}
+
+#[test]
+fn isolation_forest_scores_outliers_highest() {
+    random::seed(40);
+    let xs = cloud_with_outliers();
+    let mut forest = IsolationForest::new().with_contamination(0.02);
+    forest.fit(&xs).unwrap();
+    let scores = forest.score_samples(&xs).unwrap();
+    let mut order: Vec<usize> = (0..xs.len()).collect();
+    order.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    let mut top: Vec<usize> = order[..3].to_vec();
+    top.sort();
+    assert_eq!(top, vec![200, 201, 202]);
+    assert!(scores[200] > 0.6 && scores[0] < 0.6, "{} {}", scores[200], scores[0]);
+
+    let flagged = forest.predict(&xs).unwrap();
+    assert!(flagged[200] && flagged[201] && flagged[202]);
+    assert!(flagged.iter().filter(|f| **f).count() <= 5);
+    assert_eq!(forest.predict(&[vec![0.0, 0.0]]).unwrap(), vec![false]);
+}
+
+#[test]
+fn z_score_filters_flag_far_rows() {
+    random::seed(41);
+    let xs = cloud_with_outliers();
+    for robust in [false, true] {
+        let mut filter = ZScoreFilter::new(3.5).with_robust(robust);
+        filter.fit(&xs).unwrap();
+        let flagged = filter.predict(&xs).unwrap();
+        assert!(flagged[200] && flagged[201] && flagged[202], "robust = {}", robust);
+        assert!(flagged[..200].iter().filter(|f| **f).count() <= 2, "robust = {}", robust);
+    }
+    // the plain mean and std are pulled by the outliers, the robust ones barely move
+    let mut plain = ZScoreFilter::new(3.0);
+    let mut robust = ZScoreFilter::new(3.0).with_robust(true);
+    plain.fit(&xs).unwrap();
+    robust.fit(&xs).unwrap();
+    assert!(robust.scale[0] < plain.scale[0]);
+}
+
+#[test]
+fn detectors_need_fitting_and_matching_columns() {
+    let forest = IsolationForest::new();
+    assert!(matches!(forest.score_samples(&[vec![1.0]]), Err(RustMlError::NotFitted)));
+    let mut filter = ZScoreFilter::new(3.0);
+    filter.fit(&[vec![1.0, 2.0], vec![2.0, 3.0]]).unwrap();
+    assert!(matches!(filter.predict(&[vec![1.0]]), Err(RustMlError::Shape(_))));
+    assert!(matches!(IsolationForest::new().with_contamination(0.7).fit(&[vec![1.0], vec![2.0]]), Err(RustMlError::Config(_))));
+}
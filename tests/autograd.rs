@@ -0,0 +1,588 @@
+// property-based checks of the backward rules: random expression trees are built over a few
+// input variables and the gradients from backward() are compared against finite differences
+use proptest::prelude::*;
+use rust_ml::error::RustMlError;
+use rust_ml::loss;
+use rust_ml::value::Value;
+
+const NVARS: usize = 3;
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Var(usize),
+    Const(f64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Pow(Box<Expr>, f64),
+    PowV(Box<Expr>, Box<Expr>),
+    Exp(Box<Expr>),
+    Ln(Box<Expr>),
+    Log(Box<Expr>, f64),
+    Sin(Box<Expr>),
+    Cos(Box<Expr>),
+    Tan(Box<Expr>),
+    Tanh(Box<Expr>),
+    Square(Box<Expr>),
+    Cube(Box<Expr>),
+    Reciprocal(Box<Expr>),
+    Rsqrt(Box<Expr>),
+    Sqrt(Box<Expr>),
+    Abs(Box<Expr>),
+    Softplus(Box<Expr>),
+    Gelu(Box<Expr>),
+    Silu(Box<Expr>),
+    Elu(Box<Expr>, f64),
+    Relu(Box<Expr>),
+    LeakyRelu(Box<Expr>, f64),
+    Sigmoid(Box<Expr>),
+    Sum(Vec<Expr>),
+    LogSumExp(Vec<Expr>),
+    Mean(Vec<Expr>),
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (0..NVARS).prop_map(Expr::Var),
+        (-2.0..2.0f64).prop_map(Expr::Const),
+    ];
+    leaf.prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Add(Box::new(a), Box::new(b))),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Sub(Box::new(a), Box::new(b))),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Mul(Box::new(a), Box::new(b))),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::Div(Box::new(a), Box::new(b))),
+            inner.clone().prop_map(|a| Expr::Neg(Box::new(a))),
+            (inner.clone(), prop_oneof![Just(2.0), Just(3.0)]).prop_map(|(a, p)| Expr::Pow(Box::new(a), p)),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| Expr::PowV(Box::new(a), Box::new(b))),
+            inner.clone().prop_map(|a| Expr::Exp(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Ln(Box::new(a))),
+            (inner.clone(), prop_oneof![Just(2.0), Just(10.0), Just(0.5)]).prop_map(|(a, b)| Expr::Log(Box::new(a), b)),
+            inner.clone().prop_map(|a| Expr::Tanh(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Sin(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Cos(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Tan(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Square(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Cube(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Reciprocal(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Rsqrt(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Sqrt(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Abs(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Softplus(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Gelu(Box::new(a))),
+            inner.clone().prop_map(|a| Expr::Silu(Box::new(a))),
+            (inner.clone(), 0.1..2.0f64).prop_map(|(a, alpha)| Expr::Elu(Box::new(a), alpha)),
+            inner.clone().prop_map(|a| Expr::Relu(Box::new(a))),
+            (inner.clone(), 0.01..0.5f64).prop_map(|(a, alpha)| Expr::LeakyRelu(Box::new(a), alpha)),
+            inner.clone().prop_map(|a| Expr::Sigmoid(Box::new(a))),
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Expr::Sum),
+            prop::collection::vec(inner.clone(), 1..4).prop_map(Expr::LogSumExp),
+            prop::collection::vec(inner, 1..4).prop_map(Expr::Mean),
+        ]
+    })
+}
+
+// builds the graph, or None if it wanders somewhere finite differences can't be trusted
+fn build(e: &Expr, vars: &[Value]) -> Option<Value> {
+    let v = match e {
+        Expr::Var(i) => vars[*i].clone_rc(),
+        Expr::Const(c) => Value::new(*c),
+        Expr::Add(a, b) => Value::add(&build(a, vars)?, &build(b, vars)?),
+        Expr::Sub(a, b) => Value::sub(&build(a, vars)?, &build(b, vars)?),
+        Expr::Mul(a, b) => Value::mul(&build(a, vars)?, &build(b, vars)?),
+        Expr::Div(a, b) => {
+            let den = build(b, vars)?;
+            if den.get_data().abs() < 0.1 {
+                return None;
+            }
+            Value::div(&build(a, vars)?, &den)
+        }
+        Expr::Neg(a) => Value::neg(&build(a, vars)?),
+        Expr::Pow(a, p) => Value::pow(&build(a, vars)?, *p),
+        Expr::PowV(a, b) => {
+            let base = build(a, vars)?;
+            if base.get_data() < 0.1 {
+                return None;
+            }
+            Value::powv(&base, &build(b, vars)?)
+        }
+        Expr::Exp(a) => Value::exp(&build(a, vars)?),
+        Expr::Ln(a) => {
+            let x = build(a, vars)?;
+            if x.get_data() < 0.1 {
+                return None;
+            }
+            Value::ln(&x)
+        }
+        Expr::Log(a, base) => {
+            let x = build(a, vars)?;
+            if x.get_data() < 0.1 {
+                return None;
+            }
+            Value::log(&x, *base)
+        }
+        Expr::Tanh(a) => Value::tanh(&build(a, vars)?),
+        Expr::Sin(a) => Value::sin(&build(a, vars)?),
+        Expr::Cos(a) => Value::cos(&build(a, vars)?),
+        Expr::Tan(a) => {
+            let x = build(a, vars)?;
+            if x.get_data().cos().abs() < 0.1 {
+                return None;
+            }
+            Value::tan(&x)
+        }
+        Expr::Square(a) => Value::square(&build(a, vars)?),
+        Expr::Cube(a) => Value::cube(&build(a, vars)?),
+        Expr::Reciprocal(a) => {
+            let x = build(a, vars)?;
+            if x.get_data().abs() < 0.1 {
+                return None;
+            }
+            Value::reciprocal(&x)
+        }
+        Expr::Rsqrt(a) => {
+            let x = build(a, vars)?;
+            if x.get_data() < 0.1 {
+                return None;
+            }
+            Value::rsqrt(&x)
+        }
+        Expr::Sqrt(a) => {
+            let x = build(a, vars)?;
+            if x.get_data() < 0.1 {
+                return None;
+            }
+            Value::sqrt(&x)
+        }
+        Expr::Abs(a) => Value::abs(&build(a, vars)?),
+        Expr::Softplus(a) => Value::softplus(&build(a, vars)?),
+        Expr::Gelu(a) => Value::gelu(&build(a, vars)?),
+        Expr::Silu(a) => Value::silu(&build(a, vars)?),
+        Expr::Elu(a, alpha) => Value::elu(&build(a, vars)?, *alpha),
+        Expr::Relu(a) => Value::relu(&build(a, vars)?),
+        Expr::LeakyRelu(a, alpha) => Value::leaky_relu(&build(a, vars)?, *alpha),
+        Expr::Sigmoid(a) => Value::sigmoid(&build(a, vars)?),
+        Expr::Sum(xs) => Value::sum(&xs.iter().map(|x| build(x, vars)).collect::<Option<Vec<Value>>>()?),
+        Expr::LogSumExp(xs) => Value::logsumexp(&xs.iter().map(|x| build(x, vars)).collect::<Option<Vec<Value>>>()?),
+        Expr::Mean(xs) => Value::mean(&xs.iter().map(|x| build(x, vars)).collect::<Option<Vec<Value>>>()?),
+    };
+    if v.get_data().abs() > 1e4 {
+        return None;
+    }
+    Some(v)
+}
+
+fn eval(e: &Expr, xs: &[f64]) -> Option<f64> {
+    let vars: Vec<Value> = xs.iter().map(|x| Value::new(*x)).collect();
+    build(e, &vars).map(|v| v.get_data())
+}
+
+proptest! {
+    #[test]
+    fn gradients_match_finite_differences(e in expr(), xs in prop::collection::vec(-2.0..2.0f64, NVARS)) {
+        let vars: Vec<Value> = xs.iter().map(|x| Value::new(*x)).collect();
+        let out = build(&e, &vars);
+        prop_assume!(out.is_some());
+        out.unwrap().backward();
+
+        let h = 1e-6;
+        for i in 0..NVARS {
+            let mut plus = xs.clone();
+            let mut minus = xs.clone();
+            plus[i] += h;
+            minus[i] -= h;
+            let (fp, fm) = match (eval(&e, &plus), eval(&e, &minus)) {
+                (Some(fp), Some(fm)) => (fp, fm),
+                _ => continue,
+            };
+            let numeric = (fp - fm) / (2.0 * h);
+            let analytic = vars[i].get_grad();
+            prop_assert!(
+                (analytic - numeric).abs() <= 1e-3 * (1.0 + numeric.abs()),
+                "d/dx{}: backward gave {}, finite differences gave {} for {:?}", i, analytic, numeric, e
+            );
+        }
+    }
+}
+
+// regression tests for expressions where the same node is used more than once
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn add_same_operand() {
+    let a = Value::new(3.0);
+    Value::add(&a, &a).backward();
+    assert!(close(a.get_grad(), 2.0));
+}
+
+#[test]
+fn mul_same_operand() {
+    let a = Value::new(3.0);
+    Value::mul(&a, &a).backward();
+    assert!(close(a.get_grad(), 6.0));
+}
+
+#[test]
+fn sub_and_div_same_operand() {
+    let a = Value::new(3.0);
+    Value::sub(&a, &a).backward();
+    assert!(close(a.get_grad(), 0.0));
+
+    let b = Value::new(3.0);
+    Value::div(&b, &b).backward();
+    assert!(close(b.get_grad(), 0.0));
+}
+
+#[test]
+fn shared_intermediate_node() {
+    // e = d + d with d = a * a, de/da = 4a
+    let a = Value::new(3.0);
+    let d = Value::mul(&a, &a);
+    let e = Value::add(&d, &d);
+    e.backward();
+    assert!(close(d.get_grad(), 2.0));
+    assert!(close(a.get_grad(), 12.0));
+}
+
+#[test]
+fn shared_intermediate_through_several_parents() {
+    // f = (d * d) + tanh(d) + d with d = a + b
+    let a = Value::new(0.3);
+    let b = Value::new(-0.1);
+    let d = Value::add(&a, &b);
+    let f = Value::add(&Value::add(&Value::mul(&d, &d), &Value::tanh(&d)), &d);
+    f.backward();
+    let x = 0.2f64;
+    let expected = 2.0 * x + (1.0 - x.tanh() * x.tanh()) + 1.0;
+    assert!(close(a.get_grad(), expected));
+    assert!(close(b.get_grad(), expected));
+}
+
+#[test]
+fn softplus_does_not_overflow() {
+    let x = Value::new(1000.0);
+    let y = Value::softplus(&x);
+    y.backward();
+    assert!(close(y.get_data(), 1000.0));
+    assert!(close(x.get_grad(), 1.0));
+
+    let x = Value::new(-1000.0);
+    let y = Value::softplus(&x);
+    y.backward();
+    assert!(close(y.get_data(), 0.0));
+    assert!(close(x.get_grad(), 0.0));
+}
+
+#[test]
+fn where_routes_gradient_to_selected_branch() {
+    // huber loss with delta = 1: 0.5 r^2 if |r| <= 1, |r| - 0.5 otherwise
+    let huber = |r: &Value| {
+        let small = Value::mul(&Value::new(0.5), &Value::square(r));
+        let abs = if r.get_data() < 0.0 { Value::neg(r) } else { r.clone_rc() };
+        let large = Value::sub(&abs, &Value::new(0.5));
+        Value::where_(r.get_data().abs() <= 1.0, &small, &large)
+    };
+
+    let r = Value::new(0.5);
+    huber(&r).backward();
+    assert!(close(r.get_grad(), 0.5));
+
+    let r = Value::new(-3.0);
+    huber(&r).backward();
+    assert!(close(r.get_grad(), -1.0));
+}
+
+// central finite difference of f with respect to input i
+fn numeric_grad(f: &dyn Fn(&[Value]) -> Value, xs: &[f64], i: usize) -> f64 {
+    let h = 1e-6;
+    let eval = |d: f64| {
+        let vals: Vec<Value> = xs.iter().enumerate().map(|(j, x)| Value::new(if j == i { x + d } else { *x })).collect();
+        f(&vals).get_data()
+    };
+    (eval(h) - eval(-h)) / (2.0 * h)
+}
+
+fn check_grads(f: &dyn Fn(&[Value]) -> Value, xs: &[f64]) {
+    let vals: Vec<Value> = xs.iter().map(|x| Value::new(*x)).collect();
+    f(&vals).backward();
+    for (i, v) in vals.iter().enumerate() {
+        let numeric = numeric_grad(f, xs, i);
+        assert!((v.get_grad() - numeric).abs() < 1e-5, "input {}: {} vs {}", i, v.get_grad(), numeric);
+    }
+}
+
+#[test]
+fn fused_vector_ops() {
+    let xs = [0.3, -1.2, 2.0, 0.7, 0.1, -0.4];
+    check_grads(&|v| Value::dot(&v[..3], &v[3..]), &xs);
+    check_grads(&|v| Value::norm_l2(v), &xs);
+    check_grads(&|v| Value::cosine_similarity(&v[..3], &v[3..]), &xs);
+    check_grads(&|v| Value::sum(v), &xs);
+    check_grads(&|v| Value::fma_sum(&v[..2], &v[2..4], &v[4]), &xs[..5]);
+    check_grads(&|v| Value::mean(v), &xs);
+    check_grads(&|v| Value::logsumexp(v), &xs);
+
+    // aliased operands: dot(x, x) = |x|^2
+    let a = Value::new(3.0);
+    Value::dot(&[a.clone_rc()], &[a.clone_rc()]).backward();
+    assert!(close(a.get_grad(), 6.0));
+}
+
+#[test]
+fn reductions_are_a_single_node() {
+    let xs: Vec<Value> = (0..100).map(|i| Value::new(i as f64)).collect();
+    let s = Value::sum(&xs);
+    assert_eq!(s.get_data(), 4950.0);
+    assert_eq!(s.get_children().len(), 100);
+    let m = Value::mean(&xs);
+    assert_eq!(m.get_data(), 49.5);
+    m.backward();
+    assert!(xs.iter().all(|x| x.get_grad() == 0.01));
+    assert_eq!(Value::sum(&[]).get_data(), 0.0);
+}
+
+#[test]
+fn softmax_losses() {
+    let xs = [0.3, -1.2, 2.0];
+    check_grads(&|v| loss::cross_entropy(v, &[0.2, 0.5, 0.3]), &xs);
+    check_grads(&|v| loss::distillation(v, &[1.0, 0.0, -1.0], 2.0), &xs);
+
+    // a student that matches the teacher has nothing left to learn
+    let same: Vec<Value> = xs.iter().map(|x| Value::new(*x)).collect();
+    assert!(loss::distillation(&same, &xs, 3.0).get_data().abs() < 1e-12);
+}
+
+#[test]
+fn operators_match_named_ops() {
+    let xs = [0.3, -1.2, 2.0];
+    check_grads(&|v| &v[0] * &(&v[1] + &v[2]), &xs);
+    check_grads(&|v| (&v[0] - &v[1]) / &v[2], &xs);
+    check_grads(&|v| -(&v[0] * 2.0) + 1.0, &xs);
+    check_grads(&|v| 3.0 / &v[2] - 0.5 * &v[1], &xs);
+    check_grads(&|v| (&v[0] + 1.0) * (2.0 - &v[1]), &xs);
+
+    // same graph as spelling it out
+    let (a, b, c) = (Value::new(1.5), Value::new(-2.0), Value::new(0.5));
+    let e = &a * &(&b + &c) - 1.0;
+    let f = Value::sub(&Value::mul(&a, &Value::add(&b, &c)), &Value::constant(1.0));
+    assert_eq!(e.get_data(), f.get_data());
+    e.backward();
+    assert!(close(a.get_grad(), -1.5) && close(b.get_grad(), 1.5) && close(c.get_grad(), 1.5));
+}
+
+#[test]
+fn relu_family_and_sigmoid() {
+    let (pos, neg) = (Value::new(1.5), Value::new(-2.0));
+    Value::add(&Value::relu(&pos), &Value::relu(&neg)).backward();
+    assert_eq!((pos.get_grad(), neg.get_grad()), (1.0, 0.0));
+
+    let x = Value::new(-2.0);
+    let y = Value::leaky_relu(&x, 0.1);
+    y.backward();
+    assert!(close(y.get_data(), -0.2) && close(x.get_grad(), 0.1));
+
+    // no overflow where 1 / (1 + exp(-x)) built from exp and div would give nan gradients
+    let big = Value::new(-800.0);
+    let s = Value::sigmoid(&big);
+    s.backward();
+    assert_eq!(s.get_data(), 0.0);
+    assert!(big.get_grad().is_finite());
+    check_grads(&|v| Value::sigmoid(&v[0]), &[0.7]);
+}
+
+#[test]
+fn logs_in_any_base() {
+    let x = Value::new(8.0);
+    let y = Value::log(&x, 2.0);
+    y.backward();
+    assert!(close(y.get_data(), 3.0));
+    assert!(close(x.get_grad(), 1.0 / (8.0 * 2f64.ln())));
+    assert_eq!(y.0.borrow().op.to_string(), "log(2)");
+    check_grads(&|v| Value::log(&v[0], 10.0), &[0.3]);
+
+    // binary cross entropy from primitives: -(t ln p + (1 - t) ln(1 - p))
+    let p = Value::new(0.8);
+    let t = 1.0;
+    let bce = -(t * Value::ln(&p) + (1.0 - t) * Value::ln(&(1.0 - &p)));
+    bce.backward();
+    assert!(close(bce.get_data(), -(0.8f64).ln()) && close(p.get_grad(), -1.0 / 0.8));
+}
+
+#[test]
+fn trig_ops() {
+    check_grads(&|v| Value::sin(&v[0]), &[0.4]);
+    check_grads(&|v| Value::cos(&v[0]), &[-1.1]);
+    check_grads(&|v| Value::tan(&v[0]), &[0.9]);
+
+    // sin^2 + cos^2 = 1, flat in x
+    let x = Value::new(0.7);
+    let one = Value::square(&Value::sin(&x)) + Value::square(&Value::cos(&x));
+    one.backward();
+    assert!(close(one.get_data(), 1.0) && x.get_grad().abs() < 1e-12);
+}
+
+#[test]
+fn sqrt_and_abs() {
+    check_grads(&|v| Value::sqrt(&v[0]), &[2.5]);
+    check_grads(&|v| Value::abs(&v[0]), &[-1.5]);
+    check_grads(&|v| Value::abs(&v[0]), &[0.3]);
+
+    // subgradient 0 at the kink
+    let x = Value::new(0.0);
+    let y = Value::abs(&x);
+    y.backward();
+    assert!(close(y.get_data(), 0.0) && close(x.get_grad(), 0.0));
+
+    // rms of a few values, d/dx_i = x_i / (n rms)
+    let xs: Vec<Value> = [3.0, -4.0].iter().map(|x| Value::new(*x)).collect();
+    let squares: Vec<Value> = xs.iter().map(Value::square).collect();
+    let rms = Value::sqrt(&Value::mean(&squares));
+    rms.backward();
+    let expected = (12.5f64).sqrt();
+    assert!(close(rms.get_data(), expected));
+    assert!(close(xs[0].get_grad(), 3.0 / (2.0 * expected)) && close(xs[1].get_grad(), -4.0 / (2.0 * expected)));
+
+    // an l1 penalty pulls every weight toward 0 by the same amount
+    let ws: Vec<Value> = [0.5, -2.0, 0.0].iter().map(|w| Value::new(*w)).collect();
+    let l1 = Value::sum(&ws.iter().map(Value::abs).collect::<Vec<Value>>());
+    l1.backward();
+    assert!(close(l1.get_data(), 2.5));
+    assert_eq!(ws.iter().map(|w| w.get_grad()).collect::<Vec<f64>>(), vec![1.0, -1.0, 0.0]);
+}
+
+#[test]
+fn value_exponents() {
+    check_grads(&|v| Value::powv(&v[0], &v[1]), &[1.7, 0.6]);
+    check_grads(&|v| Value::powv(&v[0], &v[1]), &[0.4, -1.3]);
+
+    // a constant exponent gives what pow gives
+    let (a, b) = (Value::new(2.5), Value::new(2.5));
+    let (x, y) = (Value::powv(&a, &Value::constant(3.0)), Value::pow(&b, 3.0));
+    x.backward();
+    y.backward();
+    assert!(close(x.get_data(), y.get_data()) && close(a.get_grad(), b.get_grad()));
+
+    // learn the exponent of y = x^1.5 from a few points
+    let p = Value::new(1.0);
+    for _ in 0..500 {
+        p.set_grad(0.0);
+        let errs: Vec<Value> = [0.5, 1.5, 2.0, 3.0].iter().map(|x: &f64| {
+            Value::square(&(Value::powv(&Value::constant(*x), &p) - x.powf(1.5)))
+        }).collect();
+        Value::mean(&errs).backward();
+        p.set_data(p.get_data() - 0.05 * p.get_grad());
+    }
+    assert!((p.get_data() - 1.5).abs() < 1e-3, "{}", p.get_data());
+
+    // a negative base has no real log, its exponent gets no gradient
+    let (a, b) = (Value::new(-2.0), Value::new(2.0));
+    let y = Value::powv(&a, &b);
+    y.backward();
+    assert!(close(y.get_data(), 4.0) && close(a.get_grad(), -4.0) && b.get_grad() == 0.0);
+}
+
+#[test]
+fn max_and_min_route_to_the_winner() {
+    check_grads(&|v| Value::max(&v[0], &v[1]) * Value::min(&v[0], &v[1]), &[0.3, -1.2]);
+
+    let (a, b) = (Value::new(2.0), Value::new(-1.0));
+    let hi = Value::max(&a, &b);
+    let lo = Value::min(&a, &b);
+    (hi.clone() * 3.0 + lo.clone() * 5.0).backward();
+    assert!(close(hi.get_data(), 2.0) && close(lo.get_data(), -1.0));
+    assert!(close(a.get_grad(), 3.0) && close(b.get_grad(), 5.0));
+
+    // a tie goes to the first argument
+    let (a, b) = (Value::new(1.0), Value::new(1.0));
+    Value::max(&a, &b).backward();
+    assert!(close(a.get_grad(), 1.0) && close(b.get_grad(), 0.0));
+
+    // hinge loss: no gradient once the margin is met
+    let f = Value::new(0.4);
+    let hinge = |f: &Value, y: f64| Value::max(&Value::constant(0.0), &(1.0 - f * y));
+    let l = hinge(&f, 1.0);
+    l.backward();
+    assert!(close(l.get_data(), 0.6) && close(f.get_grad(), -1.0));
+    let f = Value::new(1.5);
+    hinge(&f, 1.0).backward();
+    assert!(close(f.get_grad(), 0.0));
+
+    // max-pool over a window
+    let xs: Vec<Value> = [0.1, 0.9, -0.3].iter().map(|x| Value::new(*x)).collect();
+    let pooled = xs[1..].iter().fold(xs[0].clone(), |m, x| Value::max(&m, x));
+    pooled.backward();
+    assert_eq!(xs.iter().map(|x| x.get_grad()).collect::<Vec<f64>>(), vec![0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn logsumexp_doesnt_overflow() {
+    let xs: Vec<Value> = [1000.0, 999.0, -5.0].iter().map(|x| Value::new(*x)).collect();
+    let y = Value::logsumexp(&xs);
+    let expected = 1000.0 + (1.0 + (-1.0f64).exp() + (-1005.0f64).exp()).ln();
+    assert!(close(y.get_data(), expected));
+    assert_eq!(y.get_children().len(), 3);
+    y.backward();
+    // the gradient is the softmax
+    let total: f64 = xs.iter().map(|x| x.get_grad()).sum();
+    assert!(close(total, 1.0) && close(xs[0].get_grad() / xs[1].get_grad(), 1.0f64.exp()));
+
+    // so cross entropy on huge logits stays finite
+    let logits: Vec<Value> = [800.0, 790.0].iter().map(|x| Value::new(*x)).collect();
+    let ce = loss::cross_entropy(&logits, &[0.0, 1.0]);
+    ce.backward();
+    assert!(close(ce.get_data(), 10.0 + (-10.0f64).exp().ln_1p()));
+    assert!(logits.iter().all(|l| l.get_grad().is_finite()));
+}
+
+#[test]
+fn safe_and_checked_division() {
+    // a regular denominator is a plain division, gradient to both sides
+    check_grads(&|v| Value::safe_div(&v[0], &v[1], 1e-6), &[1.5, -0.5]);
+
+    // a near-zero one is clamped to eps with its sign and held constant: finite, and no gradient
+    // goes into the denominator
+    for d in [0.0, 1e-9, -1e-9] {
+        let (a, b) = (Value::new(2.0), Value::new(d));
+        let y = Value::safe_div(&a, &b, 1e-3);
+        y.backward();
+        let clamped = if d < 0.0 { -1e-3 } else { 1e-3 };
+        assert!(close(y.get_data(), 2.0 / clamped) && close(a.get_grad(), 1.0 / clamped));
+        assert_eq!(b.get_grad(), 0.0);
+    }
+
+    let (a, b) = (Value::new(3.0), Value::new(0.0));
+    assert!(matches!(Value::checked_div(&a, &b), Err(RustMlError::Numerical(_))));
+    // tiny but nonzero overflows to inf, also an error
+    assert!(matches!(Value::checked_div(&Value::new(1e300), &Value::new(1e-300)), Err(RustMlError::Numerical(_))));
+    let y = Value::checked_div(&a, &Value::new(-1.5)).unwrap();
+    y.backward();
+    assert!(close(y.get_data(), -2.0) && close(a.get_grad(), -1.0 / 1.5));
+}
+
+#[test]
+fn argmax_and_topk_ties() {
+    let xs: Vec<Value> = [1.0, 3.0, 3.0, -2.0, 3.0].iter().map(|x| Value::new(*x)).collect();
+    // first of the tied maxima, and ties keep their order in topk
+    assert_eq!(Value::argmax(&xs), 1);
+    assert_eq!(Value::topk(&xs, 4), vec![1, 2, 4, 0]);
+    assert_eq!(Value::topk(&xs, 10).len(), 5);
+    assert_eq!(Value::greater_than(&xs, 1.0), vec![false, true, true, false, true]);
+}
+
+#[test]
+fn cumulative_ops() {
+    let xs = [0.5, -1.5, 2.0, 0.8];
+    // every prefix, weighted so each output's gradient counts differently
+    check_grads(&|v| Value::sum(&Value::cumsum(v).iter().enumerate().map(|(i, c)| c * (i as f64 + 1.0)).collect::<Vec<Value>>()), &xs);
+    check_grads(&|v| Value::sum(&Value::cumprod(v).iter().enumerate().map(|(i, c)| c * (i as f64 + 1.0)).collect::<Vec<Value>>()), &xs);
+
+    let vals: Vec<Value> = xs.iter().map(|x| Value::new(*x)).collect();
+    let data = |ys: Vec<Value>| ys.iter().map(|y| y.get_data()).collect::<Vec<f64>>();
+    assert_eq!(data(Value::cumsum(&vals)), vec![0.5, -1.0, 1.0, 1.8]);
+    assert!(data(Value::cumprod(&vals)).iter().zip([0.5, -0.75, -1.5, -1.2]).all(|(a, b)| close(*a, b)));
+    assert!(Value::cumsum(&[]).is_empty());
+}
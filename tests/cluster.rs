@@ -0,0 +1,94 @@
+use rust_ml::cluster::{davies_bouldin, elbow_curve, inertia, silhouette_samples, silhouette_score, Agglomerative, Dbscan, Linkage};
+use rust_ml::error::RustMlError;
+use rust_ml::random;
+
+// three tight blobs, 20 points each, in order
+fn blobs() -> Vec<Vec<f64>> {
+    let centers = [[0.0, 0.0], [5.0, 5.0], [0.0, 6.0]];
+    centers.iter().flat_map(|c| (0..20).map(move |_| vec![c[0] + random::normal(0.0, 0.3), c[1] + random::normal(0.0, 0.3)])).collect()
+}
+
+// the same partition up to renaming the clusters
+fn same_partition(a: &[usize], b: &[usize]) -> bool {
+    (0..a.len()).all(|i| (0..a.len()).all(|j| (a[i] == a[j]) == (b[i] == b[j])))
+}
+
+#[test]
+fn dbscan_finds_blobs_and_noise() {
+    random::seed(50);
+    let mut xs = blobs();
+    xs.push(vec![20.0, -20.0]);
+    let mut dbscan = Dbscan::new(1.0, 4);
+    let labels = dbscan.fit_predict(&xs).unwrap();
+    assert_eq!(dbscan.n_clusters(), 3);
+    assert_eq!(labels[60], None);
+    assert!(!dbscan.core[60]);
+    let truth: Vec<usize> = (0..60).map(|i| i / 20).collect();
+    let found: Vec<usize> = labels[..60].iter().map(|l| l.unwrap()).collect();
+    assert!(same_partition(&found, &truth));
+}
+
+#[test]
+fn agglomerative_recovers_blobs_with_every_linkage() {
+    random::seed(51);
+    let xs = blobs();
+    let truth: Vec<usize> = (0..60).map(|i| i / 20).collect();
+    for linkage in [Linkage::Single, Linkage::Complete, Linkage::Average, Linkage::Ward] {
+        let mut model = Agglomerative::new(3).with_linkage(linkage);
+        let labels = model.fit_predict(&xs).unwrap();
+        assert!(same_partition(&labels, &truth), "{:?}", linkage);
+
+        // the dendrogram: n - 1 merges, non-decreasing heights, ending with everything in one cluster
+        let merges = model.dendrogram();
+        assert_eq!(merges.len(), 59);
+        assert!(merges.windows(2).all(|w| w[0].distance <= w[1].distance + 1e-12), "{:?}", linkage);
+        assert_eq!(merges[58].size, 60);
+        assert_eq!(model.cut(1), vec![0; 60]);
+        assert_eq!(model.cut(60), (0..60).collect::<Vec<usize>>());
+    }
+}
+
+#[test]
+fn single_linkage_merges_at_nearest_neighbour_distance() {
+    let xs = vec![vec![0.0], vec![1.0], vec![3.0], vec![7.0]];
+    let mut model = Agglomerative::new(2).with_linkage(Linkage::Single);
+    model.fit(&xs).unwrap();
+    let heights: Vec<f64> = model.merges.iter().map(|m| m.distance).collect();
+    assert_eq!(heights, vec![1.0, 2.0, 4.0]);
+    assert_eq!((model.merges[1].a, model.merges[1].b), (4, 2));
+    assert_eq!(model.labels, vec![0, 0, 0, 1]);
+    assert!(matches!(Agglomerative::new(5).fit(&xs), Err(RustMlError::Config(_))));
+}
+
+#[test]
+fn metrics_prefer_the_true_partition() {
+    random::seed(52);
+    let xs = blobs();
+    let truth: Vec<usize> = (0..60).map(|i| i / 20).collect();
+    let shuffled: Vec<usize> = (0..60).map(|i| i % 3).collect();
+
+    let good = silhouette_score(&xs, &truth).unwrap();
+    assert!(good > 0.8, "{}", good);
+    assert!(silhouette_score(&xs, &shuffled).unwrap() < 0.0);
+    assert!(silhouette_samples(&xs, &truth).unwrap().iter().all(|s| (-1.0..=1.0).contains(s)));
+
+    let db = davies_bouldin(&xs, &truth).unwrap();
+    assert!(db < 0.3, "{}", db);
+    assert!(davies_bouldin(&xs, &shuffled).unwrap() > db);
+
+    assert!(inertia(&xs, &truth).unwrap() < inertia(&xs, &shuffled).unwrap());
+    assert_eq!(inertia(&xs, &(0..60).collect::<Vec<usize>>()).unwrap(), 0.0);
+    assert!(matches!(silhouette_score(&xs, &[0; 60]), Err(RustMlError::Config(_))));
+}
+
+#[test]
+fn elbow_curve_bends_at_the_number_of_blobs() {
+    random::seed(53);
+    let xs = blobs();
+    let curve = elbow_curve(&xs, &[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(curve.iter().map(|(k, _)| *k).collect::<Vec<usize>>(), vec![1, 2, 3, 4, 5]);
+    assert!(curve.windows(2).all(|w| w[1].1 <= w[0].1));
+    // big drops up to 3 clusters, small ones after
+    let drop = |k: usize| curve[k - 2].1 - curve[k - 1].1;
+    assert!(drop(3) > 10.0 * drop(4), "{:?}", curve);
+}
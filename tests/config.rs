@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rust_ml::config::{self, DataConfig, LossConfig, OptimizerConfig, TrainConfig};
+use rust_ml::error::RustMlError;
+use rust_ml::nn::{Activation, Module};
+use rust_ml::optim::Schedule;
+use rust_ml::serialize::ModelBundle;
+
+const MOONS: &str = r#"
+seed = 7
+
+[model]
+layers = [2, 8, 1]
+
+[optimizer]
+type = "adam"
+lr = 0.05
+
+[scheduler]
+type = "cosine"
+min_lr = 0.005
+
+[data]
+source = "moons"
+n = 40
+noise = 0.1
+
+[training]
+epochs = 30
+batch_size = 16
+"#;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-ml-config-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn toml_parses_with_defaults_and_round_trips() {
+    let config = TrainConfig::from_toml(MOONS).unwrap();
+    assert_eq!(config.seed, Some(7));
+    assert_eq!(config.model.layers, vec![2, 8, 1]);
+    assert_eq!(config.model.activation, Activation::Tanh);
+    assert_eq!(config.optimizer, OptimizerConfig::Adam { lr: 0.05 });
+    assert_eq!(config.scheduler, Schedule::Cosine { min_lr: 0.005 });
+    assert_eq!(config.data, DataConfig::Moons { n: 40, noise: 0.1 });
+    assert_eq!(config.training.batch_size, Some(16));
+    assert_eq!(config.training.loss, LossConfig::Mse);
+
+    assert_eq!(TrainConfig::from_toml(&config.to_toml().unwrap()).unwrap(), config);
+    assert_eq!(TrainConfig::from_json(&config.to_json().unwrap()).unwrap(), config);
+}
+
+#[test]
+fn bad_configs_are_rejected() {
+    let typo = MOONS.replace("epochs = 30", "epoch = 30");
+    assert!(matches!(TrainConfig::from_toml(&typo), Err(RustMlError::Serialization(_))));
+    let unknown_optimizer = MOONS.replace("\"adam\"", "\"adamw\"");
+    assert!(matches!(TrainConfig::from_toml(&unknown_optimizer), Err(RustMlError::Serialization(_))));
+
+    for (from, to) in [("layers = [2, 8, 1]", "layers = [2]"), ("lr = 0.05", "lr = -1.0"), ("epochs = 30", "epochs = 0"), ("batch_size = 16", "batch_size = 0"), ("type = \"cosine\"", "type = \"cyclic\"\nperiod = 0"), ("type = \"cosine\"", "type = \"onecycle\"\nwarmup = 1.5")] {
+        assert!(matches!(TrainConfig::from_toml(&MOONS.replace(from, to)), Err(RustMlError::Config(_))), "{}", to);
+    }
+}
+
+#[test]
+fn schedules() {
+    assert_eq!(Schedule::Constant.lr(0.1, 5, 10), 0.1);
+    let step = Schedule::Step { every: 3, gamma: 0.5 };
+    assert_eq!((0..7).map(|e| step.lr(1.0, e, 7)).collect::<Vec<f64>>(), vec![1.0, 1.0, 1.0, 0.5, 0.5, 0.5, 0.25]);
+    assert!((Schedule::Exponential { gamma: 0.9 }.lr(1.0, 2, 10) - 0.81).abs() < 1e-12);
+    let cosine = Schedule::Cosine { min_lr: 0.1 };
+    assert_eq!(cosine.lr(1.0, 0, 11), 1.0);
+    assert!((cosine.lr(1.0, 5, 11) - 0.55).abs() < 1e-12);
+    assert!((cosine.lr(1.0, 10, 11) - 0.1).abs() < 1e-12);
+    let one_cycle = Schedule::OneCycle { warmup: 0.3, div: 25.0, final_div: 1e4, momentum: Some((0.95, 0.85)) };
+    let lrs: Vec<f64> = (0..11).map(|e| one_cycle.lr(1.0, e, 11)).collect();
+    assert!((lrs[0] - 0.04).abs() < 1e-12 && (lrs[3] - 1.0).abs() < 1e-12 && (lrs[10] - 4e-6).abs() < 1e-12, "{:?}", lrs);
+    assert!(lrs[..4].windows(2).all(|w| w[1] > w[0]) && lrs[3..].windows(2).all(|w| w[1] < w[0]));
+    let momenta: Vec<f64> = (0..11).map(|e| one_cycle.momentum(e, 11).unwrap()).collect();
+    assert!((momenta[0] - 0.95).abs() < 1e-12 && (momenta[3] - 0.85).abs() < 1e-12 && (momenta[10] - 0.95).abs() < 1e-12);
+    assert_eq!(Schedule::Cosine { min_lr: 0.1 }.momentum(0, 10), None);
+    let cyclic = Schedule::Cyclic { min_lr: 0.2, period: 4 };
+    let lrs: Vec<f64> = (0..8).map(|e| cyclic.lr(1.0, e, 8)).collect();
+    for (lr, expected) in lrs.iter().zip([0.8, 0.6, 0.4, 0.2, 0.8, 0.6, 0.4, 0.2]) {
+        assert!((lr - expected).abs() < 1e-12, "{:?}", lrs);
+    }
+}
+
+#[test]
+fn runs_are_reproducible_and_save_their_model() {
+    let dir = scratch("run");
+    let path = dir.join("moons.toml");
+    fs::write(&path, format!("output = \"model.json\"\n{}", MOONS)).unwrap();
+    let config = TrainConfig::load(&path).unwrap();
+    assert_eq!(config.output, Some(dir.join("model.json")));
+
+    let (bundle, losses) = config::run(&config).unwrap();
+    assert_eq!(losses.len(), 30);
+    assert!(losses[29] < losses[0]);
+    assert_eq!(bundle.info().seed, Some(7));
+    assert_eq!(bundle.info().hyperparameters["epochs"], "30");
+    assert_eq!(bundle.info().metrics["loss"], losses[29]);
+
+    let saved = ModelBundle::load(dir.join("model.json")).unwrap();
+    assert_eq!(saved.info(), bundle.info());
+    assert_eq!(saved.model.predict(&[0.5, 0.25]), bundle.model.predict(&[0.5, 0.25]));
+
+    let (_, again) = config::run(&config).unwrap();
+    assert_eq!(again, losses);
+}
+
+#[test]
+fn csv_data_is_relative_to_the_config() {
+    let dir = scratch("csv");
+    fs::write(dir.join("xor.csv"), "a,b,y\n0,0,-1\n0,1,1\n1,0,1\n1,1,-1\n").unwrap();
+    let toml = r#"
+        [model]
+        layers = [2, 4, 1]
+
+        [optimizer]
+        type = "sgd"
+        lr = 0.1
+        momentum = 0.9
+
+        [data]
+        source = "csv"
+        path = "xor.csv"
+        header = true
+
+        [training]
+        epochs = 5
+    "#;
+    fs::write(dir.join("xor.toml"), toml).unwrap();
+    let config = TrainConfig::load(dir.join("xor.toml")).unwrap();
+    let (xs, ys) = config.data.load(1).unwrap();
+    assert_eq!(xs, vec![vec![0.0, 0.0], vec![0.0, 1.0], vec![1.0, 0.0], vec![1.0, 1.0]]);
+    assert_eq!(ys, vec![vec![-1.0], vec![1.0], vec![1.0], vec![-1.0]]);
+    assert_eq!(config::run(&config).unwrap().1.len(), 5);
+
+    // 3 features don't fit a model with 2 inputs
+    fs::write(dir.join("xor.csv"), "a,b,c,y\n0,0,0,-1\n").unwrap();
+    let config = TrainConfig::load(dir.join("xor.toml")).unwrap();
+    assert!(matches!(config::run(&config), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn runs_pick_up_from_their_checkpoint() {
+    let dir = scratch("checkpoint");
+    let with_checkpoint = MOONS.replace("batch_size = 16", "batch_size = 16\ncheckpoint = \"ckpt.json\"\nmax_seconds = 600.0");
+    fs::write(dir.join("run.toml"), &with_checkpoint).unwrap();
+    let config = TrainConfig::load(dir.join("run.toml")).unwrap();
+    assert_eq!(config.training.checkpoint, Some(dir.join("ckpt.json")));
+    assert_eq!(config::run(&config).unwrap().1.len(), 30);
+    assert!(dir.join("ckpt.json").exists());
+
+    // a longer budget trains only the epochs that are left
+    fs::write(dir.join("run.toml"), with_checkpoint.replace("epochs = 30", "epochs = 40")).unwrap();
+    let (bundle, losses) = config::run(&TrainConfig::load(dir.join("run.toml")).unwrap()).unwrap();
+    assert_eq!(losses.len(), 10);
+    assert_eq!(bundle.info().metrics["loss"], losses[9]);
+}
+
+#[test]
+fn seeds_are_aggregated_the_same_in_parallel() {
+    let config = TrainConfig::from_toml(MOONS).unwrap();
+    let runs = config::run_seeds(&config, &[1, 2, 3], false).unwrap();
+    assert_eq!(runs.seeds, vec![1, 2, 3]);
+    let loss = &runs.metrics["loss"];
+    assert_eq!(loss.values.len(), 3);
+    assert!((loss.mean - loss.values.iter().sum::<f64>() / 3.0).abs() < 1e-12);
+    assert!(loss.std > 0.0);
+    assert!(runs.metrics["accuracy"].values.iter().all(|a| (0.0..=1.0).contains(a)));
+    assert!(runs.to_string().contains("± "));
+
+    // each seed is its own reproducible run, whichever thread it lands on
+    assert_eq!(config::run_seeds(&config, &[1, 2, 3], true).unwrap(), runs);
+    let mut one = config.clone();
+    one.seed = Some(2);
+    assert_eq!(config::run(&one).unwrap().0.info().metrics["loss"], loss.values[1]);
+    assert!(config::run_seeds(&config, &[], false).is_err());
+}
+
+#[test]
+fn one_cycle_from_toml() {
+    let toml = MOONS.replace("type = \"cosine\"\nmin_lr = 0.005", "type = \"onecycle\"\nmomentum = [0.95, 0.85]");
+    let config = TrainConfig::from_toml(&toml).unwrap();
+    assert_eq!(config.scheduler, Schedule::OneCycle { warmup: 0.3, div: 25.0, final_div: 1e4, momentum: Some((0.95, 0.85)) });
+    assert_eq!(TrainConfig::from_toml(&config.to_toml().unwrap()).unwrap(), config);
+
+    // adam with its beta1 cycled still trains
+    let (_, losses) = config::run(&config).unwrap();
+    assert!(losses[29] < losses[0]);
+}
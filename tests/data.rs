@@ -0,0 +1,142 @@
+use rust_ml::data::{self, Augment, DataLoader, Mode};
+use rust_ml::random;
+
+fn labelled(n: usize, classes: usize) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let xs = (0..n).map(|i| vec![i as f64]).collect();
+    let ys = (0..n).map(|i| vec![(i % classes) as f64]).collect();
+    (xs, ys)
+}
+
+#[test]
+fn sample_batches_cover_every_sample_once() {
+    random::seed(20);
+    let (xs, ys) = labelled(10, 2);
+    let batches = DataLoader::new(&xs, &ys, 4).batches();
+    assert_eq!(batches.iter().map(|b| b.xs.len()).collect::<Vec<_>>(), vec![4, 4, 2]);
+    let mut seen: Vec<f64> = batches.iter().flat_map(|b| b.xs.iter().map(|x| x[0])).collect();
+    seen.sort_by(f64::total_cmp);
+    assert_eq!(seen, (0..10).map(|i| i as f64).collect::<Vec<f64>>());
+    for b in batches.iter() {
+        for (x, y) in b.xs.iter().zip(b.ys.iter()) {
+            assert_eq!(y[0], x[0] % 2.0);
+        }
+    }
+}
+
+#[test]
+fn pairs_are_labelled_by_class() {
+    random::seed(21);
+    let (xs, ys) = labelled(40, 3);
+    let batches = DataLoader::new(&xs, &ys, 8).with_mode(Mode::Pairs).batches();
+    let mut similar = 0;
+    for b in batches.iter() {
+        for (x, y) in b.xs.iter().zip(b.ys.iter()) {
+            assert_eq!(x.len(), 2);
+            assert_ne!(x[0], x[1]);
+            let same = x[0] % 3.0 == x[1] % 3.0;
+            assert_eq!(y[0], if same { 1.0 } else { 0.0 });
+            similar += same as usize;
+        }
+    }
+    assert!((10..=30).contains(&similar), "{} similar pairs out of 40", similar);
+}
+
+#[test]
+fn triplets_have_a_positive_and_a_negative() {
+    random::seed(22);
+    let (xs, ys) = labelled(12, 3);
+    let batches = DataLoader::new(&xs, &ys, 5).with_shuffle(false).with_mode(Mode::Triplets).batches();
+    let rows: Vec<&Vec<f64>> = batches.iter().flat_map(|b| b.xs.iter()).collect();
+    assert_eq!(rows.len(), 12);
+    for x in rows {
+        assert_ne!(x[0], x[1]);
+        assert_eq!(x[0] % 3.0, x[1] % 3.0);
+        assert_ne!(x[0] % 3.0, x[2] % 3.0);
+    }
+}
+
+#[test]
+fn mixup_blends_inputs_and_targets_with_the_same_weight() {
+    random::seed(24);
+    // y = 3 x + 1 is linear, so it survives any convex blend of samples
+    let xs: Vec<Vec<f64>> = (0..16).map(|i| vec![i as f64, 1.0]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![3.0 * x[0] + 1.0]).collect();
+    let batches = DataLoader::new(&xs, &ys, 8).with_augment(Augment::Mixup { alpha: 0.4 }).batches();
+    let mut blended = 0;
+    for b in batches.iter() {
+        for (x, y) in b.xs.iter().zip(b.ys.iter()) {
+            assert!((y[0] - (3.0 * x[0] + 1.0)).abs() < 1e-9);
+            assert!((x[1] - 1.0).abs() < 1e-12);
+            blended += (x[0].fract() != 0.0) as usize;
+        }
+    }
+    assert!(blended > 0);
+}
+
+#[test]
+fn label_noise_flips_about_p_of_the_targets() {
+    random::seed(25);
+    let (xs, ys) = labelled(2000, 4);
+    let batches = DataLoader::new(&xs, &ys, 100).with_augment(Augment::LabelNoise { p: 0.2 }).batches();
+    let mut flipped = 0;
+    for b in batches.iter() {
+        for (x, y) in b.xs.iter().zip(b.ys.iter()) {
+            assert!([0.0, 1.0, 2.0, 3.0].contains(&y[0]));
+            flipped += (y[0] != x[0] % 4.0) as usize;
+        }
+    }
+    assert!((300..500).contains(&flipped), "{} of 2000 flipped", flipped);
+}
+
+#[test]
+fn beta_samples_have_the_right_mean() {
+    random::seed(26);
+    let n = 20000;
+    let mean = (0..n).map(|_| random::beta(2.0, 6.0)).sum::<f64>() / n as f64;
+    assert!((mean - 0.25).abs() < 0.01, "{}", mean);
+    let small = (0..n).map(|_| random::beta(0.3, 0.3)).sum::<f64>() / n as f64;
+    assert!((small - 0.5).abs() < 0.02, "{}", small);
+}
+
+// 90 samples of class 0, 10 of class 1
+fn imbalanced() -> Vec<Vec<f64>> {
+    (0..100).map(|i| vec![if i % 10 == 0 { 1.0 } else { 0.0 }]).collect()
+}
+
+fn count_minority(ys: &[Vec<f64>], idx: &[usize]) -> usize {
+    idx.iter().filter(|&&i| ys[i][0] == 1.0).count()
+}
+
+#[test]
+fn stratified_split_keeps_class_proportions() {
+    random::seed(27);
+    let ys = imbalanced();
+    let split = data::stratified_split(&ys, 0.2);
+    assert_eq!((split.train.len(), split.test.len()), (80, 20));
+    assert_eq!(count_minority(&ys, &split.test), 2);
+    assert_eq!(count_minority(&ys, &split.train), 8);
+    let mut all = [split.train.clone(), split.test.clone()].concat();
+    all.sort();
+    assert_eq!(all, (0..100).collect::<Vec<usize>>());
+
+    let plain = data::train_test_split(100, 0.25);
+    assert_eq!((plain.train.len(), plain.test.len()), (75, 25));
+    assert_eq!(data::select(&ys, &plain.test).len(), 25);
+}
+
+#[test]
+fn stratified_k_fold_partitions_the_samples_by_class() {
+    random::seed(28);
+    let ys = imbalanced();
+    let folds = data::stratified_k_fold(&ys, 5);
+    let mut tested: Vec<usize> = folds.iter().flat_map(|f| f.test.iter().cloned()).collect();
+    tested.sort();
+    assert_eq!(tested, (0..100).collect::<Vec<usize>>());
+    for f in folds.iter() {
+        assert_eq!(f.test.len(), 20);
+        assert_eq!(f.train.len(), 80);
+        assert_eq!(count_minority(&ys, &f.test), 2);
+        assert!(f.test.iter().all(|i| !f.train.contains(i)));
+    }
+    assert_eq!(data::k_fold(10, 3).iter().map(|f| f.test.len()).collect::<Vec<_>>(), vec![4, 3, 3]);
+}
@@ -0,0 +1,54 @@
+use rust_ml::decoding::{beam_search, greedy, log_softmax};
+
+const EOS: usize = 3;
+
+// next-token probabilities given the last token, as logits: greedy takes 1 (0.5) and then has to
+// end on a weak token, while 2 (0.4) is followed by eos with 0.9, the better sequence overall
+fn step(seq: &[usize]) -> Vec<f64> {
+    let probs = match seq.last() {
+        Some(0) => [0.001, 0.5, 0.4, 0.099],
+        Some(1) => [0.001, 0.3, 0.299, 0.4],
+        Some(2) => [0.001, 0.05, 0.049, 0.9],
+        _ => [0.25, 0.25, 0.25, 0.25],
+    };
+    probs.iter().map(|p: &f64| p.ln()).collect()
+}
+
+#[test]
+fn greedy_takes_the_best_token_until_eos() {
+    assert_eq!(greedy(step, &[0], 10, Some(EOS)), vec![0, 1, 3]);
+    // without an eos it runs to max_len
+    assert_eq!(greedy(step, &[1], 3, None), vec![1, 3, 0, 1]);
+    assert_eq!(greedy(step, &[0], 0, Some(EOS)), vec![0]);
+    // ties go to the lowest token
+    assert_eq!(greedy(|_: &[usize]| vec![1.0, 2.0, 2.0], &[], 2, None), vec![1, 1]);
+}
+
+#[test]
+fn beam_search_finds_the_better_sequence() {
+    let beams = beam_search(step, &[0], 10, 2, Some(EOS));
+    assert_eq!(beams.len(), 2);
+    assert_eq!(beams[0].0, vec![0, 2, 3]);
+    assert!((beams[0].1 - (0.4f64 * 0.9).ln()).abs() < 1e-12);
+    assert_eq!(beams[1].0, vec![0, 1, 3]);
+    assert!((beams[1].1 - (0.5f64 * 0.4).ln()).abs() < 1e-12);
+
+    // a width of 1 is greedy
+    assert_eq!(beam_search(step, &[0], 10, 1, Some(EOS))[0].0, greedy(step, &[0], 10, Some(EOS)));
+}
+
+#[test]
+fn beam_search_without_eos_stops_at_max_len() {
+    let beams = beam_search(step, &[0], 2, 3, None);
+    assert_eq!(beams.len(), 3);
+    assert!(beams.iter().all(|(seq, _)| seq.len() == 3));
+    assert!(beams.windows(2).all(|w| w[0].1 >= w[1].1));
+    // eos is just another token here, [0, 2, 3] is still the best
+    assert_eq!(beams[0].0, vec![0, 2, 3]);
+}
+
+#[test]
+fn log_softmax_is_stable() {
+    let lp = log_softmax(&[1000.0, 1000.0]);
+    assert!(lp.iter().all(|l| (l - 0.5f64.ln()).abs() < 1e-12));
+}
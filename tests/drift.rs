@@ -0,0 +1,53 @@
+use rust_ml::drift::{self, Ddm, DriftMonitor, DriftState};
+use rust_ml::nn::{Activation, Layer};
+use rust_ml::random;
+
+#[test]
+fn ddm_flags_a_jump_in_the_error_rate() {
+    random::seed(90);
+    let mut ddm = Ddm::new();
+    // 10% errors for 1000 samples, no verdict
+    for _ in 0..1000 {
+        assert_ne!(ddm.update(random::bernoulli(0.1)), DriftState::Drift);
+    }
+    // then 50%: a warning first, drift soon after
+    let states: Vec<DriftState> = (0..300).map(|_| ddm.update(random::bernoulli(0.5))).collect();
+    let warning = states.iter().position(|s| *s == DriftState::Warning).unwrap();
+    let drift = states.iter().position(|s| *s == DriftState::Drift).unwrap();
+    assert!(warning < drift && drift < 150, "warning at {}, drift at {}", warning, drift);
+}
+
+#[test]
+fn two_sample_statistics_separate_shifted_samples() {
+    random::seed(91);
+    let a: Vec<f64> = (0..500).map(|_| random::normal(0.0, 1.0)).collect();
+    let same: Vec<f64> = (0..500).map(|_| random::normal(0.0, 1.0)).collect();
+    let shifted: Vec<f64> = (0..500).map(|_| random::normal(0.5, 1.0)).collect();
+
+    assert_eq!(drift::ks_statistic(&a, &a), 0.0);
+    assert_eq!(drift::ks_statistic(&[1.0, 2.0], &[3.0, 4.0]), 1.0);
+    let d_same = drift::ks_statistic(&a, &same);
+    let d_shift = drift::ks_statistic(&a, &shifted);
+    assert!(drift::ks_p_value(d_same, 500, 500) > 0.01);
+    assert!(drift::ks_p_value(d_shift, 500, 500) < 1e-6);
+
+    assert!(drift::psi(&a, &same, 10) < 0.1);
+    assert!(drift::psi(&a, &shifted, 10) > 0.2);
+}
+
+#[test]
+fn monitor_reports_which_inputs_moved() {
+    random::seed(92);
+    let model = Layer::with_activation(2, 1, Activation::Tanh);
+    let sample = |shift: f64| -> Vec<Vec<f64>> { (0..400).map(|_| vec![random::normal(shift, 1.0), random::normal(0.0, 1.0)]).collect() };
+    let monitor = DriftMonitor::new(&model, &sample(0.0));
+
+    let (out, report) = monitor.predict_batch(&sample(0.0));
+    assert_eq!(out.len(), 400);
+    assert!(!report.drifted(), "{:?}", report);
+
+    let (_, report) = monitor.predict_batch(&sample(1.5));
+    assert!(report.drifted());
+    assert!(report.features[0].drifted && !report.features[1].drifted, "{:?}", report.features);
+    assert_eq!(report.predictions.len(), 1);
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rust_ml::error::RustMlError;
+use rust_ml::experiments::{self, Run};
+use rust_ml::nn::{MLP, Module};
+use rust_ml::serialize;
+
+// a fresh directory per test under the system temp dir
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-ml-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn runs_lay_out_config_metrics_checkpoints_and_plots() {
+    let root = scratch("layout");
+    let mut run = Run::create(&root, "baseline").unwrap();
+    run.log_config("lr", 0.1).unwrap();
+    run.log_config("hidden", 16).unwrap();
+    for step in 0..5 {
+        run.log_metrics(step, &[("loss", 1.0 / (step + 1) as f64), ("accuracy", 0.5 + 0.1 * step as f64)]).unwrap();
+    }
+    let model = MLP::new(&[2, 3, 1]);
+    run.save_checkpoint(&model, 2).unwrap();
+    let last = run.save_checkpoint(&model, 10).unwrap();
+    let plot = run.plot_metrics().unwrap();
+
+    assert!(run.dir.join("config.json").exists() && plot.exists());
+    assert_eq!(run.latest_checkpoint().unwrap(), Some(last.clone()));
+    assert_eq!(serialize::load(&last).unwrap().predict(&[1.0, 2.0]), model.predict(&[1.0, 2.0]));
+    let metrics = run.metrics().unwrap();
+    assert_eq!(metrics["loss"].len(), 5);
+    assert_eq!(metrics["accuracy"][4], (4, 0.9));
+
+    // a second run with the same name gets its own directory, reopening a run keeps its config
+    let again = Run::create(&root, "baseline").unwrap();
+    assert_eq!(again.name, "baseline-2");
+    assert_eq!(Run::open(&run.dir).unwrap().config["lr"], "0.1");
+    assert!(run.log_metrics(5, &[("a,b", 1.0)]).is_err());
+    // names that would leave root, or aren't a directory name at all, are config errors
+    for name in ["..", "a/..", "", "a/b"] {
+        assert!(matches!(Run::create(&root, name), Err(RustMlError::Config(_))), "{:?}", name);
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn compare_runs_summarizes_side_by_side() {
+    let root = scratch("compare");
+    let mut dirs = vec![];
+    for (name, lr, final_loss) in [("slow", 0.01, 0.5), ("fast", 0.1, 0.2)] {
+        let mut run = Run::create(&root, name).unwrap();
+        run.log_config("lr", lr).unwrap();
+        run.log_metrics(0, &[("loss", 1.0)]).unwrap();
+        run.log_metrics(9, &[("loss", final_loss)]).unwrap();
+        dirs.push(run.dir.clone());
+    }
+    let mut extra = Run::create(&root, "tuned").unwrap();
+    extra.log_config("momentum", 0.9).unwrap();
+    dirs.push(extra.dir.clone());
+
+    let summaries = experiments::compare_runs(&dirs).unwrap();
+    assert_eq!(summaries.iter().map(|s| s.name.as_str()).collect::<Vec<&str>>(), vec!["slow", "fast", "tuned"]);
+    assert_eq!(summaries[1].last["loss"], 0.2);
+    assert_eq!(summaries[1].max["loss"], 1.0);
+    assert_eq!(summaries[0].steps, 9);
+    assert!(summaries[2].last.is_empty());
+
+    let table = experiments::comparison_table(&summaries);
+    assert!(table.starts_with("| | slow | fast | tuned |"), "{}", table);
+    assert!(table.contains("| lr | 0.01 | 0.1 |  |"), "{}", table);
+    assert!(table.contains("| loss | 0.5000 | 0.2000 |  |"), "{}", table);
+    fs::remove_dir_all(&root).unwrap();
+}
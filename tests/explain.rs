@@ -0,0 +1,108 @@
+use rust_ml::explain;
+use rust_ml::nn::{Activation, Layer, Module, MLP, Neuron};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+// y = 2 x0 - 3 x1 + 0 x2 + 1
+fn linear_model() -> Layer {
+    Layer::from_neurons(vec![Neuron {
+        w: vec![Value::new(2.0), Value::new(-3.0), Value::new(0.0)],
+        b: Value::new(1.0),
+        act: Activation::Linear,
+    }])
+}
+
+#[test]
+fn saliency_of_a_linear_model_is_its_weights() {
+    let model = linear_model();
+    assert_eq!(explain::saliency(&model, &[0.5, 0.5, 0.5], 0), vec![2.0, 3.0, 0.0]);
+}
+
+#[test]
+fn integrated_gradients_add_up_to_the_output_difference() {
+    random::seed(6);
+    let model = MLP::new(&[3, 8, 2]);
+    let x = [0.7, -0.3, 1.1];
+    let baseline = [0.0; 3];
+    let attr = explain::integrated_gradients(&model, &x, &baseline, 1, 200);
+    let diff = model.predict(&x)[1] - model.predict(&baseline)[1];
+    assert!((attr.iter().sum::<f64>() - diff).abs() < 1e-3, "{:?} vs {}", attr, diff);
+}
+
+#[test]
+fn permutation_importance_ranks_the_features_a_model_uses() {
+    random::seed(7);
+    let model = linear_model();
+    let xs: Vec<Vec<f64>> = (0..50).map(|_| (0..3).map(|_| random::uniform(-1.0, 1.0)).collect()).collect();
+    let ys: Vec<f64> = xs.iter().map(|x| model.predict(x)[0]).collect();
+    let neg_mse = |xs: &[Vec<f64>]| -xs.iter().zip(ys.iter()).map(|(x, y)| (model.predict(x)[0] - y).powi(2)).sum::<f64>() / xs.len() as f64;
+
+    let imp = explain::permutation_importance(&xs, neg_mse, 10);
+    let order: Vec<usize> = imp.iter().map(|f| f.feature).collect();
+    assert_eq!(order, vec![1, 0, 2]);
+    assert_eq!(imp[2].mean, 0.0);
+    assert!(imp[0].ci.0 > 0.0 && imp[0].ci.0 <= imp[0].mean && imp[0].mean <= imp[0].ci.1);
+}
+
+#[test]
+fn partial_dependence_of_a_linear_model_is_a_line() {
+    let model = linear_model();
+    let xs = vec![vec![0.0, 1.0, 2.0], vec![1.0, -1.0, 0.5]];
+    let grid = explain::feature_grid(&xs, 0, 3);
+    assert_eq!(grid, vec![0.0, 0.5, 1.0]);
+
+    let pd = explain::partial_dependence(&model, &xs, 0, &grid, 0);
+    // mean over the samples of 2 g - 3 x1 + 1, with mean x1 = 0
+    assert_eq!(pd.average, vec![1.0, 2.0, 3.0]);
+    assert_eq!(pd.ice[0], vec![-2.0, -1.0, 0.0]);
+    assert!(pd.plot("pd").starts_with("<svg"));
+
+    let pd2 = explain::partial_dependence_2d(&model, &xs, (0, 1), &[0.0, 1.0], &[0.0, 1.0], 0);
+    assert_eq!(pd2, vec![vec![1.0, -2.0], vec![3.0, 0.0]]);
+}
+
+fn squared_error(out: &[Value], y: &[f64]) -> Value {
+    Value::square(&Value::sub(&out[0], &Value::constant(y[0])))
+}
+
+// least squares fit of y = w x + b, set straight into a linear neuron
+fn fitted_line(xs: &[Vec<f64>], ys: &[Vec<f64>]) -> Layer {
+    let n = xs.len() as f64;
+    let mx = xs.iter().map(|x| x[0]).sum::<f64>() / n;
+    let my = ys.iter().map(|y| y[0]).sum::<f64>() / n;
+    let sxy: f64 = xs.iter().zip(ys.iter()).map(|(x, y)| (x[0] - mx) * (y[0] - my)).sum();
+    let sxx: f64 = xs.iter().map(|x| (x[0] - mx) * (x[0] - mx)).sum();
+    let w = sxy / sxx;
+    Layer::from_neurons(vec![Neuron {
+        w: vec![Value::new(w)],
+        b: Value::new(my - w * mx),
+        act: Activation::Linear,
+    }])
+}
+
+#[test]
+fn hessian_vector_product_of_least_squares() {
+    // the hessian of mean (w x + b - y)^2 is 2 mean([x, 1] [x, 1]^T)
+    let xs = vec![vec![1.0], vec![2.0], vec![4.0]];
+    let ys = vec![vec![1.0], vec![3.0], vec![2.0]];
+    let model = fitted_line(&xs, &ys);
+    let hv = explain::hessian_vector_product(&model, &squared_error, &xs, &ys, &[1.0, -2.0]);
+    // mean x^2 = 7, mean x = 7/3
+    let expected = [2.0 * (7.0 - 2.0 * 7.0 / 3.0), 2.0 * (7.0 / 3.0 - 2.0)];
+    for (a, b) in hv.iter().zip(expected.iter()) {
+        assert!((a - b).abs() < 1e-5, "{:?} vs {:?}", hv, expected);
+    }
+}
+
+#[test]
+fn an_outlier_is_the_most_harmful_training_point() {
+    let mut xs: Vec<Vec<f64>> = (0..10).map(|i| vec![i as f64 / 3.0]).collect();
+    let mut ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![2.0 * x[0] + 1.0]).collect();
+    xs.push(vec![3.0]);
+    ys.push(vec![-4.0]);
+    let model = fitted_line(&xs, &ys);
+    let scores = explain::influence(&model, &squared_error, &xs, &ys, &[3.0], &[7.0], 0.0);
+    let worst = (0..scores.len()).max_by(|&a, &b| scores[a].total_cmp(&scores[b])).unwrap();
+    assert_eq!(worst, 10, "{:?}", scores);
+    assert!(scores[10] > 0.0);
+}
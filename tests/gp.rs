@@ -0,0 +1,56 @@
+use rust_ml::gp::{GaussianProcess, Kernel};
+use rust_ml::matrix::Matrix;
+use rust_ml::random;
+
+// noisy samples of sin on [0, 5]
+fn sine(n: usize, noise: f64) -> (Matrix, Vec<f64>) {
+    let xs: Vec<f64> = (0..n).map(|i| 5.0 * i as f64 / (n - 1) as f64).collect();
+    let ys = xs.iter().map(|x| x.sin() + random::normal(0.0, noise)).collect();
+    (Matrix::new(n, 1, xs), ys)
+}
+
+#[test]
+fn optimize_never_lowers_the_marginal_likelihood() {
+    random::seed(1);
+    let (x, y) = sine(25, 0.1);
+    for kernel in [Kernel::Rbf, Kernel::Matern32, Kernel::Matern52] {
+        let mut gp = GaussianProcess::new(kernel);
+        // a poor start, far too short a lengthscale
+        gp.lengthscale = 0.05;
+        gp.fit(&x, &y).unwrap();
+        let before = gp.log_marginal_likelihood(&y).unwrap();
+        let after = gp.optimize(&x, &y, 30, 0.05).unwrap();
+        assert!(after > before + 1.0, "{:?}: {} -> {}", kernel, before, after);
+        assert!((gp.log_marginal_likelihood(&y).unwrap() - after).abs() < 1e-9);
+        assert!(gp.lengthscale > 0.05);
+    }
+}
+
+#[test]
+fn predict_interpolates_the_training_points() {
+    random::seed(2);
+    let (x, y) = sine(12, 0.0);
+    let mut gp = GaussianProcess::new(Kernel::Rbf);
+    gp.noise = 1e-8;
+    gp.fit(&x, &y).unwrap();
+    let (mean, var) = gp.predict(&x).unwrap();
+    for i in 0..y.len() {
+        assert!((mean[i] - y[i]).abs() < 1e-4, "{}: {} vs {}", i, mean[i], y[i]);
+        assert!(var[i] < 1e-4, "{}: variance {}", i, var[i]);
+    }
+    // far from the data the prior variance comes back
+    let (_, far) = gp.predict(&Matrix::new(1, 1, vec![50.0])).unwrap();
+    assert!((far[0] - gp.variance).abs() < 1e-6);
+}
+
+#[test]
+fn optimize_handles_a_thousand_points() {
+    // used to build an n^2 / 2 deep graph per step, which overflowed the stack on drop
+    random::seed(3);
+    let (x, y) = sine(1000, 0.1);
+    let mut gp = GaussianProcess::new(Kernel::Rbf);
+    gp.fit(&x, &y).unwrap();
+    let before = gp.log_marginal_likelihood(&y).unwrap();
+    let after = gp.optimize(&x, &y, 1, 1e-4).unwrap();
+    assert!(after >= before);
+}
@@ -0,0 +1,74 @@
+use rust_ml::graph::{self, JsonGraph};
+use rust_ml::value::Value;
+
+#[test]
+fn json_export_lists_every_node_once() {
+    let a = Value::new(2.0);
+    let b = Value::constant(3.0);
+    let c = Value::mul(&a, &b);
+    let d = Value::add(&c, &a);
+    d.backward();
+
+    let g = JsonGraph::from_value(&d);
+    assert_eq!(g.nodes.len(), 4);
+    assert_eq!(g.root, 3);
+    assert_eq!(g.nodes[3].op, "+");
+    assert_eq!(g.nodes[3].data, 8.0);
+    assert_eq!(g.edges.len(), 4);
+    // a feeds both the product and the sum
+    let a_id = g.nodes.iter().position(|n| n.data == 2.0).unwrap();
+    assert_eq!(g.edges.iter().filter(|e| e.from == a_id).count(), 2);
+    assert_eq!(g.nodes[a_id].grad, 4.0);
+
+    let parsed: JsonGraph = serde_json::from_str(&graph::to_json(&d).unwrap()).unwrap();
+    assert_eq!(parsed, g);
+    assert!(graph::to_dot(&d).starts_with("digraph"));
+}
+
+#[test]
+fn traced_backward_follows_the_chain_rule() {
+    // e = a * (b + c)
+    let a = Value::new(2.0);
+    let b = Value::new(3.0);
+    let c = Value::new(-1.0);
+    let s = Value::add(&b, &c);
+    let e = Value::mul(&a, &s);
+    let steps = e.backward_traced();
+
+    let summary: Vec<(String, f64, f64)> = steps.iter().map(|st| (st.node.0.borrow().op.to_string(), st.local_grad, st.contribution)).collect();
+    assert_eq!(summary, vec![
+        ("*".to_string(), 2.0, 2.0),
+        ("*".to_string(), 2.0, 2.0),
+        ("+".to_string(), 1.0, 2.0),
+        ("+".to_string(), 1.0, 2.0),
+    ]);
+    assert!(steps[0].child == a && steps[1].child == s);
+    assert_eq!((a.get_grad(), b.get_grad(), c.get_grad()), (2.0, 2.0, 2.0));
+
+    // an aliased operand shows up once with the summed contribution
+    let x = Value::new(3.0);
+    let steps = Value::add(&x, &x).backward_traced();
+    assert_eq!(steps.len(), 1);
+    assert_eq!(steps[0].contribution, 2.0);
+}
+
+#[test]
+fn graph_equal_compares_structure_not_identity() {
+    let build = |x: f64, y: f64| {
+        let a = Value::new(x);
+        let b = Value::new(y);
+        Value::tanh(&Value::add(&Value::mul(&a, &b), &a))
+    };
+    assert!(Value::graph_equal(&build(1.0, 2.0), &build(1.0, 2.0), 0.0));
+    assert!(Value::graph_equal(&build(1.0, 2.0), &build(1.0 + 1e-12, 2.0), 1e-9));
+    assert!(!Value::graph_equal(&build(1.0, 2.0), &build(1.5, 2.0), 1e-9));
+
+    // same values, but a different op
+    let a = Value::new(2.0);
+    assert!(!Value::graph_equal(&Value::add(&a, &a), &Value::mul(&a, &Value::new(2.0)), 0.0));
+
+    // a + a shares its operand, a + b with equal values doesn't
+    let b = Value::new(2.0);
+    assert!(!Value::graph_equal(&Value::add(&a, &a), &Value::add(&a, &b), 0.0));
+    assert!(!Value::graph_equal(&Value::add(&a, &b), &Value::add(&a, &a), 0.0));
+}
@@ -0,0 +1,45 @@
+use rust_ml::error::RustMlError;
+use rust_ml::manifold::Tsne;
+use rust_ml::plot;
+use rust_ml::random;
+
+// three well separated blobs in 10-d, 30 points each, in order
+fn blobs() -> Vec<Vec<f64>> {
+    (0..90).map(|i| (0..10).map(|d| if d == i / 30 { 8.0 } else { 0.0 } + random::normal(0.0, 0.5)).collect()).collect()
+}
+
+fn dist(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+// every point's nearest neighbour in the embedding comes from its own blob
+fn neighbours_agree(y: &[(f64, f64)]) -> bool {
+    (0..y.len()).all(|i| {
+        let nearest = (0..y.len()).filter(|&j| j != i).min_by(|&a, &b| dist(y[i], y[a]).total_cmp(&dist(y[i], y[b]))).unwrap();
+        nearest / 30 == i / 30
+    })
+}
+
+#[test]
+fn tsne_keeps_blobs_apart_exact_and_barnes_hut() {
+    random::seed(70);
+    let xs = blobs();
+    for theta in [0.0, 0.5] {
+        let mut tsne = Tsne::new().with_perplexity(10.0).with_iterations(400).with_theta(theta);
+        let y = tsne.fit_transform(&xs).unwrap();
+        assert_eq!(y.len(), 90);
+        assert!(neighbours_agree(&y), "theta = {}", theta);
+        assert!(tsne.kl_divergence.is_finite() && tsne.kl_divergence < 1.0, "theta = {}: kl {}", theta, tsne.kl_divergence);
+
+        let points: Vec<(f64, f64, usize)> = y.iter().enumerate().map(|(i, p)| (p.0, p.1, i / 30)).collect();
+        let svg = plot::scatter("t-sne", &points);
+        assert_eq!(svg.matches("<circle").count(), 90);
+    }
+}
+
+#[test]
+fn tsne_needs_enough_rows_for_the_perplexity() {
+    let xs: Vec<Vec<f64>> = (0..20).map(|i| vec![i as f64]).collect();
+    assert!(matches!(Tsne::new().fit_transform(&xs), Err(RustMlError::Config(_))));
+    assert!(matches!(Tsne::new().with_perplexity(2.0).fit_transform(&[vec![1.0], vec![1.0, 2.0]]), Err(RustMlError::Shape(_))));
+}
@@ -0,0 +1,210 @@
+use rust_ml::error::RustMlError;
+use rust_ml::matrix::Matrix;
+use rust_ml::value::Value;
+
+#[test]
+fn scalar_loss_on_a_matrix_element_reaches_the_matrix_graph() {
+    // loss = 3 * square(m)[0][1], d loss / d m[0][1] = 6 m[0][1]
+    let m = Matrix::new(2, 2, vec![1.0, 2.0, 3.0, 4.0]);
+    let sq = Matrix::square(&m);
+    let e = Value::from_matrix(&sq, 0, 1);
+    assert_eq!(e.get_data(), 4.0);
+    Value::mul(&e, &Value::constant(3.0)).backward();
+    assert_eq!(sq.get_grad(), vec![0.0, 3.0, 0.0, 0.0]);
+    assert_eq!(m.get_grad(), vec![0.0, 12.0, 0.0, 0.0]);
+}
+
+#[test]
+fn matrix_built_from_values_routes_gradients_back() {
+    // sum(cube([a, a * b])) with a = 2, b = 3
+    let a = Value::new(2.0);
+    let b = Value::new(3.0);
+    let ab = Value::mul(&a, &b);
+    let m = Matrix::from_values(1, 2, &[a.clone_rc(), ab]);
+    Matrix::cube(&m).backward();
+    // d/da = 3 a^2 + 3 (ab)^2 b = 12 + 324, d/db = 3 (ab)^2 a = 216
+    assert_eq!(a.get_grad(), 336.0);
+    assert_eq!(b.get_grad(), 216.0);
+}
+
+#[test]
+fn elements_of_dependent_matrices_are_propagated_once() {
+    // loss = m[0] + square(m)[0], d/dm[0] = 1 + 2 m[0]
+    let m = Matrix::new(1, 1, vec![5.0]);
+    let sq = Matrix::square(&m);
+    let loss = Value::add(&Value::from_matrix(&m, 0, 0), &Value::from_matrix(&sq, 0, 0));
+    loss.backward();
+    assert_eq!(m.get_grad(), vec![11.0]);
+}
+
+#[test]
+fn graphs_crossing_between_engines_several_times_are_one_backward_pass() {
+    // x -> [x] -> square -> x^2 -> x^2 * x -> [x^3] -> square -> x^6, d/dx = 6 x^5
+    let x = Value::new(2.0);
+    let y = Value::from_matrix(&Matrix::square(&Matrix::from_values(1, 1, &[x.clone_rc()])), 0, 0);
+    let z = Value::mul(&y, &x);
+    let out = Value::from_matrix(&Matrix::square(&Matrix::from_values(1, 1, &[z])), 0, 0);
+    assert_eq!(out.get_data(), 64.0);
+    out.backward();
+    assert_eq!(x.get_grad(), 192.0);
+}
+
+#[test]
+fn row_argmax_and_topk_ties() {
+    let m = Matrix::new(3, 3, vec![
+        2.0, 2.0, 1.0,
+        0.0, -1.0, 0.0,
+        5.0, 7.0, 7.0,
+    ]);
+    assert_eq!(m.argmax_rows(), vec![0, 0, 1]);
+    assert_eq!(m.topk_rows(2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+    assert_eq!(m.topk_rows(3)[1], vec![0, 2, 1]);
+}
+
+// plain row-major product, for checking decompositions
+fn matmul(a: &Matrix, b: &Matrix) -> Matrix {
+    let (n, k, m) = (a.rows(), a.cols(), b.cols());
+    assert_eq!(k, b.rows());
+    let data = (0..n * m).map(|idx| (0..k).map(|p| a.get(idx / m, p) * b.get(p, idx % m)).sum()).collect();
+    Matrix::new(n, m, data)
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    Matrix::new(a.cols(), a.rows(), (0..a.rows() * a.cols()).map(|idx| a.get(idx % a.rows(), idx / a.rows())).collect())
+}
+
+fn assert_close(a: &Matrix, b: &Matrix, tol: f64) {
+    assert_eq!((a.rows(), a.cols()), (b.rows(), b.cols()));
+    for (x, y) in a.get_data().iter().zip(b.get_data().iter()) {
+        assert!((x - y).abs() < tol, "{} vs {}", a, b);
+    }
+}
+
+#[test]
+fn det_inverse_and_solve() {
+    let a = Matrix::new(3, 3, vec![
+        2.0, 1.0, 1.0,
+        4.0, -6.0, 0.0,
+        -2.0, 7.0, 2.0,
+    ]);
+    assert!((a.det().unwrap() - -16.0).abs() < 1e-12);
+    // a row swap flips the sign
+    assert!((Matrix::new(2, 2, vec![0.0, 1.0, 1.0, 0.0]).det().unwrap() + 1.0).abs() < 1e-12);
+    assert_eq!(Matrix::identity(4).det().unwrap(), 1.0);
+
+    let inv = Matrix::new(2, 2, vec![4.0, 7.0, 2.0, 6.0]).inverse().unwrap();
+    assert_close(&inv, &Matrix::new(2, 2, vec![0.6, -0.7, -0.2, 0.4]), 1e-12);
+    assert_close(&matmul(&a, &a.inverse().unwrap()), &Matrix::identity(3), 1e-12);
+
+    // x = (1, 1, 2) and (1, 0, 0) as two right hand sides
+    let b = Matrix::new(3, 2, vec![5.0, 2.0, -2.0, 4.0, 9.0, -2.0]);
+    let x = Matrix::solve(&a, &b).unwrap();
+    assert_close(&x, &Matrix::new(3, 2, vec![1.0, 1.0, 1.0, 0.0, 2.0, 0.0]), 1e-12);
+
+    let singular = Matrix::new(3, 3, vec![1.0, 2.0, 3.0, 2.0, 4.0, 6.0, 1.0, 0.0, 1.0]);
+    assert_eq!(singular.det().unwrap(), 0.0);
+    assert!(matches!(singular.inverse(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::solve(&singular, &Matrix::zeros(3, 1)), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::zeros(2, 3).det(), Err(RustMlError::Shape(_))));
+    assert!(matches!(Matrix::solve(&a, &Matrix::zeros(2, 1)), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn cholesky_factors_spd_matrices() {
+    let a = Matrix::new(3, 3, vec![
+        4.0, 12.0, -16.0,
+        12.0, 37.0, -43.0,
+        -16.0, -43.0, 98.0,
+    ]);
+    let l = a.cholesky().unwrap();
+    assert_close(&l, &Matrix::new(3, 3, vec![2.0, 0.0, 0.0, 6.0, 1.0, 0.0, -8.0, 5.0, 3.0]), 1e-12);
+    assert_close(&matmul(&l, &transpose(&l)), &a, 1e-12);
+
+    let b = Matrix::new(3, 1, vec![1.0, 2.0, 3.0]);
+    assert_close(&Matrix::cholesky_solve(&l, &b).unwrap(), &Matrix::solve(&a, &b).unwrap(), 1e-9);
+
+    // indefinite, and not symmetric
+    assert!(matches!(Matrix::new(2, 2, vec![1.0, 2.0, 2.0, 1.0]).cholesky(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::new(2, 2, vec![0.0, 0.0, 0.0, 1.0]).cholesky(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::new(2, 2, vec![2.0, 1.0, 0.0, 2.0]).cholesky(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::zeros(2, 3).cholesky(), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn qr_and_least_squares() {
+    let a = Matrix::new(4, 3, vec![
+        1.0, 2.0, 0.0,
+        0.0, 1.0, 1.0,
+        1.0, 0.0, 3.0,
+        2.0, 1.0, -1.0,
+    ]);
+    let (q, r) = a.qr().unwrap();
+    assert_close(&matmul(&transpose(&q), &q), &Matrix::identity(3), 1e-12);
+    assert_close(&matmul(&q, &r), &a, 1e-12);
+    for i in 0..3 {
+        assert!(r.get(i, i) > 0.0);
+        for j in 0..i {
+            assert_eq!(r.get(i, j), 0.0);
+        }
+    }
+
+    // third column is the sum of the first two
+    let rank_deficient = Matrix::new(3, 3, vec![1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 2.0]);
+    assert!(matches!(rank_deficient.qr(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::zeros(2, 3).qr(), Err(RustMlError::Shape(_))));
+
+    // line through noisy points: y = 1 + 2t fitted to 5 points, against the normal equations
+    let ts = [0.0, 1.0, 2.0, 3.0, 4.0];
+    let ys = [1.1, 2.9, 5.2, 6.8, 9.1];
+    let design = Matrix::new(5, 2, ts.iter().flat_map(|t| [1.0, *t]).collect());
+    let y = Matrix::new(5, 1, ys.to_vec());
+    let x = Matrix::lstsq(&design, &y).unwrap();
+    let normal = Matrix::solve(&matmul(&transpose(&design), &design), &matmul(&transpose(&design), &y)).unwrap();
+    assert_close(&x, &normal, 1e-9);
+    assert!((x.get(0, 0) - 1.04).abs() < 1e-9 && (x.get(1, 0) - 1.99).abs() < 1e-9);
+    // an exact system is solved exactly
+    let exact = Matrix::new(5, 1, ts.iter().map(|t| 1.0 + 2.0 * t).collect());
+    assert_close(&Matrix::lstsq(&design, &exact).unwrap(), &Matrix::new(2, 1, vec![1.0, 2.0]), 1e-12);
+    assert!(matches!(Matrix::lstsq(&design, &Matrix::zeros(4, 1)), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn eigh_decomposes_symmetric_matrices() {
+    let a = Matrix::new(3, 3, vec![
+        2.0, -1.0, 0.0,
+        -1.0, 2.0, -1.0,
+        0.0, -1.0, 2.0,
+    ]);
+    let (values, vectors) = a.eigh().unwrap();
+    let expected = [2.0 + 2f64.sqrt(), 2.0, 2.0 - 2f64.sqrt()];
+    for (got, want) in values.iter().zip(expected.iter()) {
+        assert!((got - want).abs() < 1e-9, "{} vs {}", got, want);
+    }
+    assert!(values.windows(2).all(|w| w[0] >= w[1]));
+    for (k, lambda) in values.iter().enumerate() {
+        let v = Matrix::new(3, 1, (0..3).map(|i| vectors.get(i, k)).collect());
+        let scaled = Matrix::new(3, 1, v.get_data().iter().map(|x| x * lambda).collect());
+        assert_close(&matmul(&a, &v), &scaled, 1e-9);
+    }
+    assert_close(&matmul(&transpose(&vectors), &vectors), &Matrix::identity(3), 1e-9);
+
+    assert!(matches!(Matrix::new(2, 2, vec![1.0, 2.0, 0.0, 1.0]).eigh(), Err(RustMlError::Numerical(_))));
+    assert!(matches!(Matrix::zeros(2, 3).eigh(), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn svd_reconstructs_tall_and_wide_inputs() {
+    let tall = Matrix::new(4, 2, vec![3.0, 1.0, 1.0, 3.0, 0.0, 2.0, -1.0, 0.5]);
+    let wide = transpose(&tall);
+    for a in [tall, wide] {
+        let (u, s, v) = a.svd().unwrap();
+        let k = s.len();
+        assert_eq!(k, 2);
+        assert_eq!((u.rows(), u.cols(), v.rows(), v.cols()), (a.rows(), k, a.cols(), k));
+        assert!(s.windows(2).all(|w| w[0] >= w[1]) && s[k - 1] >= 0.0);
+        let us = Matrix::new(u.rows(), k, (0..u.rows() * k).map(|idx| u.get(idx / k, idx % k) * s[idx % k]).collect());
+        assert_close(&matmul(&us, &transpose(&v)), &a, 1e-9);
+        assert_close(&matmul(&transpose(&u), &u), &Matrix::identity(k), 1e-9);
+        assert_close(&matmul(&transpose(&v), &v), &Matrix::identity(k), 1e-9);
+    }
+}
@@ -0,0 +1,65 @@
+use rust_ml::error::RustMlError;
+use rust_ml::matrix::Matrix;
+use rust_ml::mixture::{CovarianceType, GaussianMixture};
+use rust_ml::random;
+
+// 150 samples from each of two well separated gaussians, the second one correlated
+fn two_gaussians() -> Matrix {
+    let mut data = vec![];
+    for _ in 0..150 {
+        data.extend([random::normal(0.0, 1.0), random::normal(0.0, 0.5)]);
+    }
+    for _ in 0..150 {
+        let z = random::normal(0.0, 1.0);
+        data.extend([6.0 + z, 6.0 + 0.9 * z + random::normal(0.0, 0.2)]);
+    }
+    Matrix::new(300, 2, data)
+}
+
+#[test]
+fn em_recovers_two_components() {
+    random::seed(60);
+    let x = two_gaussians();
+    let mut gmm = GaussianMixture::new(2);
+    gmm.fit(&x).unwrap();
+    assert!(gmm.converged);
+    // em never decreases the likelihood
+    assert!(gmm.log_likelihoods.windows(2).all(|w| w[1] >= w[0] - 1e-9), "{:?}", gmm.log_likelihoods);
+
+    let far = if gmm.means[0][0] > gmm.means[1][0] { 0 } else { 1 };
+    assert!((gmm.means[far][0] - 6.0).abs() < 0.3 && (gmm.means[1 - far][0]).abs() < 0.3, "{:?}", gmm.means);
+    assert!((gmm.weights[0] - 0.5).abs() < 0.05);
+    // the correlated component keeps its off-diagonal covariance
+    assert!(gmm.covariances[far].get(0, 1) > 0.6, "{}", gmm.covariances[far].get(0, 1));
+
+    let labels = gmm.predict(&x).unwrap();
+    assert!(labels[..150].iter().all(|l| *l != far) && labels[150..].iter().all(|l| *l == far));
+    let proba = gmm.predict_proba(&x).unwrap();
+    assert_eq!((proba.rows(), proba.cols()), (300, 2));
+    assert!((0..300).all(|i| (proba.get(i, 0) + proba.get(i, 1) - 1.0).abs() < 1e-9));
+    let scores = gmm.score_samples(&x).unwrap();
+    assert!((scores.iter().sum::<f64>() / 300.0 - gmm.score(&x).unwrap()).abs() < 1e-9);
+}
+
+#[test]
+fn diagonal_covariances_drop_correlations() {
+    random::seed(61);
+    let x = two_gaussians();
+    let mut full = GaussianMixture::new(2);
+    let mut diag = GaussianMixture::new(2).with_covariance_type(CovarianceType::Diag);
+    full.fit(&x).unwrap();
+    diag.fit(&x).unwrap();
+    assert!(diag.covariances.iter().all(|c| c.get(0, 1) == 0.0 && c.get(1, 0) == 0.0));
+    // fewer parameters, so a worse fit of the correlated component
+    assert!(diag.score(&x).unwrap() < full.score(&x).unwrap());
+}
+
+#[test]
+fn mixture_needs_fitting_and_enough_rows() {
+    let x = Matrix::new(2, 1, vec![0.0, 1.0]);
+    assert!(matches!(GaussianMixture::new(1).predict(&x), Err(RustMlError::NotFitted)));
+    assert!(matches!(GaussianMixture::new(3).fit(&x), Err(RustMlError::Config(_))));
+    let mut gmm = GaussianMixture::new(1);
+    gmm.fit(&x).unwrap();
+    assert!(matches!(gmm.predict(&Matrix::new(1, 2, vec![0.0, 0.0])), Err(RustMlError::Shape(_))));
+}
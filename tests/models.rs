@@ -0,0 +1,138 @@
+use rust_ml::error::RustMlError;
+use rust_ml::matrix::Matrix;
+use rust_ml::models::{BayesianLinearRegression, SgdClassifier, SgdRegressor};
+use rust_ml::random;
+
+// rows around one center per class
+fn classes(centers: &[(f64, f64)], per_class: usize) -> (Matrix, Vec<usize>) {
+    let mut data = vec![];
+    let mut labels = vec![];
+    for (c, (cx, cy)) in centers.iter().enumerate() {
+        for _ in 0..per_class {
+            data.extend([cx + random::normal(0.0, 0.3), cy + random::normal(0.0, 0.3)]);
+            labels.push(c);
+        }
+    }
+    (Matrix::new(labels.len(), 2, data), labels)
+}
+
+#[test]
+fn sgd_regressor_learns_from_chunks_as_well_as_at_once() {
+    random::seed(80);
+    let n = 200;
+    let data: Vec<f64> = (0..2 * n).map(|_| random::normal(0.0, 1.0)).collect();
+    let x = Matrix::new(n, 2, data);
+    let y: Vec<f64> = (0..n).map(|i| 3.0 * x.get(i, 0) - 2.0 * x.get(i, 1) + 1.0).collect();
+
+    let mut streamed = SgdRegressor::new(0.05, 0.0);
+    for _ in 0..5 {
+        for chunk in 0..4 {
+            let rows: Vec<f64> = (chunk * 50..(chunk + 1) * 50).flat_map(|i| [x.get(i, 0), x.get(i, 1)]).collect();
+            streamed.partial_fit(&Matrix::new(50, 2, rows), &y[chunk * 50..(chunk + 1) * 50]).unwrap();
+        }
+    }
+    assert!((streamed.weights[0] - 3.0).abs() < 0.05 && (streamed.weights[1] + 2.0).abs() < 0.05, "{:?}", streamed.weights);
+    assert!((streamed.intercept - 1.0).abs() < 0.05);
+
+    let mut batch = SgdRegressor::new(0.05, 0.0).with_epochs(5);
+    batch.fit(&x, &y).unwrap();
+    let (a, b) = (streamed.predict(&x).unwrap(), batch.predict(&x).unwrap());
+    assert!(a.iter().zip(b.iter()).all(|(u, v)| (u - v).abs() < 0.1));
+    assert!(matches!(SgdRegressor::new(0.1, 0.0).predict(&x), Err(RustMlError::NotFitted)));
+}
+
+#[test]
+fn sgd_classifier_picks_up_classes_it_has_not_seen() {
+    random::seed(81);
+    let centers = [(0.0, 0.0), (3.0, 0.0), (0.0, 3.0)];
+    let (x, labels) = classes(&centers[..2], 50);
+    let mut model = SgdClassifier::new(0.1, 1e-4);
+    for _ in 0..5 {
+        model.partial_fit(&x, &labels).unwrap();
+    }
+    assert_eq!(model.n_classes(), 2);
+    assert_eq!(model.predict(&x).unwrap(), labels);
+
+    // a third class shows up later: the model grows, keeps the old ones and learns the new one
+    let (x3, labels3) = classes(&centers, 50);
+    for _ in 0..5 {
+        model.partial_fit(&x3, &labels3).unwrap();
+    }
+    assert_eq!(model.n_classes(), 3);
+    let predicted = model.predict(&x3).unwrap();
+    let correct = predicted.iter().zip(labels3.iter()).filter(|(p, l)| p == l).count();
+    assert!(correct >= 145, "{} of 150", correct);
+    let proba = model.predict_proba(&x3).unwrap();
+    assert!((0..150).all(|i| ((0..3).map(|c| proba.get(i, c)).sum::<f64>() - 1.0).abs() < 1e-9));
+
+    assert!(matches!(model.partial_fit(&Matrix::new(1, 3, vec![0.0; 3]), &[0]), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn bayesian_regression_matches_the_closed_form() {
+    random::seed(3);
+    let (n, d) = (20, 3);
+    let x = Matrix::new(n, d, (0..n * d).map(|_| random::normal(0.0, 1.0)).collect());
+    let y: Vec<f64> = (0..n).map(|i| 1.5 * x.get(i, 0) - 2.0 * x.get(i, 1) + 0.5 * x.get(i, 2) + random::normal(0.0, 0.1)).collect();
+    let (alpha, beta) = (0.5, 4.0);
+
+    let mut model = BayesianLinearRegression::new(alpha, beta).unwrap();
+    model.fit_intercept = false;
+    model.fit(&x, &y).unwrap();
+
+    // S = (alpha I + beta X^T X)^-1, m = S beta X^T y
+    let mut p = vec![0.0; d * d];
+    let mut xty = vec![0.0; d];
+    for a in 0..d {
+        p[a * d + a] += alpha;
+        for b in 0..d {
+            p[a * d + b] += beta * (0..n).map(|i| x.get(i, a) * x.get(i, b)).sum::<f64>();
+        }
+        xty[a] = beta * (0..n).map(|i| x.get(i, a) * y[i]).sum::<f64>();
+    }
+    let s = Matrix::new(d, d, p).inverse().unwrap();
+    let m: Vec<f64> = (0..d).map(|a| (0..d).map(|b| s.get(a, b) * xty[b]).sum()).collect();
+
+    for (got, want) in model.mean.iter().zip(m.iter()) {
+        assert!((got - want).abs() < 1e-9, "{} vs {}", got, want);
+    }
+    let cov = model.covariance().unwrap();
+    for a in 0..d {
+        for b in 0..d {
+            assert!((cov.get(a, b) - s.get(a, b)).abs() < 1e-9);
+        }
+    }
+
+    // predictive variance is the noise plus x^T S x
+    let row = Matrix::new(1, d, vec![0.3, -0.2, 1.0]);
+    let (mu, var) = model.predict(&row).unwrap();
+    let r = row.get_data();
+    let quad: f64 = (0..d).map(|a| (0..d).map(|b| r[a] * s.get(a, b) * r[b]).sum::<f64>()).sum();
+    assert!((mu[0] - (0..d).map(|a| r[a] * m[a]).sum::<f64>()).abs() < 1e-9);
+    assert!((var[0] - (1.0 / beta + quad)).abs() < 1e-9);
+}
+
+#[test]
+fn bayesian_regression_update_on_chunks_equals_one_fit() {
+    random::seed(4);
+    let (n, d) = (30, 2);
+    let x = Matrix::new(n, d, (0..n * d).map(|_| random::normal(0.0, 1.0)).collect());
+    let y: Vec<f64> = (0..n).map(|i| 0.7 - x.get(i, 0) + 3.0 * x.get(i, 1) + random::normal(0.0, 0.2)).collect();
+    let rows = |lo: usize, hi: usize| Matrix::new(hi - lo, d, (lo * d..hi * d).map(|k| x.get(k / d, k % d)).collect());
+
+    let mut whole = BayesianLinearRegression::new(1.0, 25.0).unwrap();
+    whole.fit(&x, &y).unwrap();
+
+    let mut chunked = BayesianLinearRegression::new(1.0, 25.0).unwrap();
+    chunked.fit(&rows(0, 12), &y[..12]).unwrap();
+    chunked.update(&rows(12, n), &y[12..]).unwrap();
+
+    assert_eq!(chunked.mean.len(), d + 1);
+    for (a, b) in chunked.mean.iter().zip(whole.mean.iter()) {
+        assert!((a - b).abs() < 1e-9, "{} vs {}", a, b);
+    }
+    let (pa, pb) = (chunked.precision.unwrap().get_data(), whole.precision.unwrap().get_data());
+    for (a, b) in pa.iter().zip(pb.iter()) {
+        assert!((a - b).abs() < 1e-9);
+    }
+}
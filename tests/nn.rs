@@ -0,0 +1,392 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust_ml::error::RustMlError;
+use rust_ml::graph;
+use rust_ml::nn::{self, Activation, DeepClone, Dropout, Embedding, FeatureExtractor, Layer, LayerId, MLP, Module, Neuron, Sequential};
+use rust_ml::optim::{Adam, Optimizer, Sgd};
+use rust_ml::prune;
+use rust_ml::random;
+use rust_ml::serialize::{self, ModelBundle, ModelInfo};
+use rust_ml::uncertainty;
+use rust_ml::value::{Op, Value};
+
+// second layer reuses the first layer's weights and biases (tied weights)
+fn tied_model() -> Sequential {
+    let first = Layer::with_activation(2, 2, Activation::Linear);
+    let tied = first.neurons().iter().map(|n| Neuron {
+        w: n.w.iter().map(|w| w.clone_rc()).collect(),
+        b: n.b.clone_rc(),
+        act: Activation::Linear,
+    }).collect();
+    let second = Layer::from_neurons(tied);
+    Sequential::new(vec![Box::new(first), Box::new(second)])
+}
+
+#[test]
+fn tied_parameters_are_listed_once() {
+    let model = tied_model();
+    assert_eq!(model.parameters().len(), 6);
+}
+
+#[test]
+fn tied_parameters_are_updated_once_per_step() {
+    let model = tied_model();
+    let params = model.parameters();
+    let before: Vec<f64> = params.iter().map(|p| p.get_data()).collect();
+
+    let x = [Value::new(1.0), Value::new(-2.0)];
+    let mut opt = Sgd::new(model.parameters(), 0.1);
+    let mut grads = vec![];
+    opt.step(&mut || {
+        model.zero_grad();
+        let out = model.forward(&x);
+        let loss = Value::add(&out[0], &out[1]);
+        loss.backward();
+        grads = params.iter().map(|p| p.get_grad()).collect();
+        loss.get_data()
+    });
+
+    for i in 0..params.len() {
+        let expected = before[i] - 0.1 * grads[i];
+        assert!((params[i].get_data() - expected).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn predict_matches_forward() {
+    let model = Sequential::new(vec![
+        Box::new(MLP::with_activation(&[3, 4], Activation::Gelu)),
+        Box::new(Dropout::new(0.5)),
+        Box::new(Layer::with_activation(4, 4, Activation::Elu(0.7))),
+        Box::new(Activation::Silu),
+        Box::new(Layer::with_activation(4, 2, Activation::Softplus)),
+        Box::new(Layer::with_activation(2, 3, Activation::LeakyRelu(0.1))),
+        Box::new(Activation::Relu),
+        Box::new(Layer::with_activation(3, 2, Activation::Sigmoid)),
+    ]);
+    model.train(false);
+
+    let x = [0.3, -1.2, 2.5];
+    let xv: Vec<Value> = x.iter().map(|xi| Value::new(*xi)).collect();
+    let expected: Vec<f64> = model.forward(&xv).iter().map(|y| y.get_data()).collect();
+    let got = model.predict(&x);
+    assert_eq!(got.len(), expected.len());
+    for (g, e) in got.iter().zip(expected.iter()) {
+        assert!((g - e).abs() < 1e-12, "{} vs {}", g, e);
+    }
+}
+
+#[test]
+fn predict_batch_matches_predict() {
+    let model = MLP::with_activation(&[3, 8, 8, 2], Activation::Tanh);
+    let xs: Vec<Vec<f64>> = (0..64).map(|i| vec![i as f64 * 0.1, -(i as f64) * 0.05, 1.0]).collect();
+    let batch = model.predict_batch(&xs);
+    assert_eq!(batch.len(), xs.len());
+    for (x, y) in xs.iter().zip(batch.iter()) {
+        assert_eq!(&model.predict(x), y);
+    }
+}
+
+#[test]
+fn saved_mlp_round_trips() {
+    for act in [Activation::Silu, Activation::Relu, Activation::LeakyRelu(0.2), Activation::Sigmoid] {
+        let model = MLP::with_activation(&[3, 4, 2], act);
+        let loaded = serialize::from_json(&serialize::to_json(&model).unwrap()).unwrap();
+        let x = [0.5, -1.0, 2.0];
+        assert_eq!(model.predict(&x), loaded.predict(&x));
+    }
+}
+
+#[test]
+fn loading_a_malformed_model_fails() {
+    let json = r#"{"layers": [[{"w": [1.0, 2.0], "b": 0.0, "act": "Tanh"}], [{"w": [1.0, 2.0], "b": 0.0, "act": "Linear"}]]}"#;
+    assert!(serialize::from_json(json).is_err());
+}
+
+#[test]
+fn unversioned_models_still_load() {
+    let json = r#"{"layers": [[{"w": [1.0, 2.0], "b": 0.5, "act": "Linear"}]]}"#;
+    let model = serialize::from_json(json).unwrap();
+    assert_eq!(model.predict(&[1.0, 1.0]), vec![3.5]);
+}
+
+#[test]
+fn newer_format_versions_are_rejected() {
+    let json = r#"{"format_version": 99, "ops": ["dense"], "layers": []}"#;
+    let err = serialize::from_json(json).err().unwrap().to_string();
+    assert!(err.contains("version 99"), "{}", err);
+}
+
+#[test]
+fn unknown_ops_are_rejected() {
+    let json = r#"{"format_version": 1, "ops": ["dense", "swiglu"], "layers": []}"#;
+    let err = serialize::from_json(json).err().unwrap().to_string();
+    assert!(err.contains("swiglu"), "{}", err);
+}
+
+#[test]
+fn magnitude_pruning_zeroes_the_smallest_parameters() {
+    let model = MLP::new(&[4, 8, 1]);
+    let mut before: Vec<f64> = model.parameters().iter().map(|p| p.get_data().abs()).collect();
+    before.sort_by(|a, b| a.total_cmp(b));
+
+    let mask = prune::magnitude(&model, 0.5);
+    let report = prune::sparsity(&model);
+    assert_eq!(report.total, 49);
+    assert_eq!(report.zeros, 25);
+    assert_eq!(mask.sparsity(), 25.0 / 49.0);
+
+    // everything left is at least as large as the largest pruned value
+    let kept_min = model.parameters().iter().map(|p| p.get_data().abs()).filter(|a| *a > 0.0).fold(f64::INFINITY, f64::min);
+    assert!(kept_min >= before[24]);
+
+    // a training step moves pruned weights, reapplying the mask puts them back to zero
+    for p in model.parameters() {
+        p.update_data(0.1);
+    }
+    mask.apply();
+    assert_eq!(prune::sparsity(&model).zeros, 25);
+}
+
+#[test]
+fn sequential_shapes_are_inferred_through_every_layer() {
+    let model = Sequential::new(vec![
+        Box::new(Embedding::new(10, 3)),
+        Box::new(Layer::new(6, 4)),
+        Box::new(Dropout::new(0.1)),
+        Box::new(MLP::new(&[4, 5, 2])),
+    ]);
+    assert_eq!(model.input_shape(), None);
+    assert_eq!(model.output_shape(2).unwrap(), 2);
+    assert!(model.output_shape(3).is_err());
+}
+
+#[test]
+fn incompatible_layers_are_rejected_at_construction() {
+    let err = Sequential::try_new(vec![
+        Box::new(Layer::new(4, 8)),
+        Box::new(Activation::Tanh),
+        Box::new(Layer::new(9, 2)),
+    ]).err().unwrap();
+    assert_eq!(err.to_string(), "shape mismatch: layer 2: dense layer 9 -> 2 got 8 inputs");
+}
+
+#[test]
+fn forward_hooks_see_every_layer_output() {
+    let mlp = MLP::new(&[3, 4, 2]);
+    let seen = Rc::new(RefCell::new(vec![]));
+    let sink = seen.clone();
+    let handle = mlp.register_forward_hook(0, Box::new(move |i, y| sink.borrow_mut().push((i, y.to_vec()))));
+
+    let x = [0.5, -1.0, 2.0];
+    let out = mlp.predict(&x);
+    let hidden = mlp.layers()[0].predict(&x);
+    assert_eq!(*seen.borrow(), vec![(0usize, hidden.clone())]);
+    assert_eq!(mlp.layers()[1].predict(&hidden), out);
+
+    // forward on Values reports the same numbers, predict_batch goes through the hooks too
+    let xv: Vec<Value> = x.iter().map(|v| Value::new(*v)).collect();
+    mlp.forward(&xv);
+    mlp.predict_batch(&[x.to_vec(), x.to_vec()]);
+    assert_eq!(seen.borrow().len(), 4);
+    assert!(seen.borrow().iter().all(|(i, y)| *i == 0 && y.iter().zip(hidden.iter()).all(|(a, b)| (a - b).abs() < 1e-12)));
+
+    assert!(mlp.remove_forward_hook(handle));
+    assert!(!mlp.remove_forward_hook(handle));
+    mlp.predict(&x);
+    assert_eq!(seen.borrow().len(), 4);
+}
+
+#[test]
+fn hidden_states_extract_one_layer_per_row() {
+    let model = Sequential::new(vec![Box::new(Layer::new(2, 3)), Box::new(Activation::Softplus), Box::new(Layer::new(3, 1))]);
+    let xs = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![-1.0, 2.0]];
+    let features = model.hidden_states(&xs, 1);
+    assert_eq!(features.len(), 3);
+    for (x, f) in xs.iter().zip(features.iter()) {
+        assert_eq!(*f, model.layers[1].predict(&model.layers[0].predict(x)));
+        assert!(f.iter().all(|v| *v > 0.0));
+    }
+    // the capturing hook is gone afterwards
+    assert_eq!(model.hidden_states(&xs, 1), features);
+}
+
+#[test]
+fn features_cut_a_model_by_index_or_name() {
+    let mlp = MLP::new(&[2, 3, 3, 1]);
+    let x = [0.3, -0.7];
+    let first = mlp.layers()[0].predict(&x);
+    assert_eq!(mlp.features(&x, 0.into()).unwrap(), first);
+    assert_eq!(mlp.features(&x, "1".into()).unwrap(), mlp.layers()[1].predict(&first));
+    assert_eq!(mlp.features(&x, 2.into()).unwrap(), mlp.predict(&x));
+    assert!(matches!(mlp.features(&x, 3.into()), Err(RustMlError::Config(_))));
+
+    let model = Sequential::named(vec![("body", Box::new(MLP::new(&[2, 4]))), ("head", Box::new(Layer::new(4, 1)))]).unwrap();
+    assert_eq!(model.features(&x, "body".into()).unwrap(), model.layers[0].predict(&x));
+    assert_eq!(model.features(&x, LayerId::Name("head".to_string())).unwrap(), model.predict(&x));
+    assert!(matches!(model.features(&x, "tail".into()), Err(RustMlError::Config(_))));
+    assert!(Sequential::named(vec![("a", Box::new(Layer::new(2, 2))), ("a", Box::new(Layer::new(2, 2)))]).is_err());
+}
+
+#[test]
+fn frozen_feature_extractor_feeds_a_trainable_head() {
+    let body = MLP::new(&[2, 4, 3]);
+    let body_params = body.parameters();
+    let body_before: Vec<f64> = body_params.iter().map(|p| p.get_data()).collect();
+    let extractor = FeatureExtractor::new(Box::new(body), 0).unwrap();
+    assert!(extractor.parameters().is_empty());
+    let model = Sequential::new(vec![Box::new(extractor), Box::new(Layer::with_activation(4, 1, Activation::Linear))]);
+    // only the head's 4 weights and bias are trainable
+    assert_eq!(model.parameters().len(), 5);
+    let head_before = model.layers[1].get_flat_params();
+
+    let x = [Value::new(0.5), Value::new(-0.5)];
+    let mut opt = Sgd::new(model.parameters(), 0.1);
+    opt.step(&mut || {
+        model.zero_grad();
+        let out = model.forward(&x);
+        let loss = Value::mul(&out[0], &out[0]);
+        loss.backward();
+        loss.get_data()
+    });
+    assert_ne!(model.layers[1].get_flat_params(), head_before);
+    assert_eq!(body_params.iter().map(|p| p.get_data()).collect::<Vec<f64>>(), body_before);
+    assert!(body_params.iter().all(|p| p.get_grad() == 0.0));
+
+    assert!(FeatureExtractor::new(Box::new(MLP::new(&[2, 2])), "body").is_err());
+}
+
+#[test]
+fn bundles_carry_their_metadata() {
+    random::seed(7);
+    let model = MLP::new(&[2, 3, 1]);
+    let xs = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+    let ys = vec![vec![1.0], vec![-1.0]];
+    let info = ModelInfo::new()
+        .with_dataset(&xs, &ys)
+        .with_hyperparameter("lr", 0.05)
+        .with_hyperparameter("optimizer", "adam")
+        .with_metric("loss", 0.012);
+    assert_eq!(info.seed, Some(7));
+    assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+
+    let json = ModelBundle::new(model.deep_clone(), info.clone()).to_json().unwrap();
+    let loaded = ModelBundle::from_json(&json).unwrap();
+    assert_eq!(*loaded.info(), info);
+    assert_eq!(loaded.info().hyperparameters["optimizer"], "adam");
+    assert_eq!(loaded.model.predict(&[0.5, 0.5]), model.predict(&[0.5, 0.5]));
+    // a bundle is still a plain saved model, and a plain one loads as a bundle without metadata
+    assert!(serialize::from_json(&json).is_ok());
+    let plain = ModelBundle::from_json(&serialize::to_json(&model).unwrap()).unwrap();
+    assert_eq!(plain.info().dataset_hash, None);
+
+    // the hash follows the data, not just its shape
+    assert_eq!(serialize::dataset_hash(&xs, &ys), info.dataset_hash.unwrap());
+    assert_ne!(serialize::dataset_hash(&xs, &[vec![1.0], vec![1.0]]), serialize::dataset_hash(&xs, &ys));
+}
+
+#[test]
+fn neuron_forward_is_one_fused_node() {
+    random::seed(1);
+    let n = Neuron::with_activation(50, Activation::Tanh);
+    let x: Vec<Value> = (0..50).map(|i| Value::new(i as f64 / 50.0 - 0.5)).collect();
+    let y = n.forward(&x);
+    // the 50 weights, 50 inputs and the bias, one fused weighted sum and the tanh, no per-input nodes
+    assert_eq!(graph::topo_order(&y).len(), 103);
+    assert_eq!(y.0.borrow().op, Op::Tanh);
+    assert_eq!(y.0.borrow().children[0].0.borrow().op, Op::FmaSum);
+
+    let plain: Vec<f64> = x.iter().map(|v| v.get_data()).collect();
+    let dot = Value::dot(&n.w, &x);
+    assert!((y.get_data() - (dot.get_data() + n.b.get_data()).tanh()).abs() < 1e-12);
+    assert!((y.get_data() - n.predict(&plain)).abs() < 1e-12);
+}
+
+#[test]
+fn soft_update_moves_the_target_towards_the_source() {
+    random::seed(2);
+    let source = MLP::new(&[2, 3, 1]);
+    let target = source.deep_clone();
+    for p in source.parameters() {
+        p.update_data(1.0);
+    }
+    let (s, t) = (source.get_flat_params(), target.get_flat_params());
+    nn::soft_update(&target, &source, 0.1);
+    for ((new, s), t) in target.get_flat_params().iter().zip(s.iter()).zip(t.iter()) {
+        assert!((new - (0.1 * s + 0.9 * t)).abs() < 1e-12);
+    }
+    // the source isn't touched, and tau 1 is a hard copy
+    assert_eq!(source.get_flat_params(), s);
+    nn::soft_update(&target, &source, 1.0);
+    assert_eq!(target.get_flat_params(), s);
+}
+
+#[test]
+fn mc_dropout_samples_and_restores_the_mode() {
+    random::seed(7);
+    let model = Sequential::new(vec![
+        Box::new(Layer::with_activation(3, 8, Activation::Tanh)),
+        Box::new(Dropout::new(0.5)),
+        Box::new(Layer::with_activation(8, 2, Activation::Linear)),
+    ]);
+    let x = [0.5, -1.0, 2.0];
+
+    model.train(false);
+    assert_eq!(model.training_mode(), Some(false));
+    let before = model.predict(&x);
+    let (mean, var) = uncertainty::mc_dropout(&model, &x, 50);
+    assert_eq!(mean.len(), 2);
+    assert!(var.iter().all(|v| *v > 0.0), "dropout passes should disagree: {:?}", var);
+
+    // still in eval mode, so forward and predict are deterministic
+    assert_eq!(model.training_mode(), Some(false));
+    let xv: Vec<Value> = x.iter().map(|v| Value::constant(*v)).collect();
+    let a: Vec<f64> = model.forward(&xv).iter().map(|y| y.get_data()).collect();
+    let b: Vec<f64> = model.forward(&xv).iter().map(|y| y.get_data()).collect();
+    assert_eq!(a, b);
+    assert_eq!(model.predict(&x), before);
+
+    // a model that was training stays in training mode
+    model.train(true);
+    uncertainty::mc_dropout(&model, &x, 2);
+    assert_eq!(model.training_mode(), Some(true));
+    assert_eq!(Layer::new(3, 2).training_mode(), None);
+}
+
+#[test]
+fn sparse_adam_leaves_untouched_embedding_rows_alone() {
+    random::seed(11);
+    let emb = Embedding::new(50, 4);
+    let lr = 0.01;
+    let mut opt = Adam::new(emb.parameters(), lr).with_sparse(true);
+    // squared norm of the looked up rows, so every touched weight gets a nonzero gradient
+    let mut step = |ids: &[usize]| {
+        opt.step(&mut || {
+            emb.zero_grad();
+            let xs: Vec<Value> = ids.iter().map(|id| Value::constant(*id as f64)).collect();
+            let out = emb.forward(&xs);
+            let squares: Vec<Value> = out.iter().map(Value::square).collect();
+            let loss = Value::sum(&squares);
+            loss.backward();
+            loss.get_data()
+        })
+    };
+
+    let before: Vec<Vec<f64>> = emb.weights.iter().map(|row| row.iter().map(|w| w.get_data()).collect()).collect();
+    for _ in 0..20 {
+        step(&[3, 7]);
+    }
+    for (id, row) in emb.weights.iter().enumerate() {
+        let moved = row.iter().zip(before[id].iter()).any(|(w, b)| w.get_data() != *b);
+        assert_eq!(moved, id == 3 || id == 7, "row {}", id);
+    }
+
+    // row 12 is seen for the first time: its moments and step count start from zero, so lazy
+    // adam moves every weight by exactly lr (as on the very first step of plain adam)
+    step(&[12]);
+    for (w, b) in emb.weights[12].iter().zip(before[12].iter()) {
+        assert!(((w.get_data() - b).abs() - lr).abs() < 1e-9, "{} -> {}", b, w.get_data());
+    }
+    assert!(emb.weights[20].iter().zip(before[20].iter()).all(|(w, b)| w.get_data() == *b));
+}
@@ -0,0 +1,105 @@
+use rust_ml::blackbox::{CmaEs, EvolutionStrategy};
+use rust_ml::optim::{Lbfgs, Optimizer};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+// sum (x_i - i)^2, minimum 0 at (0, 1, 2, ...)
+fn quadratic(xs: &[f64]) -> f64 {
+    xs.iter().enumerate().map(|(i, x)| (x - i as f64).powi(2)).sum()
+}
+
+// minimum 0 at (1, 1) at the end of a long curved valley
+fn rosenbrock(x: f64, y: f64) -> f64 {
+    (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2)
+}
+
+fn data(params: &[Value]) -> Vec<f64> {
+    params.iter().map(|p| p.get_data()).collect()
+}
+
+#[test]
+fn evolution_strategy_minimizes_a_quadratic() {
+    random::seed(1);
+    let params: Vec<Value> = (0..3).map(|_| Value::new(5.0)).collect();
+    let es = EvolutionStrategy::new(0.1, 0.05, 20);
+    let start = quadratic(&data(&params));
+    let mut loss = start;
+    for _ in 0..300 {
+        loss = es.step(&params, || quadratic(&data(&params)));
+    }
+    assert!(loss < 1e-3 * start, "{} -> {}", start, loss);
+    for (i, x) in data(&params).iter().enumerate() {
+        assert!((x - i as f64).abs() < 0.05, "x{} = {}", i, x);
+    }
+}
+
+#[test]
+fn cma_es_minimizes_a_quadratic_and_rosenbrock() {
+    random::seed(2);
+    let params: Vec<Value> = (0..4).map(|_| Value::new(3.0)).collect();
+    let mut cma = CmaEs::new(data(&params), 1.0);
+    let mut loss = f64::INFINITY;
+    for _ in 0..200 {
+        loss = cma.step(&params, || quadratic(&data(&params))).unwrap();
+    }
+    assert!(loss < 1e-8, "quadratic loss {}", loss);
+
+    let params = vec![Value::new(-1.2), Value::new(1.0)];
+    let mut cma = CmaEs::new(data(&params), 0.5);
+    for _ in 0..400 {
+        loss = cma.step(&params, || rosenbrock(params[0].get_data(), params[1].get_data())).unwrap();
+    }
+    assert!(loss < 1e-6, "rosenbrock loss {}", loss);
+    assert!((params[0].get_data() - 1.0).abs() < 1e-2 && (params[1].get_data() - 1.0).abs() < 1e-2);
+    assert_eq!(cma.generation, 400);
+}
+
+// the closure the optimizers expect: zero the gradients, build the loss, backward, return it
+fn evaluate(params: &[Value], loss: impl Fn(&[Value]) -> Value) -> f64 {
+    for p in params {
+        p.set_grad(0.0);
+    }
+    let l = loss(params);
+    l.backward();
+    l.get_data()
+}
+
+#[test]
+fn lbfgs_solves_a_quadratic_in_a_few_iterations() {
+    let params: Vec<Value> = (0..5).map(|_| Value::new(4.0)).collect();
+    // badly scaled on purpose, plain gradient descent would crawl along the flat directions
+    let quadratic = |ps: &[Value]| {
+        let terms: Vec<Value> = ps.iter().enumerate().map(|(i, p)| {
+            let scale = 10f64.powi(i as i32 - 2);
+            Value::mul(&Value::constant(scale), &Value::square(&Value::sub(p, &Value::constant(i as f64))))
+        }).collect();
+        Value::sum(&terms)
+    };
+    let mut lbfgs = Lbfgs::new(params.clone(), 1.0);
+    lbfgs.max_iter = 50;
+    let loss = lbfgs.step(&mut || evaluate(&params, quadratic));
+    assert!(loss < 1e-9, "loss {}", loss);
+    for (i, x) in data(&params).iter().enumerate() {
+        assert!((x - i as f64).abs() < 1e-3, "x{} = {}", i, x);
+    }
+}
+
+#[test]
+fn lbfgs_follows_the_rosenbrock_valley() {
+    let params = vec![Value::new(-1.2), Value::new(1.0)];
+    let f = |ps: &[Value]| {
+        let a = Value::square(&Value::sub(&Value::constant(1.0), &ps[0]));
+        let b = Value::square(&Value::sub(&ps[1], &Value::square(&ps[0])));
+        Value::add(&a, &Value::mul(&Value::constant(100.0), &b))
+    };
+    let mut lbfgs = Lbfgs::new(params.clone(), 1.0);
+    let mut loss = f64::INFINITY;
+    let mut steps = 0;
+    while loss > 1e-10 && steps < 20 {
+        loss = lbfgs.step(&mut || evaluate(&params, f));
+        steps += 1;
+    }
+    assert!(loss < 1e-10, "loss {} after {} steps", loss, steps);
+    assert!((params[0].get_data() - 1.0).abs() < 1e-4 && (params[1].get_data() - 1.0).abs() < 1e-4);
+    assert!((loss - rosenbrock(params[0].get_data(), params[1].get_data())).abs() < 1e-12);
+}
@@ -0,0 +1,78 @@
+// deterministic mode is process wide, so these tests live in their own binary
+use rust_ml::nn::{DeepClone, Dropout, Layer, Module, Sequential, MLP};
+use rust_ml::optim::Adam;
+use rust_ml::parallel::{self, DataParallelTrainer, Hogwild};
+use rust_ml::random;
+
+fn wave() -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let xs: Vec<Vec<f64>> = (0..40).map(|i| vec![i as f64 / 10.0 - 2.0, (i % 7) as f64 / 7.0]).collect();
+    let ys = xs.iter().map(|x| vec![(x[0] * 1.3).sin() + 0.5 * x[1]]).collect();
+    (xs, ys)
+}
+
+// parameters and losses of a run from the same starting model and shuffles, as raw bits
+fn run(start: &MLP, workers: usize) -> (Vec<u64>, Vec<u64>) {
+    let (xs, ys) = wave();
+    let model = start.deep_clone();
+    let mut trainer = DataParallelTrainer::new(&model, || Box::new(MLP::new(&[2, 8, 1])), Adam::new(model.parameters(), 0.01), workers)
+        .with_batch_size(16);
+    random::seed(9);
+    let losses = trainer.fit(&xs, &ys, 5);
+    (model.get_flat_params().iter().map(|p| p.to_bits()).collect(), losses.iter().map(|l| l.to_bits()).collect())
+}
+
+fn dropout_net() -> Sequential {
+    Sequential::new(vec![
+        Box::new(Layer::new(2, 8)),
+        Box::new(Dropout::new(0.3)),
+        Box::new(Layer::new(8, 1)),
+    ])
+}
+
+// epoch losses of a seeded run, the dropout masks come from the workers' rngs
+fn seeded_dropout_run(seed: u64) -> Vec<u64> {
+    let (xs, ys) = wave();
+    random::seed(seed);
+    let model = dropout_net();
+    let mut trainer = DataParallelTrainer::new(&model, || Box::new(dropout_net()), Adam::new(model.parameters(), 0.01), 3)
+        .with_batch_size(10);
+    trainer.fit(&xs, &ys, 4).iter().map(|l| l.to_bits()).collect()
+}
+
+#[test]
+fn deterministic_mode_repeats_runs_bit_for_bit() {
+    parallel::set_deterministic(true);
+    assert!(parallel::is_deterministic());
+    random::seed(8);
+    let start = MLP::new(&[2, 8, 1]);
+
+    let first = run(&start, 4);
+    for _ in 0..3 {
+        assert_eq!(run(&start, 4), first);
+    }
+
+    // worker rngs follow random::seed, so dropout in the replicas repeats too
+    assert_eq!(seeded_dropout_run(21), seeded_dropout_run(21));
+    assert_ne!(seeded_dropout_run(21), seeded_dropout_run(22));
+
+    // partials are combined in chunk order whatever order they arrive in
+    let partials = vec![(2, 1e-17), (0, 1.0), (1, -1.0), (3, 3e-17)];
+    let mut reversed = partials.clone();
+    reversed.reverse();
+    assert_eq!(parallel::sum_partials(partials).to_bits(), parallel::sum_partials(reversed).to_bits());
+    assert_eq!(parallel::tree_sum(&[1.0, -1.0, 1e-17, 3e-17]), parallel::sum_partials(vec![(0, 1.0), (1, -1.0), (2, 1e-17), (3, 3e-17)]));
+
+    // batch prediction runs on this thread and matches predict row by row
+    let (xs, _) = wave();
+    let batch = start.predict_batch(&xs);
+    for (x, y) in xs.iter().zip(batch.iter()) {
+        assert_eq!(&start.predict(x), y);
+    }
+
+    // hogwild can't promise any of this
+    let model = start.deep_clone();
+    let mut hogwild = Hogwild::new(&model, || Box::new(MLP::new(&[2, 8, 1])), 0.01, 2);
+    let (xs, ys) = wave();
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hogwild.fit(&xs, &ys, 1))).is_err());
+    parallel::set_deterministic(false);
+}
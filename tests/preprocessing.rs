@@ -0,0 +1,167 @@
+use rust_ml::error::RustMlError;
+use rust_ml::preprocessing::{self, ImputeStrategy, OrdinalEncoder, Pipeline, SelectFromModel, SelectKBest, SimpleImputer, TargetEncoder, Transformer, VarianceThreshold};
+use rust_ml::random;
+
+const NAN: f64 = f64::NAN;
+
+fn with_gaps() -> Vec<Vec<f64>> {
+    vec![
+        vec![1.0, 2.0, NAN],
+        vec![NAN, 2.0, 5.0],
+        vec![4.0, 3.0, 5.0],
+        vec![7.0, NAN, 1.0],
+    ]
+}
+
+#[test]
+fn imputer_strategies_fill_each_column() {
+    let xs = with_gaps();
+    let expected = [
+        (ImputeStrategy::Mean, vec![4.0, 7.0 / 3.0, 11.0 / 3.0]),
+        (ImputeStrategy::Median, vec![4.0, 2.0, 5.0]),
+        (ImputeStrategy::MostFrequent, vec![1.0, 2.0, 5.0]),
+        (ImputeStrategy::Constant(-1.0), vec![-1.0, -1.0, -1.0]),
+    ];
+    for (strategy, stats) in expected {
+        let mut imputer = SimpleImputer::new(strategy);
+        let out = imputer.fit_transform(&xs, None).unwrap();
+        assert_eq!(imputer.statistics, stats, "{:?}", strategy);
+        assert_eq!(out[1][0], stats[0]);
+        assert_eq!(out[3][1], stats[1]);
+        assert_eq!(out[0][2], stats[2]);
+        assert_eq!(out[2], vec![4.0, 3.0, 5.0]);
+    }
+}
+
+#[test]
+fn indicator_columns_mark_imputed_values() {
+    let mut imputer = SimpleImputer::new(ImputeStrategy::Median).with_indicator(true);
+    imputer.fit(&[vec![1.0, NAN, 0.0], vec![3.0, 4.0, 1.0]], None).unwrap();
+    assert_eq!(imputer.indicator_columns, vec![1]);
+    let out = imputer.transform(&[vec![NAN, NAN, 2.0], vec![5.0, 6.0, NAN]]).unwrap();
+    // column 2 had no gaps when fit, so it gets no indicator, but its NaN is still filled
+    assert_eq!(out, vec![vec![2.0, 4.0, 2.0, 1.0], vec![5.0, 6.0, 0.5, 0.0]]);
+}
+
+#[test]
+fn imputer_errors() {
+    let imputer = SimpleImputer::new(ImputeStrategy::Mean);
+    assert!(matches!(imputer.transform(&[vec![1.0]]), Err(RustMlError::NotFitted)));
+    let mut imputer = SimpleImputer::new(ImputeStrategy::Mean);
+    assert!(matches!(imputer.fit(&[vec![1.0, NAN], vec![2.0, NAN]], None), Err(RustMlError::Numerical(_))));
+    imputer.fit(&with_gaps(), None).unwrap();
+    assert!(matches!(imputer.transform(&[vec![1.0, 2.0]]), Err(RustMlError::Shape(_))));
+}
+
+#[test]
+fn pipeline_fits_steps_on_the_previous_output() {
+    // the indicator column added by the first step is seen by the second
+    let mut pipeline = Pipeline::new(vec![
+        Box::new(SimpleImputer::new(ImputeStrategy::Mean).with_indicator(true)),
+        Box::new(SimpleImputer::new(ImputeStrategy::Constant(0.0))),
+    ]);
+    let out = pipeline.fit_transform(&[vec![1.0, NAN], vec![3.0, 5.0]], None).unwrap();
+    assert_eq!(out, vec![vec![1.0, 5.0, 1.0], vec![3.0, 5.0, 0.0]]);
+    assert!(pipeline.transform(&[vec![NAN, NAN]]).unwrap()[0].iter().all(|v| !v.is_nan()));
+}
+
+#[test]
+fn ordinal_encoder_maps_categories_to_their_rank() {
+    let mut enc = OrdinalEncoder::new(vec![1]);
+    enc.fit(&[vec![0.5, 30.0], vec![0.1, 10.0], vec![0.2, 30.0], vec![0.3, NAN]], None).unwrap();
+    assert_eq!(enc.categories, vec![vec![10.0, 30.0]]);
+    let out = enc.transform(&[vec![1.0, 30.0], vec![2.0, 10.0], vec![3.0, 20.0]]).unwrap();
+    assert_eq!(out[0], vec![1.0, 1.0]);
+    assert_eq!(out[1], vec![2.0, 0.0]);
+    assert!(out[2][1].is_nan());
+    let enc = enc.with_unknown_value(-1.0);
+    assert_eq!(enc.transform(&[vec![0.0, 20.0]]).unwrap(), vec![vec![0.0, -1.0]]);
+}
+
+#[test]
+fn target_encoder_shrinks_rare_categories_towards_the_mean() {
+    // category 0: targets 1, 1, 1, 1; category 1: target 0 once; overall mean 0.8
+    let xs: Vec<Vec<f64>> = [0.0, 0.0, 0.0, 0.0, 1.0].iter().map(|c| vec![*c]).collect();
+    let ys = [1.0, 1.0, 1.0, 1.0, 0.0];
+    let mut enc = TargetEncoder::new(vec![0], 1.0);
+    enc.fit(&xs, Some(&ys)).unwrap();
+    assert!((enc.prior - 0.8).abs() < 1e-12);
+    let out = enc.transform(&[vec![0.0], vec![1.0], vec![7.0], vec![NAN]]).unwrap();
+    let expected = [(4.0 + 0.8) / 5.0, 0.8 / 2.0, 0.8, 0.8];
+    for (o, e) in out.iter().zip(expected.iter()) {
+        assert!((o[0] - e).abs() < 1e-12, "{:?}", out);
+    }
+    assert!(matches!(TargetEncoder::new(vec![0], 1.0).fit(&xs, None), Err(RustMlError::Config(_))));
+}
+
+#[test]
+fn out_of_fold_target_encoding_does_not_leak_labels() {
+    // every category is unique, so in-sample encoding would just copy the target into the feature
+    random::seed(30);
+    let xs: Vec<Vec<f64>> = (0..50).map(|i| vec![i as f64]).collect();
+    let ys: Vec<f64> = (0..50).map(|i| (i % 2) as f64).collect();
+    let mut enc = TargetEncoder::new(vec![0], 0.5).with_folds(5);
+    let out = enc.fit_transform(&xs, Some(&ys)).unwrap();
+    // out of fold none of them has been seen, so all get their fold's prior
+    for (o, y) in out.iter().zip(ys.iter()) {
+        assert!((o[0] - 0.5).abs() < 0.2, "{} for target {}", o[0], y);
+    }
+    // in sample, they're pulled towards their own target
+    let in_sample = enc.transform(&xs).unwrap();
+    assert!(in_sample.iter().zip(ys.iter()).all(|(o, y)| (o[0] - y).abs() < 0.4));
+}
+
+// y = 3 x0 - 2 x2 + noise, x1 is noise, x3 is constant
+fn regression(n: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
+    let xs: Vec<Vec<f64>> = (0..n).map(|_| vec![random::normal(0.0, 1.0), random::normal(0.0, 1.0), random::normal(0.0, 1.0), 4.0]).collect();
+    let ys = xs.iter().map(|x| 3.0 * x[0] - 2.0 * x[2] + random::normal(0.0, 0.1)).collect();
+    (xs, ys)
+}
+
+#[test]
+fn variance_threshold_drops_constant_columns() {
+    random::seed(31);
+    let (xs, _) = regression(50);
+    let mut sel = VarianceThreshold::new(0.0);
+    let out = sel.fit_transform(&xs, None).unwrap();
+    assert_eq!(sel.selected, vec![0, 1, 2]);
+    assert_eq!(out[7], xs[7][..3].to_vec());
+}
+
+#[test]
+fn select_k_best_keeps_the_most_correlated_columns() {
+    random::seed(32);
+    let (xs, ys) = regression(200);
+    let mut sel = SelectKBest::new(2);
+    let out = sel.fit_transform(&xs, Some(&ys)).unwrap();
+    assert_eq!(sel.selected, vec![0, 2]);
+    assert_eq!(sel.scores[3], 0.0);
+    assert_eq!(out[0], vec![xs[0][0], xs[0][2]]);
+    assert!(matches!(SelectKBest::new(5).fit(&xs, Some(&ys)), Err(RustMlError::Config(_))));
+}
+
+#[test]
+fn lasso_selection_zeroes_out_irrelevant_columns() {
+    random::seed(33);
+    let (xs, ys) = regression(200);
+    let w = preprocessing::lasso(&xs, &ys, 0.1).unwrap();
+    assert!(w[0] > 2.0 && w[2] < -1.0, "{:?}", w);
+    assert_eq!((w[1], w[3]), (0.0, 0.0));
+
+    let mut sel = SelectFromModel::lasso(0.1);
+    sel.fit(&xs, Some(&ys)).unwrap();
+    assert_eq!(sel.selected, vec![0, 2]);
+    assert_eq!(sel.transform(&xs).unwrap()[3].len(), 2);
+}
+
+#[test]
+fn selectors_in_a_pipeline() {
+    random::seed(34);
+    let (xs, ys) = regression(100);
+    let mut pipeline = Pipeline::new(vec![
+        Box::new(VarianceThreshold::new(0.0)),
+        Box::new(SelectKBest::new(1)),
+    ]);
+    let out = pipeline.fit_transform(&xs, Some(&ys)).unwrap();
+    assert_eq!(out[5], vec![xs[5][0]]);
+}
@@ -0,0 +1,44 @@
+use rust_ml::profiler::{self, Phase};
+use rust_ml::value::Value;
+
+// the profiler is thread local, so every test here sees only its own recordings
+
+fn count(op: &str, phase: Phase) -> u64 {
+    profiler::report().iter().find(|s| s.op == op && s.phase == phase).map_or(0, |s| s.count)
+}
+
+#[test]
+fn records_forward_and_backward_only_while_enabled() {
+    profiler::reset();
+    let (a, b) = (Value::new(2.0), Value::new(3.0));
+    Value::mul(&a, &b);
+    assert!(!profiler::is_enabled());
+    assert!(profiler::report().is_empty());
+
+    profiler::enable();
+    let y = Value::tanh(&Value::add(&Value::mul(&a, &b), &Value::mul(&a, &a)));
+    y.backward();
+    profiler::disable();
+    assert_eq!(count("*", Phase::Forward), 2);
+    assert_eq!(count("+", Phase::Forward), 1);
+    assert_eq!(count("tanh", Phase::Forward), 1);
+    assert_eq!(count("*", Phase::Backward), 2);
+    assert_eq!(count("tanh", Phase::Backward), 1);
+    assert_eq!(count("graph bookkeeping", Phase::Backward), 1);
+
+    // sorted by total time, most expensive first
+    let report = profiler::report();
+    assert!(report.windows(2).all(|w| w[0].total >= w[1].total));
+
+    // nothing more once it's off, and reset clears it
+    Value::mul(&a, &b);
+    assert_eq!(count("*", Phase::Forward), 2);
+    profiler::reset();
+    assert!(profiler::report().is_empty());
+}
+
+#[test]
+fn print_report_runs_on_an_empty_profile() {
+    profiler::reset();
+    profiler::print_report();
+}
@@ -0,0 +1,74 @@
+// snapshot tests for the graphs nn layers build: each layer runs on a fixed seed and input,
+// its graph is written out as text (one line per node: op, children, data) and compared with
+// tests/snapshots/<name>.txt, so a refactor of a forward pass can't change what it computes unnoticed
+// run with UPDATE_SNAPSHOTS=1 to rewrite the files after an intended change
+use std::fs;
+use std::path::PathBuf;
+
+use rust_ml::graph::JsonGraph;
+use rust_ml::nn::{Activation, Dropout, Embedding, Layer, Module, MLP, Sequential};
+use rust_ml::random;
+use rust_ml::value::Value;
+
+fn render(outputs: &[Value]) -> String {
+    let mut text = String::new();
+    for (k, out) in outputs.iter().enumerate() {
+        let g = JsonGraph::from_value(out);
+        text.push_str(&format!("output {} ({} nodes)\n", k, g.nodes.len()));
+        for n in g.nodes.iter() {
+            let children: Vec<String> = g.edges.iter().filter(|e| e.to == n.id).map(|e| e.from.to_string()).collect();
+            let op = match (n.op.as_str(), n.requires_grad) {
+                ("", true) => "param",
+                ("", false) => "const",
+                (op, _) => op,
+            };
+            text.push_str(&format!("  {} {} [{}] {:.6}\n", n.id, op, children.join(" "), n.data));
+        }
+    }
+    text
+}
+
+fn check(name: &str, model: &dyn Module, x: &[f64]) {
+    let xv: Vec<Value> = x.iter().map(|xi| Value::constant(*xi)).collect();
+    let actual = render(&model.forward(&xv));
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", &format!("{}.txt", name)].iter().collect();
+    if std::env::var("UPDATE_SNAPSHOTS").is_ok() || !path.exists() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert!(expected == actual, "graph of {} changed, rerun with UPDATE_SNAPSHOTS=1 if that's intended\n--- expected\n{}--- actual\n{}", name, expected, actual);
+}
+
+#[test]
+fn layer_graphs_match_snapshots() {
+    let activations = [
+        ("linear", Activation::Linear),
+        ("tanh", Activation::Tanh),
+        ("softplus", Activation::Softplus),
+        ("gelu", Activation::Gelu),
+        ("silu", Activation::Silu),
+        ("elu", Activation::Elu(0.5)),
+    ];
+    for (name, act) in activations {
+        random::seed(11);
+        check(&format!("layer_{}", name), &Layer::with_activation(2, 2, act), &[0.5, -1.5]);
+    }
+
+    random::seed(12);
+    check("mlp", &MLP::new(&[2, 3, 1]), &[1.0, -0.25]);
+
+    random::seed(13);
+    check("embedding", &Embedding::new(4, 2), &[3.0, 1.0]);
+
+    random::seed(14);
+    check("dropout", &Dropout::new(0.5), &[1.0, 2.0, 3.0, 4.0]);
+
+    random::seed(15);
+    let seq = Sequential::new(vec![
+        Box::new(Layer::with_activation(2, 2, Activation::Gelu)),
+        Box::new(Activation::Tanh),
+        Box::new(Layer::with_activation(2, 1, Activation::Linear)),
+    ]);
+    check("sequential", &seq, &[0.3, 0.7]);
+}
@@ -0,0 +1,77 @@
+use rust_ml::matrix::Matrix;
+use rust_ml::stats;
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+}
+
+#[test]
+fn variance_and_quantiles() {
+    let xs = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+    assert!(close(stats::mean(&xs), 5.0));
+    assert!(close(stats::var(&xs, 0), 4.0));
+    assert!(close(stats::var(&xs, 1), 32.0 / 7.0));
+    assert!(close(stats::std(&xs, 0), 2.0));
+    // a single value has zero population variance
+    assert_eq!(stats::var(&[3.0], 0), 0.0);
+
+    // positions q * (n - 1) on the sorted [1, 2, 4, 8]
+    let ys = [8.0, 1.0, 4.0, 2.0];
+    assert_eq!(stats::quantile(&ys, 0.0), 1.0);
+    assert_eq!(stats::quantile(&ys, 1.0), 8.0);
+    assert!(close(stats::quantile(&ys, 0.5), 3.0));
+    assert!(close(stats::quantile(&ys, 0.25), 1.75));
+    assert!(close(stats::quantile(&ys, 0.9), 6.8));
+    assert_eq!(stats::quantile(&[5.0], 0.3), 5.0);
+}
+
+#[test]
+fn covariance_and_correlation() {
+    // columns: x, 2x + 1, -x, and a constant
+    let x = Matrix::new(4, 4, vec![
+        1.0, 3.0, -1.0, 7.0,
+        2.0, 5.0, -2.0, 7.0,
+        3.0, 7.0, -3.0, 7.0,
+        6.0, 13.0, -6.0, 7.0,
+    ]);
+    // mean of x is 3, squared deviations 4 + 1 + 0 + 9 = 14, over n - 1
+    let var_x = 14.0 / 3.0;
+    let cov = stats::covariance(&x);
+    let expected = [
+        [var_x, 2.0 * var_x, -var_x, 0.0],
+        [2.0 * var_x, 4.0 * var_x, -2.0 * var_x, 0.0],
+        [-var_x, -2.0 * var_x, var_x, 0.0],
+        [0.0, 0.0, 0.0, 0.0],
+    ];
+    for (a, row) in expected.iter().enumerate() {
+        for (b, want) in row.iter().enumerate() {
+            assert!(close(cov.get(a, b), *want), "cov[{}][{}] = {} vs {}", a, b, cov.get(a, b), want);
+        }
+    }
+
+    let corr = stats::correlation(&x);
+    let expected = [
+        [1.0, 1.0, -1.0, 0.0],
+        [1.0, 1.0, -1.0, 0.0],
+        [-1.0, -1.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+    for (a, row) in expected.iter().enumerate() {
+        for (b, want) in row.iter().enumerate() {
+            assert!(close(corr.get(a, b), *want), "corr[{}][{}] = {} vs {}", a, b, corr.get(a, b), want);
+        }
+    }
+
+    // a partial correlation: x against [1, 3, 2, 4]
+    let y = Matrix::new(4, 2, vec![1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 4.0, 4.0]);
+    assert!(close(stats::correlation(&y).get(0, 1), 0.8));
+}
+
+#[test]
+fn degenerate_inputs_are_rejected() {
+    let caught = |f: fn()| std::panic::catch_unwind(f).is_err();
+    assert!(caught(|| { stats::var(&[], 0); }));
+    assert!(caught(|| { stats::var(&[1.0], 1); }));
+    assert!(caught(|| { stats::quantile(&[], 0.5); }));
+    assert!(caught(|| { stats::covariance(&Matrix::new(1, 2, vec![1.0, 2.0])); }));
+}
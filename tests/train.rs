@@ -0,0 +1,590 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+
+use rust_ml::attacks;
+use rust_ml::datasets;
+use rust_ml::federated;
+use rust_ml::loss;
+use rust_ml::data::{DataLoader, Mode};
+use rust_ml::nn::{Activation, DeepClone, Dropout, Layer, Module, MultiHead, Sequential, Siamese, MLP};
+use rust_ml::optim::{Adam, Schedule, Sgd};
+use rust_ml::parallel::{DataParallelTrainer, Hogwild};
+use rust_ml::privacy;
+use rust_ml::random;
+use rust_ml::train::{self, Stage, Stop, StreamMetrics, Task, TaskWeights, Trainer};
+use rust_ml::value::Value;
+use rust_ml::viz;
+
+fn argmax(xs: &[f64]) -> usize {
+    (0..xs.len()).max_by(|&a, &b| xs[a].total_cmp(&xs[b])).unwrap()
+}
+
+#[test]
+fn trainer_fits_a_small_regression() {
+    random::seed(1);
+    let model = MLP::new(&[1, 8, 1]);
+    let xs: Vec<Vec<f64>> = (0..20).map(|i| vec![i as f64 / 10.0 - 1.0]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.05));
+    let losses = trainer.fit(&xs, &ys, 200);
+    assert!(losses[losses.len() - 1] < 0.1 * losses[0], "{} -> {}", losses[0], losses[losses.len() - 1]);
+    assert_eq!(trainer.history.len(), 200);
+}
+
+#[test]
+fn distilled_student_follows_the_teacher() {
+    random::seed(2);
+    let teacher = MLP::with_activation(&[2, 16, 3], Activation::Tanh);
+    let student = MLP::with_activation(&[2, 4, 3], Activation::Tanh);
+    let xs: Vec<Vec<f64>> = (0..30).map(|_| vec![random::uniform(-1.0, 1.0), random::uniform(-1.0, 1.0)]).collect();
+    // hard labels are the teacher's own predictions, one-hot
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| {
+        let mut y = vec![0.0; 3];
+        y[argmax(&teacher.predict(x))] = 1.0;
+        y
+    }).collect();
+
+    let mut trainer = Trainer::new(&student, Adam::new(student.parameters(), 0.05))
+        .with_loss(loss::cross_entropy)
+        .with_distillation(&teacher, 2.0, 0.7);
+    let losses = trainer.fit(&xs, &ys, 300);
+    assert!(losses[losses.len() - 1] < 0.5 * losses[0], "{} -> {}", losses[0], losses[losses.len() - 1]);
+
+    let agree = xs.iter().filter(|x| argmax(&student.predict(x)) == argmax(&teacher.predict(x))).count();
+    assert!(agree >= 25, "student agrees with the teacher on {} of 30 samples", agree);
+}
+
+#[test]
+fn federated_averaging_trains_the_global_model() {
+    random::seed(3);
+    let global = MLP::new(&[1, 8, 1]);
+    let xs: Vec<Vec<f64>> = (0..40).map(|i| vec![i as f64 / 20.0 - 1.0]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    let clients = federated::split_clients(&xs, &ys, 4);
+    assert_eq!(clients.iter().map(|c| c.xs.len()).sum::<usize>(), 40);
+
+    let mse = |m: &MLP| xs.iter().zip(ys.iter()).map(|(x, y)| (m.predict(x)[0] - y[0]).powi(2)).sum::<f64>() / 40.0;
+    let before = mse(&global);
+    federated::train(&global, &clients, 20, 5, 0.1);
+    let after = mse(&global);
+    assert!(after < 0.2 * before, "{} -> {}", before, after);
+}
+
+#[test]
+fn deep_clone_does_not_share_parameters() {
+    let model = MLP::new(&[2, 3, 1]);
+    let copy = model.deep_clone();
+    assert_eq!(model.get_flat_params(), copy.get_flat_params());
+    copy.parameters()[0].update_data(1.0);
+    assert_ne!(model.get_flat_params(), copy.get_flat_params());
+}
+
+#[test]
+fn dp_sgd_clips_each_sample() {
+    // one parameter, two samples whose gradients are 10 and -1, clipped to 1 and -1
+    let w = Value::new(0.0);
+    let mut opt = privacy::DpSgd::new(Sgd::new(vec![w.clone_rc()], 1.0), 1.0, 0.0);
+    let grads = [10.0, -1.0];
+    opt.step(2, &mut |i| {
+        let loss = Value::mul(&w, &Value::constant(grads[i]));
+        loss.backward();
+        loss.get_data()
+    });
+    assert!(w.get_data().abs() < 1e-12, "clipped gradients should cancel, w = {}", w.get_data());
+}
+
+#[test]
+fn privacy_budget_grows_with_steps_and_shrinks_with_noise() {
+    let e1 = privacy::epsilon(1.0, 100, 1e-5);
+    let e2 = privacy::epsilon(1.0, 400, 1e-5);
+    let e3 = privacy::epsilon(4.0, 100, 1e-5);
+    assert!(e2 > e1 && e3 < e1, "{} {} {}", e1, e2, e3);
+    assert_eq!(privacy::epsilon(0.0, 1, 1e-5), f64::INFINITY);
+}
+
+#[test]
+fn attacks_increase_the_loss_within_the_budget() {
+    random::seed(4);
+    let model = MLP::new(&[3, 8, 1]);
+    let mse = |out: &[Value], y: &[f64]| loss::mse(out, &[Value::constant(y[0])]);
+    let x = [0.2, -0.5, 0.9];
+    let y = [1.0];
+    let (clean, _) = attacks::input_gradient(&model, &mse, &x, &y);
+
+    let adv = attacks::fgsm(&model, &mse, &x, &y, 0.1);
+    assert!(attacks::input_gradient(&model, &mse, &adv, &y).0 > clean);
+
+    let adv = attacks::pgd(&model, &mse, &x, &y, 0.1, 0.03, 10);
+    assert!(adv.iter().zip(x.iter()).all(|(a, xi)| (a - xi).abs() <= 0.1 + 1e-12));
+    assert!(attacks::input_gradient(&model, &mse, &adv, &y).0 > clean);
+    assert!(model.parameters().iter().all(|p| p.get_grad() == 0.0));
+}
+
+#[test]
+fn adversarial_training_reduces_the_loss() {
+    random::seed(5);
+    let model = MLP::new(&[1, 8, 1]);
+    let xs: Vec<Vec<f64>> = (0..10).map(|i| vec![i as f64 / 5.0 - 1.0]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.05)).with_adversarial(0.05, 3, 0.5);
+    let losses = trainer.fit(&xs, &ys, 100);
+    assert!(losses[losses.len() - 1] < 0.2 * losses[0], "{} -> {}", losses[0], losses[losses.len() - 1]);
+}
+
+#[test]
+fn decision_boundary_separates_blobs() {
+    random::seed(8);
+    let data = datasets::blobs(40, &[(-2.0, 0.0), (2.0, 0.0)], 0.3);
+    let model = MLP::new(&[2, 4, 1]);
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.05));
+    trainer.fit(&data.xs, &data.signs(), 100);
+
+    let db = viz::decision_boundary(&model, &data, 20);
+    assert_eq!(db.classes.len(), 20);
+    // left edge is class 0, right edge class 1
+    assert!(db.classes.iter().all(|row| row[0] == 0 && row[19] == 1));
+    assert!(db.svg("blobs", &data).contains("<circle"));
+    assert!(viz::learning_curve(&trainer.history).contains("<polyline"));
+}
+
+#[test]
+fn mini_batch_training_fits_a_small_regression() {
+    random::seed(9);
+    let model = MLP::new(&[1, 8, 1]);
+    let xs: Vec<Vec<f64>> = (0..50).map(|i| vec![i as f64 / 25.0 - 1.0]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.02)).with_batch_size(8);
+    let losses = trainer.fit(&xs, &ys, 40);
+    assert_eq!(losses.len(), 40);
+    assert!(losses[losses.len() - 1] < 0.1 * losses[0], "{} -> {}", losses[0], losses[losses.len() - 1]);
+}
+
+#[test]
+fn per_sample_gradients_add_up_to_the_batch_gradient() {
+    random::seed(10);
+    let model = MLP::new(&[2, 3, 1]);
+    let xs: Vec<Vec<f64>> = (0..5).map(|_| vec![random::uniform(-1.0, 1.0), random::uniform(-1.0, 1.0)]).collect();
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| vec![x[0] - x[1]]).collect();
+    let trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1));
+    let rows = trainer.per_sample_gradients(&xs, &ys);
+    assert_eq!(rows.len(), 5);
+
+    model.zero_grad();
+    let losses: Vec<Value> = xs.iter().zip(ys.iter()).map(|(x, y)| trainer.sample_loss(x, y)).collect();
+    Value::sum(&losses).backward();
+    for (k, p) in model.parameters().iter().enumerate() {
+        let total: f64 = rows.iter().map(|r| r[k]).sum();
+        assert!((total - p.get_grad()).abs() < 1e-12, "{} vs {}", total, p.get_grad());
+    }
+}
+
+#[test]
+fn per_sample_gradients_include_shared_terms_in_every_row() {
+    // loss_i = w * x_i + w^2, where w^2 is one node shared by both losses
+    let w = Value::new(3.0);
+    let shared = Value::square(&w);
+    let losses: Vec<Value> = [1.0, 2.0].iter().map(|x| Value::add(&Value::mul(&w, &Value::constant(*x)), &shared)).collect();
+    w.set_grad(0.5);
+    let rows = train::per_sample_gradients(&[w.clone_rc()], &losses);
+    assert_eq!(rows, vec![vec![7.0], vec![8.0]]);
+    assert_eq!(w.get_grad(), 0.5);
+}
+
+#[test]
+fn stages_run_in_order_and_frozen_parameters_stay_put() {
+    random::seed(11);
+    let model = MLP::new(&[1, 6, 1]);
+    let xs: Vec<Vec<f64>> = (0..20).map(|i| vec![i as f64 / 10.0 - 1.0]).collect();
+    let easy: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    let hard: Vec<Vec<f64>> = xs.iter().map(|x| vec![0.8 * x[0] * x[0] - 0.3]).collect();
+    let trunk = model.layers()[0].parameters();
+
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.05));
+    let pretrain = trainer.fit_stages(&[Stage::new("pretrain", xs.clone(), easy, 50)]);
+    let pretrained: Vec<f64> = trunk.iter().map(|p| p.get_data()).collect();
+
+    let fine_tune = Stage::new("fine-tune", xs.clone(), hard, 40)
+        .with_lr(0.02)
+        .with_batch_size(5)
+        .with_frozen(trunk.clone());
+    let losses = trainer.fit_stages(&[fine_tune]);
+    assert_eq!((pretrain[0].len(), losses[0].len()), (50, 40));
+    assert_eq!(trainer.history.len(), 90);
+    assert_eq!(trunk.iter().map(|p| p.get_data()).collect::<Vec<f64>>(), pretrained);
+    assert!(trunk.iter().all(|p| p.requires_grad()));
+    // only the head moved, and it still learned something
+    assert!(losses[0][39] < losses[0][0], "{} -> {}", losses[0][0], losses[0][39]);
+}
+
+#[test]
+fn multi_task_model_learns_regression_and_classification_jointly() {
+    random::seed(12);
+    let model = MultiHead::new(
+        Box::new(MLP::new(&[2, 8])),
+        vec![
+            Box::new(Layer::with_activation(8, 1, Activation::Linear)),
+            Box::new(Layer::with_activation(8, 2, Activation::Linear)),
+        ],
+    );
+    assert_eq!(model.head_ranges(2).unwrap(), vec![0..1, 1..3]);
+    let xs: Vec<Vec<f64>> = (0..30).map(|_| vec![random::uniform(-1.0, 1.0), random::uniform(-1.0, 1.0)]).collect();
+    // target row: [x0 + x1, one-hot of x0 > x1]
+    let ys: Vec<Vec<f64>> = xs.iter().map(|x| {
+        let above = if x[0] > x[1] { 1.0 } else { 0.0 };
+        vec![x[0] + x[1], above, 1.0 - above]
+    }).collect();
+
+    let weights = TaskWeights::uncertainty(2);
+    let params = [model.parameters(), weights.parameters()].concat();
+    let tasks = vec![
+        Task::new("sum", 0..1, 0..1, |out, y| loss::mse(out, &[Value::constant(y[0])])),
+        Task::new("order", 1..3, 1..3, loss::cross_entropy),
+    ];
+    let mut trainer = Trainer::new(&model, Adam::new(params, 0.05)).with_tasks(tasks, weights.clone());
+    let losses = trainer.fit(&xs, &ys, 150);
+    assert!(losses[149] < 0.5 * losses[0], "{} -> {}", losses[0], losses[149]);
+    assert!(weights.weights().iter().all(|w| *w != 1.0));
+
+    let correct = xs.iter().zip(ys.iter()).filter(|(x, y)| {
+        let out = model.predict(x);
+        (out[1] > out[2]) == (y[1] == 1.0)
+    }).count();
+    assert!(correct >= 25, "{} of 30", correct);
+}
+
+#[test]
+fn triplet_training_separates_the_classes() {
+    random::seed(23);
+    let data = datasets::blobs(60, &[(-1.0, 0.0), (1.0, 0.0), (0.0, 1.5)], 0.3);
+    let ys: Vec<Vec<f64>> = data.labels.iter().map(|l| vec![*l as f64]).collect();
+    let encoder = MLP::with_activation(&[2, 8, 2], Activation::Tanh);
+    let model = Siamese::new(Box::new(encoder), 3);
+    let loader = DataLoader::new(&data.xs, &ys, 20).with_mode(Mode::Triplets);
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.02))
+        .with_loss(|out, _| loss::triplet(&out[0..2], &out[2..4], &out[4..6], 1.0));
+    let losses = trainer.fit_loader(&loader, 60);
+    assert!(losses[59] < 0.5 * losses[0], "{} -> {}", losses[0], losses[59]);
+
+    // a sample's nearest neighbour in embedding space is mostly from its own class
+    let emb: Vec<Vec<f64>> = data.xs.iter().map(|x| model.encoder.predict(x)).collect();
+    let dist = |a: &[f64], b: &[f64]| a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum::<f64>();
+    let agree = (0..emb.len()).filter(|&i| {
+        let nearest = (0..emb.len()).filter(|&j| j != i).min_by(|&a, &b| dist(&emb[i], &emb[a]).total_cmp(&dist(&emb[i], &emb[b]))).unwrap();
+        data.labels[nearest] == data.labels[i]
+    }).count();
+    assert!(agree >= 50, "{} of 60", agree);
+}
+
+#[test]
+fn contrastive_loss_pulls_similar_pairs_and_pushes_dissimilar_ones() {
+    let a = [Value::new(0.0), Value::new(0.0)];
+    let b = [Value::new(0.3), Value::new(0.4)];
+    assert!((loss::contrastive(&a, &b, true, 1.0).get_data() - 0.25).abs() < 1e-12);
+    assert!((loss::contrastive(&a, &b, false, 1.0).get_data() - 0.25).abs() < 1e-9);
+    assert_eq!(loss::contrastive(&a, &b, false, 0.4).get_data(), 0.0);
+}
+
+#[test]
+fn partial_fit_continues_from_the_current_model() {
+    random::seed(82);
+    let model = MLP::with_activation(&[1, 8, 1], Activation::Tanh);
+    let mut trainer = Trainer::new(&model, Adam::new(model.parameters(), 0.02)).with_batch_size(16);
+    let batch = |lo: f64| -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let xs: Vec<Vec<f64>> = (0..32).map(|i| vec![lo + i as f64 / 32.0]).collect();
+        let ys = xs.iter().map(|x| vec![(2.0 * x[0]).sin()]).collect();
+        (xs, ys)
+    };
+    // data arriving in chunks, each seen once per round
+    let mut first = vec![];
+    for round in 0..60 {
+        for lo in [-1.0, 0.0] {
+            let (xs, ys) = batch(lo);
+            let loss = trainer.partial_fit(&xs, &ys);
+            if round == 0 {
+                first.push(loss);
+            }
+        }
+    }
+    assert_eq!(trainer.history.len(), 120);
+    let (xs, ys) = batch(-1.0);
+    let final_loss = trainer.partial_fit(&xs, &ys);
+    assert!(final_loss < 0.1 * first[0], "{} vs {}", final_loss, first[0]);
+}
+
+#[test]
+fn running_metrics_weigh_recent_samples_more() {
+    let mut m = StreamMetrics::new(0.5);
+    m.update(4.0, true);
+    // no pull towards the zero start
+    assert_eq!((m.loss, m.accuracy), (4.0, 1.0));
+    m.update(1.0, false);
+    // weights 0.5 and 1
+    assert!((m.loss - 2.0).abs() < 1e-12 && (m.accuracy - 1.0 / 3.0).abs() < 1e-12);
+    assert_eq!(m.samples, 2);
+    assert!(train::is_correct(&[0.3], &[1.0]) && !train::is_correct(&[0.3], &[-1.0]));
+    assert!(train::is_correct(&[0.1, 0.7, 0.2], &[0.0, 1.0, 0.0]));
+}
+
+#[test]
+fn online_learning_tracks_concept_drift() {
+    random::seed(83);
+    let model = Layer::with_activation(2, 1, Activation::Tanh);
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.2));
+    // the label is the sign of x0 for 400 samples, then it flips
+    let stream = (0..800).map(|i| {
+        let x = vec![random::uniform(-1.0, 1.0), random::uniform(-1.0, 1.0)];
+        let sign = if (x[0] > 0.0) == (i < 400) { 1.0 } else { -1.0 };
+        (x, vec![sign])
+    });
+    let mut metrics = StreamMetrics::new(0.95);
+    let trace = trainer.fit_stream(stream, 4, &mut metrics);
+    assert_eq!(trace.len(), 200);
+    assert_eq!(metrics.samples, 800);
+    // learned, hit by the drift, recovered
+    assert!(trace[99].1 > 0.9, "{:?}", trace[99]);
+    let worst = trace[100..120].iter().map(|t| t.1).fold(1.0, f64::min);
+    assert!(worst < 0.4, "{}", worst);
+    assert!(trace[199].1 > 0.85, "{:?}", trace[199]);
+    assert!(trace[199].0 < trace[105].0);
+}
+
+fn line_data() -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let xs: Vec<Vec<f64>> = (0..20).map(|i| vec![i as f64 / 10.0 - 1.0]).collect();
+    let ys = xs.iter().map(|x| vec![0.5 * x[0]]).collect();
+    (xs, ys)
+}
+
+#[test]
+fn budgets_cut_training_short() {
+    random::seed(3);
+    let (xs, ys) = line_data();
+    let model = MLP::new(&[1, 4, 1]);
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1)).with_epoch_budget(5);
+    assert_eq!(trainer.fit(&xs, &ys, 3).len(), 3);
+    assert_eq!(trainer.stopped, None);
+    // the budget counts every epoch trained, not just this call's
+    assert_eq!(trainer.fit(&xs, &ys, 100).len(), 2);
+    assert_eq!(trainer.stopped, Some(Stop::EpochBudget));
+
+    // out of time after the first mini-batch
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1)).with_batch_size(5).with_time_budget(Duration::ZERO);
+    assert_eq!(trainer.fit(&xs, &ys, 10).len(), 1);
+    assert_eq!(trainer.stopped, Some(Stop::TimeBudget));
+    // a budget only applies inside fit
+    trainer.partial_fit(&xs, &ys);
+    assert_eq!(trainer.history.len(), 2);
+}
+
+#[test]
+fn interrupted_runs_checkpoint_and_resume() {
+    random::seed(4);
+    let (xs, ys) = line_data();
+    let path = std::env::temp_dir().join(format!("rust-ml-interrupt-{}.json", std::process::id()));
+    let flag = Arc::new(AtomicBool::new(true));
+    let model = MLP::new(&[1, 4, 1]);
+    let before = model.get_flat_params();
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1))
+        .with_batch_size(4)
+        .with_epoch_budget(3)
+        .with_interrupt(flag.clone())
+        .with_checkpoint(&path);
+    // the batch that was running finishes, then it stops
+    assert_eq!(trainer.fit(&xs, &ys, 3).len(), 1);
+    assert_eq!(trainer.stopped, Some(Stop::Interrupted));
+    assert_ne!(model.get_flat_params(), before);
+
+    let fresh = MLP::new(&[1, 4, 1]);
+    flag.store(false, Ordering::SeqCst);
+    let mut resumed = Trainer::new(&fresh, Sgd::new(fresh.parameters(), 0.1)).with_epoch_budget(3).with_interrupt(flag);
+    resumed.resume(&path).unwrap();
+    assert_eq!(fresh.get_flat_params(), model.get_flat_params());
+    assert_eq!(resumed.history, trainer.history);
+    assert_eq!(resumed.fit(&xs, &ys, 10).len(), 2);
+    assert_eq!(resumed.history.len(), 3);
+
+    let wrong = MLP::new(&[1, 5, 1]);
+    assert!(Trainer::new(&wrong, Sgd::new(wrong.parameters(), 0.1)).resume(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn data_parallel_training_matches_a_single_thread() {
+    let (xs, ys) = line_data();
+    random::seed(5);
+    let model = MLP::new(&[1, 6, 1]);
+    let single = model.deep_clone();
+    let mut parallel = DataParallelTrainer::new(&model, || Box::new(MLP::new(&[1, 6, 1])), Sgd::new(model.parameters(), 0.1).with_momentum(0.9), 3)
+        .with_batch_size(8);
+    let mut trainer = Trainer::new(&single, Sgd::new(single.parameters(), 0.1).with_momentum(0.9)).with_batch_size(8);
+    // same shuffles for both
+    random::seed(6);
+    let a = parallel.fit(&xs, &ys, 20);
+    random::seed(6);
+    let b = trainer.fit(&xs, &ys, 20);
+    assert!(a[19] < a[0]);
+    for (x, y) in a.iter().zip(b.iter()).chain(model.get_flat_params().iter().zip(single.get_flat_params().iter())) {
+        assert!((x - y).abs() < 1e-9, "{} vs {}", x, y);
+    }
+    assert_eq!(parallel.history, a);
+
+    // more workers than rows, the extra ones sit idle
+    let mut wide = DataParallelTrainer::new(&model, || Box::new(MLP::new(&[1, 6, 1])), Adam::new(model.parameters(), 0.01), 64);
+    assert_eq!(wide.fit(&xs[..3], &ys[..3], 2).len(), 2);
+
+    // no rows, no epochs and no change to the model
+    let before = model.get_flat_params();
+    assert!(wide.fit(&[], &[], 3).is_empty());
+    assert_eq!(model.get_flat_params(), before);
+}
+
+#[test]
+fn hogwild_with_one_worker_is_plain_sgd() {
+    let (xs, ys) = line_data();
+    random::seed(5);
+    let model = MLP::new(&[1, 6, 1]);
+    let single = model.deep_clone();
+    let mut hogwild = Hogwild::new(&model, || Box::new(MLP::new(&[1, 6, 1])), 0.05, 1).with_batch_size(4);
+    let mut trainer = Trainer::new(&single, Sgd::new(single.parameters(), 0.05)).with_batch_size(4);
+    random::seed(6);
+    let a = hogwild.fit(&xs, &ys, 10);
+    random::seed(6);
+    // hogwild takes one draw for its worker seeds before the first shuffle
+    random::with_rng(|r| r.gen::<u64>());
+    let b = trainer.fit(&xs, &ys, 10);
+    for (x, y) in a.iter().zip(b.iter()).chain(model.get_flat_params().iter().zip(single.get_flat_params().iter())) {
+        assert!((x - y).abs() < 1e-9, "{} vs {}", x, y);
+    }
+
+    // with several workers the result depends on thread timing, but it still has to learn
+    random::seed(5);
+    let model = MLP::new(&[1, 6, 1]);
+    let mut hogwild = Hogwild::new(&model, || Box::new(MLP::new(&[1, 6, 1])), 0.02, 4);
+    let losses = hogwild.fit(&xs, &ys, 30);
+    assert!(losses[29] < losses[0] / 2.0, "{:?}", losses);
+    assert_eq!(hogwild.history, losses);
+
+    // the workers' rngs (dropout here) are seeded from the caller's, so a seeded run repeats
+    let net = || Sequential::new(vec![Box::new(Layer::new(1, 6)), Box::new(Dropout::new(0.3)), Box::new(Layer::new(6, 1))]);
+    let seeded = |seed: u64| {
+        random::seed(seed);
+        let model = net();
+        let mut hogwild = Hogwild::new(&model, || Box::new(net()), 0.02, 1);
+        hogwild.fit(&xs, &ys, 3)
+    };
+    assert_eq!(seeded(3), seeded(3));
+
+    // no rows: no epochs, so no 0 / 0 losses, and the model stays as it was
+    let before = model.get_flat_params();
+    let empty = hogwild.fit(&[], &[], 3);
+    assert!(empty.is_empty() && !hogwild.history.iter().any(|l| l.is_nan()));
+    assert_eq!(model.get_flat_params(), before);
+}
+
+#[test]
+fn grad_monitor_tracks_norms_and_update_ratios() {
+    let (xs, ys) = line_data();
+    let run = |lr: f64| {
+        random::seed(4);
+        let model = MLP::new(&[1, 6, 1]);
+        assert_eq!(model.layer_parameters().iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), vec!["0", "1"]);
+        let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), lr)).with_batch_size(5).with_grad_monitor(3);
+        random::seed(5);
+        trainer.fit(&xs, &ys, 3);
+        trainer.grad_monitor.unwrap()
+    };
+    let slow = run(0.01);
+    // 4 batches an epoch, 12 steps, every third one recorded
+    assert_eq!(slow.history.iter().map(|s| s.step).collect::<Vec<usize>>(), vec![0, 3, 6, 9]);
+    assert_eq!(slow.ratio_history("1").len(), 4);
+
+    // plain sgd moves every layer by lr times its gradient
+    let first = &slow.history[0];
+    let layer_grads: f64 = first.layers.iter().map(|l| l.grad_norm * l.grad_norm).sum();
+    assert!((layer_grads.sqrt() - first.grad_norm).abs() < 1e-9);
+    for l in first.layers.iter() {
+        assert!((l.update_ratio * l.weight_norm - 0.01 * l.grad_norm).abs() < 1e-9);
+    }
+
+    // same first step at ten times the learning rate, ten times the ratio
+    let fast = run(0.1);
+    for (a, b) in slow.history[0].layers.iter().zip(fast.history[0].layers.iter()) {
+        assert!((b.update_ratio - 10.0 * a.update_ratio).abs() < 1e-9 * b.update_ratio.max(1.0));
+    }
+}
+
+#[test]
+fn lr_finder_sweeps_and_suggests_a_range() {
+    let (xs, ys) = line_data();
+    random::seed(4);
+    let model = MLP::new(&[1, 6, 1]);
+    let before = model.get_flat_params();
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1)).with_batch_size(5);
+    let finder = trainer.lr_finder(&xs, &ys, 1e-4, 100.0, 200);
+    assert_eq!(model.get_flat_params(), before);
+    assert!(finder.lrs.windows(2).all(|w| w[1] > w[0]));
+    assert!((finder.lrs[0] - 1e-4).abs() < 1e-15);
+    assert_eq!(finder.losses.len(), 200);
+
+    let (low, high) = finder.suggestion().unwrap();
+    assert!(1e-4 <= low && low <= high && high < 100.0, "{} {}", low, high);
+    assert!(finder.plot().contains("<polyline"));
+
+    // the low end trains
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), low)).with_batch_size(5);
+    let losses = trainer.fit(&xs, &ys, 20);
+    assert!(losses[19] < losses[0]);
+
+    // nothing saturates in a linear model, a big enough lr blows the loss up before the sweep's end
+    let linear = MLP::with_activation(&[1, 1], Activation::Linear);
+    let mut trainer = Trainer::new(&linear, Sgd::new(linear.parameters(), 0.1)).with_batch_size(5);
+    let finder = trainer.lr_finder(&xs, &ys, 1e-4, 100.0, 200);
+    assert!(finder.lrs.len() < 200 && *finder.lrs.last().unwrap() > 1.0, "{}", finder.lrs.len());
+
+    // a loss that's negative near its best doesn't stop the sweep early, and still diverges
+    let mut trainer = Trainer::new(&linear, Sgd::new(linear.parameters(), 0.1)).with_batch_size(5)
+        .with_loss(|ypred, y| Value::sub(&loss::mse(ypred, &[Value::constant(y[0])]), &Value::constant(10.0)));
+    let finder = trainer.lr_finder(&xs, &ys, 1e-4, 100.0, 200);
+    assert!(finder.smoothed.iter().any(|l| *l < 0.0));
+    assert!(finder.lrs.len() > 100 && finder.lrs.len() < 200, "{}", finder.lrs.len());
+
+    // no samples to sweep over
+    let mut trainer = Trainer::new(&linear, Sgd::new(linear.parameters(), 0.1));
+    assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| trainer.lr_finder(&[], &[], 1e-4, 1.0, 10))).is_err());
+}
+
+#[test]
+fn swa_averages_the_tail_of_training() {
+    let (xs, ys) = line_data();
+    random::seed(7);
+    let model = MLP::new(&[1, 6, 1]);
+    let mut trainer = Trainer::new(&model, Sgd::new(model.parameters(), 0.1))
+        .with_batch_size(5)
+        .with_schedule(0.1, Schedule::Cyclic { min_lr: 0.01, period: 2 })
+        .with_epoch_budget(10)
+        .with_swa(4, 2);
+    assert!(!trainer.finalize_swa());
+
+    // one epoch at a time to see the weights the average should be taken over
+    let mut snapshots = vec![];
+    for epoch in 1..=10 {
+        trainer.fit(&xs, &ys, 1);
+        if epoch > 4 && epoch % 2 == 0 {
+            snapshots.push(model.get_flat_params());
+        }
+    }
+    let swa = trainer.swa.as_ref().unwrap();
+    assert_eq!(swa.samples, 3);
+    let averaged = swa.average().unwrap().to_vec();
+    let expected: Vec<f64> = (0..snapshots[0].len()).map(|i| snapshots.iter().map(|s| s[i]).sum::<f64>() / 3.0).collect();
+    for (a, b) in averaged.iter().zip(expected.iter()) {
+        assert!((a - b).abs() < 1e-12);
+    }
+
+    assert!(trainer.finalize_swa());
+    assert_eq!(model.get_flat_params(), averaged);
+    let mse = |m: &MLP| xs.iter().zip(ys.iter()).map(|(x, y)| (m.predict(x)[0] - y[0]).powi(2)).sum::<f64>() / xs.len() as f64;
+    assert!(mse(&model) < 0.05, "{}", mse(&model));
+}